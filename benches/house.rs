@@ -0,0 +1,146 @@
+// Замеры построения дома, поиска устройств и отчётов; запуск: cargo bench --bench house
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use lesson_3::{prelude::*, BorrowingDeviceInfoProvider};
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+const DEVICES_PER_ROOM: usize = 100;
+const RUNS: u32 = 10;
+
+// Считаем выделения памяти, чтобы видеть эффект предвыделения
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations(name: &str, size: usize, f: impl FnOnce()) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    let count = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!("{name:<20} {size:>6}  allocations: {count}");
+}
+
+fn measure(name: &str, size: usize, mut f: impl FnMut()) {
+    f();
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let started = Instant::now();
+        f();
+        best = best.min(started.elapsed());
+    }
+    println!("{name:<20} {size:>6}  best of {RUNS}: {best:?}");
+}
+
+fn sockets(count: usize) -> Vec<Arc<SmartSocket>> {
+    (0..count)
+        .map(|i| Arc::new(SmartSocket::new(format!("socket {i}"))))
+        .collect()
+}
+
+fn build(sockets: &[Arc<SmartSocket>]) -> SmartHouse {
+    let rooms = sockets.len().div_ceil(DEVICES_PER_ROOM);
+    let mut house = SmartHouse::with_capacity("house", rooms);
+    for (r, chunk) in sockets.chunks(DEVICES_PER_ROOM).enumerate() {
+        let mut room = SmartRoom::with_capacity(format!("room {r}"), chunk.len());
+        for socket in chunk {
+            room.plug(socket.clone()).unwrap();
+        }
+        house.add(room).unwrap();
+    }
+    house
+}
+
+fn main() {
+    for size in SIZES {
+        let sockets = sockets(size);
+
+        measure("plug into one room", size, || {
+            let mut room = SmartRoom::new("room");
+            for socket in &sockets {
+                room.plug(socket.clone()).unwrap();
+            }
+            black_box(room);
+        });
+
+        measure("build house", size, || {
+            black_box(build(&sockets));
+        });
+
+        allocations("load, growing", size, || {
+            let mut room = SmartRoom::new("room");
+            for socket in &sockets {
+                room.plug(socket.clone()).unwrap();
+            }
+            black_box(room);
+        });
+
+        allocations("load, with_capacity", size, || {
+            let mut room = SmartRoom::with_capacity("room", sockets.len());
+            for socket in &sockets {
+                room.plug(socket.clone()).unwrap();
+            }
+            black_box(room);
+        });
+
+        let mut room = SmartRoom::new("room");
+        for socket in &sockets {
+            room.plug(socket.clone()).unwrap();
+        }
+        measure("device lookup", size, || {
+            for socket in &sockets {
+                black_box(room.device(black_box("socket 0")));
+                black_box(room.is_connected(socket.as_ref()));
+            }
+        });
+
+        measure("devices() names", size, || {
+            black_box(room.devices().len());
+        });
+
+        measure("device_names() names", size, || {
+            black_box(room.device_names().count());
+        });
+
+        let house = build(&sockets);
+        measure("full house report", size, || {
+            black_box(house.create_report(HouseReport).unwrap());
+        });
+
+        let last = sockets.last().unwrap();
+        let thermo = SmartThermometer::new("thermometer");
+        measure("device report", size, || {
+            let report = BorrowingDeviceInfoProvider {
+                socket: last,
+                thermo: &thermo,
+            };
+            black_box(house.create_report(report).unwrap());
+        });
+    }
+}
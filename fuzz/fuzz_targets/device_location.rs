@@ -0,0 +1,11 @@
+#![no_main]
+
+use lesson_3::DeviceLocation;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    if let Ok(location) = text.parse::<DeviceLocation>() {
+        let printed = location.to_string();
+        assert_eq!(printed.parse(), Ok(location), "{printed}");
+    }
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use lesson_3::protocol::{decode, Request, Response};
+use libfuzzer_sys::fuzz_target;
+
+// Любая последовательность байт — либо кадр, либо типизированная ошибка
+fuzz_target!(|data: &[u8]| {
+    if let Ok((frame, used)) = decode(data) {
+        assert!(used <= data.len());
+        let _ = Request::from_frame(frame.clone());
+        let _ = Response::from_frame(frame);
+    }
+    let _ = Request::decode(data);
+    if let Ok(response) = Response::decode(data) {
+        let bytes = response.encode().unwrap();
+        let again = Response::decode(&bytes).unwrap();
+        // NaN в показаниях не равен сам себе
+        if !matches!(response, Response::Reading(v) if v.is_nan()) {
+            assert_eq!(again, response);
+        }
+    }
+});
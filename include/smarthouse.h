@@ -0,0 +1,43 @@
+/* C interface to the lesson_3 house model, see src/ffi.rs.
+ *
+ * Kept by hand in the layout cbindgen produces for src/ffi.rs; regenerate with
+ * `cbindgen --config cbindgen.toml --output include/smarthouse.h` when it is
+ * installed. */
+
+#ifndef SMARTHOUSE_H
+#define SMARTHOUSE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define SMARTHOUSE_OK 0
+
+#define SMARTHOUSE_ERROR -1
+
+typedef struct SmartHouse SmartHouse;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+SmartHouse *smarthouse_new(const char *name);
+
+void smarthouse_free(SmartHouse *house);
+
+int smarthouse_add_room(SmartHouse *house, const char *room);
+
+int smarthouse_plug_socket(SmartHouse *house, const char *room, const char *device);
+
+int smarthouse_plug_thermometer(SmartHouse *house, const char *room, const char *device);
+
+int64_t smarthouse_device_count(const SmartHouse *house);
+
+size_t smarthouse_report(const SmartHouse *house, char *buf, size_t len);
+
+const char *smarthouse_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SMARTHOUSE_H */
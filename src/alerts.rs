@@ -0,0 +1,303 @@
+use std::{
+    error::Error,
+    fmt::{self, Write},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{DeviceLocation, Reading, Reportable, SmartHouse};
+
+/// A range a device reading should stay in. Either bound may be left open.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertThreshold {
+    pub device: DeviceLocation,
+    pub metric: Reading,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// How far back inside the range a reading has to come before its alert clears, so
+    /// readings wobbling on a bound do not raise and clear the alert over and over.
+    pub hysteresis: f64,
+}
+
+impl AlertThreshold {
+    pub fn new(device: DeviceLocation, metric: Reading) -> Self {
+        Self {
+            device,
+            metric,
+            min: None,
+            max: None,
+            hysteresis: 0.0,
+        }
+    }
+
+    pub fn min(mut self, min: f64) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    pub fn max(mut self, max: f64) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    pub fn hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    fn breach(&self, value: f64) -> Option<Breach> {
+        match (self.min, self.max) {
+            (Some(min), _) if value < min => Some(Breach::BelowMin(min)),
+            (_, Some(max)) if value > max => Some(Breach::AboveMax(max)),
+            _ => None,
+        }
+    }
+
+    fn cleared(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min + self.hysteresis)
+            && self.max.is_none_or(|max| value <= max - self.hysteresis)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AlertId(u64);
+
+/// Which bound a reading went past.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breach {
+    BelowMin(f64),
+    AboveMax(f64),
+}
+
+/// A threshold that is breached, with the latest reading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub id: AlertId,
+    pub device: DeviceLocation,
+    pub metric: Reading,
+    pub value: f64,
+    pub breach: Breach,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {}", self.device, self.metric, self.value)?;
+        match self.breach {
+            Breach::BelowMin(min) => write!(f, " below min {min}"),
+            Breach::AboveMax(max) => write!(f, " above max {max}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlertEvent {
+    Raised(Alert),
+    Cleared { alert: AlertId, value: f64 },
+}
+
+type Callback = dyn Fn(&AlertEvent) + Send + Sync;
+type Listener = Box<Callback>;
+
+#[derive(Clone)]
+struct Watched {
+    id: AlertId,
+    threshold: AlertThreshold,
+    active: Option<Alert>,
+}
+
+#[derive(Default)]
+pub(crate) struct Alerts {
+    next: u64,
+    watched: Mutex<Vec<Watched>>,
+    listeners: Vec<Arc<Callback>>,
+}
+
+impl Clone for Alerts {
+    fn clone(&self) -> Self {
+        Self {
+            next: self.next,
+            watched: Mutex::new(lock(&self.watched).clone()),
+            listeners: self.listeners.clone(),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl SmartHouse {
+    pub fn add_alert(&mut self, threshold: AlertThreshold) -> AlertId {
+        let id = AlertId(self.alerts.next);
+        self.alerts.next += 1;
+        lock(&self.alerts.watched).push(Watched {
+            id,
+            threshold,
+            active: None,
+        });
+        id
+    }
+
+    pub fn remove_alert(&mut self, id: AlertId) -> Option<AlertThreshold> {
+        let mut watched = lock(&self.alerts.watched);
+        let position = watched.iter().position(|watched| watched.id == id)?;
+        Some(watched.remove(position).threshold)
+    }
+
+    /// Calls `listener` for every alert raised or cleared by
+    /// [`check_alerts`](Self::check_alerts).
+    pub fn on_alert(&mut self, listener: Listener) {
+        self.alerts.listeners.push(Arc::from(listener));
+    }
+
+    /// Reads every thresholded device once, raising alerts for readings out of range and
+    /// clearing alerts whose readings came back past the hysteresis. A device that cannot
+    /// be read keeps its alert as it was.
+    pub fn check_alerts(&self) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        for watched in lock(&self.alerts.watched).iter_mut() {
+            let threshold = &watched.threshold;
+            let Ok(value) = self.read(&threshold.device, threshold.metric) else {
+                continue;
+            };
+            match &mut watched.active {
+                Some(alert) if threshold.cleared(value) => {
+                    events.push(AlertEvent::Cleared {
+                        alert: alert.id,
+                        value,
+                    });
+                    watched.active = None;
+                }
+                Some(alert) => alert.value = value,
+                None => {
+                    if let Some(breach) = threshold.breach(value) {
+                        let alert = Alert {
+                            id: watched.id,
+                            device: threshold.device.clone(),
+                            metric: threshold.metric,
+                            value,
+                            breach,
+                        };
+                        events.push(AlertEvent::Raised(alert.clone()));
+                        watched.active = Some(alert);
+                    }
+                }
+            }
+        }
+
+        for event in &events {
+            for listener in &self.alerts.listeners {
+                listener(event);
+            }
+        }
+        events
+    }
+
+    /// Alerts breached as of the latest [`check_alerts`](Self::check_alerts).
+    pub fn active_alerts(&self) -> Vec<Alert> {
+        lock(&self.alerts.watched)
+            .iter()
+            .filter_map(|watched| watched.active.clone())
+            .collect()
+    }
+}
+
+/// Active alerts, one per line. Reports the state of the latest check, it does not read
+/// devices itself.
+#[derive(Debug)]
+pub struct AlertReportProvider;
+
+impl Reportable for AlertReportProvider {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        let alerts = house.active_alerts();
+        if alerts.is_empty() {
+            return Ok("No active alerts\n".to_string());
+        }
+        let mut out = String::new();
+        for alert in alerts {
+            writeln!(out, "{alert}")?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::testing::fixtures::{small_house, socket, thermometer};
+
+    #[test]
+    fn alerts_raise_and_clear_past_hysteresis() {
+        let mut house = small_house();
+        let thermometer = thermometer(&house, "limb", "t1");
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        house.on_alert(Box::new(move |event| lock(&sink).push(event.clone())));
+        let id = house.add_alert(
+            AlertThreshold::new("limb/t1".parse().unwrap(), Reading::Temperature)
+                .min(10.0)
+                .max(75.0)
+                .hysteresis(2.0),
+        );
+
+        // без показаний тревоги нет
+        assert!(house.check_alerts().is_empty());
+        thermometer.set_temperature(70.0);
+        assert!(house.check_alerts().is_empty());
+
+        thermometer.set_temperature(76.0);
+        let raised = house.check_alerts();
+        assert!(matches!(
+            &raised[..],
+            [AlertEvent::Raised(Alert {
+                breach: Breach::AboveMax(75.0),
+                ..
+            })]
+        ));
+
+        // колебания у границы не сбрасывают тревогу
+        for celsius in [74.5, 75.5, 74.0, 76.5] {
+            thermometer.set_temperature(celsius);
+            assert!(house.check_alerts().is_empty());
+        }
+        assert_eq!(house.active_alerts()[0].value, 76.5);
+
+        thermometer.set_temperature(73.0);
+        assert_eq!(
+            house.check_alerts(),
+            [AlertEvent::Cleared {
+                alert: id,
+                value: 73.0
+            }]
+        );
+        assert!(house.active_alerts().is_empty());
+        assert_eq!(lock(&seen).len(), 2);
+    }
+
+    #[test]
+    fn report_lists_active_alerts() {
+        let mut house = small_house();
+        let (thermometer, socket) = (
+            thermometer(&house, "limb", "t1"),
+            socket(&house, "limb", "s1"),
+        );
+        house.add_alert(
+            AlertThreshold::new("limb/t1".parse().unwrap(), Reading::Temperature).min(5.0),
+        );
+        house.add_alert(AlertThreshold::new("s1".parse().unwrap(), Reading::Power).max(2000.0));
+        assert_eq!(
+            house.create_report(AlertReportProvider).unwrap(),
+            "No active alerts\n"
+        );
+
+        thermometer.set_temperature(-3.0);
+        socket.set_load(2500.0);
+        house.check_alerts();
+        assert_eq!(
+            house.create_report(AlertReportProvider).unwrap(),
+            "limb/t1: temperature -3 below min 5\n\
+             s1: power 2500 above max 2000\n"
+        );
+    }
+}
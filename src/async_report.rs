@@ -0,0 +1,464 @@
+//! Asynchronous reports for providers that have to ask networked devices for their state.
+//!
+//! The crate has no async runtime dependency: [`block_on`] drives a report on the current
+//! thread, [`spawn_blocking`] turns a blocking call (such as a [`SocketClient`] request)
+//! into a future backed by its own thread, and [`join_all`] waits for many futures at once.
+
+use core::fmt;
+use std::{
+    any::Any,
+    error::Error,
+    fmt::Write,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use crate::{net::SocketClient, Pluggable, Reportable, SmartHouse};
+
+pub trait AsyncReportable {
+    fn make(&self, house: &SmartHouse) -> impl Future<Output = Result<String, Box<dyn Error>>>;
+}
+
+// Любой синхронный отчёт работает и в асинхронном режиме
+impl<T: Reportable> AsyncReportable for T {
+    fn make(&self, house: &SmartHouse) -> impl Future<Output = Result<String, Box<dyn Error>>> {
+        let report = Reportable::make(self, house);
+        async move { report }
+    }
+}
+
+impl SmartHouse {
+    pub async fn create_report_async<T: AsyncReportable>(
+        &self,
+        report: T,
+    ) -> Result<String, Box<dyn Error>> {
+        #[cfg(feature = "tracing")]
+        {
+            let span = crate::tracing::Span::new(
+                "report",
+                &[("house", &self.name), ("devices", &self.device_count())],
+            );
+            span.instrument(report.make(self)).await
+        }
+        #[cfg(not(feature = "tracing"))]
+        report.make(self).await
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs a future to completion on the current thread, parking it while the future is pending.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+struct Slot<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Future returned by [`spawn_blocking`].
+pub struct BlockingTask<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+/// Runs `f` on a new thread; the returned future resolves to its result.
+///
+/// A panic in `f` is propagated when the future is polled.
+pub fn spawn_blocking<T, F>(f: F) -> BlockingTask<thread::Result<T>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let slot = Arc::new(Mutex::new(Slot {
+        value: None,
+        waker: None,
+    }));
+
+    let shared = Arc::clone(&slot);
+    thread::spawn(move || {
+        let value = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+        let mut slot = lock(&shared);
+        slot.value = Some(value);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    });
+
+    BlockingTask { slot }
+}
+
+impl<T> fmt::Debug for BlockingTask<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockingTask")
+            .field("done", &lock(&self.slot).value.is_some())
+            .finish()
+    }
+}
+
+impl<T> Future for BlockingTask<thread::Result<T>> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = lock(&self.slot);
+        match slot.value.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+enum Joined<F: Future> {
+    Pending(Pin<Box<F>>),
+    Done(Option<F::Output>),
+}
+
+/// Future returned by [`join_all`].
+pub struct JoinAll<F: Future> {
+    futures: Vec<Joined<F>>,
+}
+
+impl<F: Future> fmt::Debug for JoinAll<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pending = self
+            .futures
+            .iter()
+            .filter(|j| matches!(j, Joined::Pending(_)))
+            .count();
+        f.debug_struct("JoinAll")
+            .field("futures", &self.futures.len())
+            .field("pending", &pending)
+            .finish()
+    }
+}
+
+// Футуры лежат в Box, поэтому JoinAll нигде не требует закрепления своих полей
+impl<F: Future> Unpin for JoinAll<F> {}
+
+/// Polls all futures concurrently and resolves to their outputs in the original order.
+pub fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> JoinAll<F> {
+    JoinAll {
+        futures: futures
+            .into_iter()
+            .map(|f| Joined::Pending(Box::pin(f)))
+            .collect(),
+    }
+}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut pending = false;
+        for joined in self.futures.iter_mut() {
+            if let Joined::Pending(future) = joined {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(output) => *joined = Joined::Done(Some(output)),
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+
+        if pending {
+            return Poll::Pending;
+        }
+
+        let outputs = self
+            .futures
+            .iter_mut()
+            .filter_map(|joined| match joined {
+                Joined::Done(output) => output.take(),
+                Joined::Pending(_) => None,
+            })
+            .collect();
+        Poll::Ready(outputs)
+    }
+}
+
+/// The error of a [`timeout`] future whose time ran out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(pub Duration);
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out after {:?}", self.0)
+    }
+}
+
+impl Error for Elapsed {}
+
+struct Timer {
+    elapsed: bool,
+    waker: Option<Waker>,
+}
+
+/// Future returned by [`timeout`].
+pub struct Timeout<F> {
+    future: Pin<Box<F>>,
+    limit: Duration,
+    timer: Option<Arc<Mutex<Timer>>>,
+}
+
+impl<F> fmt::Debug for Timeout<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("limit", &self.limit)
+            .finish_non_exhaustive()
+    }
+}
+
+// Будущее лежит в Box, как и в JoinAll
+impl<F> Unpin for Timeout<F> {}
+
+/// Resolves to the output of `future`, or to [`Elapsed`] if it is not done within
+/// `limit` of the first poll. The future is then dropped; a [`spawn_blocking`] thread
+/// behind it runs on until its call returns.
+pub fn timeout<F: Future>(limit: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future: Box::pin(future),
+        limit,
+        timer: None,
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        let limit = self.limit;
+        let timer = self.timer.get_or_insert_with(|| {
+            let timer = Arc::new(Mutex::new(Timer {
+                elapsed: false,
+                waker: None,
+            }));
+            let shared = Arc::clone(&timer);
+            thread::spawn(move || {
+                thread::sleep(limit);
+                let mut timer = lock(&shared);
+                timer.elapsed = true;
+                if let Some(waker) = timer.waker.take() {
+                    waker.wake();
+                }
+            });
+            timer
+        });
+        let mut timer = lock(timer);
+        if timer.elapsed {
+            return Poll::Ready(Err(Elapsed(limit)));
+        }
+        timer.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Reports the live state of every [`SocketClient`] in the house.
+///
+/// All sockets are asked at the same time, each on its own thread; other devices are
+/// listed by name only. With a `timeout`, a socket that does not answer in time is
+/// marked as timed out and the report goes on without it.
+#[derive(Debug, Default)]
+pub struct LiveSocketReport {
+    pub timeout: Option<Duration>,
+}
+
+impl LiveSocketReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+        }
+    }
+}
+
+impl AsyncReportable for LiveSocketReport {
+    async fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        let devices: Vec<Vec<Arc<dyn Pluggable>>> = house
+            .get_rooms()
+            .iter()
+            .map(|room| room.live_devices().collect())
+            .collect();
+        let clients: Vec<Option<Arc<SocketClient>>> = devices
+            .iter()
+            .flatten()
+            .map(|device| {
+                let any: Arc<dyn Any + Send + Sync> = device.clone();
+                any.downcast::<SocketClient>().ok()
+            })
+            .collect();
+
+        let states = join_all(clients.iter().flatten().cloned().map(|client| {
+            let task = spawn_blocking(move || client.is_on());
+            let limit = self.timeout;
+            async move {
+                match limit {
+                    Some(limit) => timeout(limit, task).await,
+                    None => Ok(task.await),
+                }
+            }
+        }))
+        .await;
+
+        let mut clients = clients.iter();
+        let mut states = states.into_iter();
+        let mut out = String::new();
+        write!(out, "{:#}", house)?;
+        for (room, devices) in house.get_rooms().iter().zip(&devices) {
+            write!(out, "{:#}", room)?;
+            for device in devices.iter().map(|d| d.name()) {
+                let state = clients.next().and_then(Option::as_ref).and(states.next());
+                match state {
+                    Some(Ok(Ok(true))) => writeln!(out, "----> Device: Socket[{}] on", device)?,
+                    Some(Ok(Ok(false))) => writeln!(out, "----> Device: Socket[{}] off", device)?,
+                    Some(Ok(Err(e))) => {
+                        writeln!(out, "----> Device: Socket[{}] unreachable: {}", device, e)?
+                    }
+                    Some(Err(e)) => writeln!(out, "----> Device: Socket[{}] {}", device, e)?,
+                    None => writeln!(out, "----> Device: {}", device)?,
+                }
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{
+        net::SocketServer, testing::fixtures::small_house, HouseReport, SmartRoom, SmartThermometer,
+    };
+
+    #[test]
+    fn sync_reports_work_async() {
+        let house = small_house();
+
+        let sync = house.create_report(HouseReport).unwrap();
+        let async_ = block_on(house.create_report_async(HouseReport)).unwrap();
+        assert_eq!(sync, async_);
+    }
+
+    #[test]
+    fn join_all_keeps_order_and_runs_concurrently() {
+        let started = Instant::now();
+        let outputs = block_on(join_all((0..8).map(|i| {
+            spawn_blocking(move || {
+                thread::sleep(Duration::from_millis(100));
+                i
+            })
+        })));
+
+        assert_eq!(outputs, (0..8).collect::<Vec<_>>());
+        assert!(started.elapsed() < Duration::from_millis(700));
+    }
+
+    #[test]
+    fn live_report_asks_every_socket() {
+        let on = SocketServer::bind("s1", "127.0.0.1:0")
+            .unwrap()
+            .spawn()
+            .unwrap();
+        let off = SocketServer::bind("s2", "127.0.0.1:0")
+            .unwrap()
+            .spawn()
+            .unwrap();
+
+        let s1 = SocketClient::connect("s1", on.local_addr()).unwrap();
+        s1.turn_on().unwrap();
+        let s2 = SocketClient::connect("s2", off.local_addr()).unwrap();
+
+        let mut room = SmartRoom::new("limb");
+        room.plug(s1).unwrap();
+        room.plug(s2).unwrap();
+        room.plug(SmartThermometer::new("t1")).unwrap();
+        let mut house = SmartHouse::new("hell");
+        house.add(room).unwrap();
+
+        let report = block_on(house.create_report_async(LiveSocketReport::new())).unwrap();
+        assert_eq!(
+            report,
+            "-> House: hell\n--> Room: limb\n----> Device: Socket[s1] on\n----> Device: Socket[s2] off\n----> Device: t1\n"
+        );
+
+        off.shutdown();
+        let report = block_on(house.create_report_async(LiveSocketReport::new())).unwrap();
+        assert!(report.contains("Socket[s2] unreachable"), "{report}");
+    }
+
+    #[test]
+    fn live_report_survives_a_hung_socket() {
+        let on = SocketServer::bind("s1", "127.0.0.1:0")
+            .unwrap()
+            .spawn()
+            .unwrap();
+        // принимает соединение, но ничего не отвечает
+        let hung = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let s1 = SocketClient::connect("s1", on.local_addr()).unwrap();
+        s1.turn_on().unwrap();
+        let s2 = SocketClient::connect("s2", hung.local_addr().unwrap())
+            .unwrap()
+            .with_reconnect(crate::net::ReconnectPolicy::none())
+            .with_timeout(Duration::from_secs(2))
+            .unwrap();
+        let mut room = SmartRoom::new("limb");
+        room.plug(s1).unwrap();
+        room.plug(s2).unwrap();
+        let mut house = SmartHouse::new("hell");
+        house.add(room).unwrap();
+
+        let started = Instant::now();
+        let report = LiveSocketReport::with_timeout(Duration::from_millis(200));
+        let report = block_on(house.create_report_async(report)).unwrap();
+        assert_eq!(
+            report,
+            "-> House: hell\n--> Room: limb\n----> Device: Socket[s1] on\n\
+             ----> Device: Socket[s2] timed out after 200ms\n"
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn timeout_lets_quick_futures_through() {
+        let quick = block_on(timeout(Duration::from_secs(5), spawn_blocking(|| 1)));
+        assert_eq!(quick, Ok(1));
+        let slow = spawn_blocking(|| thread::sleep(Duration::from_secs(1)));
+        let slow = block_on(timeout(Duration::from_millis(50), slow));
+        assert_eq!(slow, Err(Elapsed(Duration::from_millis(50))));
+    }
+}
@@ -0,0 +1,85 @@
+// 64-битные атомики есть не везде: у ESP32 на Xtensa и у Cortex-M только 32-битные.
+// Там u64 охраняет спин-блокировка на AtomicBool с тем же набором методов
+#[cfg(target_has_atomic = "64")]
+pub(crate) use core::sync::atomic::AtomicU64;
+
+#[cfg(not(target_has_atomic = "64"))]
+pub(crate) use fallback::AtomicU64;
+
+#[cfg(not(target_has_atomic = "64"))]
+mod fallback {
+    use core::{
+        cell::UnsafeCell,
+        fmt,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    // Порядок доступа задаёт блокировка, переданный Ordering не нужен
+    #[derive(Default)]
+    pub(crate) struct AtomicU64 {
+        locked: AtomicBool,
+        value: UnsafeCell<u64>,
+    }
+
+    // SAFETY: к value обращаются только под блокировкой в with
+    unsafe impl Sync for AtomicU64 {}
+
+    impl AtomicU64 {
+        pub(crate) const fn new(value: u64) -> Self {
+            Self {
+                locked: AtomicBool::new(false),
+                value: UnsafeCell::new(value),
+            }
+        }
+
+        fn with<R>(&self, f: impl FnOnce(&mut u64) -> R) -> R {
+            while self
+                .locked
+                .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                core::hint::spin_loop();
+            }
+            // SAFETY: блокировка взята, других ссылок на value нет
+            let result = f(unsafe { &mut *self.value.get() });
+            self.locked.store(false, Ordering::Release);
+            result
+        }
+
+        pub(crate) fn load(&self, _: Ordering) -> u64 {
+            self.with(|value| *value)
+        }
+
+        pub(crate) fn store(&self, new: u64, _: Ordering) {
+            self.with(|value| *value = new);
+        }
+
+        // нужен только телеметрии
+        #[cfg_attr(not(feature = "std"), allow(dead_code))]
+        pub(crate) fn swap(&self, new: u64, _: Ordering) -> u64 {
+            self.with(|value| core::mem::replace(value, new))
+        }
+
+        pub(crate) fn fetch_add(&self, n: u64, _: Ordering) -> u64 {
+            self.with(|value| {
+                let old = *value;
+                *value = old.wrapping_add(n);
+                old
+            })
+        }
+
+        pub(crate) fn fetch_max(&self, n: u64, _: Ordering) -> u64 {
+            self.with(|value| {
+                let old = *value;
+                *value = old.max(n);
+                old
+            })
+        }
+    }
+
+    impl fmt::Debug for AtomicU64 {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+        }
+    }
+}
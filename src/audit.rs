@@ -0,0 +1,322 @@
+use core::fmt;
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    io,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Clock, HouseEvent, SmartHouse, SystemClock};
+
+/// Entries a new house keeps before the oldest ones are dropped.
+pub const DEFAULT_AUDIT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub at: SystemTime,
+    /// The house's actor when the change was made, see [`SmartHouse::set_actor`].
+    pub actor: Option<String>,
+    pub change: HouseEvent,
+}
+
+/// Successful structural changes of a house, oldest first.
+///
+/// The log holds at most `capacity` entries; recording past that drops the oldest one.
+#[derive(Clone)]
+pub struct AuditLog {
+    entries: VecDeque<AuditEntry>,
+    capacity: usize,
+    dropped: u64,
+    actor: Option<String>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: DEFAULT_AUDIT_CAPACITY,
+            dropped: 0,
+            actor: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("entries", &self.entries)
+            .field("capacity", &self.capacity)
+            .field("dropped", &self.dropped)
+            .field("actor", &self.actor)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuditLog {
+    pub fn entries(&self) -> impl Iterator<Item = &AuditEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How many entries were dropped to stay within the capacity.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub(crate) fn record(&mut self, change: &HouseEvent) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(AuditEntry {
+            at: self.clock.now(),
+            actor: self.actor.clone(),
+            change: change.clone(),
+        });
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.capacity = capacity;
+    }
+
+    /// Writes one JSON object per entry and line, e.g.
+    /// `{"at_ms":1700000000000,"actor":"admin","operation":"room_added","room":"Hall"}`.
+    pub fn write_jsonl(&self, out: &mut impl io::Write) -> io::Result<()> {
+        let mut line = String::new();
+        for entry in &self.entries {
+            line.clear();
+            let at = entry
+                .at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis());
+            let _ = write!(line, "{{\"at_ms\":{at},\"actor\":");
+            match &entry.actor {
+                Some(actor) => json_string(&mut line, actor),
+                None => line.push_str("null"),
+            }
+
+            let (operation, names): (_, &[(&str, &String)]) = match &entry.change {
+                HouseEvent::RoomAdded { room } => ("room_added", &[("room", room)]),
+                HouseEvent::RoomRemoved { room } => ("room_removed", &[("room", room)]),
+                HouseEvent::DevicePlugged { room, device } => {
+                    ("device_plugged", &[("room", room), ("device", device)])
+                }
+                HouseEvent::DeviceUnplugged { room, device } => {
+                    ("device_unplugged", &[("room", room), ("device", device)])
+                }
+                HouseEvent::DeviceMoved { device, from, to } => (
+                    "device_moved",
+                    &[("device", device), ("from", from), ("to", to)],
+                ),
+            };
+            let _ = write!(line, ",\"operation\":\"{operation}\"");
+            for (key, value) in names {
+                let _ = write!(line, ",\"{key}\":");
+                json_string(&mut line, value);
+            }
+            line.push_str("}\n");
+            out.write_all(line.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl SmartHouse {
+    /// Every change made through the house's own methods, the same ones
+    /// [`subscribe`](SmartHouse::subscribe) reports. Failed operations are not recorded.
+    pub fn audit_entries(&self) -> impl Iterator<Item = &AuditEntry> {
+        self.audit.entries()
+    }
+
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Keeps at most `capacity` entries, dropping the oldest ones right away if needed.
+    /// A capacity of zero turns recording off.
+    pub fn set_audit_capacity(&mut self, capacity: usize) {
+        self.audit.set_capacity(capacity);
+    }
+
+    /// Attributes the following changes to `actor` until it is changed or cleared.
+    pub fn set_actor(&mut self, actor: impl Into<String>) {
+        self.audit.actor = Some(actor.into());
+    }
+
+    pub fn clear_actor(&mut self) {
+        self.audit.actor = None;
+    }
+
+    /// Stamps audit entries, times rule debouncing and refills rate limits with `clock`
+    /// instead of the system time.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.audit.clock = Arc::new(clock);
+    }
+
+    pub(crate) fn now(&self) -> std::time::SystemTime {
+        self.audit.clock.now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{testing::ManualClock, SmartRoom, SmartSocket};
+
+    #[test]
+    fn records_successful_changes_with_actor() {
+        let clock = ManualClock::default();
+        let mut house = SmartHouse::new("Home");
+        house.set_clock(clock.clone());
+        house.add(SmartRoom::new("Boiler")).unwrap();
+        clock.advance(Duration::from_secs(1));
+        house.set_actor("admin \"root\"");
+        assert!(house.add(SmartRoom::new("Boiler")).is_err());
+        house.plug("Boiler", SmartSocket::new("Kettle")).unwrap();
+        assert!(house.plug("Hall", SmartSocket::new("Kettle")).is_err());
+        assert!(house.move_device("Kettle", "Boiler", "Hall").is_err());
+        house.clear_actor();
+        house.unplug("Boiler", "Kettle").unwrap();
+
+        let entries: Vec<_> = house.audit_entries().collect();
+        assert_eq!(entries.len(), 3);
+        let start = ManualClock::default().now();
+        assert_eq!(entries[0].at, start);
+        assert!(entries[1..]
+            .iter()
+            .all(|e| e.at == start + Duration::from_secs(1)));
+        assert_eq!(entries[0].actor, None);
+        assert_eq!(
+            entries[1].change,
+            HouseEvent::DevicePlugged {
+                room: "Boiler".to_string(),
+                device: "Kettle".to_string()
+            }
+        );
+        assert_eq!(entries[1].actor.as_deref(), Some("admin \"root\""));
+        assert_eq!(entries[2].actor, None);
+
+        let mut out = Vec::new();
+        house.audit_log().write_jsonl(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(r#","actor":null,"operation":"room_added","room":"Boiler"}"#));
+        assert!(lines[1].ends_with(
+            r#","actor":"admin \"root\"","operation":"device_plugged","room":"Boiler","device":"Kettle"}"#
+        ));
+        assert!(lines[2].contains(r#""operation":"device_unplugged""#));
+
+        assert_eq!(house.clone().audit_log().len(), 0);
+    }
+
+    #[test]
+    fn log_carries_over_house_cell_updates() {
+        let mut house = SmartHouse::new("Home");
+        house.set_audit_capacity(2);
+        house.add(SmartRoom::new("Boiler")).unwrap();
+        house.set_actor("admin");
+        let cell = crate::HouseCell::new(house);
+
+        cell.update(|house| house.plug("Boiler", SmartSocket::new("Kettle")))
+            .unwrap();
+        // откаченное обновление в журнал не попадает
+        assert!(cell
+            .try_update(|house| {
+                house.add(SmartRoom::new("Hall"))?;
+                house.add(SmartRoom::new("Boiler"))
+            })
+            .is_err());
+
+        let house = cell.load();
+        let changes: Vec<_> = house.audit_entries().map(|e| &e.change).collect();
+        assert_eq!(
+            changes,
+            [
+                &HouseEvent::RoomAdded {
+                    room: "Boiler".to_string()
+                },
+                &HouseEvent::DevicePlugged {
+                    room: "Boiler".to_string(),
+                    device: "Kettle".to_string()
+                },
+            ]
+        );
+        assert_eq!(house.audit_log().capacity(), 2);
+        assert_eq!(
+            house.audit_entries().last().unwrap().actor.as_deref(),
+            Some("admin")
+        );
+    }
+
+    #[test]
+    fn capacity_drops_oldest() {
+        let mut house = SmartHouse::new("Home");
+        house.set_audit_capacity(2);
+        for room in ["a", "b", "c"] {
+            house.add(SmartRoom::new(room)).unwrap();
+        }
+
+        let rooms: Vec<_> = house
+            .audit_entries()
+            .map(|e| match &e.change {
+                HouseEvent::RoomAdded { room } => room.as_str(),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(rooms, ["b", "c"]);
+        assert_eq!(house.audit_log().dropped(), 1);
+
+        house.set_audit_capacity(1);
+        assert_eq!(house.audit_log().len(), 1);
+        assert_eq!(house.audit_log().dropped(), 2);
+
+        house.set_audit_capacity(0);
+        house.remove_room("a").unwrap();
+        assert!(house.audit_log().is_empty());
+        assert_eq!(house.audit_log().dropped(), 4);
+    }
+}
@@ -0,0 +1,164 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
+    error::Error,
+    fmt::Write,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{DeviceLocation, Named, Reportable, SmartHouse, SmartThermometer};
+
+/// Below this charge, in percent, a device is low on battery unless the house says
+/// otherwise.
+pub const DEFAULT_LOW_BATTERY: u8 = 20;
+
+/// A device running on a battery.
+pub trait BatteryPowered {
+    /// The charge in percent, or `None` if the device has not reported it yet. `Some(0)`
+    /// is a flat battery, not a missing value.
+    fn battery_level(&self) -> Option<u8>;
+
+    /// Records a charge reported by the device, as the receiving side does. Values over
+    /// 100 are taken as 100.
+    fn set_battery_level(&self, percent: u8);
+}
+
+// u8::MAX означает, что заряд ещё неизвестен
+const UNKNOWN: u8 = u8::MAX;
+
+pub(crate) struct Battery(AtomicU8);
+
+impl Battery {
+    fn get(&self) -> Option<u8> {
+        Some(self.0.load(Ordering::SeqCst)).filter(|&level| level != UNKNOWN)
+    }
+
+    fn set(&self, percent: u8) {
+        self.0.store(percent.min(100), Ordering::SeqCst);
+    }
+}
+
+impl Default for Battery {
+    fn default() -> Self {
+        Self(AtomicU8::new(UNKNOWN))
+    }
+}
+
+impl Clone for Battery {
+    fn clone(&self) -> Self {
+        Self(AtomicU8::new(self.0.load(Ordering::SeqCst)))
+    }
+}
+
+impl BatteryPowered for SmartThermometer {
+    fn battery_level(&self) -> Option<u8> {
+        self.battery.get()
+    }
+
+    fn set_battery_level(&self, percent: u8) {
+        self.battery.set(percent);
+        self.battery_changed();
+    }
+}
+
+impl SmartHouse {
+    pub fn low_battery_threshold(&self) -> u8 {
+        self.low_battery
+    }
+
+    /// Devices charged below `percent` count as low on battery; the default is
+    /// [`DEFAULT_LOW_BATTERY`].
+    pub fn set_low_battery_threshold(&mut self, percent: u8) {
+        self.low_battery = percent;
+    }
+
+    /// Battery devices charged below the threshold, emptiest first. Devices without a
+    /// battery, or that have not reported a charge yet, are left out.
+    pub fn low_battery_devices(&self) -> Vec<(DeviceLocation, u8)> {
+        let mut low: Vec<_> = self
+            .all_devices()
+            .filter_map(|(room, device)| {
+                let level = device.as_battery_powered()?.battery_level()?;
+                let path = DeviceLocation {
+                    house: None,
+                    room: room.name().into(),
+                    device: device.name().into(),
+                };
+                (level < self.low_battery).then_some((path, level))
+            })
+            .collect();
+        // сортировка устойчивая: при равном заряде порядок как в доме
+        low.sort_by_key(|&(_, level)| level);
+        low
+    }
+}
+
+/// Devices low on battery, see [`SmartHouse::low_battery_devices`].
+#[derive(Debug)]
+pub struct BatteryReportProvider;
+
+impl Reportable for BatteryReportProvider {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        let low = house.low_battery_devices();
+        if low.is_empty() {
+            return Ok(String::from("No devices low on battery\n"));
+        }
+        let mut out = String::new();
+        for (path, level) in low {
+            writeln!(out, "{path}: {level}%")?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::*;
+    use crate::{Pluggable, SmartRoom, SmartSocket};
+
+    fn thermometer(name: &str, level: Option<u8>) -> Arc<SmartThermometer> {
+        let thermometer = Arc::new(SmartThermometer::new(name));
+        if let Some(level) = level {
+            thermometer.set_battery_level(level);
+        }
+        thermometer
+    }
+
+    #[test]
+    fn flat_battery_is_not_unknown() {
+        let thermometer = SmartThermometer::new("t1");
+        assert_eq!(thermometer.battery_level(), None);
+        thermometer.set_battery_level(0);
+        assert_eq!(thermometer.battery_level(), Some(0));
+        thermometer.set_battery_level(250);
+        assert_eq!(thermometer.clone().battery_level(), Some(100));
+        assert!(SmartSocket::new("s1").as_battery_powered().is_none());
+    }
+
+    #[test]
+    fn report_lists_low_devices_emptiest_first() {
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(thermometer("t1", Some(15))).unwrap();
+        hall.plug(thermometer("t2", Some(60))).unwrap();
+        hall.plug(thermometer("t3", None)).unwrap();
+        hall.plug(SmartSocket::new("s1")).unwrap();
+        let mut bedroom = SmartRoom::new("bedroom");
+        bedroom.plug(thermometer("t4", Some(0))).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+        house.add(bedroom).unwrap();
+
+        assert_eq!(
+            house.create_report(BatteryReportProvider).unwrap(),
+            "bedroom/t4: 0%\nhall/t1: 15%\n"
+        );
+        house.set_low_battery_threshold(10);
+        assert_eq!(house.low_battery_devices().len(), 1);
+        house.set_low_battery_threshold(0);
+        assert_eq!(
+            house.create_report(BatteryReportProvider).unwrap(),
+            "No devices low on battery\n"
+        );
+    }
+}
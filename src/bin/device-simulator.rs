@@ -0,0 +1,354 @@
+// Симулятор сетевых устройств для демонстраций и интеграционных тестов.
+//
+// Поднимает N розеток и M термометров на последовательных портах, печатает карту портов
+// и строку `ready`, после чего работает до остановки процесса.
+
+use std::{
+    fs,
+    io::{self, Write},
+    process::ExitCode,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lesson_3::{
+    net::{ServerHandle, SocketServer},
+    udp::{EmitterHandle, ThermometerEmitter},
+};
+
+const USAGE: &str = "\
+usage: device-simulator [--config FILE] [options]
+
+options (also accepted as `key = value` lines in the config file):
+    --sockets N              number of socket servers (default 1)
+    --thermometers M         number of thermometer emitters (default 1)
+    --host HOST              address to bind (default 127.0.0.1)
+    --base-port PORT         first port, 0 picks free ports (default 7000)
+    --temperature C          mean thermometer value (default 21)
+    --jitter C               maximum deviation from the mean (default 0.5)
+    --drop-probability P     chance per tick to drop every connection (default 0)
+    --interval-ms MS         emit and fault-injection tick (default 1000)
+    --seed N                 random seed (default: current time)
+    --token TOKEN            require this token on socket servers";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Config {
+    sockets: usize,
+    thermometers: usize,
+    host: String,
+    base_port: u16,
+    temperature: f64,
+    jitter: f64,
+    drop_probability: f64,
+    interval: Duration,
+    seed: Option<u64>,
+    token: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sockets: 1,
+            thermometers: 1,
+            host: "127.0.0.1".to_string(),
+            base_port: 7000,
+            temperature: 21.0,
+            jitter: 0.5,
+            drop_probability: 0.0,
+            interval: Duration::from_secs(1),
+            seed: None,
+            token: None,
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{key}: invalid value {value:?}"))
+}
+
+impl Config {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "sockets" => self.sockets = number(key, value)?,
+            "thermometers" => self.thermometers = number(key, value)?,
+            "host" => self.host = value.to_string(),
+            "base-port" => self.base_port = number(key, value)?,
+            "temperature" => self.temperature = number(key, value)?,
+            "jitter" => self.jitter = number::<f64>(key, value)?.abs(),
+            "drop-probability" => {
+                let p: f64 = number(key, value)?;
+                if !(0.0..=1.0).contains(&p) {
+                    return Err(format!("{key}: {p} is not a probability"));
+                }
+                self.drop_probability = p;
+            }
+            "interval-ms" => match number(key, value)? {
+                0 => return Err(format!("{key}: must be positive")),
+                ms => self.interval = Duration::from_millis(ms),
+            },
+            "seed" => self.seed = Some(number(key, value)?),
+            "token" => self.token = Some(value.to_string()),
+            other => return Err(format!("unknown option {other}")),
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, text: &str) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected key = value", i + 1))?;
+            self.set(key.trim(), value.trim())
+                .map_err(|e| format!("line {}: {e}", i + 1))?;
+        }
+
+        Ok(())
+    }
+
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let key = arg
+                .strip_prefix("--")
+                .ok_or(format!("unexpected argument {arg}"))?;
+            let value = args.next().ok_or(format!("--{key} needs a value"))?;
+
+            match key {
+                "config" => {
+                    let text = fs::read_to_string(&value).map_err(|e| format!("{value}: {e}"))?;
+                    config.load(&text)?;
+                }
+                key => config.set(key, &value)?,
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn port(&self, index: usize) -> Result<u16, String> {
+        if self.base_port == 0 {
+            return Ok(0);
+        }
+
+        u16::try_from(index)
+            .ok()
+            .and_then(|i| self.base_port.checked_add(i))
+            .ok_or(format!(
+                "port range starting at {} overflows",
+                self.base_port
+            ))
+    }
+}
+
+// xorshift64*: детерминированный при заданном seed, внешние зависимости не нужны
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        bits as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+}
+
+struct Simulation {
+    sockets: Vec<ServerHandle>,
+    thermometers: Vec<EmitterHandle>,
+}
+
+fn start(config: &Config, seed: u64) -> io::Result<Simulation> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+    let mut sockets = Vec::with_capacity(config.sockets);
+    let mut thermometers = Vec::with_capacity(config.thermometers);
+
+    for i in 0..config.sockets {
+        let port = config.port(i).map_err(invalid)?;
+        let mut server = SocketServer::bind(format!("socket-{}", i + 1), (&*config.host, port))?;
+        if let Some(token) = &config.token {
+            server = server.with_token(token.clone());
+        }
+        sockets.push(server.spawn()?);
+    }
+
+    for i in 0..config.thermometers {
+        let port = config.port(config.sockets + i).map_err(invalid)?;
+        let (mean, jitter) = (config.temperature, config.jitter);
+        let mut rng = Rng::new(seed.wrapping_add(i as u64 + 1));
+
+        let emitter =
+            ThermometerEmitter::bind(format!("thermometer-{}", i + 1), (&*config.host, port))?
+                .with_interval(config.interval)
+                .with_source(move || mean + jitter * (rng.next_f64() * 2.0 - 1.0));
+        thermometers.push(emitter.spawn()?);
+    }
+
+    Ok(Simulation {
+        sockets,
+        thermometers,
+    })
+}
+
+fn port_map(simulation: &Simulation) -> String {
+    let mut out = String::new();
+    for socket in &simulation.sockets {
+        out.push_str(&format!(
+            "socket {} {}\n",
+            socket.name(),
+            socket.local_addr()
+        ));
+    }
+    for (i, thermometer) in simulation.thermometers.iter().enumerate() {
+        out.push_str(&format!(
+            "thermometer thermometer-{} {}\n",
+            i + 1,
+            thermometer.local_addr()
+        ));
+    }
+    out.push_str("ready\n");
+    out
+}
+
+fn main() -> ExitCode {
+    let config = match Config::parse(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let seed = config.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |d| d.as_nanos() as u64)
+    });
+
+    let simulation = match start(&config, seed) {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(port_map(&simulation).as_bytes());
+    let _ = stdout.flush();
+
+    let mut rng = Rng::new(seed);
+    loop {
+        thread::sleep(config.interval);
+
+        if config.drop_probability > 0.0 {
+            for socket in &simulation.sockets {
+                if rng.chance(config.drop_probability) {
+                    socket.drop_connections();
+                }
+            }
+            for thermometer in &simulation.thermometers {
+                if rng.chance(config.drop_probability) {
+                    thermometer.drop_subscribers();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Config, String> {
+        Config::parse(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn flags_override_defaults() {
+        let config = parse(
+            "--sockets 3 --thermometers 2 --base-port 9000 --jitter -1.5 \
+             --drop-probability 0.25 --interval-ms 50 --seed 7 --token abc",
+        )
+        .unwrap();
+
+        assert_eq!(config.sockets, 3);
+        assert_eq!(config.thermometers, 2);
+        assert_eq!(config.base_port, 9000);
+        assert_eq!(config.jitter, 1.5);
+        assert_eq!(config.drop_probability, 0.25);
+        assert_eq!(config.interval, Duration::from_millis(50));
+        assert_eq!(config.seed, Some(7));
+        assert_eq!(config.token.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn config_file_lines() {
+        let mut config = Config::default();
+        config
+            .load("# demo\nsockets = 4\n\n  temperature=18.5  \n")
+            .unwrap();
+
+        assert_eq!(config.sockets, 4);
+        assert_eq!(config.temperature, 18.5);
+        assert!(config.load("sockets 4").is_err());
+        assert!(config.load("colour = red").is_err());
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!(parse("--sockets many").is_err());
+        assert!(parse("--drop-probability 1.5").is_err());
+        assert!(parse("--interval-ms 0").is_err());
+        assert!(parse("--sockets").is_err());
+        assert!(parse("sockets 1").is_err());
+    }
+
+    #[test]
+    fn consecutive_ports() {
+        let config = parse("--base-port 7000").unwrap();
+        assert_eq!(config.port(0).unwrap(), 7000);
+        assert_eq!(config.port(3).unwrap(), 7003);
+
+        let config = parse("--base-port 65535").unwrap();
+        assert!(config.port(1).is_err());
+
+        let config = parse("--base-port 0").unwrap();
+        assert_eq!(config.port(5).unwrap(), 0);
+    }
+
+    #[test]
+    fn rng_is_deterministic_and_bounded() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        for _ in 0..1000 {
+            let value = a.next_f64();
+            assert_eq!(value, b.next_f64());
+            assert!((0.0..1.0).contains(&value));
+        }
+        assert!(!Rng::new(1).chance(0.0));
+        assert!(Rng::new(1).chance(1.0));
+    }
+}
@@ -0,0 +1,377 @@
+// Консольный клиент для проверки дома по сети без написания кода на Rust
+
+use std::{fmt::Write, process::ExitCode};
+
+use lesson_3::{
+    net::{HouseClient, NetError},
+    protocol::HouseLayout,
+};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+
+const USAGE: &str = "\
+usage: smarthouse-cli [--addr HOST:PORT] [--token TOKEN] <command>
+
+commands:
+    list-rooms
+    list-devices <room>
+    status <room> <device>
+    socket on|off <room> <device>
+    report [--format json|text]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    ListRooms,
+    ListDevices {
+        room: String,
+    },
+    Status {
+        room: String,
+        device: String,
+    },
+    Socket {
+        room: String,
+        device: String,
+        on: bool,
+    },
+    Report {
+        format: Format,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    addr: String,
+    token: Option<String>,
+    command: Command,
+}
+
+fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut args = args.into_iter();
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut token = None;
+
+    let command = loop {
+        match args.next().as_deref() {
+            Some("--addr") => addr = args.next().ok_or("--addr needs a value")?,
+            Some("--token") => token = Some(args.next().ok_or("--token needs a value")?),
+            Some(command) => break command.to_string(),
+            None => return Err("missing command".to_string()),
+        }
+    };
+
+    let mut operand = |what: &str| args.next().ok_or(format!("{command}: missing {what}"));
+    let parsed = match command.as_str() {
+        "list-rooms" => Command::ListRooms,
+        "list-devices" => Command::ListDevices {
+            room: operand("room")?,
+        },
+        "status" => Command::Status {
+            room: operand("room")?,
+            device: operand("device")?,
+        },
+        "socket" => {
+            let on = match operand("on|off")?.as_str() {
+                "on" => true,
+                "off" => false,
+                other => return Err(format!("socket: expected on or off, got {other}")),
+            };
+            Command::Socket {
+                room: operand("room")?,
+                device: operand("device")?,
+                on,
+            }
+        }
+        "report" => {
+            let format = match args.next().as_deref() {
+                None => Format::Text,
+                Some("--format") => match args.next().as_deref() {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    Some(other) => return Err(format!("report: unknown format {other}")),
+                    None => return Err("report: --format needs a value".to_string()),
+                },
+                Some(other) => return Err(format!("report: unexpected argument {other}")),
+            };
+            Command::Report { format }
+        }
+        other => return Err(format!("unknown command {other}")),
+    };
+
+    if let Some(extra) = args.next() {
+        return Err(format!("{command}: unexpected argument {extra}"));
+    }
+
+    Ok(Args {
+        addr,
+        token,
+        command: parsed,
+    })
+}
+
+fn render_rooms(layout: &HouseLayout) -> String {
+    layout.rooms.iter().fold(String::new(), |mut out, room| {
+        let _ = writeln!(out, "{}", room.name);
+        out
+    })
+}
+
+fn render_devices(layout: &HouseLayout, room: &str) -> Result<String, NetError> {
+    let room = layout
+        .rooms
+        .iter()
+        .find(|r| r.name == room)
+        .ok_or_else(|| NetError::NotFound(format!("room {room}")))?;
+
+    Ok(room.devices.iter().fold(String::new(), |mut out, device| {
+        let _ = writeln!(out, "{device}");
+        out
+    }))
+}
+
+fn json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn render_json(layout: &HouseLayout) -> String {
+    let mut out = String::from("{\"house\":");
+    json_string(&mut out, &layout.name);
+    out.push_str(",\"rooms\":[");
+
+    for (i, room) in layout.rooms.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":");
+        json_string(&mut out, &room.name);
+        out.push_str(",\"devices\":[");
+        for (j, device) in room.devices.iter().enumerate() {
+            if j > 0 {
+                out.push(',');
+            }
+            json_string(&mut out, device);
+        }
+        out.push_str("]}");
+    }
+
+    out.push_str("]}\n");
+    out
+}
+
+fn run(args: Args) -> Result<String, NetError> {
+    let mut client = HouseClient::connect(&args.addr)?;
+    if let Some(token) = args.token {
+        client = client.with_token(token)?;
+    }
+
+    match args.command {
+        Command::ListRooms => Ok(render_rooms(&client.layout()?)),
+        Command::ListDevices { room } => render_devices(&client.layout()?, &room),
+        Command::Status { room, device } => Ok(format!("{}\n", client.status(&room, &device)?)),
+        Command::Socket { room, device, on } => {
+            let on = client.set_state(&room, &device, on)?;
+            Ok(format!("{device}: {}\n", if on { "on" } else { "off" }))
+        }
+        Command::Report {
+            format: Format::Text,
+        } => Ok(client.layout()?.to_string()),
+        Command::Report {
+            format: Format::Json,
+        } => Ok(render_json(&client.layout()?)),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(out) => {
+            print!("{out}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::*;
+    use lesson_3::{
+        net::HouseServer, protocol::RoomLayout, SmartHouse, SmartSocket, SmartThermometer,
+    };
+
+    fn args(line: &str) -> Result<Args, String> {
+        parse(line.split_whitespace().map(str::to_string))
+    }
+
+    fn command(line: &str) -> Command {
+        args(line).unwrap().command
+    }
+
+    fn layout() -> HouseLayout {
+        HouseLayout {
+            name: "hell".to_string(),
+            rooms: vec![
+                RoomLayout {
+                    name: "limb".to_string(),
+                    devices: vec!["s1".to_string(), "t \"1\"".to_string()],
+                },
+                RoomLayout {
+                    name: "lust".to_string(),
+                    devices: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(command("list-rooms"), Command::ListRooms);
+        assert_eq!(
+            command("list-devices limb"),
+            Command::ListDevices {
+                room: "limb".to_string()
+            }
+        );
+        assert_eq!(
+            command("status limb s1"),
+            Command::Status {
+                room: "limb".to_string(),
+                device: "s1".to_string()
+            }
+        );
+        assert_eq!(
+            command("socket off limb s1"),
+            Command::Socket {
+                room: "limb".to_string(),
+                device: "s1".to_string(),
+                on: false
+            }
+        );
+        assert_eq!(
+            command("report"),
+            Command::Report {
+                format: Format::Text
+            }
+        );
+        assert_eq!(
+            command("report --format json"),
+            Command::Report {
+                format: Format::Json
+            }
+        );
+    }
+
+    #[test]
+    fn parse_global_options() {
+        let parsed = args("--addr 10.0.0.1:9000 --token s3cr3t list-rooms").unwrap();
+        assert_eq!(parsed.addr, "10.0.0.1:9000");
+        assert_eq!(parsed.token.as_deref(), Some("s3cr3t"));
+
+        let parsed = args("list-rooms").unwrap();
+        assert_eq!(parsed.addr, DEFAULT_ADDR);
+        assert_eq!(parsed.token, None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(args("").is_err(), "Command is required");
+        assert!(args("--addr").is_err(), "Option value is required");
+        assert!(args("explode").is_err(), "Unknown command");
+        assert!(args("list-devices").is_err(), "Room is required");
+        assert!(args("status limb").is_err(), "Device is required");
+        assert!(args("socket toggle limb s1").is_err(), "Only on/off");
+        assert!(args("report --format xml").is_err(), "Unknown format");
+        assert!(args("list-rooms extra").is_err(), "Trailing argument");
+    }
+
+    #[test]
+    fn render_listings() {
+        assert_eq!(render_rooms(&layout()), "limb\nlust\n");
+        assert_eq!(render_devices(&layout(), "limb").unwrap(), "s1\nt \"1\"\n");
+        assert_eq!(render_devices(&layout(), "lust").unwrap(), "");
+        assert!(matches!(
+            render_devices(&layout(), "hall"),
+            Err(NetError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn render_reports() {
+        assert_eq!(
+            layout().to_string(),
+            "-> House: hell\n--> Room: limb\n----> Device: s1\n----> Device: t \"1\"\n--> Room: lust\n"
+        );
+        assert_eq!(
+            render_json(&layout()),
+            "{\"house\":\"hell\",\"rooms\":[{\"name\":\"limb\",\"devices\":[\"s1\",\"t \\\"1\\\"\"]},{\"name\":\"lust\",\"devices\":[]}]}\n"
+        );
+
+        let mut escaped = String::new();
+        json_string(&mut escaped, "a\\b\n\u{1}");
+        assert_eq!(escaped, "\"a\\\\b\\n\\u0001\"");
+    }
+
+    #[test]
+    fn status_shows_device_state() {
+        const CONNECTIONS: usize = 4;
+
+        let server = HouseServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let serving = thread::spawn(move || {
+            let socket = SmartSocket::new("s1");
+            socket.set_load(60.0);
+            socket.turn_on();
+            let thermometer = SmartThermometer::new("t1");
+            thermometer.set_temperature(21.5);
+            let house = SmartHouse::builder("hell")
+                .room("limb", |r| {
+                    r.device(Arc::new(socket)).device(Arc::new(thermometer))
+                })
+                .build()
+                .unwrap();
+            for _ in 0..CONNECTIONS {
+                server.serve_once(&house).unwrap();
+            }
+        });
+
+        // каждый запуск открывает своё соединение
+        let cli = |line: &str| run(args(&format!("--addr {addr} {line}")).unwrap()).unwrap();
+        assert_eq!(cli("status limb s1"), "on, 60.0 W\n");
+        assert_eq!(cli("status limb t1"), "21.5 °C\n");
+        assert_eq!(cli("socket off limb s1"), "s1: off\n");
+        assert_eq!(cli("status limb s1"), "off, 0.0 W\n");
+        serving.join().unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{error::Error, fmt::Write, sync::atomic::Ordering};
+
+use crate::{
+    devices::downcast, CommandError, DeviceCommand, Reportable, SmartHouse, SmartRoom, SmartSocket,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetEvent {
+    Exceeded {
+        room: String,
+        power: f64,
+        budget: f64,
+    },
+    /// The room is back within its budget, or its budget was lifted.
+    Restored {
+        room: String,
+        power: f64,
+        budget: Option<f64>,
+    },
+}
+
+type Callback = dyn Fn(&BudgetEvent) + Send + Sync;
+type Listener = Box<Callback>;
+
+#[derive(Default, Clone)]
+pub(crate) struct Budgets {
+    enforce: bool,
+    listeners: Vec<Arc<Callback>>,
+}
+
+impl SmartRoom {
+    /// Limits the power drawn through the room's sockets, in watts.
+    pub fn set_power_budget(&mut self, watts: f64) {
+        self.power_budget = Some(watts);
+    }
+
+    pub fn clear_power_budget(&mut self) {
+        self.power_budget = None;
+    }
+
+    pub fn power_budget(&self) -> Option<f64> {
+        self.power_budget
+    }
+
+    /// Power drawn through the room's sockets right now, in watts.
+    pub fn power(&self) -> f64 {
+        self.live_devices()
+            .filter_map(|device| downcast::<SmartSocket>(&*device).map(SmartSocket::power))
+            .fold(0.0, |total, power| total + power)
+    }
+
+    /// Whether the sockets draw more than the budget; drawing exactly the budget is fine.
+    pub fn is_over_budget(&self) -> bool {
+        self.power_budget
+            .is_some_and(|budget| self.power() > budget)
+    }
+}
+
+impl SmartHouse {
+    /// Makes [`execute`](Self::execute) refuse a command that would take a room with a
+    /// budget over it, with [`CommandError::OverBudget`]. Commands that lower the draw
+    /// always go through. Off by default: sockets switched directly are never checked.
+    pub fn enforce_power_budgets(&mut self, enforce: bool) {
+        self.budgets.enforce = enforce;
+    }
+
+    /// Calls `listener` whenever a room goes over its budget or comes back within it.
+    pub fn on_budget(&mut self, listener: Listener) {
+        self.budgets.listeners.push(Arc::from(listener));
+    }
+
+    /// Looks at every room and reports rooms that went over or came back since the last
+    /// look. `execute` looks at the room it changed on its own; sockets switched directly
+    /// are seen at the next check.
+    pub fn check_budgets(&self) -> Vec<BudgetEvent> {
+        self.rooms
+            .iter()
+            .filter_map(|room| self.check_budget(room))
+            .collect()
+    }
+
+    pub(crate) fn check_budget(&self, room: &SmartRoom) -> Option<BudgetEvent> {
+        let over = room.is_over_budget();
+        if room.over_budget.swap(over, Ordering::SeqCst) == over {
+            return None;
+        }
+        let power = room.power();
+        let event = match (over, room.power_budget) {
+            (true, Some(budget)) => BudgetEvent::Exceeded {
+                room: room.name.clone(),
+                power,
+                budget,
+            },
+            (_, budget) => BudgetEvent::Restored {
+                room: room.name.clone(),
+                power,
+                budget,
+            },
+        };
+        for listener in &self.budgets.listeners {
+            listener(&event);
+        }
+        Some(event)
+    }
+
+    pub(crate) fn check_power(
+        &self,
+        room: &SmartRoom,
+        socket: &SmartSocket,
+        command: DeviceCommand,
+    ) -> Result<(), CommandError> {
+        let Some(budget) = room.power_budget.filter(|_| self.budgets.enforce) else {
+            return Ok(());
+        };
+        let draw = match command {
+            DeviceCommand::TurnOn => socket.load(),
+            DeviceCommand::SetLoad(watts) if socket.is_on() => watts,
+            _ => return Ok(()),
+        };
+        let now = room.power();
+        let power = now - socket.power() + draw;
+        if power > budget && power > now {
+            return Err(CommandError::OverBudget {
+                room: room.name.clone(),
+                power,
+                budget,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Power drawn in every room, with its budget, flagging rooms over budget.
+#[derive(Debug)]
+pub struct EnergyReport;
+
+impl Reportable for EnergyReport {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        let mut out = String::new();
+        let mut total = 0.0;
+        for room in house.get_rooms() {
+            let power = room.power();
+            total += power;
+            write!(out, "{}: {power:.1} W", room.name)?;
+            if let Some(budget) = room.power_budget {
+                write!(out, " of {budget:.1} W")?;
+            }
+            match room.is_over_budget() {
+                true => writeln!(out, ", OVER BUDGET")?,
+                false => writeln!(out)?,
+            }
+        }
+        writeln!(out, "total: {total:.1} W")?;
+        Ok(out.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::*;
+    use crate::DeviceLocation;
+
+    fn socket(name: &str, watts: f64) -> Arc<SmartSocket> {
+        let socket = Arc::new(SmartSocket::new(name));
+        socket.set_load(watts);
+        socket
+    }
+
+    fn kitchen() -> (SmartHouse, [Arc<SmartSocket>; 3]) {
+        let sockets = [
+            socket("kettle", 1500.0),
+            socket("oven", 800.0),
+            socket("toaster", 700.0),
+        ];
+        let mut kitchen = SmartRoom::new("kitchen");
+        kitchen.set_power_budget(2300.0);
+        for socket in &sockets {
+            kitchen.plug(socket.clone()).unwrap();
+        }
+        let mut house = SmartHouse::new("home");
+        house.add(kitchen).unwrap();
+        (house, sockets)
+    }
+
+    fn path(text: &str) -> DeviceLocation {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn exactly_the_budget_is_not_over() {
+        let (house, [kettle, oven, _]) = kitchen();
+        kettle.turn_on();
+        oven.turn_on();
+        let kitchen = house.room("kitchen").unwrap();
+        assert_eq!(kitchen.power(), 2300.0);
+        assert!(!kitchen.is_over_budget());
+        assert!(house.check_budgets().is_empty());
+    }
+
+    #[test]
+    fn one_load_change_pushes_the_room_over() {
+        let (mut house, [kettle, oven, _]) = kitchen();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        house.on_budget(Box::new(move |event| {
+            sink.lock().unwrap().push(event.clone())
+        }));
+        kettle.turn_on();
+        oven.turn_on();
+
+        oven.set_load(801.0);
+        assert!(house.room("kitchen").unwrap().is_over_budget());
+        assert_eq!(
+            house.check_budgets(),
+            [BudgetEvent::Exceeded {
+                room: "kitchen".to_string(),
+                power: 2301.0,
+                budget: 2300.0,
+            }]
+        );
+        assert!(house.check_budgets().is_empty());
+
+        house
+            .execute(&path("kitchen/oven"), DeviceCommand::TurnOff)
+            .unwrap();
+        assert!(matches!(
+            seen.lock().unwrap()[..],
+            [BudgetEvent::Exceeded { .. }, BudgetEvent::Restored { .. }]
+        ));
+    }
+
+    #[test]
+    fn enforced_budget_refuses_further_sockets() {
+        let (mut house, [kettle, oven, toaster]) = kitchen();
+        house.enforce_power_budgets(true);
+        house
+            .execute(&path("kettle"), DeviceCommand::TurnOn)
+            .unwrap();
+        house.execute(&path("oven"), DeviceCommand::TurnOn).unwrap();
+
+        assert_eq!(
+            house.execute(&path("toaster"), DeviceCommand::TurnOn),
+            Err(CommandError::OverBudget {
+                room: "kitchen".to_string(),
+                power: 3000.0,
+                budget: 2300.0,
+            })
+        );
+        assert!(!toaster.is_on());
+        assert!(house
+            .execute(&path("oven"), DeviceCommand::SetLoad(900.0))
+            .is_err());
+
+        // прямое включение мимо дома не проверяется, но выключать можно всегда
+        toaster.turn_on();
+        house
+            .execute(&path("kettle"), DeviceCommand::TurnOff)
+            .unwrap();
+        assert!(!kettle.is_on() && oven.is_on());
+    }
+
+    #[test]
+    fn energy_report_flags_rooms_over_budget() {
+        let (mut house, [kettle, oven, toaster]) = kitchen();
+        house.add(SmartRoom::new("hall")).unwrap();
+        kettle.turn_on();
+        oven.turn_on();
+        toaster.turn_on();
+        assert_eq!(
+            house.create_report(EnergyReport).unwrap(),
+            "kitchen: 3000.0 W of 2300.0 W, OVER BUDGET\nhall: 0.0 W\ntotal: 3000.0 W\n"
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{error::Error, fmt};
+
+use crate::{IntoDevice, Named, Pluggable, SmartHouse, SmartRoom, SmartSocket, SmartThermometer};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    DuplicateRoom(String),
+    DuplicateDevice {
+        room: String,
+        device: String,
+    },
+    InvalidRoomName(String),
+    InvalidDeviceName {
+        room: String,
+        device: String,
+    },
+    TooManyDevices {
+        room: String,
+        limit: usize,
+        devices: usize,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::DuplicateRoom(room) => write!(f, "room {room} already constructed"),
+            Violation::DuplicateDevice { room, device } => {
+                write!(f, "Device with name {device} already pluged in room {room}")
+            }
+            Violation::InvalidRoomName(room) => write!(f, "invalid room name {room:?}"),
+            Violation::InvalidDeviceName { room, device } => {
+                write!(f, "invalid device name {device:?} in room {room}")
+            }
+            Violation::TooManyDevices {
+                room,
+                limit,
+                devices,
+            } => write!(f, "room {room} holds {devices} devices, limit is {limit}"),
+        }
+    }
+}
+
+/// Every rule a builder broke, in the order the rooms and devices were added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildError(pub Vec<Violation>);
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, violation) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{violation}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for BuildError {}
+
+// Пустые имена и имена из одних пробелов не принимаем
+fn valid_name(name: &str) -> bool {
+    !name.trim().is_empty()
+}
+
+pub struct SmartRoomBuilder {
+    name: String,
+    devices: Vec<Arc<dyn Pluggable>>,
+    capacity_limit: Option<usize>,
+}
+
+impl fmt::Debug for SmartRoomBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let devices: Vec<_> = self.devices.iter().map(|d| d.name()).collect();
+        f.debug_struct("SmartRoomBuilder")
+            .field("name", &self.name)
+            .field("devices", &devices)
+            .field("capacity_limit", &self.capacity_limit)
+            .finish()
+    }
+}
+
+impl SmartRoomBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            devices: Vec::new(),
+            capacity_limit: None,
+        }
+    }
+
+    pub fn with_socket(self, name: impl Into<String>) -> Self {
+        self.with_device(SmartSocket::new(name))
+    }
+
+    pub fn with_thermometer(self, name: impl Into<String>) -> Self {
+        self.with_device(SmartThermometer::new(name))
+    }
+
+    pub fn with_device(mut self, device: impl IntoDevice) -> Self {
+        self.devices.push(device.into_device());
+        self
+    }
+
+    pub fn with_devices<T: IntoDevice>(mut self, devices: impl IntoIterator<Item = T>) -> Self {
+        self.devices
+            .extend(devices.into_iter().map(IntoDevice::into_device));
+        self
+    }
+
+    pub fn with_capacity_limit(mut self, limit: usize) -> Self {
+        self.capacity_limit = Some(limit);
+        self
+    }
+
+    /// Short form of [`with_socket`](Self::with_socket) for house builder closures.
+    pub fn socket(self, name: impl Into<String>) -> Self {
+        self.with_socket(name)
+    }
+
+    /// Short form of [`with_thermometer`](Self::with_thermometer).
+    pub fn thermometer(self, name: impl Into<String>) -> Self {
+        self.with_thermometer(name)
+    }
+
+    /// Short form of [`with_device`](Self::with_device).
+    pub fn device(self, device: impl IntoDevice) -> Self {
+        self.with_device(device)
+    }
+
+    pub fn build(self) -> Result<SmartRoom, BuildError> {
+        let mut violations = Vec::new();
+        let room = self.assemble(&mut violations);
+        match violations.is_empty() {
+            true => Ok(room),
+            false => Err(BuildError(violations)),
+        }
+    }
+
+    // Нарушения копятся в общий список, чтобы дом сообщил обо всех сразу
+    fn assemble(self, violations: &mut Vec<Violation>) -> SmartRoom {
+        if !valid_name(&self.name) {
+            violations.push(Violation::InvalidRoomName(self.name.clone()));
+        }
+        if let Some(limit) = self.capacity_limit.filter(|&l| self.devices.len() > l) {
+            violations.push(Violation::TooManyDevices {
+                room: self.name.clone(),
+                limit,
+                devices: self.devices.len(),
+            });
+        }
+
+        let mut room = SmartRoom::with_capacity(self.name, self.devices.len());
+        for device in self.devices {
+            let name = device.name().to_string();
+            if !valid_name(&name) {
+                violations.push(Violation::InvalidDeviceName {
+                    room: room.name().to_string(),
+                    device: name,
+                });
+            } else if room.plug(device).is_err() {
+                violations.push(Violation::DuplicateDevice {
+                    room: room.name().to_string(),
+                    device: name,
+                });
+            }
+        }
+        room
+    }
+}
+
+impl SmartRoom {
+    pub fn builder(name: impl Into<String>) -> SmartRoomBuilder {
+        SmartRoomBuilder::new(name)
+    }
+}
+
+#[derive(Debug)]
+pub struct SmartHouseBuilder {
+    name: String,
+    rooms: Vec<SmartRoomBuilder>,
+}
+
+impl SmartHouseBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            rooms: Vec::new(),
+        }
+    }
+
+    pub fn room(
+        self,
+        name: impl Into<String>,
+        f: impl FnOnce(SmartRoomBuilder) -> SmartRoomBuilder,
+    ) -> Self {
+        self.with_room(f(SmartRoomBuilder::new(name)))
+    }
+
+    pub fn with_room(mut self, room: SmartRoomBuilder) -> Self {
+        self.rooms.push(room);
+        self
+    }
+
+    /// Checks every room and device name at once and reports all violations together.
+    pub fn build(self) -> Result<SmartHouse, BuildError> {
+        let mut violations = Vec::new();
+        let mut house = SmartHouse::with_capacity(self.name, self.rooms.len());
+
+        for room in self.rooms {
+            let room = room.assemble(&mut violations);
+            let name = room.name().to_string();
+            if house.add(room).is_err() {
+                violations.push(Violation::DuplicateRoom(name));
+            }
+        }
+
+        match violations.is_empty() {
+            true => Ok(house),
+            false => Err(BuildError(violations)),
+        }
+    }
+}
+
+impl SmartHouse {
+    pub fn builder(name: impl Into<String>) -> SmartHouseBuilder {
+        SmartHouseBuilder::new(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_house() {
+        let shared: Arc<dyn Pluggable> = Arc::new(SmartSocket::new("s3"));
+        let house = SmartHouse::builder("hell")
+            .room("limb", |r| r.socket("s1").thermometer("t1"))
+            .room("lust", |r| r.socket("s2").device(shared.clone()))
+            .build()
+            .unwrap();
+
+        assert_eq!(house.get_rooms().len(), 2);
+        assert_eq!(house.room("limb").unwrap().devices(), ["s1", "t1"]);
+        assert_eq!(house.room("lust").unwrap().devices(), ["s2", "s3"]);
+        assert!(house.room("lust").unwrap().is_connected(shared.as_ref()));
+    }
+
+    #[test]
+    fn all_violations_are_reported() {
+        let err = SmartHouse::builder("hell")
+            .room("limb", |r| r.socket("s1").thermometer("s1"))
+            .room("lust", |r| r.socket("s2").socket("s2"))
+            .room("limb", |r| r)
+            .build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.0,
+            [
+                Violation::DuplicateDevice {
+                    room: "limb".to_string(),
+                    device: "s1".to_string()
+                },
+                Violation::DuplicateDevice {
+                    room: "lust".to_string(),
+                    device: "s2".to_string()
+                },
+                Violation::DuplicateRoom("limb".to_string()),
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "Device with name s1 already pluged in room limb; \
+             Device with name s2 already pluged in room lust; \
+             room limb already constructed"
+        );
+    }
+
+    #[test]
+    fn build_room_alone() {
+        let room =
+            SmartRoom::builder("Boiler")
+                .with_socket("Main socket")
+                .with_thermometer("T1")
+                .with_devices((1..=3).map(|i| {
+                    Arc::new(SmartSocket::new(format!("socket {i}"))) as Arc<dyn Pluggable>
+                }))
+                .with_capacity_limit(8)
+                .build()
+                .unwrap();
+
+        assert_eq!(
+            room.devices(),
+            ["Main socket", "T1", "socket 1", "socket 2", "socket 3"]
+        );
+    }
+
+    #[test]
+    fn room_validation() {
+        let err = SmartRoom::builder(" ")
+            .with_socket("")
+            .with_socket("s1")
+            .with_socket("s2")
+            .with_capacity_limit(2)
+            .build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.0,
+            [
+                Violation::InvalidRoomName(" ".to_string()),
+                Violation::TooManyDevices {
+                    room: " ".to_string(),
+                    limit: 2,
+                    devices: 3
+                },
+                Violation::InvalidDeviceName {
+                    room: " ".to_string(),
+                    device: "".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn builders_compose() {
+        let boiler = SmartRoom::builder("Boiler")
+            .with_socket("Main socket")
+            .with_capacity_limit(1);
+        let house = SmartHouse::builder("Home")
+            .with_room(boiler)
+            .room("Kitchen", |r| r.thermometer("T1"))
+            .build()
+            .unwrap();
+        assert_eq!(house.room("Boiler").unwrap().devices(), ["Main socket"]);
+
+        let crowded = SmartRoom::builder("Boiler")
+            .with_socket("s1")
+            .with_socket("s2")
+            .with_capacity_limit(1);
+        let err = SmartHouse::builder("Home")
+            .with_room(crowded)
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err.0[..], [Violation::TooManyDevices { .. }]));
+    }
+}
@@ -0,0 +1,169 @@
+//! What a device can do, independent of its type.
+//!
+//! A device tells about its capabilities through
+//! [`Pluggable::as_switchable`](crate::Pluggable::as_switchable) and
+//! [`as_measurable`](crate::Pluggable::as_measurable), so code working with `dyn Pluggable` never needs to know
+//! the concrete type. [`SmartHouse::execute`](crate::SmartHouse::execute), groups, scenes
+//! and house modes switch any device that is [`Switchable`].
+
+use alloc::boxed::Box;
+use core::error::Error;
+
+use crate::{SmartSocket, SmartThermometer};
+
+/// Why a device could not do what it was asked, e.g. a network socket that is offline.
+pub type CapabilityError = Box<dyn Error + Send + Sync>;
+
+/// A device that can be turned on and off.
+///
+/// ```
+/// use lesson_3::{
+///     CapabilityError, DeviceCommand, Named, Pluggable, SmartHouse, SmartRoom, Switchable,
+/// };
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// #[derive(Default)]
+/// struct Fan(AtomicBool);
+///
+/// impl Named for Fan {
+///     fn name(&self) -> &str {
+///         "fan"
+///     }
+/// }
+///
+/// impl Switchable for Fan {
+///     fn turn_on(&self) -> Result<(), CapabilityError> {
+///         Ok(self.0.store(true, Ordering::SeqCst))
+///     }
+///
+///     fn turn_off(&self) -> Result<(), CapabilityError> {
+///         Ok(self.0.store(false, Ordering::SeqCst))
+///     }
+///
+///     fn is_on(&self) -> Result<bool, CapabilityError> {
+///         Ok(self.0.load(Ordering::SeqCst))
+///     }
+/// }
+///
+/// impl Pluggable for Fan {
+///     fn boxed_clone(&self) -> std::sync::Arc<dyn Pluggable> {
+///         std::sync::Arc::new(Fan(AtomicBool::new(self.0.load(Ordering::SeqCst))))
+///     }
+///
+///     fn as_switchable(&self) -> Option<&dyn Switchable> {
+///         Some(self)
+///     }
+/// }
+///
+/// let mut house = SmartHouse::new("home");
+/// house.add(SmartRoom::new("hall")).unwrap();
+/// house.plug("hall", Fan::default()).unwrap();
+/// house.execute(&"hall/fan".parse().unwrap(), DeviceCommand::TurnOn).unwrap();
+/// ```
+pub trait Switchable {
+    fn turn_on(&self) -> Result<(), CapabilityError>;
+    fn turn_off(&self) -> Result<(), CapabilityError>;
+    fn is_on(&self) -> Result<bool, CapabilityError>;
+}
+
+/// A device that measures one quantity.
+pub trait Measurable {
+    /// What is measured, such as `temperature` or `power`.
+    fn metric_name(&self) -> &str;
+
+    /// The latest value, or `None` if there is none yet.
+    fn value(&self) -> Option<f64>;
+}
+
+impl Switchable for SmartSocket {
+    fn turn_on(&self) -> Result<(), CapabilityError> {
+        SmartSocket::turn_on(self);
+        Ok(())
+    }
+
+    fn turn_off(&self) -> Result<(), CapabilityError> {
+        SmartSocket::turn_off(self);
+        Ok(())
+    }
+
+    fn is_on(&self) -> Result<bool, CapabilityError> {
+        Ok(SmartSocket::is_on(self))
+    }
+}
+
+/// The power drawn right now, in watts.
+impl Measurable for SmartSocket {
+    fn metric_name(&self) -> &str {
+        "power"
+    }
+
+    fn value(&self) -> Option<f64> {
+        Some(self.power())
+    }
+}
+
+/// In °C.
+impl Measurable for SmartThermometer {
+    fn metric_name(&self) -> &str {
+        "temperature"
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.temperature()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Switchable for crate::net::SocketClient {
+    fn turn_on(&self) -> Result<(), CapabilityError> {
+        Ok(crate::net::SocketClient::turn_on(self)?)
+    }
+
+    fn turn_off(&self) -> Result<(), CapabilityError> {
+        Ok(crate::net::SocketClient::turn_off(self)?)
+    }
+
+    fn is_on(&self) -> Result<bool, CapabilityError> {
+        Ok(crate::net::SocketClient::is_on(self)?)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Measurable for crate::udp::ThermometerReceiver {
+    fn metric_name(&self) -> &str {
+        "temperature"
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.temperature()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pluggable;
+
+    fn capabilities(device: &dyn Pluggable) -> (bool, bool) {
+        (
+            device.as_switchable().is_some(),
+            device.as_measurable().is_some(),
+        )
+    }
+
+    #[test]
+    fn built_in_devices_report_their_capabilities() {
+        let socket = SmartSocket::new("s1");
+        socket.set_load(40.0);
+        let thermometer = SmartThermometer::new("t1");
+        assert_eq!(capabilities(&socket), (true, true));
+        assert_eq!(capabilities(&thermometer), (false, true));
+
+        let switch = socket.as_switchable().unwrap();
+        switch.turn_on().unwrap();
+        assert!(switch.is_on().unwrap());
+        let power = socket.as_measurable().unwrap();
+        assert_eq!((power.metric_name(), power.value()), ("power", Some(40.0)));
+        assert_eq!(thermometer.as_measurable().unwrap().value(), None);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::SmartRoom;
+
+/// The climate of a room from its measuring devices, see [`SmartRoom::climate`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ClimateSummary {
+    /// Mean of the temperature readings, in °C.
+    pub temperature: Option<f64>,
+    /// Mean of the humidity readings, in percent.
+    pub humidity: Option<f64>,
+}
+
+#[derive(Default)]
+struct Mean {
+    sum: f64,
+    count: u32,
+}
+
+impl Mean {
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn get(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / f64::from(self.count))
+    }
+}
+
+impl SmartRoom {
+    /// Averages what the room's [`Measurable`](crate::Measurable) devices measure as `temperature` and as
+    /// `humidity`. Devices without a reading yet are left out; a field is `None` when no
+    /// device in the room reads it.
+    pub fn climate(&self) -> ClimateSummary {
+        let (mut temperature, mut humidity) = (Mean::default(), Mean::default());
+        for device in self.live_devices() {
+            let Some(sensor) = device.as_measurable() else {
+                continue;
+            };
+            let mean = match sensor.metric_name() {
+                "temperature" => &mut temperature,
+                "humidity" => &mut humidity,
+                _ => continue,
+            };
+            if let Some(value) = sensor.value() {
+                mean.add(value);
+            }
+        }
+        ClimateSummary {
+            temperature: temperature.get(),
+            humidity: humidity.get(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::*;
+    use crate::{Measurable, Named, Pluggable, SmartSocket, SmartThermometer};
+
+    struct Hygrometer(f64);
+
+    impl Named for Hygrometer {
+        fn name(&self) -> &str {
+            "hygrometer"
+        }
+    }
+
+    impl Measurable for Hygrometer {
+        fn metric_name(&self) -> &str {
+            "humidity"
+        }
+
+        fn value(&self) -> Option<f64> {
+            Some(self.0)
+        }
+    }
+
+    impl Pluggable for Hygrometer {
+        fn boxed_clone(&self) -> Arc<dyn Pluggable> {
+            Arc::new(Hygrometer(self.0))
+        }
+
+        fn as_measurable(&self) -> Option<&dyn Measurable> {
+            Some(self)
+        }
+    }
+
+    #[test]
+    fn readings_are_averaged() {
+        let mut room = SmartRoom::new("hall");
+        for (name, celsius) in [("t1", 19.0), ("t2", 23.0)] {
+            let thermometer = SmartThermometer::new(name);
+            thermometer.set_temperature(celsius);
+            room.plug(thermometer).unwrap();
+        }
+        // без показаний термометр в среднее не входит
+        room.plug(SmartThermometer::new("t3")).unwrap();
+        room.plug(Hygrometer(45.0)).unwrap();
+        // мощность розетки к климату не относится
+        room.plug(SmartSocket::new("s1")).unwrap();
+
+        assert_eq!(
+            room.climate(),
+            ClimateSummary {
+                temperature: Some(21.0),
+                humidity: Some(45.0),
+            }
+        );
+    }
+
+    #[test]
+    fn room_without_sensors_has_no_climate() {
+        let mut room = SmartRoom::new("closet");
+        assert_eq!(room.climate(), ClimateSummary::default());
+        room.plug(SmartSocket::new("s1")).unwrap();
+        room.plug(SmartThermometer::new("t1")).unwrap();
+        assert_eq!(room.climate(), ClimateSummary::default());
+    }
+}
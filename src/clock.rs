@@ -0,0 +1,23 @@
+use std::{sync::Arc, time::SystemTime};
+
+/// Source of the current time for everything that stamps or ages readings and changes:
+/// the audit log, UDP receivers and heartbeat monitors. Tests swap in
+/// `testing::ManualClock`.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> SystemTime {
+        C::now(self)
+    }
+}
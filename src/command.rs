@@ -0,0 +1,382 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::{fmt, time::Duration};
+
+use crate::{
+    devices::downcast, CapabilityError, DeviceLocation, LocateError, Pluggable, SmartHouse,
+    SmartSocket,
+};
+
+/// An operation on one device, run by [`SmartHouse::execute`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceCommand {
+    TurnOn,
+    TurnOff,
+    /// Watts drawn through a socket.
+    SetLoad(f64),
+    /// No built-in device takes brightness or volume yet; both are refused as the wrong
+    /// kind. Colour and colour temperature will come with a light device.
+    SetBrightness(u8),
+    SetVolume(u8),
+}
+
+impl DeviceCommand {
+    /// Short name of the command, as used in errors and logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TurnOn => "turn_on",
+            Self::TurnOff => "turn_off",
+            Self::SetLoad(_) => "set_load",
+            Self::SetBrightness(_) => "set_brightness",
+            Self::SetVolume(_) => "set_volume",
+        }
+    }
+}
+
+/// A command that went through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandOutcome {
+    pub device: String,
+    /// The command that puts the device back the way it was before.
+    pub undo: DeviceCommand,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Missing(LocateError),
+    WrongKind {
+        device: String,
+        command: DeviceCommand,
+    },
+    // устройство само отказало, например сетевая розетка недоступна
+    Failed {
+        device: String,
+        reason: String,
+    },
+    /// The device is disabled for maintenance, see
+    /// [`SmartRoom::disable_device`](crate::SmartRoom::disable_device).
+    Disabled {
+        device: String,
+    },
+    /// Too many commands to the device too fast, see
+    /// [`SmartHouse::set_rate_limit`](crate::SmartHouse::set_rate_limit).
+    RateLimited {
+        device: String,
+        retry_after: Duration,
+    },
+    /// The device did not finish within the time given to
+    /// [`SmartHouse::execute_with_timeout`](crate::SmartHouse::execute_with_timeout).
+    Timeout {
+        device: String,
+        after: Duration,
+    },
+    /// The room's draw would go over its budget, see
+    /// [`SmartHouse::enforce_power_budgets`].
+    OverBudget {
+        room: String,
+        power: f64,
+        budget: f64,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Missing(err) => err.fmt(f),
+            CommandError::WrongKind { device, command } => {
+                write!(f, "device {device} does not support {}", command.name())
+            }
+            CommandError::Failed { device, reason } => {
+                write!(f, "device {device} failed: {reason}")
+            }
+            CommandError::Disabled { device } => write!(f, "device {device} is disabled"),
+            CommandError::RateLimited {
+                device,
+                retry_after,
+            } => write!(
+                f,
+                "device {device} is rate limited, retry in {retry_after:?}"
+            ),
+            CommandError::Timeout { device, after } => {
+                write!(f, "device {device} timed out after {after:?}")
+            }
+            CommandError::OverBudget {
+                room,
+                power,
+                budget,
+            } => write!(f, "room {room} would draw {power} W of {budget} W allowed"),
+        }
+    }
+}
+
+impl core::error::Error for CommandError {}
+
+impl SmartHouse {
+    /// Finds the device at `path` and runs `command` on it. Scenes, schedules, rules and
+    /// the house server all change devices through here, so none of them can change a
+    /// disabled device or go over a [rate limit](Self::set_rate_limit).
+    pub fn execute(
+        &self,
+        path: &DeviceLocation,
+        command: DeviceCommand,
+    ) -> Result<CommandOutcome, CommandError> {
+        self.execute_by(path, command, |device, command| run(&**device, command))
+    }
+
+    /// Like [`execute`](Self::execute), but gives up on a device that takes longer than
+    /// `timeout` with [`CommandError::Timeout`]. The command then runs on in a thread of
+    /// its own and is not waited for; its result, if any, is lost, and the room's budget
+    /// and telemetry are not updated. `None` waits as long as `execute` does.
+    #[cfg(feature = "std")]
+    pub fn execute_with_timeout(
+        &self,
+        path: &DeviceLocation,
+        command: DeviceCommand,
+        timeout: Option<Duration>,
+    ) -> Result<CommandOutcome, CommandError> {
+        self.execute_by(path, command, |device, command| match timeout {
+            Some(timeout) => run_within(device.clone(), command, timeout),
+            None => run(&**device, command),
+        })
+    }
+
+    fn execute_by(
+        &self,
+        path: &DeviceLocation,
+        command: DeviceCommand,
+        run: impl FnOnce(&Arc<dyn Pluggable>, DeviceCommand) -> Result<DeviceCommand, CommandError>,
+    ) -> Result<CommandOutcome, CommandError> {
+        let device = self.locate(path).map_err(CommandError::Missing)?;
+        let room = match path.room.is_empty() {
+            true => self.rooms.iter().find(|room| room.is_connected(&*device)),
+            false => self.room(&path.room),
+        };
+        if room.is_some_and(|room| !room.is_enabled(device.name())) {
+            return Err(CommandError::Disabled {
+                device: device.name().to_string(),
+            });
+        }
+        if !accepts(&*device, command) {
+            return Err(CommandError::WrongKind {
+                device: device.name().to_string(),
+                command,
+            });
+        }
+        if let (Some(room), Some(socket)) = (room, downcast::<SmartSocket>(&*device)) {
+            self.check_power(room, socket, command)?;
+        }
+        #[cfg(feature = "std")]
+        let rate_room = room.map_or(&path.room, |room| &room.name);
+        #[cfg(feature = "std")]
+        self.check_rate(rate_room, device.name())?;
+
+        let result = run(&device, command);
+        // отказ устройства лимит не расходует; команда с таймаутом до него дошла
+        #[cfg(feature = "std")]
+        if let Err(CommandError::Failed { .. }) = result {
+            self.refund_rate(rate_room, device.name());
+        }
+        let undo = result?;
+        if let Some(room) = room {
+            self.check_budget(room);
+            #[cfg(feature = "std")]
+            self.record_telemetry(room, &*device);
+        }
+        Ok(CommandOutcome {
+            device: device.name().to_string(),
+            undo,
+        })
+    }
+}
+
+// Команда выполняется в отдельном потоке, который при таймауте просто бросаем
+#[cfg(feature = "std")]
+fn run_within(
+    device: Arc<dyn Pluggable>,
+    command: DeviceCommand,
+    timeout: Duration,
+) -> Result<DeviceCommand, CommandError> {
+    let name = device.name().to_string();
+    let (done, result) = std::sync::mpsc::channel();
+    std::thread::spawn(move || done.send(run(&*device, command)));
+    result
+        .recv_timeout(timeout)
+        .unwrap_or_else(|err| match err {
+            std::sync::mpsc::RecvTimeoutError::Timeout => Err(CommandError::Timeout {
+                device: name,
+                after: timeout,
+            }),
+            // поток упал с паникой
+            std::sync::mpsc::RecvTimeoutError::Disconnected => Err(CommandError::Failed {
+                device: name,
+                reason: "device panicked".to_string(),
+            }),
+        })
+}
+
+pub(crate) fn switch(on: bool) -> DeviceCommand {
+    match on {
+        true => DeviceCommand::TurnOn,
+        false => DeviceCommand::TurnOff,
+    }
+}
+
+// Подходит ли команда устройству; то же решает run, но не трогая устройство
+fn accepts(device: &dyn Pluggable, command: DeviceCommand) -> bool {
+    match command {
+        DeviceCommand::TurnOn | DeviceCommand::TurnOff => device.as_switchable().is_some(),
+        DeviceCommand::SetLoad(_) => downcast::<SmartSocket>(device).is_some(),
+        DeviceCommand::SetBrightness(_) | DeviceCommand::SetVolume(_) => false,
+    }
+}
+
+// Возвращает команду, которая вернёт прежнее состояние
+pub(crate) fn run(
+    device: &dyn Pluggable,
+    command: DeviceCommand,
+) -> Result<DeviceCommand, CommandError> {
+    match (command, device.as_switchable()) {
+        (DeviceCommand::TurnOn | DeviceCommand::TurnOff, Some(switchable)) => {
+            let failed = |err: CapabilityError| CommandError::Failed {
+                device: device.name().to_string(),
+                reason: err.to_string(),
+            };
+            let was = switch(switchable.is_on().map_err(failed)?);
+            match command {
+                DeviceCommand::TurnOn => switchable.turn_on(),
+                _ => switchable.turn_off(),
+            }
+            .map_err(failed)?;
+            return Ok(was);
+        }
+        // нагрузка бывает только у встроенной розетки
+        (DeviceCommand::SetLoad(watts), _) => {
+            if let Some(socket) = downcast::<SmartSocket>(device) {
+                let was = DeviceCommand::SetLoad(socket.load());
+                socket.set_load(watts);
+                return Ok(was);
+            }
+        }
+        _ => {}
+    }
+
+    Err(CommandError::WrongKind {
+        device: device.name().to_string(),
+        command,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{small_house, socket};
+
+    fn path(text: &str) -> DeviceLocation {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn outcome_undoes_the_command() {
+        let house = small_house();
+        let socket = socket(&house, "limb", "s1");
+        socket.set_load(25.0);
+
+        let on = house
+            .execute(&path("limb/s1"), DeviceCommand::TurnOn)
+            .unwrap();
+        assert!(socket.is_on());
+        assert_eq!(on.undo, DeviceCommand::TurnOff);
+
+        let load = house
+            .execute(&path("s1"), DeviceCommand::SetLoad(60.0))
+            .unwrap();
+        assert_eq!(socket.load(), 60.0);
+
+        house.execute(&path("s1"), load.undo).unwrap();
+        house.execute(&path("s1"), on.undo).unwrap();
+        assert_eq!(socket.load(), 25.0);
+        assert!(!socket.is_on());
+    }
+
+    #[test]
+    fn commands_check_the_device_kind() {
+        let house = small_house();
+        assert_eq!(
+            house.execute(&path("limb/s1"), DeviceCommand::SetBrightness(50)),
+            Err(CommandError::WrongKind {
+                device: "s1".to_string(),
+                command: DeviceCommand::SetBrightness(50),
+            })
+        );
+        assert!(matches!(
+            house.execute(&path("limb/t1"), DeviceCommand::TurnOn),
+            Err(CommandError::WrongKind { .. })
+        ));
+        assert_eq!(
+            house.execute(&path("hall/s1"), DeviceCommand::TurnOn),
+            Err(CommandError::Missing(LocateError::Room("hall".to_string())))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn slow_devices_time_out() {
+        use std::time::Duration;
+
+        use crate::{CapabilityError, Named, Pluggable, Switchable};
+
+        // Зависает на полсекунды при любой команде
+        #[derive(Clone)]
+        struct Hung;
+
+        impl Named for Hung {
+            fn name(&self) -> &str {
+                "hung"
+            }
+        }
+
+        impl Switchable for Hung {
+            fn turn_on(&self) -> Result<(), CapabilityError> {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(())
+            }
+
+            fn turn_off(&self) -> Result<(), CapabilityError> {
+                self.turn_on()
+            }
+
+            fn is_on(&self) -> Result<bool, CapabilityError> {
+                Ok(false)
+            }
+        }
+
+        impl Pluggable for Hung {
+            crate::boxed_clone!();
+
+            fn as_switchable(&self) -> Option<&dyn Switchable> {
+                Some(self)
+            }
+        }
+
+        let mut house = small_house();
+        let socket = socket(&house, "limb", "s1");
+        house.plug("limb", Hung).unwrap();
+        let limit = Some(Duration::from_millis(50));
+        assert_eq!(
+            house.execute_with_timeout(&path("limb/hung"), DeviceCommand::TurnOn, limit),
+            Err(CommandError::Timeout {
+                device: "hung".to_string(),
+                after: Duration::from_millis(50),
+            })
+        );
+        house
+            .execute_with_timeout(&path("limb/s1"), DeviceCommand::TurnOn, limit)
+            .unwrap();
+        assert!(socket.is_on());
+        assert!(house
+            .execute_with_timeout(&path("limb/hung"), DeviceCommand::TurnOff, None)
+            .is_ok());
+    }
+}
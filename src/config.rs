@@ -0,0 +1,236 @@
+use alloc::string::String;
+use core::fmt;
+
+use crate::{Named, Pluggable, SmartHouse, SmartHouseError, SmartRoom};
+
+/// Limits a house enforces when rooms are added and devices plugged or moved through
+/// it; `None` means no limit. Changing the config does not touch what is already in the
+/// house, it only decides what comes next.
+///
+/// ```
+/// use lesson_3::{HouseConfig, SmartHouse, SmartRoom};
+///
+/// let config = HouseConfig {
+///     max_rooms: Some(1),
+///     ..HouseConfig::default()
+/// };
+/// let mut house = SmartHouse::with_config("home", config);
+/// house.add(SmartRoom::new("hall")).unwrap();
+/// assert!(house.add(SmartRoom::new("kitchen")).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HouseConfig {
+    pub max_rooms: Option<usize>,
+    pub max_devices_total: Option<usize>,
+    pub max_devices_per_room: Option<usize>,
+    /// The longest room or device name, in characters.
+    pub name_max_len: Option<usize>,
+}
+
+/// Which [`HouseConfig`] limit a change would break.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Rooms,
+    DevicesTotal,
+    DevicesPerRoom,
+    NameLength,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Rooms => f.write_str("rooms"),
+            Limit::DevicesTotal => f.write_str("devices in the house"),
+            Limit::DevicesPerRoom => f.write_str("devices in a room"),
+            Limit::NameLength => f.write_str("name length"),
+        }
+    }
+}
+
+fn check(limit: Limit, max: Option<usize>, attempted: usize) -> Result<(), SmartHouseError> {
+    match max {
+        Some(max) if attempted > max => Err(SmartHouseError::LimitExceeded {
+            limit,
+            max,
+            attempted,
+        }),
+        _ => Ok(()),
+    }
+}
+
+impl HouseConfig {
+    fn check_name(&self, name: &str) -> Result<(), SmartHouseError> {
+        check(Limit::NameLength, self.name_max_len, name.chars().count())
+    }
+}
+
+impl SmartHouse {
+    pub fn with_config(name: impl Into<String>, config: HouseConfig) -> Self {
+        let mut house = Self::new(name);
+        house.config = config;
+        house
+    }
+
+    pub fn config(&self) -> &HouseConfig {
+        &self.config
+    }
+
+    /// Takes effect with the next change; rooms and devices over a lowered limit stay.
+    pub fn set_config(&mut self, config: HouseConfig) {
+        self.config = config;
+    }
+
+    pub(crate) fn check_room_limits(&self, room: &SmartRoom) -> Result<(), SmartHouseError> {
+        let config = &self.config;
+        config.check_name(room.name())?;
+        check(Limit::Rooms, config.max_rooms, self.rooms.len() + 1)?;
+        let devices = room.device_names().count();
+        check(Limit::DevicesPerRoom, config.max_devices_per_room, devices)?;
+        for device in room.device_names() {
+            config.check_name(device)?;
+        }
+        check(
+            Limit::DevicesTotal,
+            config.max_devices_total,
+            self.device_count() + devices,
+        )
+    }
+
+    // При переносе общее число устройств не меняется, его проверяет только plug
+    pub(crate) fn check_device_limits(
+        &self,
+        room: &SmartRoom,
+        device: &dyn Pluggable,
+        moving: bool,
+    ) -> Result<(), SmartHouseError> {
+        let config = &self.config;
+        config.check_name(device.name())?;
+        let in_room = room.device_names().count() + 1;
+        check(Limit::DevicesPerRoom, config.max_devices_per_room, in_room)?;
+        if moving {
+            return Ok(());
+        }
+        check(
+            Limit::DevicesTotal,
+            config.max_devices_total,
+            self.device_count() + 1,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::SmartSocket;
+
+    fn exceeded(limit: Limit, max: usize, attempted: usize) -> SmartHouseError {
+        SmartHouseError::LimitExceeded {
+            limit,
+            max,
+            attempted,
+        }
+    }
+
+    fn room(name: &str, devices: &[&str]) -> SmartRoom {
+        let mut room = SmartRoom::new(name);
+        for device in devices {
+            room.plug(SmartSocket::new(*device)).unwrap();
+        }
+        room
+    }
+
+    #[test]
+    fn room_limit_can_be_raised() {
+        let config = HouseConfig {
+            max_rooms: Some(2),
+            ..HouseConfig::default()
+        };
+        let mut house = SmartHouse::with_config("home", config);
+        house.add(room("hall", &[])).unwrap();
+        house.add(room("kitchen", &[])).unwrap();
+        assert_eq!(
+            house.add(room("attic", &[])),
+            Err(exceeded(Limit::Rooms, 2, 3))
+        );
+        assert!(house.room("attic").is_none());
+
+        house.set_config(HouseConfig {
+            max_rooms: Some(3),
+            ..config
+        });
+        house.add(room("attic", &[])).unwrap();
+        assert_eq!(house.clone().config().max_rooms, Some(3));
+    }
+
+    #[test]
+    fn per_room_and_total_caps_work_together() {
+        let config = HouseConfig {
+            max_devices_total: Some(3),
+            max_devices_per_room: Some(2),
+            ..HouseConfig::default()
+        };
+        let mut house = SmartHouse::with_config("home", config);
+        assert_eq!(
+            house.add(room("hall", &["s1", "s2", "s3"])),
+            Err(exceeded(Limit::DevicesPerRoom, 2, 3))
+        );
+        house.add(room("hall", &["s1", "s2"])).unwrap();
+        house.add(room("kitchen", &["s3"])).unwrap();
+        assert_eq!(
+            house.plug("hall", SmartSocket::new("s4")),
+            Err(exceeded(Limit::DevicesPerRoom, 2, 3))
+        );
+        // в kitchen место есть, но в доме уже три устройства
+        assert_eq!(
+            house.plug("kitchen", SmartSocket::new("s4")),
+            Err(exceeded(Limit::DevicesTotal, 3, 4))
+        );
+        assert_eq!(
+            house.add(room("attic", &["s4"])),
+            Err(exceeded(Limit::DevicesTotal, 3, 4))
+        );
+
+        // перенос не добавляет устройств в дом
+        house.move_device("s1", "hall", "kitchen").unwrap();
+        assert_eq!(
+            house.move_device("s2", "hall", "kitchen"),
+            Err(exceeded(Limit::DevicesPerRoom, 2, 3))
+        );
+
+        house.set_config(HouseConfig {
+            max_devices_total: None,
+            ..config
+        });
+        house.plug("hall", SmartSocket::new("s4")).unwrap();
+        assert_eq!(house.device_count(), 4);
+    }
+
+    #[test]
+    fn long_names_are_refused() {
+        let config = HouseConfig {
+            name_max_len: Some(5),
+            ..HouseConfig::default()
+        };
+        let mut house = SmartHouse::with_config("home", config);
+        assert_eq!(
+            house.add(room("kitchen", &[])),
+            Err(exceeded(Limit::NameLength, 5, 7))
+        );
+        assert_eq!(
+            house.add(room("hall", &["socket"])),
+            Err(exceeded(Limit::NameLength, 5, 6))
+        );
+        // длина считается в символах, а не в байтах
+        house.add(room("кухня", &[])).unwrap();
+        assert_eq!(
+            house.plug("кухня", SmartSocket::new("s".repeat(6))),
+            Err(exceeded(Limit::NameLength, 5, 6))
+        );
+        assert_eq!(
+            exceeded(Limit::NameLength, 5, 6).to_string(),
+            "name length limit is 5, got 6"
+        );
+    }
+}
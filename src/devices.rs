@@ -0,0 +1,601 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{atomic::AtomicU64, log::info, BatteryPowered, DeviceInfo, Measurable, Switchable};
+
+pub trait Named {
+    fn name(&self) -> &str;
+}
+
+/// A device that can be plugged into a room.
+///
+/// Rooms hold devices as shared `Arc`s, so any mutable state must live behind `&self`
+/// (atomics or locks). Implementations must not hold a lock while calling into another
+/// device, including while formatting it, and must not block on I/O under a lock that
+/// a report could be waiting for.
+///
+/// `Any` lets a caller get the concrete device back from a room, e.g. by upcasting
+/// `Arc<dyn Pluggable>` to `Arc<dyn Any + Send + Sync>` and calling `downcast`.
+///
+/// Every device must be able to copy itself with [`boxed_clone`](Pluggable::boxed_clone).
+/// For a device that implements `Clone`, the [`boxed_clone!`](crate::boxed_clone) macro
+/// writes the method:
+///
+/// ```
+/// use lesson_3::{boxed_clone, Named, Pluggable};
+///
+/// #[derive(Clone)]
+/// struct Lamp(String);
+///
+/// impl Named for Lamp {
+///     fn name(&self) -> &str {
+///         &self.0
+///     }
+/// }
+///
+/// impl Pluggable for Lamp {
+///     boxed_clone!();
+/// }
+/// ```
+pub trait Pluggable: Named + Any + Send + Sync {
+    /// A new device with the same name and a snapshot of the current state, not shared
+    /// with this one. Copies of rooms and houses that should not share devices use it.
+    fn boxed_clone(&self) -> Arc<dyn Pluggable>;
+
+    /// Whether `other` is an equivalent device, used to compare rooms structurally.
+    /// By default two devices are equivalent when they have the same type and name;
+    /// devices with state should also compare it.
+    fn same_device(&self, other: &dyn Pluggable) -> bool {
+        let any: &dyn Any = other;
+        self.type_id() == any.type_id() && self.name() == other.name()
+    }
+
+    /// When the state last changed, as a stamp that orders changes across all devices, or
+    /// `0` if it never has. Smart sockets, thermometers and UDP thermometer receivers keep
+    /// one; other devices do not and are left out of [`SmartHouse::state_generation`](crate::SmartHouse::state_generation).
+    fn state_stamp(&self) -> u64 {
+        0
+    }
+
+    /// A short description of the current state for detailed reports, if the device has
+    /// one it can tell without blocking.
+    fn status(&self) -> Option<String> {
+        None
+    }
+
+    /// Which of the known kinds the device is, if any.
+    fn kind(&self) -> Option<DeviceKind> {
+        None
+    }
+
+    /// Whether the device carries `tag`, such as [`CRITICAL`]; see
+    /// [`SmartHouse::switch_all_except_tagged`](crate::SmartHouse::switch_all_except_tagged).
+    fn has_tag(&self, _tag: &str) -> bool {
+        false
+    }
+
+    /// The device as something that can be turned on and off, if it can.
+    fn as_switchable(&self) -> Option<&dyn Switchable> {
+        None
+    }
+
+    /// The device as something that measures, if it does.
+    fn as_measurable(&self) -> Option<&dyn Measurable> {
+        None
+    }
+
+    /// The device as something running on a battery, if it does.
+    fn as_battery_powered(&self) -> Option<&dyn BatteryPowered> {
+        None
+    }
+
+    /// Who made the device, if it tells; see [`SmartHouse::inventory`].
+    fn info(&self) -> Option<DeviceInfo> {
+        None
+    }
+}
+
+/// Anything [`SmartRoom::plug`] accepts: a device by value, an `Arc` of a concrete
+/// device, or an `Arc<dyn Pluggable>`.
+pub trait IntoDevice {
+    fn into_device(self) -> Arc<dyn Pluggable>;
+}
+
+impl<T: Pluggable> IntoDevice for T {
+    fn into_device(self) -> Arc<dyn Pluggable> {
+        Arc::new(self)
+    }
+}
+
+impl<T: Pluggable> IntoDevice for Arc<T> {
+    fn into_device(self) -> Arc<dyn Pluggable> {
+        self
+    }
+}
+
+impl IntoDevice for Arc<dyn Pluggable> {
+    fn into_device(self) -> Arc<dyn Pluggable> {
+        self
+    }
+}
+
+pub(crate) fn downcast<T: 'static>(device: &dyn Pluggable) -> Option<&T> {
+    let device: &dyn Any = device;
+    device.downcast_ref()
+}
+
+pub(crate) fn downcast_arc<T: Send + Sync + 'static>(device: Arc<dyn Pluggable>) -> Option<Arc<T>> {
+    let device: Arc<dyn Any + Send + Sync> = device;
+    device.downcast().ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceKind {
+    Socket,
+    Thermometer,
+}
+
+/// Helpers for lists of devices, such as [`SmartRoom::devices_raw`](crate::SmartRoom::devices_raw).
+pub trait DeviceSliceExt {
+    fn by_kind(&self, kind: DeviceKind) -> impl Iterator<Item = &Arc<dyn Pluggable>>;
+
+    fn names(&self) -> impl Iterator<Item = &str>;
+
+    /// The first device whose name matches `name` ignoring case.
+    fn find_named(&self, name: &str) -> Option<&Arc<dyn Pluggable>>;
+}
+
+fn lowercase(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars().flat_map(char::to_lowercase)
+}
+
+impl DeviceSliceExt for [Arc<dyn Pluggable>] {
+    fn by_kind(&self, kind: DeviceKind) -> impl Iterator<Item = &Arc<dyn Pluggable>> {
+        self.iter().filter(move |d| d.kind() == Some(kind))
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|d| d.name())
+    }
+
+    fn find_named(&self, name: &str) -> Option<&Arc<dyn Pluggable>> {
+        self.iter()
+            .find(|d| lowercase(d.name()).eq(lowercase(name)))
+    }
+}
+
+// Метки изменений общие для всех устройств, так что по двум меткам видно, какое
+// изменение было позже
+static STAMPS: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn next_stamp() -> u64 {
+    STAMPS.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+// Устройство запоминает метку своего последнего изменения
+fn stamp(changed_at: &AtomicU64) {
+    changed_at.fetch_max(next_stamp(), Ordering::Relaxed);
+}
+
+// f64 в атомике хранится как биты
+fn load_f64(cell: &AtomicU64) -> f64 {
+    f64::from_bits(cell.load(Ordering::SeqCst))
+}
+
+fn store_f64(cell: &AtomicU64, value: f64) {
+    cell.store(value.to_bits(), Ordering::SeqCst)
+}
+
+/// The tag of devices the house must keep powered, see [`SmartSocket::critical`].
+pub const CRITICAL: &str = "critical";
+
+/// Cloning takes a snapshot of the current state; clones do not share it afterwards.
+/// Share the device through an `Arc` to observe changes.
+pub struct SmartSocket {
+    name: String,
+    on: AtomicBool,
+    load: AtomicU64,
+    changed_at: AtomicU64,
+    tags: Vec<String>,
+    #[cfg(feature = "std")]
+    pub(crate) firmware: crate::firmware::Firmware,
+    #[cfg(feature = "async")]
+    pub(crate) watch: crate::watch::Sender<crate::watch::DeviceStateSnapshot>,
+}
+
+impl SmartSocket {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            on: AtomicBool::new(false),
+            load: AtomicU64::new(0f64.to_bits()),
+            changed_at: AtomicU64::new(0),
+            tags: Vec::new(),
+            #[cfg(feature = "std")]
+            firmware: Default::default(),
+            #[cfg(feature = "async")]
+            watch: Default::default(),
+        }
+    }
+
+    /// Tags the socket, so that switches can pick it out by the tag; see
+    /// [`Pluggable::has_tag`].
+    pub fn tagged(mut self, tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Tags the socket [`CRITICAL`], as one the house must keep powered, such as a
+    /// fridge: it is left alone when the house goes [`Away`](crate::HouseMode::Away).
+    pub fn critical(self) -> Self {
+        self.tagged(CRITICAL)
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.has_tag(CRITICAL)
+    }
+
+    fn changed(&self) {
+        stamp(&self.changed_at);
+        #[cfg(feature = "async")]
+        self.watch.send_with(|| self.snapshot());
+    }
+
+    pub fn turn_on(&self) {
+        self.on.store(true, Ordering::SeqCst);
+        self.changed();
+        info!("socket {} turned on", self.name);
+    }
+
+    pub fn turn_off(&self) {
+        self.on.store(false, Ordering::SeqCst);
+        self.changed();
+        info!("socket {} turned off", self.name);
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on.load(Ordering::SeqCst)
+    }
+
+    /// Sets the load connected to the socket, in watts.
+    pub fn set_load(&self, watts: f64) {
+        store_f64(&self.load, watts);
+        self.changed();
+    }
+
+    /// The load connected to the socket, in watts, whether it is on or off.
+    pub fn load(&self) -> f64 {
+        load_f64(&self.load)
+    }
+
+    /// Power currently drawn through the socket, in watts: the load when on, zero when off.
+    pub fn power(&self) -> f64 {
+        match self.is_on() {
+            true => load_f64(&self.load),
+            false => 0.0,
+        }
+    }
+}
+
+impl fmt::Debug for SmartSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmartSocket")
+            .field("name", &self.name)
+            .field("on", &self.is_on())
+            .field("load", &load_f64(&self.load))
+            .finish()
+    }
+}
+
+impl Clone for SmartSocket {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            on: AtomicBool::new(self.is_on()),
+            load: AtomicU64::new(self.load.load(Ordering::SeqCst)),
+            changed_at: AtomicU64::new(self.state_stamp()),
+            tags: self.tags.clone(),
+            #[cfg(feature = "std")]
+            firmware: self.firmware.clone(),
+            #[cfg(feature = "async")]
+            watch: Default::default(),
+        }
+    }
+}
+
+impl Named for SmartSocket {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Pluggable for SmartSocket {
+    crate::boxed_clone!();
+
+    fn as_switchable(&self) -> Option<&dyn Switchable> {
+        Some(self)
+    }
+
+    fn as_measurable(&self) -> Option<&dyn Measurable> {
+        Some(self)
+    }
+
+    fn kind(&self) -> Option<DeviceKind> {
+        Some(DeviceKind::Socket)
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own == tag)
+    }
+
+    fn state_stamp(&self) -> u64 {
+        self.changed_at.load(Ordering::Relaxed)
+    }
+
+    fn status(&self) -> Option<String> {
+        let state = if self.is_on() { "on" } else { "off" };
+        Some(format!("{}, {:.1} W", state, self.power()))
+    }
+
+    // Нагрузка сравнивается побитно, чтобы сравнение было рефлексивным и для NaN
+    fn same_device(&self, other: &dyn Pluggable) -> bool {
+        downcast::<Self>(other).is_some_and(|other| {
+            self == other
+                && self.is_on() == other.is_on()
+                && self.load.load(Ordering::SeqCst) == other.load.load(Ordering::SeqCst)
+        })
+    }
+}
+
+/// Sockets are equal when their names are: the name is what identifies a device in a
+/// room, and the state can change while the socket sits in a set. Rooms and houses
+/// compare the state as well, see [`Pluggable::same_device`].
+impl PartialEq for SmartSocket {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for SmartSocket {}
+
+impl Hash for SmartSocket {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+/// Cloning takes a snapshot of the current reading, like [`SmartSocket`].
+pub struct SmartThermometer {
+    name: String,
+    // NaN означает, что показаний ещё не было
+    temperature: AtomicU64,
+    changed_at: AtomicU64,
+    pub(crate) battery: crate::battery::Battery,
+    #[cfg(feature = "std")]
+    pub(crate) firmware: crate::firmware::Firmware,
+    #[cfg(feature = "async")]
+    pub(crate) watch: crate::watch::Sender<crate::watch::DeviceStateSnapshot>,
+}
+
+impl SmartThermometer {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            temperature: AtomicU64::new(f64::NAN.to_bits()),
+            changed_at: AtomicU64::new(0),
+            battery: Default::default(),
+            #[cfg(feature = "std")]
+            firmware: Default::default(),
+            #[cfg(feature = "async")]
+            watch: Default::default(),
+        }
+    }
+
+    fn changed(&self) {
+        stamp(&self.changed_at);
+        #[cfg(feature = "async")]
+        self.watch.send_with(|| self.snapshot());
+    }
+
+    // заряд меняет состояние, но подписчикам watch о нём не сообщается
+    pub(crate) fn battery_changed(&self) {
+        stamp(&self.changed_at);
+    }
+
+    pub fn set_temperature(&self, celsius: f64) {
+        store_f64(&self.temperature, celsius);
+        self.changed();
+    }
+
+    pub fn temperature(&self) -> Option<f64> {
+        Some(load_f64(&self.temperature)).filter(|t| !t.is_nan())
+    }
+}
+
+/// Thermometers are equal when their names are, like [`SmartSocket`].
+impl PartialEq for SmartThermometer {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for SmartThermometer {}
+
+impl Hash for SmartThermometer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl fmt::Debug for SmartThermometer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmartThermometer")
+            .field("name", &self.name)
+            .field("temperature", &self.temperature())
+            .finish()
+    }
+}
+
+impl Clone for SmartThermometer {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            temperature: AtomicU64::new(self.temperature.load(Ordering::SeqCst)),
+            changed_at: AtomicU64::new(self.state_stamp()),
+            battery: self.battery.clone(),
+            #[cfg(feature = "std")]
+            firmware: self.firmware.clone(),
+            #[cfg(feature = "async")]
+            watch: Default::default(),
+        }
+    }
+}
+
+impl Named for SmartThermometer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Pluggable for SmartThermometer {
+    crate::boxed_clone!();
+
+    fn as_measurable(&self) -> Option<&dyn Measurable> {
+        Some(self)
+    }
+
+    fn as_battery_powered(&self) -> Option<&dyn BatteryPowered> {
+        Some(self)
+    }
+
+    fn kind(&self) -> Option<DeviceKind> {
+        Some(DeviceKind::Thermometer)
+    }
+
+    fn state_stamp(&self) -> u64 {
+        self.changed_at.load(Ordering::Relaxed)
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(match self.temperature() {
+            Some(t) => format!("{:.1} °C", t),
+            None => "no reading".to_string(),
+        })
+    }
+
+    // Как у розетки, температура сравнивается побитно
+    fn same_device(&self, other: &dyn Pluggable) -> bool {
+        downcast::<Self>(other).is_some_and(|other| {
+            self == other
+                && self.temperature.load(Ordering::SeqCst)
+                    == other.temperature.load(Ordering::SeqCst)
+        })
+    }
+}
+
+/// `{}` is the compact `Socket[name]`; `{:#}` is the report line with the state and a
+/// trailing newline.
+impl fmt::Display for SmartSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !f.alternate() {
+            return write!(f, "Socket[{}]", self.name());
+        }
+
+        let state = if self.is_on() { "on" } else { "off" };
+        writeln!(
+            f,
+            "----> Device: Socket[{}] {}, {:.1} W",
+            self.name(),
+            state,
+            self.power()
+        )
+    }
+}
+
+/// `{}` is the compact `Thermometer[name]`; `{:#}` is the report line with the reading.
+impl fmt::Display for SmartThermometer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !f.alternate() {
+            return write!(f, "Thermometer[{}]", self.name());
+        }
+
+        match self.temperature() {
+            Some(t) => writeln!(f, "----> Device: Thermometer[{}] {:.1} °C", self.name(), t),
+            None => writeln!(f, "----> Device: Thermometer[{}] no reading", self.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxed_clone_does_not_share_state() {
+        let socket = SmartSocket::new("s1");
+        socket.turn_on();
+        let copy = socket.boxed_clone();
+        socket.turn_off();
+
+        let any: Arc<dyn Any + Send + Sync> = copy;
+        let copy = any.downcast::<SmartSocket>().unwrap();
+        assert_eq!(copy.name(), "s1");
+        assert!(copy.is_on());
+        assert!(!socket.is_on());
+    }
+
+    fn devices() -> Vec<Arc<dyn Pluggable>> {
+        vec![
+            Arc::new(SmartSocket::new("Main socket")),
+            Arc::new(SmartThermometer::new("Термометр")),
+            Arc::new(SmartSocket::new("Kettle")),
+        ]
+    }
+
+    #[test]
+    fn by_kind() {
+        let devices = devices();
+        let sockets: Vec<_> = devices
+            .by_kind(DeviceKind::Socket)
+            .map(|d| d.name())
+            .collect();
+        assert_eq!(sockets, ["Main socket", "Kettle"]);
+        assert_eq!(devices.by_kind(DeviceKind::Thermometer).count(), 1);
+        assert_eq!([].by_kind(DeviceKind::Socket).count(), 0);
+    }
+
+    #[test]
+    fn names() {
+        assert!(devices().names().eq(["Main socket", "Термометр", "Kettle"]));
+        assert_eq!([].names().count(), 0);
+    }
+
+    #[test]
+    fn find_named() {
+        let devices = devices();
+        assert_eq!(
+            devices.find_named("MAIN SOCKET").unwrap().name(),
+            "Main socket"
+        );
+        assert_eq!(devices.find_named("термометр").unwrap().name(), "Термометр");
+        assert!(devices.find_named("main").is_none());
+        assert!([].find_named("Kettle").is_none());
+    }
+}
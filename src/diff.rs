@@ -0,0 +1,222 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt;
+
+use crate::{HouseEvent, Pluggable, SmartHouse};
+
+/// The changes that turn one house into another, see [`SmartHouse::diff`].
+///
+/// Changes come in an order they can be replayed in: added rooms, unplugged devices,
+/// moves, plugged devices and then removed rooms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HouseDiff {
+    pub changes: Vec<HouseEvent>,
+}
+
+impl HouseDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &HouseEvent> {
+        self.changes.iter()
+    }
+}
+
+/// One change per line, e.g. `+ room hall`, `- hall/s1` or `~ s1: hall -> yard`.
+impl fmt::Display for HouseDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            match change {
+                HouseEvent::RoomAdded { room } => writeln!(f, "+ room {room}")?,
+                HouseEvent::RoomRemoved { room } => writeln!(f, "- room {room}")?,
+                HouseEvent::DevicePlugged { room, device } => writeln!(f, "+ {room}/{device}")?,
+                HouseEvent::DeviceUnplugged { room, device } => writeln!(f, "- {room}/{device}")?,
+                HouseEvent::DeviceMoved { device, from, to } => {
+                    writeln!(f, "~ {device}: {from} -> {to}")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Entry<'a> {
+    room: &'a str,
+    device: Arc<dyn Pluggable>,
+    matched: bool,
+}
+
+fn entries(house: &SmartHouse) -> Vec<Entry<'_>> {
+    house
+        .all_devices()
+        .map(|(room, device)| Entry {
+            room: &room.name,
+            device,
+            matched: false,
+        })
+        .collect()
+}
+
+// Одно и то же устройство: сравниваются адреса данных, без vtable
+fn same_arc(a: &Arc<dyn Pluggable>, b: &Arc<dyn Pluggable>) -> bool {
+    Arc::as_ptr(a).cast::<()>() == Arc::as_ptr(b).cast::<()>()
+}
+
+impl SmartHouse {
+    /// What changed from this house to `other`.
+    ///
+    /// Rooms are matched by name. A device is matched first by identity: the same `Arc`
+    /// in both houses, as after [`Clone`], is the same device wherever it is, and is
+    /// reported as moved if its room changed. Devices left over are matched by room and
+    /// name; the rest are reported as unplugged from this house and plugged into `other`,
+    /// so a copied device that changed rooms shows up as both.
+    pub fn diff(&self, other: &SmartHouse) -> HouseDiff {
+        let mut changes = Vec::new();
+        for room in &other.rooms {
+            if self.room(&room.name).is_none() {
+                changes.push(HouseEvent::RoomAdded {
+                    room: room.name.clone(),
+                });
+            }
+        }
+
+        let (mut ours, mut theirs) = (entries(self), entries(other));
+        let mut moves = Vec::new();
+        for old in &mut ours {
+            let Some(new) = theirs
+                .iter_mut()
+                .find(|new| !new.matched && same_arc(&old.device, &new.device))
+            else {
+                continue;
+            };
+            (old.matched, new.matched) = (true, true);
+            if old.room != new.room {
+                moves.push(HouseEvent::DeviceMoved {
+                    device: old.device.name().into(),
+                    from: old.room.into(),
+                    to: new.room.into(),
+                });
+            }
+        }
+        for old in ours.iter_mut().filter(|old| !old.matched) {
+            if let Some(new) = theirs.iter_mut().find(|new| {
+                !new.matched && new.room == old.room && new.device.name() == old.device.name()
+            }) {
+                (old.matched, new.matched) = (true, true);
+            }
+        }
+
+        let left = |entries: Vec<Entry<'_>>| {
+            entries
+                .into_iter()
+                .filter(|entry| !entry.matched)
+                .map(|entry| (entry.room.into(), entry.device.name().into()))
+                .collect::<Vec<_>>()
+        };
+        changes.extend(
+            left(ours)
+                .into_iter()
+                .map(|(room, device)| HouseEvent::DeviceUnplugged { room, device }),
+        );
+        changes.extend(moves);
+        changes.extend(
+            left(theirs)
+                .into_iter()
+                .map(|(room, device)| HouseEvent::DevicePlugged { room, device }),
+        );
+
+        for room in &self.rooms {
+            if other.room(&room.name).is_none() {
+                changes.push(HouseEvent::RoomRemoved {
+                    room: room.name.clone(),
+                });
+            }
+        }
+        HouseDiff { changes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::{testing::fixtures::small_house, SmartRoom, SmartSocket};
+
+    #[test]
+    fn same_house_has_no_changes() {
+        let house = small_house();
+        assert!(house.diff(&house.clone()).is_empty());
+        // другие экземпляры с теми же именами совпадают по комнате и имени
+        assert!(house.diff(&small_house()).is_empty());
+    }
+
+    #[test]
+    fn shared_device_that_moved_is_a_move() {
+        let before = small_house();
+        let mut after = before.clone();
+        after.move_device("s1", "limb", "lust").unwrap();
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.changes,
+            [HouseEvent::DeviceMoved {
+                device: "s1".to_string(),
+                from: "limb".to_string(),
+                to: "lust".to_string(),
+            }]
+        );
+        assert_eq!(diff.to_string(), "~ s1: limb -> lust\n");
+    }
+
+    #[test]
+    fn changes_come_in_replay_order() {
+        let before = small_house();
+        let mut after = before.clone();
+        after.add(SmartRoom::new("attic")).unwrap();
+        after.move_device("t1", "limb", "attic").unwrap();
+        after.unplug("limb", "s1").unwrap();
+        after.plug("limb", SmartSocket::new("s1")).unwrap();
+        after.remove_room("lust").unwrap();
+
+        // s1 подменили другим экземпляром: он совпал по комнате и имени
+        assert_eq!(
+            before.diff(&after).to_string(),
+            "+ room attic\n- lust/s2\n~ t1: limb -> attic\n- room lust\n"
+        );
+    }
+
+    #[test]
+    fn unshared_device_that_moved_is_unplugged_and_plugged() {
+        let before = small_house();
+        let mut after = SmartHouse::new("hell");
+        after.add(SmartRoom::new("limb")).unwrap();
+        after.add(SmartRoom::new("lust")).unwrap();
+        for (room, device) in before.all_devices() {
+            let room = if device.name() == "s1" {
+                "lust"
+            } else {
+                &room.name
+            };
+            after.plug(room, device.boxed_clone()).unwrap();
+        }
+
+        assert_eq!(
+            before.diff(&after).changes,
+            [
+                HouseEvent::DeviceUnplugged {
+                    room: "limb".to_string(),
+                    device: "s1".to_string(),
+                },
+                HouseEvent::DevicePlugged {
+                    room: "lust".to_string(),
+                    device: "s1".to_string(),
+                },
+            ]
+        );
+    }
+}
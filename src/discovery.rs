@@ -0,0 +1,525 @@
+//! mDNS/DNS-SD style discovery of networked devices.
+//!
+//! Servers periodically multicast an unsolicited DNS response with PTR, SRV, TXT and A
+//! records for `_smarthouse._tcp.local`; [`discover_devices`] listens on the group for a
+//! while and collects what it heard. The TXT record carries the full device name and kind.
+
+use core::fmt;
+use std::{
+    error::Error,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    net::{ServerHandle, SocketClient},
+    DeviceId, DeviceKind, SmartRoom,
+};
+
+pub const SERVICE_TYPE: &str = "_smarthouse._tcp.local";
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+const TTL: u32 = 120;
+const MAX_LABEL_LEN: usize = 63;
+const MAX_TXT_LEN: usize = 255;
+const MAX_PACKET_LEN: usize = 9000;
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    pub group: Ipv4Addr,
+    pub port: u16,
+    pub interface: Ipv4Addr,
+    pub interval: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            group: Ipv4Addr::new(224, 0, 0, 251),
+            port: 5353,
+            interface: Ipv4Addr::UNSPECIFIED,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredDevice {
+    pub name: String,
+    pub kind: DeviceKind,
+    pub addr: SocketAddr,
+}
+
+fn kind_as_str(kind: DeviceKind) -> &'static str {
+    match kind {
+        DeviceKind::Socket => "socket",
+        DeviceKind::Thermometer => "thermometer",
+    }
+}
+
+fn kind_from_str(kind: &str) -> Option<DeviceKind> {
+    match kind {
+        "socket" => Some(DeviceKind::Socket),
+        "thermometer" => Some(DeviceKind::Thermometer),
+        _ => None,
+    }
+}
+
+fn push_name(out: &mut Vec<u8>, name: &str) {
+    for label in name.split('.') {
+        push_label(out, label);
+    }
+    out.push(0);
+}
+
+fn push_label(out: &mut Vec<u8>, label: &str) {
+    out.push(label.len() as u8);
+    out.extend_from_slice(label.as_bytes());
+}
+
+fn push_record(out: &mut Vec<u8>, name: &[u8], kind: u16, class: u16, rdata: &[u8]) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(&kind.to_be_bytes());
+    out.extend_from_slice(&class.to_be_bytes());
+    out.extend_from_slice(&TTL.to_be_bytes());
+    out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    out.extend_from_slice(rdata);
+}
+
+fn instance_label(name: &str) -> &str {
+    let mut end = name.len().min(MAX_LABEL_LEN);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+fn encode_announcement(device: &DiscoveredDevice) -> io::Result<Vec<u8>> {
+    let name_entry = format!("name={}", device.name);
+    let kind_entry = format!("kind={}", kind_as_str(device.kind));
+    let label = instance_label(&device.name);
+    if label.is_empty() || name_entry.len() > MAX_TXT_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("device name {:?} cannot be announced", device.name),
+        ));
+    }
+
+    let mut instance = Vec::new();
+    push_label(&mut instance, label);
+    push_name(&mut instance, SERVICE_TYPE);
+
+    let mut service = Vec::new();
+    push_name(&mut service, SERVICE_TYPE);
+
+    let mut target = Vec::new();
+    push_name(
+        &mut target,
+        &format!("smarthouse-{}.local", device.addr.port()),
+    );
+
+    let mut out = Vec::new();
+    let answers: u16 = if device.addr.is_ipv4() { 4 } else { 3 };
+    out.extend_from_slice(&[0, 0, 0x84, 0, 0, 0]);
+    out.extend_from_slice(&answers.to_be_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0]);
+
+    push_record(&mut out, &service, TYPE_PTR, CLASS_IN, &instance);
+
+    let mut srv = vec![0, 0, 0, 0];
+    srv.extend_from_slice(&device.addr.port().to_be_bytes());
+    srv.extend_from_slice(&target);
+    push_record(&mut out, &instance, TYPE_SRV, CLASS_IN, &srv);
+
+    let mut txt = Vec::new();
+    for entry in [&name_entry, &kind_entry] {
+        txt.push(entry.len() as u8);
+        txt.extend_from_slice(entry.as_bytes());
+    }
+    push_record(&mut out, &instance, TYPE_TXT, CLASS_IN, &txt);
+
+    if let IpAddr::V4(ip) = device.addr.ip() {
+        push_record(&mut out, &target, TYPE_A, CLASS_IN, &ip.octets());
+    }
+
+    Ok(out)
+}
+
+#[derive(Debug)]
+struct Malformed;
+
+struct Reader<'a> {
+    packet: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Malformed> {
+        let end = self.pos.checked_add(len).ok_or(Malformed)?;
+        let out = self.packet.get(self.pos..end).ok_or(Malformed)?;
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn u16(&mut self) -> Result<u16, Malformed> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn name(&mut self) -> Result<String, Malformed> {
+        let (name, end) = read_name(self.packet, self.pos)?;
+        self.pos = end;
+        Ok(name)
+    }
+}
+
+/// Reads a possibly compressed name at `pos`, returning it and the offset just past it.
+fn read_name(packet: &[u8], mut pos: usize) -> Result<(String, usize), Malformed> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *packet.get(pos).ok_or(Malformed)? as usize;
+        match len {
+            0 => {
+                let end = end.unwrap_or(pos + 1);
+                return Ok((labels.join("."), end));
+            }
+            l if l & 0xc0 == 0xc0 => {
+                let low = *packet.get(pos + 1).ok_or(Malformed)? as usize;
+                jumps += 1;
+                if jumps > 16 {
+                    return Err(Malformed);
+                }
+                end.get_or_insert(pos + 2);
+                pos = (l & 0x3f) << 8 | low;
+            }
+            l if l <= MAX_LABEL_LEN => {
+                let label = packet.get(pos + 1..pos + 1 + l).ok_or(Malformed)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + l;
+            }
+            _ => return Err(Malformed),
+        }
+    }
+}
+
+struct Record<'a> {
+    name: String,
+    kind: u16,
+    rdata_pos: usize,
+    rdata: &'a [u8],
+}
+
+fn decode_announcement(
+    packet: &[u8],
+    from: SocketAddr,
+) -> Result<Vec<DiscoveredDevice>, Malformed> {
+    let mut reader = Reader { packet, pos: 0 };
+    let header = reader.bytes(12)?;
+    let count = |i: usize| u16::from_be_bytes([header[i], header[i + 1]]) as usize;
+    let (questions, records) = (count(4), count(6) + count(8) + count(10));
+
+    for _ in 0..questions {
+        reader.name()?;
+        reader.bytes(4)?;
+    }
+
+    let mut all = Vec::with_capacity(records.min(64));
+    for _ in 0..records {
+        let name = reader.name()?;
+        let kind = reader.u16()?;
+        reader.bytes(6)?;
+        let len = reader.u16()? as usize;
+        let rdata_pos = reader.pos;
+        let rdata = reader.bytes(len)?;
+        all.push(Record {
+            name,
+            kind,
+            rdata_pos,
+            rdata,
+        });
+    }
+
+    let mut devices = Vec::new();
+    for ptr in all
+        .iter()
+        .filter(|r| r.kind == TYPE_PTR && r.name.eq_ignore_ascii_case(SERVICE_TYPE))
+    {
+        let (instance, _) = read_name(packet, ptr.rdata_pos)?;
+        let find = |kind| all.iter().find(|r| r.kind == kind && r.name == instance);
+
+        let (Some(srv), Some(txt)) = (find(TYPE_SRV), find(TYPE_TXT)) else {
+            continue;
+        };
+        if srv.rdata.len() < 6 {
+            return Err(Malformed);
+        }
+        let port = u16::from_be_bytes([srv.rdata[4], srv.rdata[5]]);
+        let (target, _) = read_name(packet, srv.rdata_pos + 6)?;
+
+        let mut name = None;
+        let mut kind = None;
+        let mut entries = txt.rdata;
+        while let Some((&len, rest)) = entries.split_first() {
+            let entry = rest.get(..len as usize).ok_or(Malformed)?;
+            entries = &rest[len as usize..];
+            match std::str::from_utf8(entry)
+                .ok()
+                .and_then(|e| e.split_once('='))
+            {
+                Some(("name", value)) => name = Some(value.to_string()),
+                Some(("kind", value)) => kind = kind_from_str(value),
+                _ => {}
+            }
+        }
+
+        let ip = all
+            .iter()
+            .find(|r| r.kind == TYPE_A && r.name == target && r.rdata.len() == 4)
+            .map(|a| {
+                IpAddr::V4(Ipv4Addr::new(
+                    a.rdata[0], a.rdata[1], a.rdata[2], a.rdata[3],
+                ))
+            })
+            .filter(|ip| !ip.is_unspecified())
+            .unwrap_or(from.ip());
+
+        if let (Some(name), Some(kind)) = (name, kind) {
+            devices.push(DiscoveredDevice {
+                name,
+                kind,
+                addr: SocketAddr::new(ip, port),
+            });
+        }
+    }
+
+    Ok(devices)
+}
+
+pub struct Announcer {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for Announcer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Announcer")
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
+impl Announcer {
+    pub fn start(config: &DiscoveryConfig, device: DiscoveredDevice) -> io::Result<Self> {
+        let packet = encode_announcement(&device)?;
+        let socket = UdpSocket::bind((config.interface, 0))?;
+        socket.set_multicast_loop_v4(true)?;
+
+        let target = SocketAddr::from((config.group, config.port));
+        let interval = config.interval;
+        let (stop, stopped) = mpsc::channel();
+
+        let thread = thread::spawn(move || loop {
+            let _ = socket.send_to(&packet, target);
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                _ => break,
+            }
+        });
+
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Announcer {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl ServerHandle {
+    pub fn announce(&self, config: &DiscoveryConfig) -> io::Result<Announcer> {
+        Announcer::start(
+            config,
+            DiscoveredDevice {
+                name: self.name().to_string(),
+                kind: DeviceKind::Socket,
+                addr: self.local_addr(),
+            },
+        )
+    }
+}
+
+pub fn discover_devices(timeout: Duration) -> io::Result<Vec<DiscoveredDevice>> {
+    discover_devices_with(&DiscoveryConfig::default(), timeout)
+}
+
+pub fn discover_devices_with(
+    config: &DiscoveryConfig,
+    timeout: Duration,
+) -> io::Result<Vec<DiscoveredDevice>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, config.port))?;
+    socket.join_multicast_v4(&config.group, &config.interface)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut found: Vec<DiscoveredDevice> = Vec::new();
+    let mut buf = vec![0u8; MAX_PACKET_LEN];
+
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        if left.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(left))?;
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Чужие и битые пакеты в группе не должны прерывать поиск
+        for device in decode_announcement(&buf[..len], from).unwrap_or_default() {
+            if !found.contains(&device) {
+                found.push(device);
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+#[derive(Debug)]
+pub struct UnsupportedKind(pub DeviceKind);
+
+impl fmt::Display for UnsupportedKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no network client for {} devices", kind_as_str(self.0))
+    }
+}
+
+impl Error for UnsupportedKind {}
+
+impl SmartRoom {
+    pub fn plug_discovered(
+        &mut self,
+        device: DiscoveredDevice,
+    ) -> Result<DeviceId, Box<dyn Error>> {
+        match device.kind {
+            DeviceKind::Socket => {
+                let client = SocketClient::connect(device.name, device.addr)?;
+                Ok(self.plug(client)?)
+            }
+            kind => Err(UnsupportedKind(kind).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::SocketServer;
+
+    fn loopback(port: u16) -> DiscoveryConfig {
+        DiscoveryConfig {
+            port,
+            interface: Ipv4Addr::LOCALHOST,
+            interval: Duration::from_millis(50),
+            ..DiscoveryConfig::default()
+        }
+    }
+
+    #[test]
+    fn announcement_round_trip() {
+        let device = DiscoveredDevice {
+            name: "Kitchen socket. With dots and ünïcode".to_string(),
+            kind: DeviceKind::Thermometer,
+            addr: "127.0.0.1:4242".parse().unwrap(),
+        };
+
+        let packet = encode_announcement(&device).unwrap();
+        let from = "127.0.0.1:5353".parse().unwrap();
+        assert_eq!(decode_announcement(&packet, from).unwrap(), vec![device]);
+    }
+
+    #[test]
+    fn truncated_announcements_are_rejected() {
+        let device = DiscoveredDevice {
+            name: "Main socket".to_string(),
+            kind: DeviceKind::Socket,
+            addr: "127.0.0.1:4242".parse().unwrap(),
+        };
+        let packet = encode_announcement(&device).unwrap();
+        let from = "127.0.0.1:5353".parse().unwrap();
+
+        for cut in 0..packet.len() {
+            assert!(decode_announcement(&packet[..cut], from).is_err());
+        }
+    }
+
+    #[test]
+    fn compression_loops_are_rejected() {
+        let mut packet = vec![0, 0, 0x84, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        packet.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1]);
+        let from = "127.0.0.1:5353".parse().unwrap();
+
+        assert!(decode_announcement(&packet, from).is_err());
+    }
+
+    #[test]
+    fn discover_and_plug_socket() {
+        let config = loopback(53_531);
+        let server = SocketServer::bind("Main socket", "127.0.0.1:0")
+            .unwrap()
+            .spawn()
+            .unwrap();
+        let _announcer = server.announce(&config).unwrap();
+        let _other = Announcer::start(
+            &config,
+            DiscoveredDevice {
+                name: "Thermometer 1".to_string(),
+                kind: DeviceKind::Thermometer,
+                addr: "127.0.0.1:1".parse().unwrap(),
+            },
+        )
+        .unwrap();
+
+        let found = discover_devices_with(&config, Duration::from_millis(400)).unwrap();
+        assert_eq!(found.len(), 2, "Both announcers should be heard: {found:?}");
+
+        let socket = found.iter().find(|d| d.kind == DeviceKind::Socket).unwrap();
+        assert_eq!(socket.name, "Main socket");
+        assert_eq!(socket.addr, server.local_addr());
+
+        let mut room = SmartRoom::new("Boiler");
+        room.plug_discovered(socket.clone()).unwrap();
+        assert_eq!(room.devices(), vec!["Main socket".to_string()]);
+
+        let thermo = found
+            .iter()
+            .find(|d| d.kind == DeviceKind::Thermometer)
+            .unwrap();
+        assert!(room.plug_discovered(thermo.clone()).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{SmartHouse, SmartHouseError};
+
+// Двери без направления: пара хранится упорядоченной
+pub(crate) type Doors = BTreeSet<(String, String)>;
+
+fn door(a: &str, b: &str) -> (String, String) {
+    match a <= b {
+        true => (a.to_string(), b.to_string()),
+        false => (b.to_string(), a.to_string()),
+    }
+}
+
+impl SmartHouse {
+    /// Puts a door between two rooms of the house. Returns `false` if they already had
+    /// one, or if `a` and `b` are the same room.
+    pub fn connect_rooms(&mut self, a: &str, b: &str) -> Result<bool, SmartHouseError> {
+        for room in [a, b] {
+            if self.room(room).is_none() {
+                return Err(SmartHouseError::RoomNotFound(room.to_string()));
+            }
+        }
+        Ok(a != b && self.doors.insert(door(a, b)))
+    }
+
+    /// Returns `false` if there was no door between the rooms.
+    pub fn disconnect_rooms(&mut self, a: &str, b: &str) -> bool {
+        self.doors.remove(&door(a, b))
+    }
+
+    /// Rooms with a door to `room`, by name.
+    pub fn neighbors(&self, room: &str) -> Vec<&str> {
+        let mut neighbors: Vec<_> = self
+            .doors
+            .iter()
+            .filter_map(|(a, b)| match (a == room, b == room) {
+                (true, _) => Some(b.as_str()),
+                (_, true) => Some(a.as_str()),
+                _ => None,
+            })
+            .collect();
+        neighbors.sort_unstable();
+        neighbors
+    }
+
+    /// Whether one can walk from `a` to `b` through doors. A room of the house is
+    /// connected to itself.
+    pub fn are_connected(&self, a: &str, b: &str) -> bool {
+        self.route(a, b).is_some()
+    }
+
+    /// A shortest walk from `a` to `b`, both included, or `None` if there is none or a
+    /// room is not in the house. Neighbors are tried in name order, so the same house
+    /// always gives the same walk.
+    pub fn route(&self, a: &str, b: &str) -> Option<Vec<&str>> {
+        let start = self.room(a)?.name.as_str();
+        self.room(b)?;
+
+        // обход в ширину; для каждой комнаты запоминаем, откуда в неё пришли
+        let mut came_from: BTreeMap<&str, &str> = BTreeMap::from([(start, start)]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(room) = queue.pop_front() {
+            if room == b {
+                let mut route = Vec::from([room]);
+                let mut at = room;
+                while at != start {
+                    at = came_from[at];
+                    route.push(at);
+                }
+                route.reverse();
+                return Some(route);
+            }
+            for next in self.neighbors(room) {
+                if !came_from.contains_key(next) {
+                    came_from.insert(next, room);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    pub(crate) fn drop_doors(&mut self, room: &str) {
+        self.doors.retain(|(a, b)| a != room && b != room);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::fixtures::large_house, SmartRoom};
+
+    // Кольцо room-0 - room-1 - room-2 - room-3 - room-0 и отдельно room-4 - room-5
+    fn house() -> SmartHouse {
+        let mut house = large_house(6, 0);
+        for (a, b) in [
+            ("room-0", "room-1"),
+            ("room-1", "room-2"),
+            ("room-2", "room-3"),
+            ("room-3", "room-0"),
+            ("room-4", "room-5"),
+        ] {
+            assert_eq!(house.connect_rooms(a, b), Ok(true));
+        }
+        house
+    }
+
+    #[test]
+    fn doors_go_both_ways() {
+        let mut house = house();
+        assert_eq!(house.connect_rooms("room-1", "room-0"), Ok(false));
+        assert_eq!(house.connect_rooms("room-0", "room-0"), Ok(false));
+        assert_eq!(
+            house.connect_rooms("room-0", "attic"),
+            Err(SmartHouseError::RoomNotFound("attic".to_string()))
+        );
+        assert_eq!(house.neighbors("room-0"), ["room-1", "room-3"]);
+        assert_eq!(house.neighbors("room-1"), ["room-0", "room-2"]);
+        assert!(house.neighbors("attic").is_empty());
+
+        assert!(house.disconnect_rooms("room-1", "room-0"));
+        assert!(!house.disconnect_rooms("room-0", "room-1"));
+        assert_eq!(house.neighbors("room-0"), ["room-3"]);
+    }
+
+    #[test]
+    fn routes_stay_within_a_component() {
+        let house = house();
+        assert_eq!(
+            house.route("room-0", "room-2"),
+            Some(Vec::from(["room-0", "room-1", "room-2"]))
+        );
+        assert_eq!(
+            house.route("room-5", "room-4"),
+            Some(Vec::from(["room-5", "room-4"]))
+        );
+        assert_eq!(house.route("room-3", "room-3"), Some(Vec::from(["room-3"])));
+        assert!(house.are_connected("room-3", "room-1"));
+        assert!(!house.are_connected("room-0", "room-4"));
+        assert!(!house.are_connected("room-0", "attic"));
+    }
+
+    #[test]
+    fn removing_a_room_removes_its_doors() {
+        let mut house = house();
+        house.remove_room("room-1").unwrap();
+        assert_eq!(house.neighbors("room-0"), ["room-3"]);
+        // по кольцу остаётся обход через room-3
+        assert_eq!(
+            house.route("room-0", "room-2"),
+            Some(Vec::from(["room-0", "room-3", "room-2"]))
+        );
+
+        house.add(SmartRoom::new("room-1")).unwrap();
+        assert!(house.neighbors("room-1").is_empty());
+    }
+}
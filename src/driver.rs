@@ -0,0 +1,253 @@
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+};
+
+use crate::{
+    CapabilityError, DeviceId, DeviceInfo, Pluggable, SmartHouse, SmartHouseError, SmartRoom,
+};
+
+/// Sets up and tears down a device that needs more than a constructor, such as a
+/// connection to a remote socket. See [`SmartRoom::plug_driver`].
+pub trait DeviceDriver: Send + Sync {
+    /// A new, ready to use device.
+    fn connect(&self) -> Result<Arc<dyn Pluggable>, CapabilityError>;
+
+    /// Releases what [`connect`](Self::connect) set up for `device`. Other holders of
+    /// the device may still have it, but it no longer talks to anything.
+    fn disconnect(&self, device: Arc<dyn Pluggable>);
+
+    /// Checks that the device is there and tells what it is, without keeping it
+    /// connected; for discovery.
+    fn probe(&self) -> Result<DeviceInfo, CapabilityError>;
+}
+
+// Драйверы подключённых через plug_driver устройств, по имени устройства
+pub(crate) type Drivers = BTreeMap<String, Arc<dyn DeviceDriver>>;
+
+impl SmartRoom {
+    /// Connects the driver's device and plugs it. The room keeps the driver, so
+    /// [`unplug`](Self::unplug), here or through the house, disconnects the device. If
+    /// the device cannot be connected nothing is plugged, and if it cannot be plugged,
+    /// say because the name is taken, it is disconnected again.
+    pub fn plug_driver(
+        &mut self,
+        driver: Box<dyn DeviceDriver>,
+    ) -> Result<DeviceId, SmartHouseError> {
+        let device = driver
+            .connect()
+            .map_err(|err| SmartHouseError::Driver(err.to_string()))?;
+        let name = device.name().to_string();
+        match self.plug(Arc::clone(&device)) {
+            Ok(id) => {
+                self.drivers.insert(name, Arc::from(driver));
+                Ok(id)
+            }
+            Err(err) => {
+                driver.disconnect(device);
+                Err(err)
+            }
+        }
+    }
+
+    /// Whether the device was plugged with [`plug_driver`](Self::plug_driver).
+    pub fn has_driver(&self, device: &str) -> bool {
+        self.drivers.contains_key(device)
+    }
+
+    // Вынутое устройство отключает его драйвер, если он есть
+    pub(crate) fn release(&mut self, name: &str, device: &Arc<dyn Pluggable>) {
+        if let Some(driver) = self.drivers.remove(name) {
+            driver.disconnect(Arc::clone(device));
+        }
+    }
+}
+
+impl SmartHouse {
+    // Как SmartRoom::release, но внутри транзакции отключение ждёт фиксации, чтобы
+    // откат вернул устройство рабочим
+    pub(crate) fn release(&mut self, room: &str, name: &str, device: &Arc<dyn Pluggable>) {
+        let driver = self
+            .room_mut(room)
+            .and_then(|room| room.drivers.remove(name));
+        if let Some(driver) = driver {
+            self.disconnect(driver, Arc::clone(device));
+        }
+    }
+
+    pub(crate) fn disconnect(&mut self, driver: Arc<dyn DeviceDriver>, device: Arc<dyn Pluggable>) {
+        match &mut self.listeners.deferred {
+            Some(deferred) => deferred.releases.push((driver, device)),
+            None => driver.disconnect(device),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{SmartHouse, SmartSocket};
+
+    // Считает подключения и отключения; без имени не подключается
+    #[derive(Default)]
+    struct Counting {
+        name: Option<&'static str>,
+        connected: Arc<AtomicUsize>,
+        disconnected: Arc<AtomicUsize>,
+    }
+
+    impl Counting {
+        fn new(name: &'static str) -> Self {
+            Self {
+                name: Some(name),
+                ..Self::default()
+            }
+        }
+
+        fn counts(&self) -> (Arc<AtomicUsize>, Arc<AtomicUsize>) {
+            (self.connected.clone(), self.disconnected.clone())
+        }
+    }
+
+    impl DeviceDriver for Counting {
+        fn connect(&self) -> Result<Arc<dyn Pluggable>, CapabilityError> {
+            let name = self.name.ok_or("nothing there")?;
+            self.connected.fetch_add(1, Ordering::SeqCst);
+            Ok(Arc::new(SmartSocket::new(name)))
+        }
+
+        fn disconnect(&self, _: Arc<dyn Pluggable>) {
+            self.disconnected.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn probe(&self) -> Result<DeviceInfo, CapabilityError> {
+            Ok(DeviceInfo::new("test", "counting"))
+        }
+    }
+
+    fn count(counter: &AtomicUsize) -> usize {
+        counter.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn failed_plugs_leave_nothing_behind() {
+        let mut room = SmartRoom::new("hall");
+        assert_eq!(
+            room.plug_driver(Box::new(Counting::default())),
+            Err(SmartHouseError::Driver("nothing there".to_string()))
+        );
+        assert!(room.devices().is_empty());
+
+        room.plug(SmartSocket::new("s1")).unwrap();
+        let taken = Counting::new("s1");
+        let (connected, disconnected) = taken.counts();
+        assert_eq!(
+            room.plug_driver(Box::new(taken)),
+            Err(SmartHouseError::DuplicateDevice("s1".to_string()))
+        );
+        assert_eq!((count(&connected), count(&disconnected)), (1, 1));
+        assert!(!room.has_driver("s1"));
+    }
+
+    #[test]
+    fn unplugging_disconnects() {
+        let driver = Counting::new("s2");
+        let (_, disconnected) = driver.counts();
+        let mut room = SmartRoom::new("hall");
+        room.plug_driver(Box::new(driver)).unwrap();
+        assert!(room.has_driver("s2"));
+        room.unplug("s2").unwrap();
+        assert_eq!(count(&disconnected), 1);
+
+        // через дом, и драйвер переезжает вместе с устройством
+        let driver = Counting::new("s3");
+        let (_, disconnected) = driver.counts();
+        room.plug_driver(Box::new(driver)).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(room).unwrap();
+        house.add(SmartRoom::new("kitchen")).unwrap();
+        house.move_device("s3", "hall", "kitchen").unwrap();
+        assert_eq!(count(&disconnected), 0);
+        assert!(house.room("kitchen").unwrap().has_driver("s3"));
+        house.unplug("kitchen", "s3").unwrap();
+        assert_eq!(count(&disconnected), 1);
+    }
+
+    #[test]
+    fn rolled_back_unplug_stays_connected() {
+        let driver = Counting::new("s1");
+        let (_, disconnected) = driver.counts();
+        let mut hall = SmartRoom::new("hall");
+        hall.plug_driver(Box::new(driver)).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+
+        let failed: Result<(), SmartHouseError> = house.transaction(|tx| {
+            tx.unplug("hall", "s1")?;
+            Err(SmartHouseError::RoomNotFound("attic".to_string()))
+        });
+        assert!(failed.is_err());
+        assert_eq!(count(&disconnected), 0);
+        assert!(house.room("hall").unwrap().has_driver("s1"));
+
+        // отключение случается при фиксации, один раз
+        house
+            .transaction(|tx| tx.unplug("hall", "s1").map(|_| ()))
+            .unwrap();
+        assert_eq!(count(&disconnected), 1);
+        assert!(!house.room("hall").unwrap().has_driver("s1"));
+    }
+
+    #[test]
+    fn removing_the_room_disconnects() {
+        let driver = Counting::new("s1");
+        let (_, disconnected) = driver.counts();
+        let mut hall = SmartRoom::new("hall");
+        hall.plug_driver(Box::new(driver)).unwrap();
+        // копия комнаты драйвер не получает и ничего не отключает
+        let mut copy = hall.clone();
+        assert!(!copy.has_driver("s1"));
+        copy.unplug("s1").unwrap();
+        assert_eq!(count(&disconnected), 0);
+
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+        let failed: Result<(), SmartHouseError> = house.transaction(|tx| {
+            tx.remove_room("hall").unwrap();
+            Err(SmartHouseError::RoomNotFound("attic".to_string()))
+        });
+        assert!(failed.is_err());
+        assert_eq!(count(&disconnected), 0);
+        assert!(house.room("hall").unwrap().has_driver("s1"));
+
+        let removed = house.remove_room("hall").unwrap();
+        assert_eq!(count(&disconnected), 1);
+        assert!(!removed.has_driver("s1"));
+        assert_eq!(removed.devices(), ["s1"]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn house_cell_disconnects_on_swap() {
+        let driver = Counting::new("s1");
+        let (_, disconnected) = driver.counts();
+        let mut hall = SmartRoom::new("hall");
+        hall.plug_driver(Box::new(driver)).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+        let cell = crate::HouseCell::new(house);
+
+        let failed = cell.try_update(|house| {
+            house.unplug("hall", "s1")?;
+            house.unplug("hall", "s1")
+        });
+        assert!(failed.is_err());
+        assert_eq!(count(&disconnected), 0);
+        cell.try_update(|house| house.unplug("hall", "s1")).unwrap();
+        assert_eq!(count(&disconnected), 1);
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::string::String;
+use core::{error::Error, fmt};
+
+use crate::{Limit, PolicyViolation};
+
+/// Why a room or a house refused a change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmartHouseError {
+    DuplicateDevice(String),
+    DuplicateRoom(String),
+    RoomNotFound(String),
+    DeviceNotFound {
+        room: String,
+        device: String,
+    },
+    // слабая ссылка пришла уже мёртвой
+    DeviceDropped,
+    Policy(PolicyViolation),
+    /// The change would go over a [`HouseConfig`](crate::HouseConfig) limit;
+    /// `attempted` is the count, or name length, it would lead to.
+    LimitExceeded {
+        limit: Limit,
+        max: usize,
+        attempted: usize,
+    },
+    /// A [`DeviceDriver`](crate::DeviceDriver) could not connect its device.
+    Driver(String),
+}
+
+impl fmt::Display for SmartHouseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmartHouseError::DuplicateDevice(device) => {
+                write!(f, "Device with name {device} already pluged")
+            }
+            SmartHouseError::DuplicateRoom(room) => write!(f, "room {room} already constructed"),
+            SmartHouseError::RoomNotFound(room) => write!(f, "room {room} not found"),
+            SmartHouseError::DeviceNotFound { room, device } => {
+                write!(f, "device {device} not found in room {room}")
+            }
+            SmartHouseError::DeviceDropped => f.write_str("Device already dropped"),
+            SmartHouseError::Policy(violation) => write!(f, "{violation}"),
+            SmartHouseError::LimitExceeded {
+                limit,
+                max,
+                attempted,
+            } => write!(f, "{limit} limit is {max}, got {attempted}"),
+            SmartHouseError::Driver(reason) => write!(f, "driver failed to connect: {reason}"),
+        }
+    }
+}
+
+impl Error for SmartHouseError {}
+
+/// Why a [`RoomId`](crate::RoomId) or [`DeviceId`](crate::DeviceId) did not resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleError {
+    /// The handle came from another house or room.
+    Foreign,
+    /// The room has moved its devices since the handle was issued.
+    Stale,
+    /// The handle is for a weakly plugged device that no longer exists.
+    Dropped,
+}
+
+impl fmt::Display for HandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandleError::Foreign => f.write_str("handle belongs to another house or room"),
+            HandleError::Stale => f.write_str("handle is stale"),
+            HandleError::Dropped => f.write_str("device behind the handle was dropped"),
+        }
+    }
+}
+
+impl Error for HandleError {}
@@ -0,0 +1,216 @@
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+
+use crate::{DeviceDriver, Pluggable, SmartHouse};
+
+/// A change in the shape of a house, sent to subscribers after it has happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HouseEvent {
+    RoomAdded {
+        room: String,
+    },
+    RoomRemoved {
+        room: String,
+    },
+    DevicePlugged {
+        room: String,
+        device: String,
+    },
+    DeviceUnplugged {
+        room: String,
+        device: String,
+    },
+    DeviceMoved {
+        device: String,
+        from: String,
+        to: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Callback = dyn Fn(&HouseEvent) + Send + Sync;
+type Listener = Box<Callback>;
+
+#[derive(Default)]
+pub(crate) struct Listeners {
+    next: u64,
+    // в Arc, чтобы копия дома в HouseCell звала тех же подписчиков
+    listeners: Vec<(SubscriptionId, Arc<Callback>)>,
+    // во время транзакции или обновления HouseCell изменения копятся здесь и уходят
+    // только при фиксации
+    pub(crate) deferred: Option<Deferred>,
+}
+
+#[derive(Default)]
+pub(crate) struct Deferred {
+    pub(crate) events: Vec<HouseEvent>,
+    // вынутые устройства, которые драйверы отключат после фиксации
+    pub(crate) releases: Vec<(Arc<dyn DeviceDriver>, Arc<dyn Pluggable>)>,
+}
+
+impl Listeners {
+    #[cfg(feature = "std")]
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            next: self.next,
+            listeners: self.listeners.clone(),
+            deferred: None,
+        }
+    }
+
+    pub(crate) fn emit(&self, event: &HouseEvent) {
+        for (_, listener) in &self.listeners {
+            listener(event);
+        }
+    }
+}
+
+impl SmartHouse {
+    // Единственный путь, которым изменения попадают в журнал аудита и к подписчикам
+    pub(crate) fn changed(&mut self, event: HouseEvent) {
+        if let Some(deferred) = &mut self.listeners.deferred {
+            deferred.events.push(event);
+            return;
+        }
+        self.commit_change(&event);
+        self.listeners.emit(&event);
+    }
+
+    // Изменение сделано: новое поколение и запись в журнале, без подписчиков
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    pub(crate) fn commit_change(&mut self, event: &HouseEvent) {
+        self.generation += 1;
+        #[cfg(feature = "std")]
+        self.audit.record(event);
+    }
+
+    /// Calls `listener` after every change made through the house's own methods: `add`,
+    /// `remove_room`, `plug`, `unplug` and `move_device`. Failed operations send nothing,
+    /// and changes made in a [transaction](Self::transaction) or a `HouseCell` update are
+    /// sent once it commits.
+    pub fn subscribe(&mut self, listener: Listener) -> SubscriptionId {
+        let id = SubscriptionId(self.listeners.next);
+        self.listeners.next += 1;
+        self.listeners.listeners.push((id, Arc::from(listener)));
+        id
+    }
+
+    /// Returns whether the subscription existed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.listeners.listeners.len();
+        self.listeners.listeners.retain(|(other, _)| *other != id);
+        self.listeners.listeners.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{testing::fixtures::small_house, SmartRoom, SmartSocket, SmartThermometer};
+
+    fn recorder(house: &mut SmartHouse) -> (SubscriptionId, Arc<Mutex<Vec<HouseEvent>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let id = house.subscribe(Box::new(move |e| sink.lock().unwrap().push(e.clone())));
+        (id, events)
+    }
+
+    #[test]
+    fn listeners_see_committed_changes_only() {
+        let mut house = small_house();
+        let (first, all) = recorder(&mut house);
+        let (_, second) = recorder(&mut house);
+
+        house.add(SmartRoom::new("hall")).unwrap();
+        assert!(house.add(SmartRoom::new("hall")).is_err());
+        house.plug("hall", SmartSocket::new("kettle")).unwrap();
+        assert!(house.plug("hall", SmartSocket::new("kettle")).is_err());
+        assert!(house.plug("attic", SmartThermometer::new("t2")).is_err());
+        house.plug("hall", SmartThermometer::new("t2")).unwrap();
+        assert!(house.move_device("kettle", "hall", "attic").is_err());
+        assert!(house.move_device("fridge", "hall", "lust").is_err());
+        house.move_device("kettle", "hall", "lust").unwrap();
+        assert!(house.unplug("hall", "kettle").is_err());
+
+        assert!(house.unsubscribe(first));
+        assert!(!house.unsubscribe(first));
+        house.unplug("limb", "t1").unwrap();
+        assert!(house.remove_room("attic").is_none());
+        house.remove_room("hall").unwrap();
+
+        let text = |s: &str| s.to_string();
+        let expected = [
+            HouseEvent::RoomAdded { room: text("hall") },
+            HouseEvent::DevicePlugged {
+                room: text("hall"),
+                device: text("kettle"),
+            },
+            HouseEvent::DevicePlugged {
+                room: text("hall"),
+                device: text("t2"),
+            },
+            HouseEvent::DeviceMoved {
+                device: text("kettle"),
+                from: text("hall"),
+                to: text("lust"),
+            },
+        ];
+        assert_eq!(*all.lock().unwrap(), expected);
+
+        let mut rest = expected.to_vec();
+        rest.push(HouseEvent::DeviceUnplugged {
+            room: text("limb"),
+            device: text("t1"),
+        });
+        rest.push(HouseEvent::RoomRemoved { room: text("hall") });
+        assert_eq!(*second.lock().unwrap(), rest);
+
+        assert_eq!(house.room("lust").unwrap().devices(), ["s2", "kettle"]);
+        assert!(house.room("hall").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn house_cell_sends_committed_updates_only() {
+        let mut house = small_house();
+        let generation = house.generation();
+        let (first, all) = recorder(&mut house);
+        let cell = Arc::new(crate::HouseCell::new(house));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let (reader, sink) = (Arc::clone(&cell), Arc::clone(&seen));
+        // подписчик, добавленный обновлением, видит уже новый дом
+        cell.update(|house| {
+            house.subscribe(Box::new(move |_| {
+                let rooms = reader.load().get_rooms().len();
+                sink.lock().unwrap().push(rooms);
+            }))
+        });
+
+        assert!(cell
+            .try_update(|house| {
+                house.add(SmartRoom::new("hall"))?;
+                house.add(SmartRoom::new("hall"))
+            })
+            .is_err());
+        assert!(all.lock().unwrap().is_empty());
+        cell.update(|house| {
+            house.add(SmartRoom::new("hall")).unwrap();
+            house.transaction(|tx| tx.add_room(SmartRoom::new("attic")))
+        })
+        .unwrap();
+        assert_eq!(*seen.lock().unwrap(), [4, 4]);
+
+        assert!(cell.update(|house| house.unsubscribe(first)));
+        cell.update(|house| house.plug("hall", SmartSocket::new("kettle")))
+            .unwrap();
+        let rooms = |room: &str| HouseEvent::RoomAdded {
+            room: room.to_string(),
+        };
+        assert_eq!(*all.lock().unwrap(), [rooms("hall"), rooms("attic")]);
+        assert_eq!(seen.lock().unwrap().len(), 3);
+        assert_eq!(cell.load().generation(), generation + 3);
+    }
+}
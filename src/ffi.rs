@@ -0,0 +1,287 @@
+//! A C interface to the house model, declared in `include/smarthouse.h`.
+//!
+//! Build a library for C with
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib` (or `staticlib`).
+//!
+//! Strings go both ways as NUL-terminated UTF-8. Functions that can fail return a
+//! negative status, or zero where they return a size, and leave a message for
+//! [`smarthouse_last_error`]. Panics are caught at the boundary and reported the same way.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{HouseReport, SmartHouse, SmartRoom, SmartSocket, SmartThermometer};
+
+pub const SMARTHOUSE_OK: c_int = 0;
+pub const SMARTHOUSE_ERROR: c_int = -1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    // NUL внутри сообщения обрезал бы его в C, заменяем
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+// Ошибка или паника превращаются в `fail` и сообщение для smarthouse_last_error
+fn guard<T>(fail: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(message)) => {
+            set_error(message);
+            fail
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_error(format!("panic: {message}"));
+            fail
+        }
+    }
+}
+
+unsafe fn text<'a>(ptr: *const c_char, what: &str) -> Result<&'a str, String> {
+    if ptr.is_null() {
+        return Err(format!("{what} is null"));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| format!("{what} is not UTF-8"))
+}
+
+unsafe fn house_mut<'a>(house: *mut SmartHouse) -> Result<&'a mut SmartHouse, String> {
+    house.as_mut().ok_or_else(|| "house is null".to_string())
+}
+
+/// Creates a house, to be freed with [`smarthouse_free`]. Returns null on error.
+///
+/// # Safety
+///
+/// `name` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn smarthouse_new(name: *const c_char) -> *mut SmartHouse {
+    guard(ptr::null_mut(), || {
+        let name = text(name, "name")?;
+        Ok(Box::into_raw(Box::new(SmartHouse::new(name))))
+    })
+}
+
+/// Frees a house. Null is ignored.
+///
+/// # Safety
+///
+/// `house` must be null or come from [`smarthouse_new`], and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn smarthouse_free(house: *mut SmartHouse) {
+    if !house.is_null() {
+        guard((), || {
+            drop(Box::from_raw(house));
+            Ok(())
+        })
+    }
+}
+
+/// # Safety
+///
+/// `house` must be null or a live house, `room` null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn smarthouse_add_room(house: *mut SmartHouse, room: *const c_char) -> c_int {
+    guard(SMARTHOUSE_ERROR, || {
+        let house = house_mut(house)?;
+        let room = text(room, "room")?;
+        house
+            .add(SmartRoom::new(room))
+            .map_err(|err| err.to_string())?;
+        Ok(SMARTHOUSE_OK)
+    })
+}
+
+/// Plugs a new switched-off socket into a room.
+///
+/// # Safety
+///
+/// As for [`smarthouse_add_room`], with `device` a NUL-terminated string too.
+#[no_mangle]
+pub unsafe extern "C" fn smarthouse_plug_socket(
+    house: *mut SmartHouse,
+    room: *const c_char,
+    device: *const c_char,
+) -> c_int {
+    guard(SMARTHOUSE_ERROR, || {
+        let house = house_mut(house)?;
+        let (room, device) = (text(room, "room")?, text(device, "device")?);
+        house
+            .plug(room, SmartSocket::new(device))
+            .map_err(|err| err.to_string())?;
+        Ok(SMARTHOUSE_OK)
+    })
+}
+
+/// Plugs a new thermometer without a reading into a room.
+///
+/// # Safety
+///
+/// As for [`smarthouse_plug_socket`].
+#[no_mangle]
+pub unsafe extern "C" fn smarthouse_plug_thermometer(
+    house: *mut SmartHouse,
+    room: *const c_char,
+    device: *const c_char,
+) -> c_int {
+    guard(SMARTHOUSE_ERROR, || {
+        let house = house_mut(house)?;
+        let (room, device) = (text(room, "room")?, text(device, "device")?);
+        house
+            .plug(room, SmartThermometer::new(device))
+            .map_err(|err| err.to_string())?;
+        Ok(SMARTHOUSE_OK)
+    })
+}
+
+/// Devices in every room of the house, or -1 on error.
+///
+/// # Safety
+///
+/// `house` must be null or a live house.
+#[no_mangle]
+pub unsafe extern "C" fn smarthouse_device_count(house: *const SmartHouse) -> i64 {
+    guard(-1, || {
+        let house = house.as_ref().ok_or("house is null")?;
+        let count: usize = house
+            .get_rooms()
+            .iter()
+            .map(|room| room.live_devices().count())
+            .sum();
+        Ok(count as i64)
+    })
+}
+
+/// Renders the house tree report into `buf` and returns its length with the closing NUL.
+/// When `buf` is null or shorter than that, nothing is written, so a first call with a
+/// null buffer tells the size to allocate. Returns 0 on error.
+///
+/// # Safety
+///
+/// `house` must be null or a live house; `buf` null or valid for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn smarthouse_report(
+    house: *const SmartHouse,
+    buf: *mut c_char,
+    len: usize,
+) -> usize {
+    guard(0, || {
+        let house = house.as_ref().ok_or("house is null")?;
+        let report = house
+            .create_report(HouseReport)
+            .map_err(|err| err.to_string())?;
+        let needed = report.len() + 1;
+        if !buf.is_null() && len >= needed {
+            ptr::copy_nonoverlapping(report.as_ptr(), buf.cast(), report.len());
+            *buf.add(report.len()) = 0;
+        }
+        Ok(needed)
+    })
+}
+
+/// The message of the latest failed call on this thread, or null if the latest call
+/// succeeded. The string stays valid until the next call on the thread.
+#[no_mangle]
+pub extern "C" fn smarthouse_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(text: &str) -> CString {
+        CString::new(text).unwrap()
+    }
+
+    fn last_error() -> Option<String> {
+        let error = smarthouse_last_error();
+        (!error.is_null()).then(|| {
+            unsafe { CStr::from_ptr(error) }
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+    }
+
+    #[test]
+    fn house_through_the_c_interface() {
+        unsafe {
+            let house = smarthouse_new(c("home").as_ptr());
+            assert!(!house.is_null());
+            assert_eq!(
+                smarthouse_add_room(house, c("hall").as_ptr()),
+                SMARTHOUSE_OK
+            );
+            let (hall, s1) = (c("hall"), c("s1"));
+            assert_eq!(
+                smarthouse_plug_socket(house, hall.as_ptr(), s1.as_ptr()),
+                SMARTHOUSE_OK
+            );
+            assert_eq!(
+                smarthouse_plug_thermometer(house, hall.as_ptr(), c("t1").as_ptr()),
+                SMARTHOUSE_OK
+            );
+            assert_eq!(smarthouse_device_count(house), 2);
+
+            let needed = smarthouse_report(house, ptr::null_mut(), 0);
+            let mut buf = vec![0 as c_char; needed];
+            assert_eq!(
+                smarthouse_report(house, buf.as_mut_ptr(), buf.len()),
+                needed
+            );
+            let report = CStr::from_ptr(buf.as_ptr()).to_str().unwrap();
+            assert_eq!(report, (*house).create_report(HouseReport).unwrap());
+            smarthouse_free(house);
+        }
+    }
+
+    #[test]
+    fn errors_are_left_for_last_error() {
+        unsafe {
+            let house = smarthouse_new(c("home").as_ptr());
+            assert_eq!(
+                smarthouse_plug_socket(house, c("attic").as_ptr(), c("s1").as_ptr()),
+                SMARTHOUSE_ERROR
+            );
+            assert_eq!(last_error().as_deref(), Some("room attic not found"));
+            assert_eq!(smarthouse_add_room(house, ptr::null()), SMARTHOUSE_ERROR);
+            assert_eq!(last_error().as_deref(), Some("room is null"));
+            assert_eq!(
+                smarthouse_add_room(house, c("hall").as_ptr()),
+                SMARTHOUSE_OK
+            );
+            assert_eq!(last_error(), None);
+
+            let bad = [0xffu8 as c_char, 0];
+            assert!(smarthouse_new(bad.as_ptr()).is_null());
+            assert_eq!(last_error().as_deref(), Some("name is not UTF-8"));
+            assert_eq!(smarthouse_device_count(ptr::null()), -1);
+            smarthouse_free(house);
+        }
+    }
+
+    #[test]
+    fn panics_do_not_cross_the_boundary() {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let failed = guard(SMARTHOUSE_ERROR, || panic!("boom"));
+        panic::set_hook(hook);
+        assert_eq!(failed, SMARTHOUSE_ERROR);
+        assert_eq!(last_error().as_deref(), Some("panic: boom"));
+    }
+}
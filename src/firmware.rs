@@ -0,0 +1,264 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+};
+
+use crate::{SmartSocket, SmartThermometer};
+
+/// Version every built-in device starts with.
+pub const FACTORY_FIRMWARE: &str = "1.0.0";
+
+/// Percentages reported while an update installs; the failure hook is asked before each.
+pub const UPDATE_STAGES: [u8; 5] = [0, 25, 50, 75, 100];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirmwareState {
+    Ready,
+    /// An update is running; the device takes no other update until it ends.
+    Updating,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateProgress {
+    Started { from: String, to: String },
+    Installing(u8),
+    Finished { version: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateError {
+    InProgress,
+    /// The update stopped at `at` percent; the old version stays.
+    Failed {
+        at: u8,
+        reason: String,
+    },
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::InProgress => f.write_str("firmware update already in progress"),
+            UpdateError::Failed { at, reason } => {
+                write!(f, "firmware update failed at {at}%: {reason}")
+            }
+        }
+    }
+}
+
+impl Error for UpdateError {}
+
+type FailureHook = Box<dyn Fn(&str, u8) -> Option<String> + Send + Sync>;
+
+/// The firmware of a device. Updates are simulated: they run through
+/// [`UPDATE_STAGES`] at once, on the calling thread.
+pub struct Firmware {
+    version: Mutex<String>,
+    updating: AtomicBool,
+    failure: Mutex<Option<FailureHook>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Default for Firmware {
+    fn default() -> Self {
+        Self {
+            version: Mutex::new(FACTORY_FIRMWARE.to_string()),
+            updating: AtomicBool::new(false),
+            failure: Mutex::new(None),
+        }
+    }
+}
+
+/// The copy has the same version, and neither the update in progress nor the hook.
+impl Clone for Firmware {
+    fn clone(&self) -> Self {
+        Self {
+            version: Mutex::new(self.version()),
+            ..Self::default()
+        }
+    }
+}
+
+impl fmt::Debug for Firmware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Firmware")
+            .field("version", &self.version())
+            .field("state", &self.state())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Firmware {
+    pub fn version(&self) -> String {
+        lock(&self.version).clone()
+    }
+
+    pub fn state(&self) -> FirmwareState {
+        match self.updating.load(Ordering::SeqCst) {
+            true => FirmwareState::Updating,
+            false => FirmwareState::Ready,
+        }
+    }
+
+    /// Makes updates fail: `hook` gets the target version and the stage about to run,
+    /// and returns the reason to fail there, or `None` to go on.
+    pub fn inject_failure(
+        &self,
+        hook: impl Fn(&str, u8) -> Option<String> + Send + Sync + 'static,
+    ) {
+        *lock(&self.failure) = Some(Box::new(hook));
+    }
+
+    pub fn clear_failure(&self) {
+        *lock(&self.failure) = None;
+    }
+
+    /// Installs `target`, reporting each stage to `progress`. An attempt made while
+    /// another update runs, from `progress` or from another thread, is refused.
+    pub fn update(
+        &self,
+        target: &str,
+        progress: impl Fn(UpdateProgress),
+    ) -> Result<(), UpdateError> {
+        if self.updating.swap(true, Ordering::SeqCst) {
+            return Err(UpdateError::InProgress);
+        }
+        let installed = self.install(target, progress);
+        self.updating.store(false, Ordering::SeqCst);
+        installed
+    }
+
+    fn install(&self, target: &str, progress: impl Fn(UpdateProgress)) -> Result<(), UpdateError> {
+        progress(UpdateProgress::Started {
+            from: self.version(),
+            to: target.to_string(),
+        });
+        for at in UPDATE_STAGES {
+            let failed = lock(&self.failure)
+                .as_ref()
+                .and_then(|hook| hook(target, at));
+            if let Some(reason) = failed {
+                return Err(UpdateError::Failed { at, reason });
+            }
+            progress(UpdateProgress::Installing(at));
+        }
+        *lock(&self.version) = target.to_string();
+        progress(UpdateProgress::Finished {
+            version: target.to_string(),
+        });
+        Ok(())
+    }
+}
+
+/// A device whose firmware can be updated.
+pub trait Updatable {
+    fn firmware(&self) -> &Firmware;
+
+    /// See [`Firmware::update`].
+    fn begin_firmware_update(
+        &self,
+        target: &str,
+        progress: impl Fn(UpdateProgress),
+    ) -> Result<(), UpdateError> {
+        self.firmware().update(target, progress)
+    }
+}
+
+impl Updatable for SmartSocket {
+    fn firmware(&self) -> &Firmware {
+        &self.firmware
+    }
+}
+
+impl Updatable for SmartThermometer {
+    fn firmware(&self) -> &Firmware {
+        &self.firmware
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn recorder() -> (Arc<Mutex<Vec<UpdateProgress>>>, impl Fn(UpdateProgress)) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        (seen, move |progress| lock(&sink).push(progress))
+    }
+
+    #[test]
+    fn update_runs_through_the_stages() {
+        let socket = SmartSocket::new("s1");
+        let (seen, progress) = recorder();
+        socket.begin_firmware_update("1.1.0", progress).unwrap();
+
+        assert_eq!(socket.firmware().version(), "1.1.0");
+        assert_eq!(socket.firmware().state(), FirmwareState::Ready);
+        let seen = lock(&seen);
+        assert_eq!(
+            seen[0],
+            UpdateProgress::Started {
+                from: FACTORY_FIRMWARE.to_string(),
+                to: "1.1.0".to_string(),
+            }
+        );
+        assert_eq!(seen[1..6], UPDATE_STAGES.map(UpdateProgress::Installing));
+        assert_eq!(
+            seen[6],
+            UpdateProgress::Finished {
+                version: "1.1.0".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn injected_failure_keeps_the_old_version() {
+        let thermometer = SmartThermometer::new("t1");
+        thermometer
+            .firmware()
+            .inject_failure(|_, at| (at == 50).then(|| "flash write error".to_string()));
+        let (seen, progress) = recorder();
+
+        assert_eq!(
+            thermometer.begin_firmware_update("2.0.0", progress),
+            Err(UpdateError::Failed {
+                at: 50,
+                reason: "flash write error".to_string(),
+            })
+        );
+        assert_eq!(thermometer.firmware().version(), FACTORY_FIRMWARE);
+        assert_eq!(thermometer.firmware().state(), FirmwareState::Ready);
+        assert_eq!(lock(&seen).last(), Some(&UpdateProgress::Installing(25)));
+
+        thermometer.firmware().clear_failure();
+        thermometer.begin_firmware_update("2.0.0", |_| {}).unwrap();
+        assert_eq!(thermometer.firmware().version(), "2.0.0");
+    }
+
+    #[test]
+    fn second_update_during_an_update_is_refused() {
+        let socket = Arc::new(SmartSocket::new("s1"));
+        let inner = Arc::new(Mutex::new(None));
+        let (device, attempt) = (Arc::clone(&socket), Arc::clone(&inner));
+        socket
+            .begin_firmware_update("1.1.0", move |progress| {
+                if progress == UpdateProgress::Installing(25) {
+                    assert_eq!(device.firmware().state(), FirmwareState::Updating);
+                    *lock(&attempt) = Some(device.begin_firmware_update("9.9.9", |_| {}));
+                }
+            })
+            .unwrap();
+
+        assert_eq!(*lock(&inner), Some(Err(UpdateError::InProgress)));
+        assert_eq!(socket.firmware().version(), "1.1.0");
+    }
+}
@@ -0,0 +1,241 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::fmt;
+
+use crate::{Pluggable, SmartHouse, SmartRoom};
+
+/// How far from its position a device is still hit by [`SmartHouse::device_at`], in the
+/// units of the floor plan.
+pub const DEVICE_HIT_RADIUS: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Position {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    // без std нет f64::sqrt, а сравнивать можно и квадраты
+    fn distance_squared(self, other: Position) -> f64 {
+        let (dx, dy) = (self.x - other.x, self.y - other.y);
+        dx * dx + dy * dy
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+/// An axis-aligned rectangle from `min` to `max`. Its edges belong to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min: Position,
+    pub max: Position,
+}
+
+impl Rect {
+    /// The rectangle spanned by two opposite corners, in any order.
+    pub fn new(a: Position, b: Position) -> Self {
+        Self {
+            min: Position::new(a.x.min(b.x), a.y.min(b.y)),
+            max: Position::new(a.x.max(b.x), a.y.max(b.y)),
+        }
+    }
+
+    pub fn contains(&self, point: Position) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x) && (self.min.y..=self.max.y).contains(&point.y)
+    }
+}
+
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.min, self.max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeometryError {
+    DeviceNotFound(String),
+    /// The device would lie outside the room's bounds.
+    Outside {
+        device: String,
+        position: Position,
+        bounds: Rect,
+    },
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeometryError::DeviceNotFound(device) => write!(f, "device {device} not found"),
+            GeometryError::Outside {
+                device,
+                position,
+                bounds,
+            } => write!(f, "device {device} at {position} is outside {bounds}"),
+        }
+    }
+}
+
+impl core::error::Error for GeometryError {}
+
+// Геометрия хранится в комнате: устройство о своём месте не знает
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Geometry {
+    bounds: Option<Rect>,
+    pub(crate) positions: BTreeMap<String, Position>,
+}
+
+impl SmartRoom {
+    /// Places the room on the floor plan. Fails if a device already placed in the room
+    /// would be outside the new bounds.
+    pub fn set_bounds(&mut self, bounds: Rect) -> Result<(), GeometryError> {
+        if let Some((device, &position)) = self
+            .geometry
+            .positions
+            .iter()
+            .find(|(_, &position)| !bounds.contains(position))
+        {
+            return Err(GeometryError::Outside {
+                device: device.clone(),
+                position,
+                bounds,
+            });
+        }
+        self.geometry.bounds = Some(bounds);
+        Ok(())
+    }
+
+    pub fn clear_bounds(&mut self) {
+        self.geometry.bounds = None;
+    }
+
+    pub fn bounds(&self) -> Option<Rect> {
+        self.geometry.bounds
+    }
+
+    /// Places a device of the room on the floor plan, inside the room's bounds if it has
+    /// any. The position is forgotten when the device leaves the room.
+    pub fn set_position(&mut self, device: &str, position: Position) -> Result<(), GeometryError> {
+        let device = self
+            .device(device)
+            .ok_or_else(|| GeometryError::DeviceNotFound(device.to_string()))?;
+        if let Some(bounds) = self.geometry.bounds.filter(|b| !b.contains(position)) {
+            return Err(GeometryError::Outside {
+                device: device.name().to_string(),
+                position,
+                bounds,
+            });
+        }
+        self.geometry
+            .positions
+            .insert(device.name().to_string(), position);
+        Ok(())
+    }
+
+    pub fn clear_position(&mut self, device: &str) -> Option<Position> {
+        self.geometry.positions.remove(device)
+    }
+
+    pub fn position(&self, device: &str) -> Option<Position> {
+        self.geometry.positions.get(device).copied()
+    }
+}
+
+impl SmartHouse {
+    /// The placed device closest to `point`, within [`DEVICE_HIT_RADIUS`]. Rooms may
+    /// overlap, so every room is searched, not only the ones containing `point`; of two
+    /// devices equally close, the one in the room added first wins.
+    pub fn device_at(&self, point: Position) -> Option<(&SmartRoom, Arc<dyn Pluggable>)> {
+        let mut closest: Option<(f64, &SmartRoom, &str)> = None;
+        for room in &self.rooms {
+            for (device, &position) in &room.geometry.positions {
+                let distance = position.distance_squared(point);
+                let nearer = closest.is_none_or(|(best, ..)| distance < best);
+                if distance <= DEVICE_HIT_RADIUS * DEVICE_HIT_RADIUS && nearer {
+                    closest = Some((distance, room, device));
+                }
+            }
+        }
+        let (_, room, device) = closest?;
+        Some((room, room.device(device)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SmartSocket, SmartThermometer};
+
+    fn rect(x0: f64, y0: f64, x1: f64, y1: f64) -> Rect {
+        Rect::new(Position::new(x0, y0), Position::new(x1, y1))
+    }
+
+    #[test]
+    fn positions_stay_inside_the_bounds() {
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(SmartSocket::new("s1")).unwrap();
+        hall.set_position("s1", Position::new(7.0, 3.0)).unwrap();
+        assert_eq!(
+            hall.set_bounds(rect(0.0, 0.0, 5.0, 5.0)),
+            Err(GeometryError::Outside {
+                device: "s1".to_string(),
+                position: Position::new(7.0, 3.0),
+                bounds: rect(0.0, 0.0, 5.0, 5.0),
+            })
+        );
+        assert_eq!(hall.bounds(), None);
+
+        hall.set_bounds(rect(10.0, 5.0, 0.0, 0.0)).unwrap();
+        assert_eq!(hall.bounds(), Some(rect(0.0, 0.0, 10.0, 5.0)));
+        // край комнаты принадлежит ей
+        hall.set_position("s1", Position::new(10.0, 0.0)).unwrap();
+        assert!(hall.set_position("s1", Position::new(10.5, 0.0)).is_err());
+        assert_eq!(hall.position("s1"), Some(Position::new(10.0, 0.0)));
+        assert_eq!(
+            hall.set_position("t1", Position::default()),
+            Err(GeometryError::DeviceNotFound("t1".to_string()))
+        );
+
+        hall.unplug("s1");
+        hall.plug(SmartSocket::new("s1")).unwrap();
+        assert_eq!(hall.position("s1"), None);
+    }
+
+    #[test]
+    fn hit_testing_across_overlapping_rooms() {
+        let mut hall = SmartRoom::new("hall");
+        hall.set_bounds(rect(0.0, 0.0, 10.0, 10.0)).unwrap();
+        hall.plug(SmartSocket::new("s1")).unwrap();
+        hall.set_position("s1", Position::new(9.0, 5.0)).unwrap();
+        let mut nook = SmartRoom::new("nook");
+        nook.set_bounds(rect(8.0, 4.0, 12.0, 6.0)).unwrap();
+        nook.plug(SmartThermometer::new("t1")).unwrap();
+        nook.set_position("t1", Position::new(10.0, 5.0)).unwrap();
+        nook.plug(SmartSocket::new("unplaced")).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+        house.add(nook).unwrap();
+
+        let hit = |x, y| {
+            house
+                .device_at(Position::new(x, y))
+                .map(|(room, device)| (room.name.clone(), device.name().to_string()))
+        };
+        assert_eq!(hit(9.1, 5.0), Some(("hall".into(), "s1".into())));
+        assert_eq!(hit(9.9, 5.0), Some(("nook".into(), "t1".into())));
+        // ровно посередине выигрывает комната, добавленная раньше
+        assert_eq!(hit(9.5, 5.0), Some(("hall".into(), "s1".into())));
+        assert_eq!(hit(9.0, 5.5), Some(("hall".into(), "s1".into())));
+        assert_eq!(hit(3.0, 3.0), None);
+    }
+}
@@ -0,0 +1,243 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{CommandError, CommandOutcome, DeviceCommand, DeviceLocation, LocateError, SmartHouse};
+
+/// Devices from any rooms, run together by [`SmartHouse::execute_group`].
+///
+/// Members are kept as full `room/device` paths. The house keeps them up to date when a
+/// device moves, and drops them when it unplugs a device or removes its room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceGroup {
+    pub name: String,
+    pub members: Vec<DeviceLocation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupError {
+    UnknownGroup(String),
+    DuplicateGroup(String),
+    Missing(LocateError),
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupError::UnknownGroup(group) => write!(f, "group {group} not found"),
+            GroupError::DuplicateGroup(group) => write!(f, "group {group} already exists"),
+            GroupError::Missing(err) => err.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for GroupError {}
+
+type MemberOutcomes = Vec<(DeviceLocation, Result<CommandOutcome, CommandError>)>;
+
+impl SmartHouse {
+    pub fn create_group(&mut self, name: impl Into<String>) -> Result<(), GroupError> {
+        let name = name.into();
+        if self.group(&name).is_some() {
+            return Err(GroupError::DuplicateGroup(name));
+        }
+        self.groups.push(DeviceGroup {
+            name,
+            members: Vec::new(),
+        });
+        Ok(())
+    }
+
+    pub fn remove_group(&mut self, name: &str) -> Option<DeviceGroup> {
+        let position = self.groups.iter().position(|group| group.name == name)?;
+        Some(self.groups.remove(position))
+    }
+
+    pub fn group(&self, name: &str) -> Option<&DeviceGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    pub fn groups(&self) -> &[DeviceGroup] {
+        &self.groups
+    }
+
+    /// Adds the device at `path` to the group; it has to be in the house. Returns `false`
+    /// if the device was already a member.
+    pub fn add_to_group(&mut self, name: &str, path: &DeviceLocation) -> Result<bool, GroupError> {
+        let member = self.resolve(path)?;
+        let group = self.group_mut(name)?;
+        if group.members.contains(&member) {
+            return Ok(false);
+        }
+        group.members.push(member);
+        Ok(true)
+    }
+
+    /// Returns `false` if the device was not a member.
+    pub fn remove_from_group(
+        &mut self,
+        name: &str,
+        path: &DeviceLocation,
+    ) -> Result<bool, GroupError> {
+        let group = self.group_mut(name)?;
+        let before = group.members.len();
+        group.members.retain(|member| !matches(path, member));
+        Ok(group.members.len() != before)
+    }
+
+    /// Names of the groups the device at `path` is a member of. A path without a room
+    /// matches the device name in any room.
+    pub fn groups_of(&self, path: &DeviceLocation) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|group| group.members.iter().any(|member| matches(path, member)))
+            .map(|group| group.name.as_str())
+            .collect()
+    }
+
+    /// Runs `command` on every member in turn, going on past members that fail. A member
+    /// whose device was taken out behind the house's back is reported as missing.
+    pub fn execute_group(
+        &self,
+        name: &str,
+        command: DeviceCommand,
+    ) -> Result<MemberOutcomes, GroupError> {
+        let group = self
+            .group(name)
+            .ok_or_else(|| GroupError::UnknownGroup(name.to_string()))?;
+        Ok(group
+            .members
+            .iter()
+            .map(|member| (member.clone(), self.execute(member, command)))
+            .collect())
+    }
+
+    fn group_mut(&mut self, name: &str) -> Result<&mut DeviceGroup, GroupError> {
+        self.groups
+            .iter_mut()
+            .find(|group| group.name == name)
+            .ok_or_else(|| GroupError::UnknownGroup(name.to_string()))
+    }
+
+    // Короткий путь превращается в полный room/device
+    fn resolve(&self, path: &DeviceLocation) -> Result<DeviceLocation, GroupError> {
+        let device = self.locate(path).map_err(GroupError::Missing)?;
+        let room = match path.room.is_empty() {
+            true => self
+                .rooms
+                .iter()
+                .find(|room| room.is_connected(&*device))
+                .map(|room| room.name.clone())
+                .unwrap_or_default(),
+            false => path.room.clone(),
+        };
+        Ok(DeviceLocation {
+            house: None,
+            room,
+            device: path.device.clone(),
+        })
+    }
+
+    pub(crate) fn move_members(&mut self, device: &str, from: &str, to: &str) {
+        for member in self.groups.iter_mut().flat_map(|group| &mut group.members) {
+            if member.room == from && member.device == device {
+                member.room = to.to_string();
+            }
+        }
+    }
+
+    pub(crate) fn drop_members(&mut self, room: &str, device: Option<&str>) {
+        for group in &mut self.groups {
+            group.members.retain(|member| {
+                member.room != room || device.is_some_and(|device| member.device != device)
+            });
+        }
+    }
+}
+
+fn matches(path: &DeviceLocation, member: &DeviceLocation) -> bool {
+    path.device == member.device && (path.room.is_empty() || path.room == member.room)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{small_house, socket};
+
+    // Розетки обеих комнат small_house в одной группе
+    fn house() -> SmartHouse {
+        let mut house = small_house();
+        house.create_group("sockets").unwrap();
+        house.add_to_group("sockets", &path("limb/s1")).unwrap();
+        house.add_to_group("sockets", &path("s2")).unwrap();
+        house
+    }
+
+    fn path(text: &str) -> DeviceLocation {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn group_commands_reach_every_member() {
+        let house = house();
+        let outcomes = house
+            .execute_group("sockets", DeviceCommand::TurnOn)
+            .unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|(_, outcome)| outcome.is_ok()));
+        assert!(socket(&house, "limb", "s1").is_on());
+        assert!(socket(&house, "lust", "s2").is_on());
+
+        assert_eq!(
+            house.execute_group("lights", DeviceCommand::TurnOn),
+            Err(GroupError::UnknownGroup("lights".to_string()))
+        );
+    }
+
+    #[test]
+    fn membership_follows_the_house() {
+        let mut house = house();
+        assert_eq!(
+            house.create_group("sockets"),
+            Err(GroupError::DuplicateGroup("sockets".to_string()))
+        );
+        assert_eq!(house.add_to_group("sockets", &path("s1")), Ok(false));
+        assert!(matches!(
+            house.add_to_group("sockets", &path("attic/fan")),
+            Err(GroupError::Missing(_))
+        ));
+
+        house.move_device("s1", "limb", "lust").unwrap();
+        assert_eq!(house.groups_of(&path("lust/s1")), ["sockets"]);
+        assert!(house.groups_of(&path("limb/s1")).is_empty());
+
+        house.unplug("lust", "s2").unwrap();
+        assert_eq!(house.group("sockets").unwrap().members, [path("lust/s1")]);
+        house.remove_room("lust").unwrap();
+        assert!(house.group("sockets").unwrap().members.is_empty());
+    }
+
+    #[test]
+    fn dangling_member_is_reported_missing() {
+        let mut house = house();
+        let s1 = socket(&house, "limb", "s1");
+        // розетку вынули мимо дома, группа об этом не знает
+        house.room_mut("limb").unwrap().unplug("s1");
+
+        let outcomes = house
+            .execute_group("sockets", DeviceCommand::TurnOn)
+            .unwrap();
+        assert!(matches!(
+            &outcomes[..],
+            [
+                (_, Err(CommandError::Missing(LocateError::Device { .. }))),
+                (_, Ok(_)),
+            ]
+        ));
+        assert!(!s1.is_on());
+        assert!(house.remove_from_group("sockets", &path("s1")).unwrap());
+        assert_eq!(house.groups_of(&path("s2")), ["sockets"]);
+    }
+}
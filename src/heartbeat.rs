@@ -0,0 +1,414 @@
+//! Watching networked devices for silent failures.
+//!
+//! A [`HeartbeatMonitor`] checks every registered device once per interval: a
+//! [`SocketClient`] must answer a state request, a [`ThermometerReceiver`] must have
+//! received a reading recently. After enough missed beats in a row a device is reported
+//! [`Offline`](Liveness::Offline), and [`Online`](Liveness::Online) again after its first
+//! successful beat.
+
+use core::fmt;
+use std::{
+    any::Any,
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    log::{info, warn},
+    net::SocketClient,
+    udp::ThermometerReceiver,
+    Clock, ErrorSink, LogSink, SmartHouse, SystemClock,
+};
+
+pub type BeatError = Box<dyn Error + Send + Sync>;
+
+/// One liveness check of a device.
+pub trait Heartbeat: Send + Sync {
+    /// Checks that the device is alive, allowing it `timeout` to show it.
+    fn beat(&self, timeout: Duration, now: SystemTime) -> Result<(), BeatError>;
+}
+
+/// Answers a state request over a short-lived connection within the timeout.
+impl Heartbeat for SocketClient {
+    fn beat(&self, timeout: Duration, _: SystemTime) -> Result<(), BeatError> {
+        self.ping(timeout)?;
+        Ok(())
+    }
+}
+
+/// Has received a reading no longer than the timeout ago.
+impl Heartbeat for ThermometerReceiver {
+    fn beat(&self, timeout: Duration, now: SystemTime) -> Result<(), BeatError> {
+        // показание «из будущего» по часам монитора считается свежим
+        let age = |at| now.duration_since(at).unwrap_or_default();
+        match self.last_received() {
+            Some(at) if age(at) <= timeout => Ok(()),
+            Some(at) => Err(format!("last reading {:?} ago", age(at)).into()),
+            None => Err("no reading yet".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HeartbeatConfig {
+    /// Time between two checks of all devices.
+    pub interval: Duration,
+    /// Missed beats in a row after which a device is offline.
+    pub miss_threshold: u32,
+    /// How long a single device may take to answer.
+    pub timeout: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            miss_threshold: 3,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Devices start online and switch only after a confirmed change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Liveness {
+    Online,
+    Offline,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeartbeatEvent {
+    Offline { device: String },
+    Online { device: String },
+}
+
+type Listener = Box<dyn Fn(&HeartbeatEvent) + Send + Sync>;
+
+struct Watched {
+    name: String,
+    device: Arc<dyn Heartbeat>,
+    missed: u32,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub struct HeartbeatMonitor {
+    config: HeartbeatConfig,
+    clock: Box<dyn Clock>,
+    devices: Vec<Watched>,
+    liveness: Arc<Mutex<HashMap<String, Liveness>>>,
+    listeners: Vec<Listener>,
+    sink: Arc<dyn ErrorSink>,
+}
+
+impl HeartbeatMonitor {
+    pub fn new(config: HeartbeatConfig) -> Self {
+        Self {
+            config,
+            clock: Box::new(SystemClock),
+            devices: Vec::new(),
+            liveness: Arc::default(),
+            listeners: Vec::new(),
+            sink: Arc::new(LogSink),
+        }
+    }
+
+    /// Receives the failed beat that takes a device offline, as the `heartbeat` component.
+    pub fn with_error_sink(mut self, sink: Arc<dyn ErrorSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Registers a device under `name`, replacing one registered under the same name.
+    pub fn watch(&mut self, name: impl Into<String>, device: Arc<dyn Heartbeat>) {
+        let name = name.into();
+        self.devices.retain(|watched| watched.name != name);
+        lock(&self.liveness).insert(name.clone(), Liveness::Online);
+        self.devices.push(Watched {
+            name,
+            device,
+            missed: 0,
+        });
+    }
+
+    /// Registers every [`SocketClient`] and [`ThermometerReceiver`] plugged into the house
+    /// and returns how many were found.
+    pub fn watch_house(&mut self, house: &SmartHouse) -> usize {
+        let mut found = 0;
+        for (_, device) in house.all_devices() {
+            let name = device.name().to_string();
+            let any: Arc<dyn Any + Send + Sync> = device;
+            let device: Arc<dyn Heartbeat> = match any.downcast::<SocketClient>() {
+                Ok(client) => client,
+                Err(any) => match any.downcast::<ThermometerReceiver>() {
+                    Ok(receiver) => receiver,
+                    Err(_) => continue,
+                },
+            };
+            self.watch(name, device);
+            found += 1;
+        }
+        found
+    }
+
+    /// Calls `listener` from the checking thread on every transition.
+    pub fn on_event(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+
+    pub fn liveness(&self, name: &str) -> Option<Liveness> {
+        lock(&self.liveness).get(name).copied()
+    }
+
+    /// Checks every device once and returns the transitions it caused.
+    pub fn check(&mut self) -> Vec<HeartbeatEvent> {
+        let mut events = Vec::new();
+        for watched in &mut self.devices {
+            let beat = watched.device.beat(self.config.timeout, self.clock.now());
+            let alive = beat.is_ok();
+            let mut liveness = lock(&self.liveness);
+            let state = liveness
+                .entry(watched.name.clone())
+                .or_insert(Liveness::Online);
+
+            watched.missed = match alive {
+                true => 0,
+                false => watched.missed.saturating_add(1),
+            };
+            let event = match *state {
+                Liveness::Online if watched.missed >= self.config.miss_threshold.max(1) => {
+                    *state = Liveness::Offline;
+                    warn!("device {} is offline", watched.name);
+                    if let Err(e) = &beat {
+                        self.sink.report("heartbeat", e.as_ref());
+                    }
+                    HeartbeatEvent::Offline {
+                        device: watched.name.clone(),
+                    }
+                }
+                Liveness::Offline if alive => {
+                    *state = Liveness::Online;
+                    info!("device {} is back online", watched.name);
+                    HeartbeatEvent::Online {
+                        device: watched.name.clone(),
+                    }
+                }
+                _ => continue,
+            };
+            drop(liveness);
+
+            for listener in &self.listeners {
+                listener(&event);
+            }
+            events.push(event);
+        }
+        events
+    }
+
+    /// Checks the devices on a background thread every interval until the handle is dropped.
+    pub fn spawn(mut self) -> MonitorHandle {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let liveness = Arc::clone(&self.liveness);
+
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || loop {
+                self.check();
+
+                let (stopped, wakeup) = &*stop;
+                let stopped = wakeup
+                    .wait_timeout_while(lock(stopped), self.config.interval, |stopped| !*stopped)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+                if *stopped {
+                    break;
+                }
+            })
+        };
+
+        MonitorHandle {
+            liveness,
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl fmt::Debug for HeartbeatMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let devices: Vec<_> = self.devices.iter().map(|watched| &watched.name).collect();
+        f.debug_struct("HeartbeatMonitor")
+            .field("config", &self.config)
+            .field("devices", &devices)
+            .field("listeners", &self.listeners.len())
+            .finish()
+    }
+}
+
+/// A running [`HeartbeatMonitor`]. Dropping the handle stops the monitor and waits for
+/// the check in progress, at most one device timeout per device.
+pub struct MonitorHandle {
+    liveness: Arc<Mutex<HashMap<String, Liveness>>>,
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MonitorHandle {
+    pub fn liveness(&self, name: &str) -> Option<Liveness> {
+        lock(&self.liveness).get(name).copied()
+    }
+
+    pub fn shutdown(self) {}
+}
+
+impl fmt::Debug for MonitorHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MonitorHandle")
+            .field("liveness", &*lock(&self.liveness))
+            .finish()
+    }
+}
+
+impl Drop for MonitorHandle {
+    fn drop(&mut self) {
+        let (stopped, wakeup) = &*self.stop;
+        *lock(stopped) = true;
+        wakeup.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        net::SocketServer,
+        testing::{ManualClock, MockDevice},
+        udp::ThermometerEmitter,
+        ChannelSink, SmartRoom,
+    };
+
+    fn config(miss_threshold: u32) -> HeartbeatConfig {
+        HeartbeatConfig {
+            interval: Duration::from_millis(10),
+            miss_threshold,
+            timeout: Duration::from_millis(200),
+        }
+    }
+
+    #[test]
+    fn offline_after_missed_beats_and_back() {
+        let lamp = Arc::new(MockDevice::new("lamp"));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut monitor = HeartbeatMonitor::new(config(2));
+        monitor.watch("lamp", lamp.clone());
+        let sink = Arc::clone(&seen);
+        monitor.on_event(Box::new(move |e| lock(&sink).push(e.clone())));
+
+        assert!(monitor.check().is_empty());
+        lamp.set_healthy(false);
+        assert!(monitor.check().is_empty());
+        assert_eq!(monitor.liveness("lamp"), Some(Liveness::Online));
+
+        let offline = HeartbeatEvent::Offline {
+            device: "lamp".to_string(),
+        };
+        assert_eq!(monitor.check(), std::slice::from_ref(&offline));
+        assert!(monitor.check().is_empty(), "Offline is reported once");
+        assert_eq!(monitor.liveness("lamp"), Some(Liveness::Offline));
+
+        lamp.set_healthy(true);
+        let online = HeartbeatEvent::Online {
+            device: "lamp".to_string(),
+        };
+        assert_eq!(monitor.check(), std::slice::from_ref(&online));
+        assert_eq!(*lock(&seen), [offline, online]);
+        assert_eq!(monitor.liveness("nope"), None);
+    }
+
+    #[test]
+    fn networked_devices_from_a_house() {
+        let server = SocketServer::bind("s1", "127.0.0.1:0")
+            .unwrap()
+            .spawn()
+            .unwrap();
+        let client = SocketClient::connect("s1", server.local_addr()).unwrap();
+        let emitter = ThermometerEmitter::bind("t1", "127.0.0.1:0")
+            .unwrap()
+            .with_interval(Duration::from_millis(10))
+            .spawn()
+            .unwrap();
+        let clock = ManualClock::default();
+        let receiver = ThermometerReceiver::subscribe("t1", emitter.local_addr())
+            .unwrap()
+            .with_clock(clock.clone());
+        assert!(receiver.wait_for_reading(Duration::from_secs(5)).is_some());
+        drop(emitter);
+
+        let mut room = SmartRoom::new("limb");
+        room.plug(client).unwrap();
+        room.plug(receiver).unwrap();
+        let mut house = SmartHouse::new("hell");
+        house.add(room).unwrap();
+
+        let (sink, errors) = ChannelSink::new();
+        let mut monitor = HeartbeatMonitor::new(config(1))
+            .with_clock(clock.clone())
+            .with_error_sink(Arc::new(sink));
+        assert_eq!(monitor.watch_house(&house), 2);
+        assert!(monitor.check().is_empty());
+
+        // показание устаревает, когда часы уходят дальше таймаута
+        clock.advance(Duration::from_secs(1));
+        server.shutdown();
+        let mut offline = monitor.check();
+        offline.sort_by_key(|e| format!("{e:?}"));
+        assert_eq!(
+            offline,
+            [
+                HeartbeatEvent::Offline {
+                    device: "s1".to_string()
+                },
+                HeartbeatEvent::Offline {
+                    device: "t1".to_string()
+                },
+            ]
+        );
+        let reported: Vec<_> = errors.try_iter().collect();
+        assert_eq!(reported.len(), 2);
+        assert!(reported.iter().all(|e| e.component == "heartbeat"));
+    }
+
+    #[test]
+    fn spawned_monitor_stops_when_dropped() {
+        let lamp = Arc::new(MockDevice::builder("lamp").healthy(false).build());
+        let mut monitor = HeartbeatMonitor::new(HeartbeatConfig {
+            interval: Duration::from_secs(3600),
+            ..config(1)
+        });
+        monitor.watch("lamp", lamp);
+
+        let handle = monitor.spawn();
+        let started = Instant::now();
+        while handle.liveness("lamp") != Some(Liveness::Offline) {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        }
+        handle.shutdown();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
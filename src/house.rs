@@ -0,0 +1,1972 @@
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    error::Error,
+    fmt, mem,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use crate::{
+    atomic::AtomicU64,
+    events::Listeners,
+    log::{debug, info, warn},
+    policy::Policies,
+    undo::Edit,
+    HandleError, HouseEvent, IntoDevice, Named, Pluggable, PolicyContext, PolicyViolation,
+    Reportable, SmartHouseError,
+};
+#[cfg(feature = "std")]
+use crate::{DeviceKind, HouseVisitor};
+
+#[cfg(feature = "std")]
+type Index = std::collections::HashMap<String, usize>;
+// Без std хэш-таблицы нет, индекс имён держим в BTreeMap
+#[cfg(not(feature = "std"))]
+type Index = alloc::collections::BTreeMap<String, usize>;
+
+#[cfg(feature = "std")]
+fn index_with_capacity(capacity: usize) -> Index {
+    Index::with_capacity(capacity)
+}
+
+#[cfg(not(feature = "std"))]
+fn index_with_capacity(_capacity: usize) -> Index {
+    Index::new()
+}
+
+fn misindexed<'a>(index: &Index, names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let names: Vec<_> = names.collect();
+    let mut wrong: Vec<String> = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        if index.get(*name) != Some(&i) && !wrong.iter().any(|w| w == name) {
+            wrong.push(name.to_string());
+        }
+    }
+    let mut stale: Vec<_> = index
+        .iter()
+        .filter(|&(name, &i)| names.get(i) != Some(&name.as_str()))
+        .map(|(name, _)| name.clone())
+        .filter(|name| !wrong.contains(name))
+        .collect();
+    stale.sort_unstable();
+    wrong.extend(stale);
+    wrong
+}
+
+// Каждый дом и каждая комната получают свой номер, по нему узнаются чужие хэндлы
+fn next_owner() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A handle to a room returned by [`SmartHouse::add`].
+///
+/// Only the house that returned it (not even a clone of it) accepts the handle, and only
+/// until a room is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RoomId {
+    house: usize,
+    epoch: u64,
+    index: usize,
+}
+
+/// A handle to a device returned by [`SmartRoom::plug`].
+///
+/// Only the room that returned it accepts the handle, and only until
+/// [`SmartRoom::prune_dead`] removes something or a dead device's place is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceId {
+    room: usize,
+    epoch: u64,
+    index: usize,
+}
+
+#[derive(Clone)]
+enum Plugged {
+    Strong(Arc<dyn Pluggable>),
+    // имя хранится отдельно: у умершего устройства его уже не спросить
+    Weak(String, Weak<dyn Pluggable>),
+}
+
+impl Plugged {
+    fn name(&self) -> &str {
+        match self {
+            Plugged::Strong(device) => device.name(),
+            Plugged::Weak(name, _) => name,
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match self {
+            Plugged::Strong(_) => true,
+            Plugged::Weak(_, device) => device.strong_count() > 0,
+        }
+    }
+
+    fn get(&self) -> Option<Arc<dyn Pluggable>> {
+        match self {
+            Plugged::Strong(device) => Some(Arc::clone(device)),
+            Plugged::Weak(_, device) => device.upgrade(),
+        }
+    }
+}
+
+pub struct SmartRoom {
+    pub(crate) name: String,
+    devices: Vec<Plugged>,
+    // имя -> позиция в devices, меняется только через insert и prune_dead
+    index: Index,
+    owner: usize,
+    // растёт, когда позиции устройств сдвигаются и старые DeviceId теряют смысл
+    epoch: u64,
+    pub(crate) power_budget: Option<f64>,
+    // превышение, о котором уже сообщили подписчикам
+    pub(crate) over_budget: AtomicBool,
+    pub(crate) geometry: crate::geometry::Geometry,
+    pub(crate) label: Option<String>,
+    pub(crate) device_labels: crate::label::DeviceLabels,
+    pub(crate) disabled: crate::maintenance::Disabled,
+    pub(crate) drivers: crate::driver::Drivers,
+}
+
+// Точная копия устройств дома вместе с номерами хэндлов, для отката транзакций
+pub(crate) struct Snapshot {
+    rooms: Vec<SmartRoom>,
+    index: Index,
+    epoch: u64,
+    groups: Vec<crate::DeviceGroup>,
+    doors: crate::doors::Doors,
+}
+
+/// The copy is a new room: handles from the original are not accepted by it. It shares
+/// the devices but not their [drivers](SmartRoom::plug_driver), so only the original
+/// disconnects them.
+impl Clone for SmartRoom {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            devices: self.devices.clone(),
+            index: self.index.clone(),
+            owner: next_owner(),
+            epoch: 0,
+            power_budget: self.power_budget,
+            over_budget: AtomicBool::new(self.over_budget.load(Ordering::Relaxed)),
+            geometry: self.geometry.clone(),
+            label: self.label.clone(),
+            device_labels: self.device_labels.clone(),
+            disabled: self.disabled.clone(),
+            drivers: Default::default(),
+        }
+    }
+}
+
+impl SmartRoom {
+    // в отличие от clone, старые хэндлы остаются действительными
+    pub(crate) fn snapshot(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            devices: self.devices.clone(),
+            index: self.index.clone(),
+            owner: self.owner,
+            epoch: self.epoch,
+            power_budget: self.power_budget,
+            over_budget: AtomicBool::new(self.over_budget.load(Ordering::Relaxed)),
+            geometry: self.geometry.clone(),
+            label: self.label.clone(),
+            device_labels: self.device_labels.clone(),
+            disabled: self.disabled.clone(),
+            drivers: self.drivers.clone(),
+        }
+    }
+}
+
+/// Name given to a room made with [`SmartRoom::unnamed`] or `Default`.
+pub const UNNAMED_ROOM: &str = "<unnamed room>";
+
+impl SmartRoom {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            devices: Vec::default(),
+            index: Index::default(),
+            owner: next_owner(),
+            epoch: 0,
+            power_budget: None,
+            over_budget: AtomicBool::new(false),
+            geometry: Default::default(),
+            label: None,
+            device_labels: Default::default(),
+            disabled: Default::default(),
+            drivers: Default::default(),
+        }
+    }
+
+    /// A scratch room named [`UNNAMED_ROOM`].
+    pub fn unnamed() -> Self {
+        Self::new(UNNAMED_ROOM.to_string())
+    }
+
+    pub fn with_capacity(name: impl Into<String>, devices: usize) -> Self {
+        Self {
+            name: name.into(),
+            devices: Vec::with_capacity(devices),
+            index: index_with_capacity(devices),
+            owner: next_owner(),
+            epoch: 0,
+            power_budget: None,
+            over_budget: AtomicBool::new(false),
+            geometry: Default::default(),
+            label: None,
+            device_labels: Default::default(),
+            disabled: Default::default(),
+            drivers: Default::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.devices.capacity()
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.devices.shrink_to_fit();
+        #[cfg(feature = "std")]
+        self.index.shrink_to_fit();
+    }
+
+    // Умершее устройство не занимает имя: новое встаёт на его место
+    fn insert(&mut self, device: Plugged) -> Result<DeviceId, Plugged> {
+        let index = match self.index.get(device.name()) {
+            Some(&i) if self.devices[i].is_alive() => return Err(device),
+            Some(&i) => {
+                self.devices[i] = device;
+                self.epoch += 1;
+                i
+            }
+            None => {
+                self.index
+                    .insert(device.name().to_string(), self.devices.len());
+                self.devices.push(device);
+                self.devices.len() - 1
+            }
+        };
+
+        Ok(DeviceId {
+            room: self.owner,
+            epoch: self.epoch,
+            index,
+        })
+    }
+
+    fn logged(&self, inserted: Result<DeviceId, Plugged>) -> Result<DeviceId, SmartHouseError> {
+        match inserted {
+            Ok(id) => {
+                let device = self.devices[id.index].name();
+                info!("plugged device {} into room {}", device, self.name);
+                Ok(id)
+            }
+            Err(device) => {
+                warn!(
+                    "room {}: device {} already plugged",
+                    self.name,
+                    device.name()
+                );
+                Err(SmartHouseError::DuplicateDevice(device.name().to_string()))
+            }
+        }
+    }
+
+    pub fn plug(&mut self, device: impl IntoDevice) -> Result<DeviceId, SmartHouseError> {
+        let inserted = self.insert(Plugged::Strong(device.into_device()));
+        self.logged(inserted)
+    }
+
+    /// Plugs a device the room alone owns and returns a typed handle to it, so its state
+    /// can be changed later without downcasting.
+    pub fn plug_new<T: Pluggable>(&mut self, device: T) -> Result<Arc<T>, SmartHouseError> {
+        let device = Arc::new(device);
+        self.plug(Arc::clone(&device))?;
+        Ok(device)
+    }
+
+    /// [`plug`](Self::plug) that hands the room back, so devices can be plugged in a row:
+    ///
+    /// ```
+    /// use lesson_3::prelude::*;
+    ///
+    /// let mut room = SmartRoom::new("Boiler");
+    /// room.plug_chain(SmartSocket::new("Main socket"))?
+    ///     .plug_chain(SmartThermometer::new("T1"))?;
+    /// assert_eq!(room.devices(), ["Main socket", "T1"]);
+    /// # Ok::<(), SmartHouseError>(())
+    /// ```
+    pub fn plug_chain(&mut self, device: impl IntoDevice) -> Result<&mut Self, SmartHouseError> {
+        self.plug(device)?;
+        Ok(self)
+    }
+
+    /// Plugs a device owned elsewhere. The room does not keep it alive: once the last
+    /// `Arc` is dropped the device disappears from listings and reports, and
+    /// [`prune_dead`](Self::prune_dead) removes the entry.
+    pub fn plug_weak(&mut self, device: Weak<dyn Pluggable>) -> Result<DeviceId, SmartHouseError> {
+        let name = match device.upgrade() {
+            Some(alive) => alive.name().to_string(),
+            None => return Err(SmartHouseError::DeviceDropped),
+        };
+        let inserted = self.insert(Plugged::Weak(name, device));
+        self.logged(inserted)
+    }
+
+    // Живое устройство вынимается вместе с записью, позиции после него сдвигаются
+    fn take(&mut self, name: &str) -> Option<Plugged> {
+        let i = *self.index.get(name)?;
+        if !self.devices[i].is_alive() {
+            return None;
+        }
+
+        self.index.remove(name);
+        self.geometry.positions.remove(name);
+        self.device_labels.remove(name);
+        self.disabled.remove(name);
+        let device = self.devices.remove(i);
+        for pos in self.index.values_mut().filter(|pos| **pos > i) {
+            *pos -= 1;
+        }
+        self.epoch += 1;
+        Some(device)
+    }
+
+    /// Removes a device and returns it, disconnected if it was plugged with a
+    /// [driver](Self::plug_driver). Device handles issued by the room become stale.
+    pub fn unplug(&mut self, name: &str) -> Option<Arc<dyn Pluggable>> {
+        let device = self.take(name)?.get();
+        if let Some(device) = &device {
+            self.release(name, device);
+        }
+        info!("unplugged device {} from room {}", name, self.name);
+        device
+    }
+
+    /// Removes weakly plugged devices that no longer exist and returns how many were removed.
+    pub fn prune_dead(&mut self) -> usize {
+        let before = self.devices.len();
+        self.devices.retain(Plugged::is_alive);
+        if self.devices.len() == before {
+            return 0;
+        }
+        let alive: Vec<_> = self.device_names().map(str::to_string).collect();
+        self.geometry
+            .positions
+            .retain(|device, _| alive.contains(device));
+        self.device_labels
+            .retain(|device, _| alive.contains(device));
+        self.disabled.retain(|device| alive.contains(device));
+
+        self.index = self
+            .devices
+            .iter()
+            .enumerate()
+            .map(|(i, d)| (d.name().to_string(), i))
+            .collect();
+        self.epoch += 1;
+        let pruned = before - self.devices.len();
+        info!("pruned {} dropped devices from room {}", pruned, self.name);
+        pruned
+    }
+
+    pub fn is_connected(&self, device: &dyn Pluggable) -> bool {
+        self.index
+            .get(device.name())
+            .is_some_and(|&i| self.devices[i].is_alive())
+    }
+
+    /// Copies every device name. Prefer [`device_names`](Self::device_names), which borrows them.
+    pub fn devices(&self) -> Vec<String> {
+        self.device_names().map(str::to_string).collect()
+    }
+
+    /// Names of the plugged devices in the order they were plugged.
+    pub fn device_names(&self) -> impl Iterator<Item = &str> {
+        self.devices
+            .iter()
+            .filter(|d| d.is_alive())
+            .map(Plugged::name)
+    }
+
+    pub fn device(&self, name: &str) -> Option<Arc<dyn Pluggable>> {
+        self.index.get(name).and_then(|&i| self.devices[i].get())
+    }
+
+    /// Same as [`device`](Self::device), named to match [`SmartHouse::get_device`].
+    pub fn get_device(&self, name: &str) -> Option<Arc<dyn Pluggable>> {
+        self.device(name)
+    }
+
+    pub fn device_by_id(&self, id: DeviceId) -> Result<Arc<dyn Pluggable>, HandleError> {
+        if id.room != self.owner {
+            return Err(HandleError::Foreign);
+        }
+        if id.epoch != self.epoch {
+            return Err(HandleError::Stale);
+        }
+        self.devices[id.index].get().ok_or(HandleError::Dropped)
+    }
+
+    /// A snapshot of the live devices, to use with [`DeviceSliceExt`](crate::DeviceSliceExt).
+    /// Weakly plugged devices are kept alive only as long as the returned `Vec`.
+    pub fn devices_raw(&self) -> Vec<Arc<dyn Pluggable>> {
+        self.live_devices().collect()
+    }
+
+    /// The plugged devices that still exist, in the order they were plugged.
+    pub fn live_devices(&self) -> impl Iterator<Item = Arc<dyn Pluggable>> + '_ {
+        self.devices.iter().filter_map(Plugged::get)
+    }
+
+    // Имена всех записей, в том числе умерших слабых устройств
+    pub(crate) fn plugged_names(&self) -> impl Iterator<Item = &str> {
+        self.devices.iter().map(Plugged::name)
+    }
+
+    // Имена, которые индекс ищет не там, где они лежат
+    pub(crate) fn misindexed(&self) -> Vec<String> {
+        misindexed(&self.index, self.plugged_names())
+    }
+
+    pub(crate) fn owner(&self) -> usize {
+        self.owner
+    }
+
+    // Выбрасывает более поздние записи с уже встреченным именем, индекс не трогает
+    pub(crate) fn drop_duplicates(&mut self) -> Vec<String> {
+        let mut seen = alloc::collections::BTreeSet::new();
+        let mut dropped = Vec::new();
+        self.devices.retain(|device| {
+            let name = device.name().to_string();
+            let first = seen.insert(name.clone());
+            if !first {
+                dropped.push(name);
+            }
+            first
+        });
+        dropped
+    }
+
+    // Строит индекс заново, если он разошёлся с устройствами; старые хэндлы устаревают
+    pub(crate) fn reindex(&mut self) -> bool {
+        if self.misindexed().is_empty() {
+            return false;
+        }
+        self.index = self
+            .plugged_names()
+            .enumerate()
+            .map(|(i, name)| (name.to_string(), i))
+            .collect();
+        self.epoch += 1;
+        true
+    }
+
+    pub(crate) fn renew_owner(&mut self) {
+        self.owner = next_owner();
+    }
+
+    /// Every live device of the concrete type `T`, in the order they were plugged.
+    pub fn devices_of_type<T: Pluggable>(&self) -> Vec<Arc<T>> {
+        self.live_devices()
+            .filter_map(crate::devices::downcast_arc)
+            .collect()
+    }
+
+    /// Live devices per kind; devices of no known kind are not counted, and kinds without
+    /// devices are left out.
+    #[cfg(feature = "std")]
+    pub fn count_by_kind(&self) -> HashMap<DeviceKind, usize> {
+        self.count_by_kind_including(&[])
+    }
+
+    /// Like [`count_by_kind`](Self::count_by_kind), but every kind in `known` is present,
+    /// with 0 if the room has none.
+    #[cfg(feature = "std")]
+    pub fn count_by_kind_including(&self, known: &[DeviceKind]) -> HashMap<DeviceKind, usize> {
+        let mut counter = KindCounter::new(known);
+        for device in self.live_devices() {
+            counter.visit_device(&*device);
+        }
+        counter.0
+    }
+}
+
+#[cfg(feature = "std")]
+struct KindCounter(HashMap<DeviceKind, usize>);
+
+#[cfg(feature = "std")]
+impl KindCounter {
+    fn new(known: &[DeviceKind]) -> Self {
+        Self(known.iter().map(|&kind| (kind, 0)).collect())
+    }
+}
+
+#[cfg(feature = "std")]
+impl HouseVisitor for KindCounter {
+    fn visit_device(&mut self, device: &dyn Pluggable) {
+        if let Some(kind) = device.kind() {
+            *self.0.entry(kind).or_default() += 1;
+        }
+    }
+}
+
+/// Rooms are equal when their names match and they hold equivalent live devices in the
+/// same order, see [`Pluggable::same_device`]. Whether devices are shared or plugged
+/// weakly does not matter.
+impl PartialEq for SmartRoom {
+    fn eq(&self, other: &Self) -> bool {
+        let ours: Vec<_> = self.live_devices().collect();
+        let theirs: Vec<_> = other.live_devices().collect();
+
+        self.name == other.name
+            && ours.len() == theirs.len()
+            && ours
+                .iter()
+                .zip(&theirs)
+                .all(|(a, b)| a.same_device(b.as_ref()))
+    }
+}
+
+impl Eq for SmartRoom {}
+
+// У устройств в комнате нет Debug, поэтому показываем их имена
+impl fmt::Debug for SmartRoom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmartRoom")
+            .field("name", &self.name)
+            .field("devices", &self.device_names().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Default for SmartRoom {
+    fn default() -> Self {
+        Self::unnamed()
+    }
+}
+
+impl Named for SmartRoom {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+pub struct SmartHouse {
+    pub(crate) name: String,
+    pub(crate) label: Option<String>,
+    pub(crate) rooms: Vec<SmartRoom>,
+    // имя -> позиция в rooms, меняется только через insert и remove_room
+    index: Index,
+    owner: usize,
+    epoch: u64,
+    pub(crate) generation: u64,
+    // наибольшая метка изменения, которую видел state_generation
+    state_seen: AtomicU64,
+    pub(crate) listeners: Listeners,
+    pub(crate) policies: Policies,
+    pub(crate) scenes: Vec<crate::scene::Scene>,
+    pub(crate) history: crate::undo::History,
+    pub(crate) budgets: crate::budget::Budgets,
+    pub(crate) mode: crate::mode::Mode,
+    pub(crate) groups: Vec<crate::DeviceGroup>,
+    pub(crate) doors: crate::doors::Doors,
+    pub(crate) low_battery: u8,
+    pub(crate) config: crate::HouseConfig,
+    #[cfg(feature = "std")]
+    pub(crate) audit: crate::AuditLog,
+    #[cfg(feature = "std")]
+    pub(crate) rules: crate::rules::Rules,
+    #[cfg(feature = "std")]
+    pub(crate) alerts: crate::alerts::Alerts,
+    #[cfg(feature = "std")]
+    pub(crate) telemetry: Option<Arc<crate::Telemetry>>,
+    #[cfg(feature = "std")]
+    pub(crate) rate_limits: crate::rate_limit::RateLimits,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Arc<crate::Metrics>,
+}
+
+/// The copy is a new house: handles from the original are not accepted by it, and
+/// subscriptions, policies, rules, alerts, telemetry, budget settings, undo history,
+/// metrics and the audit log are not copied; scenes, groups, doors between rooms, room
+/// budgets, the low-battery threshold, the limits, rate limits and the house mode are.
+/// The copy keeps the [`generation`](SmartHouse::generation), so a copy swapped back in
+/// after a change never goes back to an older number. `HouseCell` updates make a fuller
+/// copy that keeps all of this.
+impl Clone for SmartHouse {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            label: self.label.clone(),
+            rooms: self.rooms.clone(),
+            index: self.index.clone(),
+            owner: next_owner(),
+            epoch: 0,
+            generation: self.generation,
+            state_seen: AtomicU64::new(self.state_generation()),
+            listeners: Listeners::default(),
+            policies: Policies::default(),
+            scenes: self.scenes.clone(),
+            history: Default::default(),
+            budgets: Default::default(),
+            mode: self.mode.copy(),
+            groups: self.groups.clone(),
+            doors: self.doors.clone(),
+            low_battery: self.low_battery,
+            config: self.config,
+            #[cfg(feature = "std")]
+            audit: Default::default(),
+            #[cfg(feature = "std")]
+            rules: Default::default(),
+            #[cfg(feature = "std")]
+            alerts: Default::default(),
+            #[cfg(feature = "std")]
+            telemetry: None,
+            #[cfg(feature = "std")]
+            rate_limits: self.rate_limits.clone(),
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+        }
+    }
+}
+
+impl SmartHouse {
+    // Копия для HouseCell, которая заменит этот же дом: хэндлы остаются действительными,
+    // вся настройка и история переходят в неё, телеметрия и счётчики общие
+    #[cfg(feature = "std")]
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            label: self.label.clone(),
+            rooms: self.rooms.iter().map(SmartRoom::snapshot).collect(),
+            index: self.index.clone(),
+            owner: self.owner,
+            epoch: self.epoch,
+            generation: self.generation,
+            state_seen: AtomicU64::new(self.state_generation()),
+            listeners: self.listeners.fork(),
+            policies: self.policies.clone(),
+            scenes: self.scenes.clone(),
+            history: self.history.clone(),
+            budgets: self.budgets.clone(),
+            mode: self.mode.fork(),
+            groups: self.groups.clone(),
+            doors: self.doors.clone(),
+            low_battery: self.low_battery,
+            config: self.config,
+            audit: self.audit.clone(),
+            rules: self.rules.clone(),
+            alerts: self.alerts.clone(),
+            telemetry: self.telemetry.clone(),
+            rate_limits: self.rate_limits.fork(),
+            #[cfg(feature = "metrics")]
+            metrics: Arc::clone(&self.metrics),
+        }
+    }
+}
+
+/// Name given to a house made with [`SmartHouse::unnamed`] or `Default`.
+pub const UNNAMED_HOUSE: &str = "<unnamed house>";
+
+impl SmartHouse {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            label: None,
+            rooms: Vec::default(),
+            index: Index::default(),
+            owner: next_owner(),
+            epoch: 0,
+            generation: 0,
+            state_seen: AtomicU64::new(0),
+            listeners: Listeners::default(),
+            policies: Policies::default(),
+            scenes: Vec::new(),
+            history: Default::default(),
+            budgets: Default::default(),
+            mode: Default::default(),
+            groups: Vec::new(),
+            doors: Default::default(),
+            low_battery: crate::battery::DEFAULT_LOW_BATTERY,
+            config: Default::default(),
+            #[cfg(feature = "std")]
+            audit: Default::default(),
+            #[cfg(feature = "std")]
+            rules: Default::default(),
+            #[cfg(feature = "std")]
+            alerts: Default::default(),
+            #[cfg(feature = "std")]
+            telemetry: None,
+            #[cfg(feature = "std")]
+            rate_limits: Default::default(),
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+        }
+    }
+
+    /// A scratch house named [`UNNAMED_HOUSE`].
+    pub fn unnamed() -> Self {
+        Self::new(UNNAMED_HOUSE.to_string())
+    }
+
+    pub fn with_capacity(name: impl Into<String>, rooms: usize) -> Self {
+        Self {
+            name: name.into(),
+            label: None,
+            rooms: Vec::with_capacity(rooms),
+            index: index_with_capacity(rooms),
+            owner: next_owner(),
+            epoch: 0,
+            generation: 0,
+            state_seen: AtomicU64::new(0),
+            listeners: Listeners::default(),
+            policies: Policies::default(),
+            scenes: Vec::new(),
+            history: Default::default(),
+            budgets: Default::default(),
+            mode: Default::default(),
+            groups: Vec::new(),
+            doors: Default::default(),
+            low_battery: crate::battery::DEFAULT_LOW_BATTERY,
+            config: Default::default(),
+            #[cfg(feature = "std")]
+            audit: Default::default(),
+            #[cfg(feature = "std")]
+            rules: Default::default(),
+            #[cfg(feature = "std")]
+            alerts: Default::default(),
+            #[cfg(feature = "std")]
+            telemetry: None,
+            #[cfg(feature = "std")]
+            rate_limits: Default::default(),
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.rooms.capacity()
+    }
+
+    /// Shrinks the house and every room in it.
+    pub fn shrink_to_fit(&mut self) {
+        self.rooms.iter_mut().for_each(SmartRoom::shrink_to_fit);
+        self.rooms.shrink_to_fit();
+        #[cfg(feature = "std")]
+        self.index.shrink_to_fit();
+    }
+
+    // комната возвращается целиком, чтобы вызывающий её не потерял
+    #[allow(clippy::result_large_err)]
+    fn insert(&mut self, room: SmartRoom) -> Result<RoomId, SmartRoom> {
+        if self.index.contains_key(room.name()) {
+            return Err(room);
+        }
+        self.index.insert(room.name().to_string(), self.rooms.len());
+        self.rooms.push(room);
+        Ok(RoomId {
+            house: self.owner,
+            epoch: self.epoch,
+            index: self.rooms.len() - 1,
+        })
+    }
+
+    pub fn add(&mut self, room: SmartRoom) -> Result<RoomId, SmartHouseError> {
+        let added = self.try_add(room);
+        #[cfg(feature = "metrics")]
+        if added.is_err() {
+            self.metrics.count_add_rejection();
+        }
+        added
+    }
+
+    fn try_add(&mut self, room: SmartRoom) -> Result<RoomId, SmartHouseError> {
+        let name = room.name().to_string();
+        if self.index.contains_key(&name) {
+            warn!("house {}: room {} already added", self.name, name);
+            return Err(SmartHouseError::DuplicateRoom(name));
+        }
+        self.check_room_limits(&room)?;
+        self.policies
+            .check(&PolicyContext::AddRoom {
+                house: self,
+                room: &room,
+            })
+            .map_err(|violation| self.vetoed(violation))?;
+
+        let id = self
+            .insert(room)
+            .map_err(|room| SmartHouseError::DuplicateRoom(room.name().to_string()))?;
+        info!("added room {} to house {}", name, self.name);
+        self.record(Edit::RemoveRoom(name.clone()));
+        self.changed(HouseEvent::RoomAdded { room: name });
+        Ok(id)
+    }
+
+    /// Takes a room out of the house. Room handles issued by the house become stale.
+    /// Devices plugged with a driver are disconnected as by [`unplug`](Self::unplug), so
+    /// the room comes back without drivers.
+    pub fn remove_room(&mut self, name: &str) -> Option<SmartRoom> {
+        let i = self.index.remove(name)?;
+        let mut room = self.rooms.remove(i);
+        for (device, driver) in mem::take(&mut room.drivers) {
+            if let Some(device) = room.device(&device) {
+                self.disconnect(driver, device);
+            }
+        }
+        for pos in self.index.values_mut().filter(|pos| **pos > i) {
+            *pos -= 1;
+        }
+        self.epoch += 1;
+
+        info!("removed room {} from house {}", name, self.name);
+        self.drop_members(name, None);
+        self.drop_doors(name);
+        self.record(Edit::AddRoom(Box::new(room.clone())));
+        self.changed(HouseEvent::RoomRemoved {
+            room: name.to_string(),
+        });
+        Some(room)
+    }
+
+    /// Plugs a device into one of the rooms. Unlike plugging through a room directly,
+    /// this notifies subscribers.
+    pub fn plug(
+        &mut self,
+        room: &str,
+        device: impl IntoDevice,
+    ) -> Result<DeviceId, SmartHouseError> {
+        let device = device.into_device();
+        let name = device.name().to_string();
+        self.check_plug(room, device.as_ref(), false)
+            .inspect_err(|_| {
+                #[cfg(feature = "metrics")]
+                self.metrics.count_plug_rejection();
+            })?;
+        let id = self
+            .room_mut(room)
+            .expect("room was checked above")
+            .insert(Plugged::Strong(device))
+            .map_err(|device| SmartHouseError::DuplicateDevice(device.name().to_string()))?;
+
+        info!(
+            "plugged device {} into room {} of house {}",
+            name, room, self.name
+        );
+        self.record(Edit::Unplug {
+            room: room.to_string(),
+            device: name.clone(),
+        });
+        self.changed(HouseEvent::DevicePlugged {
+            room: room.to_string(),
+            device: name,
+        });
+        Ok(id)
+    }
+
+    // Сначала собственные проверки, потом политики: вето не должно прятать дубликат
+    fn check_plug(
+        &self,
+        room: &str,
+        device: &dyn Pluggable,
+        moving: bool,
+    ) -> Result<(), SmartHouseError> {
+        let target = self
+            .room(room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_string()))?;
+        if target.device(device.name()).is_some() {
+            warn!(
+                "house {}: device {} already plugged in room {}",
+                self.name,
+                device.name(),
+                room
+            );
+            return Err(SmartHouseError::DuplicateDevice(device.name().to_string()));
+        }
+        self.check_device_limits(target, device, moving)?;
+        self.policies
+            .check(&PolicyContext::PlugDevice {
+                house: self,
+                room: target,
+                device,
+            })
+            .map_err(|violation| self.vetoed(violation))
+    }
+
+    fn vetoed(&self, violation: PolicyViolation) -> SmartHouseError {
+        warn!("house {}: {}", self.name, violation);
+        SmartHouseError::Policy(violation)
+    }
+
+    pub fn unplug(
+        &mut self,
+        room: &str,
+        device: &str,
+    ) -> Result<Arc<dyn Pluggable>, SmartHouseError> {
+        let target = self
+            .room_mut(room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_string()))?;
+        let unplugged = target
+            .take(device)
+            .and_then(|plugged| plugged.get())
+            .ok_or_else(|| SmartHouseError::DeviceNotFound {
+                room: room.to_string(),
+                device: device.to_string(),
+            })?;
+        self.release(room, device, &unplugged);
+
+        info!(
+            "unplugged device {} from room {} of house {}",
+            device, room, self.name
+        );
+        self.drop_members(room, Some(device));
+        self.record(Edit::Plug {
+            room: room.to_string(),
+            device: Arc::clone(&unplugged),
+        });
+        self.changed(HouseEvent::DeviceUnplugged {
+            room: room.to_string(),
+            device: device.to_string(),
+        });
+        Ok(unplugged)
+    }
+
+    /// Moves a device between rooms, keeping it weak if it was plugged weakly. Nothing
+    /// changes unless both rooms exist, the device is in `from` and its name is free in `to`.
+    pub fn move_device(
+        &mut self,
+        device: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<DeviceId, SmartHouseError> {
+        let moving = self
+            .room(from)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(from.to_string()))?
+            .device(device)
+            .ok_or_else(|| SmartHouseError::DeviceNotFound {
+                room: from.to_string(),
+                device: device.to_string(),
+            })?;
+        self.check_plug(to, moving.as_ref(), true)?;
+
+        let source = self.room_mut(from).expect("source room was checked above");
+        let plugged = source
+            .take(device)
+            .ok_or_else(|| SmartHouseError::DeviceNotFound {
+                room: from.to_string(),
+                device: device.to_string(),
+            })?;
+        // драйвер переезжает вместе с устройством
+        let driver = source.drivers.remove(device);
+        let target = self.room_mut(to).expect("target room was checked above");
+        let id = match target.insert(plugged) {
+            Ok(id) => id,
+            Err(_) => unreachable!("target room was checked above"),
+        };
+        if let Some(driver) = driver {
+            target.drivers.insert(device.to_string(), driver);
+        }
+
+        info!(
+            "moved device {} from room {} to room {} of house {}",
+            device, from, to, self.name
+        );
+        self.move_members(device, from, to);
+        self.record(Edit::Move {
+            device: device.to_string(),
+            from: to.to_string(),
+            to: from.to_string(),
+        });
+        self.changed(HouseEvent::DeviceMoved {
+            device: device.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+        Ok(id)
+    }
+
+    /// [`add`](Self::add) that hands the house back, so rooms can be added in a row.
+    pub fn add_chain(&mut self, room: SmartRoom) -> Result<&mut Self, SmartHouseError> {
+        self.add(room)?;
+        Ok(self)
+    }
+
+    pub fn room(&self, name: &str) -> Option<&SmartRoom> {
+        self.index.get(name).map(|&i| &self.rooms[i])
+    }
+
+    fn check(&self, id: RoomId) -> Result<usize, HandleError> {
+        if id.house != self.owner {
+            return Err(HandleError::Foreign);
+        }
+        match id.epoch == self.epoch {
+            true => Ok(id.index),
+            false => Err(HandleError::Stale),
+        }
+    }
+
+    pub fn room_by_id(&self, id: RoomId) -> Result<&SmartRoom, HandleError> {
+        self.check(id).map(|i| &self.rooms[i])
+    }
+
+    /// Changes made through the returned room do not notify subscribers. Taking the room
+    /// counts as a change for [`generation`](SmartHouse::generation).
+    pub fn room_by_id_mut(&mut self, id: RoomId) -> Result<&mut SmartRoom, HandleError> {
+        let i = self.check(id)?;
+        self.generation += 1;
+        Ok(&mut self.rooms[i])
+    }
+
+    /// Grows by one with every successful structural change: `add`, `remove_room`, `plug`,
+    /// `unplug`, `move_device` and [`room_by_id_mut`](SmartHouse::room_by_id_mut).
+    /// Reads and device state changes leave it as it is; those are counted by
+    /// [`state_generation`](SmartHouse::state_generation).
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Grows when a smart socket or thermometer of this house changes state (switching,
+    /// load, temperature, battery), and only then. It is the latest
+    /// [stamp](Pluggable::state_stamp) of the house's devices, not a count, so it may jump
+    /// by more than one; it never goes back, even when a device leaves.
+    ///
+    /// Devices are shared between houses and do not know which ones hold them, so there
+    /// is no counter for them to bump: every call reads the stamp of every live device,
+    /// O(devices) with no allocation. Compare it with an earlier value before doing
+    /// anything that costs more than the walk, as
+    /// [`sample_telemetry`](Self::sample_telemetry) does.
+    pub fn state_generation(&self) -> u64 {
+        let latest = self
+            .rooms
+            .iter()
+            .flat_map(SmartRoom::live_devices)
+            .map(|device| device.state_stamp())
+            .max()
+            .unwrap_or(0);
+        self.state_seen
+            .fetch_max(latest, Ordering::Relaxed)
+            .max(latest)
+    }
+
+    pub(crate) fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            rooms: self.rooms.iter().map(SmartRoom::snapshot).collect(),
+            index: self.index.clone(),
+            epoch: self.epoch,
+            groups: self.groups.clone(),
+            doors: self.doors.clone(),
+        }
+    }
+
+    pub(crate) fn restore(&mut self, snapshot: Snapshot) {
+        self.rooms = snapshot.rooms;
+        self.index = snapshot.index;
+        self.epoch = snapshot.epoch;
+        self.groups = snapshot.groups;
+        self.doors = snapshot.doors;
+    }
+
+    pub(crate) fn room_mut(&mut self, name: &str) -> Option<&mut SmartRoom> {
+        self.index.get(name).map(|&i| &mut self.rooms[i])
+    }
+
+    pub(crate) fn misindexed(&self) -> Vec<String> {
+        misindexed(&self.index, self.rooms.iter().map(SmartRoom::name))
+    }
+
+    pub(crate) fn reindex(&mut self) -> bool {
+        if self.misindexed().is_empty() {
+            return false;
+        }
+        self.index = self
+            .rooms
+            .iter()
+            .enumerate()
+            .map(|(i, room)| (room.name.clone(), i))
+            .collect();
+        self.epoch += 1;
+        true
+    }
+
+    pub(crate) fn get_rooms(&self) -> &[SmartRoom] {
+        // Размер возвращаемого массива можно выбрать самостоятельно
+        &self.rooms
+    }
+
+    // fn devices(&self, room: &str) -> Vec<String> {
+    //     // Размер возвращаемого массива можно выбрать самостоятельно
+
+    //     for r in &self.rooms {
+    //         if r.name() == room {
+    //             return  r.devices();
+    //         }
+    //     }
+
+    //     Vec::new()
+    // }
+
+    /// A shared handle to a device, found through the room and device name indexes
+    /// without allocating.
+    pub fn get_device(&self, room: &str, device: &str) -> Option<Arc<dyn Pluggable>> {
+        self.room(room)?.get_device(device)
+    }
+
+    /// Every live device with its room, room by room in the order they were added.
+    pub fn all_devices(&self) -> impl Iterator<Item = (&SmartRoom, Arc<dyn Pluggable>)> {
+        self.rooms
+            .iter()
+            .flat_map(|room| room.live_devices().map(move |device| (room, device)))
+    }
+
+    /// Every live device of the concrete type `T` with the name of its room, room by room.
+    ///
+    /// ```
+    /// use lesson_3::{SmartHouse, SmartSocket};
+    ///
+    /// let house = SmartHouse::builder("Home")
+    ///     .room("Hall", |r| r.socket("Lamp").thermometer("T1"))
+    ///     .build()
+    ///     .unwrap();
+    /// for (_, socket) in house.devices_of_type::<SmartSocket>() {
+    ///     socket.turn_off();
+    /// }
+    /// ```
+    pub fn devices_of_type<T: Pluggable>(&self) -> Vec<(String, Arc<T>)> {
+        self.rooms
+            .iter()
+            .flat_map(|room| {
+                room.devices_of_type()
+                    .into_iter()
+                    .map(move |device| (room.name.clone(), device))
+            })
+            .collect()
+    }
+
+    /// The live devices for which `predicate` holds, lazily and room by room.
+    ///
+    /// ```
+    /// use lesson_3::{DeviceKind, Named, SmartHouse};
+    ///
+    /// let house = SmartHouse::builder("Home")
+    ///     .room("floor2-hall", |r| r.socket("Lamp").thermometer("T1"))
+    ///     .room("floor1-hall", |r| r.socket("Kettle"))
+    ///     .build()
+    ///     .unwrap();
+    /// let mut found = house.find_devices(|room, device| {
+    ///     room.name().starts_with("floor2") && device.kind() == Some(DeviceKind::Socket)
+    /// });
+    /// assert_eq!(found.next().unwrap().1.name(), "Lamp");
+    /// assert!(found.next().is_none());
+    /// ```
+    pub fn find_devices<F>(
+        &self,
+        mut predicate: F,
+    ) -> impl Iterator<Item = (&SmartRoom, Arc<dyn Pluggable>)>
+    where
+        F: FnMut(&SmartRoom, &dyn Pluggable) -> bool,
+    {
+        self.all_devices()
+            .filter(move |(room, device)| predicate(room, device.as_ref()))
+    }
+
+    /// The first live device for which `predicate` holds; later devices are not looked at.
+    pub fn find_first<F>(&self, predicate: F) -> Option<(&SmartRoom, Arc<dyn Pluggable>)>
+    where
+        F: FnMut(&SmartRoom, &dyn Pluggable) -> bool,
+    {
+        self.find_devices(predicate).next()
+    }
+
+    /// The first device called `name` in any room.
+    pub fn find_device(&self, name: &str) -> Option<(&SmartRoom, Arc<dyn Pluggable>)> {
+        self.all_devices().find(|(_, device)| device.name() == name)
+    }
+
+    pub fn device_count(&self) -> usize {
+        self.all_devices().count()
+    }
+
+    /// Live devices per kind in all rooms, counted in one pass, see
+    /// [`SmartRoom::count_by_kind`].
+    #[cfg(feature = "std")]
+    pub fn count_by_kind(&self) -> HashMap<DeviceKind, usize> {
+        self.count_by_kind_including(&[])
+    }
+
+    #[cfg(feature = "std")]
+    pub fn count_by_kind_including(&self, known: &[DeviceKind]) -> HashMap<DeviceKind, usize> {
+        let mut counter = KindCounter::new(known);
+        self.accept(&mut counter);
+        counter.0
+    }
+
+    pub fn create_report<T: Reportable>(&self, report: T) -> Result<String, Box<dyn Error>> {
+        #[cfg(feature = "tracing")]
+        let span = crate::tracing::Span::new(
+            "report",
+            &[("house", &self.name), ("devices", &self.device_count())],
+        );
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        #[cfg(feature = "std")]
+        let started = std::time::Instant::now();
+        let report = report.make(self);
+        #[cfg(feature = "metrics")]
+        self.metrics.count_report();
+        let outcome = if report.is_ok() { "made" } else { "failed" };
+        #[cfg(feature = "std")]
+        debug!(
+            "house {}: report {} in {:?}",
+            self.name,
+            outcome,
+            started.elapsed()
+        );
+        #[cfg(not(feature = "std"))]
+        debug!("house {}: report {}", self.name, outcome);
+        report
+    }
+}
+
+/// Houses are equal when their names match and they hold equal rooms in the same order.
+impl PartialEq for SmartHouse {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.rooms == other.rooms
+    }
+}
+
+impl Eq for SmartHouse {}
+
+impl fmt::Debug for SmartHouse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmartHouse")
+            .field("name", &self.name)
+            .field("rooms", &self.rooms)
+            .finish()
+    }
+}
+
+impl Default for SmartHouse {
+    fn default() -> Self {
+        Self::unnamed()
+    }
+}
+
+/// `{}` is the compact `Room[name]`; `{:#}` is the report heading with a trailing newline.
+impl fmt::Display for SmartRoom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match f.alternate() {
+            true => writeln!(f, "--> Room: {}", self.name()),
+            false => write!(f, "Room[{}]", self.name()),
+        }
+    }
+}
+
+/// `{}` is the compact `House[name]`; `{:#}` is the report heading with a trailing newline.
+impl fmt::Display for SmartHouse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match f.alternate() {
+            true => writeln!(f, "-> House: {}", self.name),
+            false => write!(f, "House[{}]", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use super::*;
+    use crate::{
+        testing::fixtures::{self, small_house},
+        BatteryPowered, BorrowingDeviceInfoProvider, HouseReport, SmartSocket, SmartThermometer,
+    };
+
+    // Проверка на этапе компиляции: дом можно передавать между потоками
+    const _: fn() = || {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SmartHouse>();
+        assert_send_sync::<SmartRoom>();
+        assert_send_sync::<Arc<dyn Pluggable>>();
+    };
+
+    #[test]
+    fn construct_house() {
+        let mut hell = SmartHouse::new("hell");
+        let limb = SmartRoom::new("limb");
+        let lust = SmartRoom::new("lust");
+
+        assert!(hell.add(limb).is_ok(), "Limb should not be added before");
+        assert!(hell.add(lust).is_ok(), "Lust should not be added before");
+
+        let limb = SmartRoom::new("limb");
+        assert!(hell.add(limb).is_err(), "Limb has already been added")
+    }
+
+    #[test]
+    fn plug_devices() {
+        let mut boiler = SmartRoom::new("Boiler");
+
+        let thermo = SmartThermometer::new("Thermometer 1");
+        let socket = SmartSocket::new("Main socket");
+
+        assert!(
+            boiler.plug(Arc::new(thermo)).is_ok(),
+            "Thermometer successfully connected"
+        );
+        assert!(
+            boiler.plug(Arc::new(socket)).is_ok(),
+            "Socket successfully connected"
+        );
+
+        let socket = SmartSocket::new("Main socket");
+        assert!(
+            boiler.plug(Arc::new(socket)).is_err(),
+            "Socket already connected"
+        );
+    }
+
+    #[test]
+    fn mutate_through_room() {
+        let mut room = SmartRoom::new("Boiler");
+        room.plug(SmartSocket::new("Main socket")).unwrap();
+        room.plug(SmartThermometer::new("Thermometer 1")).unwrap();
+
+        let any: Arc<dyn Any + Send + Sync> = room.device("Main socket").unwrap();
+        let socket = any.downcast::<SmartSocket>().unwrap();
+        let any: Arc<dyn Any + Send + Sync> = room.device("Thermometer 1").unwrap();
+        let thermo = any.downcast::<SmartThermometer>().unwrap();
+        assert!(room.device("Nope").is_none());
+
+        let mut house = SmartHouse::new("Home");
+        house.add(room.clone()).unwrap();
+        let report = BorrowingDeviceInfoProvider {
+            socket: &socket,
+            thermo: &thermo,
+        };
+        let before = house.create_report(report).unwrap();
+        assert!(before.contains("Socket[Main socket] off, 0.0 W"));
+        assert!(before.contains("Thermometer[Thermometer 1] no reading"));
+
+        socket.set_load(1500.0);
+        socket.turn_on();
+        thermo.set_temperature(21.5);
+
+        let report = BorrowingDeviceInfoProvider {
+            socket: &socket,
+            thermo: &thermo,
+        };
+        let after = house.create_report(report).unwrap();
+        assert!(after.contains("Socket[Main socket] on, 1500.0 W"));
+        assert!(after.contains("Thermometer[Thermometer 1] 21.5 °C"));
+
+        socket.turn_off();
+        assert_eq!(socket.power(), 0.0);
+        assert_eq!(socket.clone().is_on(), socket.is_on());
+    }
+
+    #[test]
+    fn name_index_follows_inserts() {
+        let mut room = SmartRoom::new("Boiler");
+        for i in 0..100 {
+            room.plug(SmartSocket::new(format!("socket {i}"))).unwrap();
+        }
+        assert!(room.plug(SmartSocket::new("socket 42")).is_err());
+        assert_eq!(room.devices().len(), 100);
+        assert!(room.device_names().eq(room.devices()));
+        assert_eq!(room.device_names().nth(42), Some("socket 42"));
+        assert_eq!(room.device("socket 42").unwrap().name(), "socket 42");
+
+        let mut house = SmartHouse::new("Home");
+        house.add(room).unwrap();
+        house.add(SmartRoom::new("Kitchen")).unwrap();
+        assert!(house.add(SmartRoom::new("Boiler")).is_err());
+        assert_eq!(house.room("Kitchen").unwrap().name(), "Kitchen");
+        assert_eq!(house.get_rooms().len(), 2);
+    }
+
+    #[test]
+    fn house_report_lists_everything() {
+        let mut house = small_house();
+        house.add(SmartRoom::new("hall")).unwrap();
+
+        assert_eq!(
+            house.create_report(HouseReport).unwrap(),
+            "-> House: hell\n--> Room: limb\n----> Device: s1\n----> Device: t1\n--> Room: lust\n----> Device: s2\n--> Room: hall\n"
+        );
+    }
+
+    #[test]
+    fn capacity_passthroughs() {
+        let mut room = SmartRoom::with_capacity("Boiler", 16);
+        assert!(room.capacity() >= 16);
+        room.plug(SmartSocket::new("Main socket")).unwrap();
+
+        let mut house = SmartHouse::with_capacity("Home", 4);
+        assert!(house.capacity() >= 4);
+        house.add(room).unwrap();
+
+        house.shrink_to_fit();
+        assert_eq!(house.capacity(), 1);
+        assert_eq!(house.room("Boiler").unwrap().capacity(), 1);
+        assert!(house
+            .room("Boiler")
+            .unwrap()
+            .device("Main socket")
+            .is_some());
+    }
+
+    #[test]
+    fn weak_devices_are_pruned() {
+        let owned: Arc<dyn Pluggable> = Arc::new(SmartSocket::new("Owned"));
+        let mut room = SmartRoom::new("Boiler");
+        room.plug(SmartSocket::new("Main socket")).unwrap();
+        room.plug_weak(Arc::downgrade(&owned)).unwrap();
+        assert!(room.plug_weak(Arc::downgrade(&owned)).is_err());
+        assert_eq!(room.devices(), ["Main socket", "Owned"]);
+        assert!(room.is_connected(owned.as_ref()));
+
+        let clone = room.clone();
+        drop(owned);
+        assert_eq!(room.devices(), ["Main socket"]);
+        assert!(room.device("Owned").is_none());
+
+        let mut house = SmartHouse::new("Home");
+        house.add(clone).unwrap();
+        let report = house.create_report(HouseReport).unwrap();
+        assert!(!report.contains("Owned"));
+
+        assert_eq!(room.prune_dead(), 1);
+        assert_eq!(room.prune_dead(), 0);
+        assert_eq!(room.device_names().count(), 1);
+
+        let dropped: Arc<dyn Pluggable> = Arc::new(SmartSocket::new("Gone"));
+        let weak = Arc::downgrade(&dropped);
+        drop(dropped);
+        assert!(room.plug_weak(weak).is_err());
+    }
+
+    #[test]
+    fn plug_new_keeps_a_typed_handle() {
+        let mut room = SmartRoom::new("Boiler");
+        let socket = room.plug_new(SmartSocket::new("Main socket")).unwrap();
+        assert!(room.plug_new(SmartSocket::new("Main socket")).is_err());
+        let mut house = SmartHouse::new("Home");
+        house.add(room).unwrap();
+
+        let report = crate::ReportBuilder::new().verbosity(crate::Verbosity::Detailed);
+        assert!(house
+            .create_report(report.clone())
+            .unwrap()
+            .contains("Main socket (off, 0.0 W)"));
+
+        socket.set_load(60.0);
+        socket.turn_on();
+        assert!(house
+            .create_report(report)
+            .unwrap()
+            .contains("Main socket (on, 60.0 W)"));
+    }
+
+    #[test]
+    fn chains_stop_at_first_error() {
+        let mut room = SmartRoom::new("Boiler");
+        let mut house = SmartHouse::new("Home");
+        let mut fill = || -> Result<(), SmartHouseError> {
+            room.plug_chain(SmartSocket::new("s1"))?
+                .plug_chain(SmartSocket::new("s1"))?
+                .plug_chain(SmartSocket::new("s2"))?;
+            Ok(())
+        };
+        assert_eq!(
+            fill(),
+            Err(SmartHouseError::DuplicateDevice("s1".to_string()))
+        );
+        assert_eq!(room.devices(), ["s1"]);
+
+        let err = house
+            .add_chain(room)
+            .and_then(|h| h.add_chain(SmartRoom::new("Boiler")))
+            .and_then(|h| h.add_chain(SmartRoom::new("Kitchen")))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "room Boiler already constructed");
+        assert_eq!(house.get_rooms().len(), 1);
+    }
+
+    #[test]
+    fn handles_find_their_own() {
+        let mut room = SmartRoom::new("Boiler");
+        let socket = room.plug(SmartSocket::new("Main socket")).unwrap();
+        let owned: Arc<dyn Pluggable> = Arc::new(SmartSocket::new("Owned"));
+        let weak = room.plug_weak(Arc::downgrade(&owned)).unwrap();
+        let thermo = room.plug(SmartThermometer::new("T1")).unwrap();
+        assert_eq!(room.device_by_id(socket).unwrap().name(), "Main socket");
+        assert_eq!(room.device_by_id(thermo).unwrap().name(), "T1");
+
+        let other = room.clone();
+        assert_eq!(other.device_by_id(socket).err(), Some(HandleError::Foreign));
+
+        drop(owned);
+        assert_eq!(room.device_by_id(weak).err(), Some(HandleError::Dropped));
+        room.prune_dead();
+        assert_eq!(room.device_by_id(thermo).err(), Some(HandleError::Stale));
+        assert!(room.device("T1").is_some());
+
+        let mut house = SmartHouse::new("Home");
+        let boiler = house.add(room).unwrap();
+        let kitchen = house.add(SmartRoom::new("Kitchen")).unwrap();
+        assert_eq!(house.room_by_id(boiler).unwrap().name(), "Boiler");
+        assert_eq!(house.room_by_id(kitchen).unwrap().name(), "Kitchen");
+        house
+            .room_by_id_mut(kitchen)
+            .unwrap()
+            .plug(SmartSocket::new("Kettle"))
+            .unwrap();
+        assert!(house.room("Kitchen").unwrap().device("Kettle").is_some());
+
+        let mut neighbour = SmartHouse::new("Home");
+        neighbour.add(SmartRoom::new("Boiler")).unwrap();
+        assert_eq!(
+            neighbour.room_by_id(boiler).err(),
+            Some(HandleError::Foreign)
+        );
+        assert_eq!(
+            house.clone().room_by_id(boiler).err(),
+            Some(HandleError::Foreign)
+        );
+
+        house.remove_room("Boiler").unwrap();
+        assert_eq!(house.room_by_id(kitchen).err(), Some(HandleError::Stale));
+    }
+
+    #[test]
+    fn get_device_by_names() {
+        let mut room = SmartRoom::new("Boiler");
+        let socket = room.plug_new(SmartSocket::new("Main socket")).unwrap();
+        let mut house = SmartHouse::new("Home");
+        house.add(room).unwrap();
+
+        let any: Arc<dyn Any + Send + Sync> = house.get_device("Boiler", "Main socket").unwrap();
+        any.downcast::<SmartSocket>().unwrap().turn_on();
+        assert!(socket.is_on());
+
+        assert!(house.get_device("Kitchen", "Main socket").is_none());
+        assert!(house.get_device("Boiler", "Kettle").is_none());
+        assert!(house.room("Boiler").unwrap().get_device("Kettle").is_none());
+    }
+
+    #[test]
+    fn all_devices_in_order() {
+        let mut house = small_house();
+        house.add(SmartRoom::new("hall")).unwrap();
+        house.move_device("s2", "lust", "hall").unwrap();
+
+        let pairs: Vec<_> = house
+            .all_devices()
+            .map(|(room, device)| (room.name(), device.name().to_string()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("limb", "s1".to_string()),
+                ("limb", "t1".to_string()),
+                ("hall", "s2".to_string()),
+            ]
+        );
+
+        assert_eq!(house.device_count(), 3);
+        let (room, device) = house.find_device("s2").unwrap();
+        assert_eq!((room.name(), device.name()), ("hall", "s2"));
+        assert!(house.find_device("s3").is_none());
+        assert_eq!(SmartHouse::new("Empty").all_devices().count(), 0);
+    }
+
+    #[test]
+    fn dead_name_can_be_reused() {
+        let owned: Arc<dyn Pluggable> = Arc::new(SmartSocket::new("Owned"));
+        let mut room = SmartRoom::new("Boiler");
+        room.plug_weak(Arc::downgrade(&owned)).unwrap();
+        drop(owned);
+
+        room.plug(SmartSocket::new("Owned")).unwrap();
+        assert_eq!(room.devices(), ["Owned"]);
+        assert_eq!(room.prune_dead(), 0);
+    }
+
+    #[test]
+    fn unnamed_placeholders() {
+        assert_eq!(SmartHouse::unnamed().name, "<unnamed house>");
+        assert_eq!(SmartRoom::default().name(), "<unnamed room>");
+
+        let mut house = SmartHouse::default();
+        house.add(SmartRoom::unnamed()).unwrap();
+        assert!(house.add(SmartRoom::default()).is_err());
+        assert_eq!(
+            house.create_report(HouseReport).unwrap(),
+            "-> House: <unnamed house>\n--> Room: <unnamed room>\n"
+        );
+    }
+
+    #[test]
+    fn structural_equality() {
+        let build = || {
+            let mut room = SmartRoom::new("Boiler");
+            room.plug(SmartSocket::new("Main socket")).unwrap();
+            room.plug(SmartThermometer::new("Thermometer 1")).unwrap();
+            let mut house = SmartHouse::new("Home");
+            house.add(room).unwrap();
+            house
+        };
+        assert!(build() == build());
+
+        let changed = build();
+        let device = changed
+            .room("Boiler")
+            .unwrap()
+            .device("Main socket")
+            .unwrap();
+        let any: Arc<dyn Any + Send + Sync> = device;
+        any.downcast::<SmartSocket>().unwrap().turn_on();
+        assert!(changed != build());
+
+        let mut room = SmartRoom::new("Boiler");
+        room.plug(SmartSocket::new("Main socket")).unwrap();
+        assert!(&room != build().room("Boiler").unwrap(), "Missing device");
+
+        // Hash и Eq смотрят только на имя, изменяемое состояние на них не влияет
+        #[allow(clippy::mutable_key_type)]
+        let sockets: std::collections::HashSet<_> = [
+            SmartSocket::new("s1"),
+            SmartSocket::new("s1"),
+            SmartSocket::new("s2"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(sockets.len(), 2);
+
+        let on = SmartSocket::new("s1");
+        on.turn_on();
+        assert!(sockets.contains(&on));
+        assert!(!on.same_device(&SmartSocket::new("s1")));
+    }
+
+    #[test]
+    fn display_forms() {
+        let socket = SmartSocket::new("Main socket");
+        socket.set_load(60.0);
+        socket.turn_on();
+        let thermo = SmartThermometer::new("T1");
+        let room = SmartRoom::new("Boiler");
+        let house = SmartHouse::new("Home");
+
+        assert_eq!(format!("{socket}"), "Socket[Main socket]");
+        assert_eq!(format!("{thermo}"), "Thermometer[T1]");
+        assert_eq!(format!("{room}"), "Room[Boiler]");
+        assert_eq!(format!("{house}"), "House[Home]");
+
+        assert_eq!(
+            format!("{socket:#}"),
+            "----> Device: Socket[Main socket] on, 60.0 W\n"
+        );
+        assert_eq!(
+            format!("{thermo:#}"),
+            "----> Device: Thermometer[T1] no reading\n"
+        );
+        thermo.set_temperature(21.5);
+        assert_eq!(
+            format!("{thermo:#}"),
+            "----> Device: Thermometer[T1] 21.5 °C\n"
+        );
+        assert_eq!(format!("{room:#}"), "--> Room: Boiler\n");
+        assert_eq!(format!("{house:#}"), "-> House: Home\n");
+    }
+
+    #[test]
+    fn debug_shows_structure() {
+        let mut room = SmartRoom::new("Boiler");
+        let socket = SmartSocket::new("Main socket");
+        socket.set_load(60.0);
+        room.plug(Arc::new(socket)).unwrap();
+        room.plug(SmartThermometer::new("T1")).unwrap();
+        let mut house = SmartHouse::new("Home");
+        house.add(room).unwrap();
+
+        assert_eq!(
+            format!("{:?}", house),
+            "SmartHouse { name: \"Home\", rooms: [SmartRoom { name: \"Boiler\", devices: [\"Main socket\", \"T1\"] }] }"
+        );
+        assert_eq!(
+            format!("{:#?}", house),
+            r#"SmartHouse {
+    name: "Home",
+    rooms: [
+        SmartRoom {
+            name: "Boiler",
+            devices: [
+                "Main socket",
+                "T1",
+            ],
+        },
+    ],
+}"#
+        );
+
+        let thermo = SmartThermometer::new("T1");
+        thermo.set_temperature(21.5);
+        assert_eq!(
+            format!("{:?}", thermo),
+            "SmartThermometer { name: \"T1\", temperature: Some(21.5) }"
+        );
+        assert_eq!(
+            format!("{:?}", SmartSocket::new("s1")),
+            "SmartSocket { name: \"s1\", on: false, load: 0.0 }"
+        );
+    }
+
+    #[test]
+    fn generation_counts_structural_changes() {
+        let mut house = SmartHouse::new("hell");
+        assert_eq!(house.generation(), 0);
+
+        let limb = house.add(SmartRoom::new("limb")).unwrap();
+        assert_eq!(house.generation(), 1);
+        house.add(SmartRoom::new("lust")).unwrap();
+        assert!(house.add(SmartRoom::new("lust")).is_err());
+        assert_eq!(house.generation(), 2);
+
+        house.plug("limb", SmartSocket::new("s1")).unwrap();
+        assert!(house.plug("limb", SmartSocket::new("s1")).is_err());
+        assert_eq!(house.generation(), 3);
+
+        house.move_device("s1", "limb", "lust").unwrap();
+        assert!(house.move_device("s1", "limb", "lust").is_err());
+        assert_eq!(house.generation(), 4);
+
+        house.unplug("lust", "s1").unwrap();
+        assert!(house.unplug("lust", "s1").is_err());
+        assert_eq!(house.generation(), 5);
+
+        house.room_by_id_mut(limb).unwrap();
+        assert_eq!(house.generation(), 6);
+
+        house.remove_room("lust").unwrap();
+        assert!(house.remove_room("lust").is_none());
+        assert_eq!(house.generation(), 7);
+
+        // чтение и отчёты счётчик не трогают
+        house.get_device("limb", "s1");
+        house.device_count();
+        house.create_report(HouseReport).unwrap();
+        assert_eq!(house.generation(), 7);
+    }
+
+    #[test]
+    fn state_generation_counts_device_state() {
+        let mut house = small_house();
+        let socket = fixtures::socket(&house, "limb", "s1");
+        let thermo = fixtures::thermometer(&house, "limb", "t1");
+        let generation = house.generation();
+
+        let changes: [&dyn Fn(); 5] = [
+            &|| socket.turn_on(),
+            &|| socket.turn_off(),
+            &|| socket.set_load(5.0),
+            &|| thermo.set_temperature(20.0),
+            &|| thermo.set_battery_level(80),
+        ];
+        for change in changes {
+            let before = house.state_generation();
+            change();
+            assert!(house.state_generation() > before);
+        }
+        assert_eq!(house.generation(), generation);
+
+        // устройства другого дома счётчик не трогают
+        let neighbour = small_house();
+        let other = fixtures::socket(&neighbour, "limb", "s1");
+        let before = house.state_generation();
+        other.turn_on();
+        assert_eq!(house.state_generation(), before);
+        assert!(neighbour.state_generation() > before);
+
+        // и не идёт назад, когда устройство уходит
+        house.unplug("limb", "t1").unwrap();
+        house.unplug("limb", "s1").unwrap();
+        assert_eq!(house.state_generation(), before);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn copies_keep_the_generation() {
+        let cell = crate::HouseCell::new(SmartHouse::new("hell"));
+        cell.update(|house| house.add(SmartRoom::new("limb")).map(|_| ()))
+            .unwrap();
+        let before = cell.load().generation();
+        assert_eq!(before, 1);
+        assert_eq!(SmartHouse::clone(&cell.load()).generation(), before);
+
+        cell.update(|house| house.plug("limb", SmartSocket::new("s1")).map(|_| ()))
+            .unwrap();
+        assert_eq!(cell.load().generation(), before + 1);
+    }
+
+    #[test]
+    fn devices_of_type_skips_other_types() {
+        #[derive(Clone)]
+        struct Lamp(&'static str);
+        #[derive(Clone)]
+        struct Kettle(&'static str);
+        impl Named for Lamp {
+            fn name(&self) -> &str {
+                self.0
+            }
+        }
+        impl Pluggable for Lamp {
+            crate::boxed_clone!();
+        }
+        impl Named for Kettle {
+            fn name(&self) -> &str {
+                self.0
+            }
+        }
+        impl Pluggable for Kettle {
+            crate::boxed_clone!();
+        }
+
+        let mut house = small_house();
+        house.plug("limb", Lamp("l1")).unwrap();
+        house.plug("limb", Kettle("k1")).unwrap();
+        house.plug("limb", SmartSocket::new("s3")).unwrap();
+        house.add(SmartRoom::new("hall")).unwrap();
+
+        let sockets = house.devices_of_type::<SmartSocket>();
+        let names: Vec<_> = sockets
+            .iter()
+            .map(|(room, socket)| (room.as_str(), socket.name()))
+            .collect();
+        assert_eq!(names, [("limb", "s1"), ("limb", "s3"), ("lust", "s2")]);
+        let s1 = house.get_device("limb", "s1").unwrap();
+        assert!(Arc::ptr_eq(
+            &(sockets[0].1.clone() as Arc<dyn Pluggable>),
+            &s1
+        ));
+
+        let limb = house.room("limb").unwrap();
+        assert_eq!(limb.devices_of_type::<Lamp>()[0].name(), "l1");
+        assert_eq!(limb.devices_of_type::<Kettle>().len(), 1);
+        assert_eq!(limb.devices_of_type::<SmartThermometer>().len(), 1);
+        assert!(house
+            .room("hall")
+            .unwrap()
+            .devices_of_type::<SmartSocket>()
+            .is_empty());
+        assert_eq!(house.devices_of_type::<Kettle>().len(), 1);
+    }
+
+    #[test]
+    fn find_devices_by_state() {
+        let mut floor1 = SmartRoom::new("floor1-hall");
+        let kettle = SmartSocket::new("Kettle");
+        kettle.turn_on();
+        floor1.plug(kettle).unwrap();
+        floor1.plug(SmartSocket::new("Fan")).unwrap();
+        let mut floor2 = SmartRoom::new("floor2-hall");
+        let lamp = SmartSocket::new("Lamp");
+        lamp.turn_on();
+        floor2.plug(SmartThermometer::new("T1")).unwrap();
+        floor2.plug(lamp).unwrap();
+        let mut house = SmartHouse::new("Home");
+        house.add(floor1).unwrap();
+        house.add(floor2).unwrap();
+
+        let is_on = |device: &dyn Pluggable| {
+            crate::devices::downcast::<SmartSocket>(device).is_some_and(SmartSocket::is_on)
+        };
+        let on: Vec<_> = house
+            .find_devices(|_, device| is_on(device))
+            .map(|(room, device)| (room.name(), device.name().to_string()))
+            .collect();
+        assert_eq!(
+            on,
+            [
+                ("floor1-hall", "Kettle".to_string()),
+                ("floor2-hall", "Lamp".to_string())
+            ]
+        );
+
+        let mut looked_at = 0;
+        let (room, device) = house
+            .find_first(|_, device| {
+                looked_at += 1;
+                is_on(device)
+            })
+            .unwrap();
+        assert_eq!((room.name(), device.name()), ("floor1-hall", "Kettle"));
+        assert_eq!(looked_at, 1);
+
+        assert!(house
+            .find_first(|room, _| room.name().starts_with("floor3"))
+            .is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn count_by_kind_over_mixed_devices() {
+        // Лампа не сообщает вида: kind() остаётся None из трейта
+        #[derive(Clone)]
+        struct Lamp(&'static str);
+        impl Named for Lamp {
+            fn name(&self) -> &str {
+                self.0
+            }
+        }
+        impl Pluggable for Lamp {
+            crate::boxed_clone!();
+        }
+
+        let mut house = small_house();
+        house.plug("limb", Lamp("l1")).unwrap();
+        house
+            .plug(
+                "limb",
+                crate::testing::MockDevice::builder("m1")
+                    .kind(DeviceKind::Socket)
+                    .build(),
+            )
+            .unwrap();
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(Lamp("l2")).unwrap();
+        house.add(hall).unwrap();
+
+        assert_eq!(Lamp("l1").kind(), None);
+        let counts = house.count_by_kind();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&DeviceKind::Socket], 3);
+        assert_eq!(counts[&DeviceKind::Thermometer], 1);
+
+        // в комнате лампу пропускают, остальные считаются
+        let limb = house.room("limb").unwrap();
+        let counts = limb.count_by_kind();
+        assert_eq!(limb.devices().len(), 4);
+        assert_eq!(counts[&DeviceKind::Socket], 2);
+        assert_eq!(counts.values().sum::<usize>(), 3);
+
+        let hall = house.room("hall").unwrap();
+        assert!(hall.count_by_kind().is_empty());
+        let known = [DeviceKind::Socket, DeviceKind::Thermometer];
+        let counts = hall.count_by_kind_including(&known);
+        assert_eq!(counts.len(), 2);
+        assert!(counts.values().all(|&n| n == 0));
+        assert_eq!(house.count_by_kind_including(&known), house.count_by_kind());
+    }
+}
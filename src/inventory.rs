@@ -0,0 +1,263 @@
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{error::Error, fmt::Write};
+
+use crate::{DeviceLocation, Named, Reportable, SmartHouse};
+
+/// Who made a device, as told by [`Pluggable::info`](crate::Pluggable::info).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceInfo {
+    pub manufacturer: String,
+    pub model: String,
+    #[cfg(feature = "std")]
+    pub installed_at: Option<std::time::SystemTime>,
+    /// How long the warranty lasts from [`installed_at`](Self::installed_at).
+    pub warranty_months: Option<u16>,
+}
+
+impl DeviceInfo {
+    pub fn new(manufacturer: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            manufacturer: manufacturer.into(),
+            model: model.into(),
+            #[cfg(feature = "std")]
+            installed_at: None,
+            warranty_months: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn installed_at(mut self, at: std::time::SystemTime) -> Self {
+        self.installed_at = Some(at);
+        self
+    }
+
+    pub fn warranty_months(mut self, months: u16) -> Self {
+        self.warranty_months = Some(months);
+        self
+    }
+}
+
+/// All devices of one manufacturer and model, see [`SmartHouse::inventory`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryLine {
+    /// `None` for devices that do not tell who made them. Installation and warranty
+    /// belong to single devices and are left out.
+    pub info: Option<DeviceInfo>,
+    pub locations: Vec<DeviceLocation>,
+}
+
+impl InventoryLine {
+    pub fn count(&self) -> usize {
+        self.locations.len()
+    }
+}
+
+/// How devices without [`DeviceInfo`] are shown.
+pub const UNKNOWN_MAKER: &str = "(unknown)";
+
+impl SmartHouse {
+    /// Devices grouped by manufacturer and model, in name order, with the devices that
+    /// do not tell last. Locations within a line are in house order.
+    pub fn inventory(&self) -> Vec<InventoryLine> {
+        self.inventory_where(|_| true)
+    }
+
+    /// The [`inventory`](Self::inventory) of one manufacturer, matched by a part of its
+    /// name regardless of case. Devices without [`DeviceInfo`] never match.
+    pub fn inventory_by_manufacturer(&self, manufacturer: &str) -> Vec<InventoryLine> {
+        let needle = manufacturer.to_lowercase();
+        self.inventory_where(|info| {
+            info.is_some_and(|info| info.manufacturer.to_lowercase().contains(&needle))
+        })
+    }
+
+    fn inventory_where(&self, keep: impl Fn(Option<&DeviceInfo>) -> bool) -> Vec<InventoryLine> {
+        // неизвестные собираются отдельно, чтобы оказаться в конце
+        let mut known: BTreeMap<DeviceInfo, Vec<DeviceLocation>> = BTreeMap::new();
+        let mut unknown = Vec::new();
+        for (room, device) in self.all_devices() {
+            let info = device.info();
+            if !keep(info.as_ref()) {
+                continue;
+            }
+            let path = DeviceLocation {
+                house: None,
+                room: room.name().to_string(),
+                device: device.name().to_string(),
+            };
+            match info {
+                Some(info) => {
+                    let model = DeviceInfo::new(info.manufacturer, info.model);
+                    known.entry(model).or_default().push(path);
+                }
+                None => unknown.push(path),
+            }
+        }
+
+        let mut lines: Vec<_> = known
+            .into_iter()
+            .map(|(info, locations)| InventoryLine {
+                info: Some(info),
+                locations,
+            })
+            .collect();
+        if !unknown.is_empty() {
+            lines.push(InventoryLine {
+                info: None,
+                locations: unknown,
+            });
+        }
+        lines
+    }
+}
+
+/// The [`inventory`](SmartHouse::inventory) as a table, optionally of one manufacturer.
+#[derive(Debug, Default)]
+pub struct InventoryReportProvider {
+    pub manufacturer: Option<String>,
+}
+
+impl Reportable for InventoryReportProvider {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        let lines = match &self.manufacturer {
+            Some(manufacturer) => house.inventory_by_manufacturer(manufacturer),
+            None => house.inventory(),
+        };
+        let rows: Vec<[String; 4]> = lines
+            .iter()
+            .map(|line| {
+                let (manufacturer, model) = match &line.info {
+                    Some(info) => (info.manufacturer.clone(), info.model.clone()),
+                    None => (UNKNOWN_MAKER.to_string(), String::new()),
+                };
+                let locations: Vec<_> = line.locations.iter().map(ToString::to_string).collect();
+                [
+                    manufacturer,
+                    model,
+                    line.count().to_string(),
+                    locations.join(", "),
+                ]
+            })
+            .collect();
+
+        let header = ["manufacturer", "model", "count", "locations"].map(String::from);
+        let mut widths = header.each_ref().map(|cell| cell.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut out = String::new();
+        for row in core::iter::once(&header).chain(&rows) {
+            // последний столбец не выравнивается, чтобы не было хвостовых пробелов
+            for (cell, width) in row[..3].iter().zip(widths) {
+                write!(out, "{cell:<width$}  ")?;
+            }
+            writeln!(out, "{}", row[3])?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::*;
+    use crate::{testing::fixtures::small_house, Pluggable};
+
+    struct Plug {
+        name: &'static str,
+        info: DeviceInfo,
+    }
+
+    impl Named for Plug {
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    impl Pluggable for Plug {
+        fn boxed_clone(&self) -> Arc<dyn Pluggable> {
+            Arc::new(Plug {
+                name: self.name,
+                info: self.info.clone(),
+            })
+        }
+
+        fn info(&self) -> Option<DeviceInfo> {
+            Some(self.info.clone())
+        }
+    }
+
+    fn plug(name: &'static str, manufacturer: &str, model: &str) -> Plug {
+        Plug {
+            name,
+            info: DeviceInfo::new(manufacturer, model),
+        }
+    }
+
+    // Устройства small_house не знают производителя, рядом с ними вилки трёх моделей
+    fn house() -> SmartHouse {
+        let mut house = small_house();
+        house.plug("limb", plug("p1", "Shelly", "Plug S")).unwrap();
+        house.plug("lust", plug("p2", "TP-Link", "HS100")).unwrap();
+        house.plug("lust", plug("p3", "Shelly", "Plug S")).unwrap();
+        house.plug("lust", plug("p4", "Shelly", "H&T")).unwrap();
+        house
+    }
+
+    fn path(text: &str) -> DeviceLocation {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn same_model_is_counted_across_rooms() {
+        let house = house();
+        let inventory = house.inventory();
+        let plug_s = DeviceInfo::new("Shelly", "Plug S");
+        assert_eq!(inventory.len(), 4);
+        assert_eq!(inventory[1].info, Some(plug_s));
+        assert_eq!(inventory[1].locations, [path("limb/p1"), path("lust/p3")]);
+        assert_eq!(
+            inventory[3],
+            InventoryLine {
+                info: None,
+                locations: Vec::from([path("limb/s1"), path("limb/t1"), path("lust/s2")]),
+            }
+        );
+
+        let shelly = house.inventory_by_manufacturer("shel");
+        assert_eq!(shelly.len(), 2);
+        assert_eq!(shelly.iter().map(InventoryLine::count).sum::<usize>(), 3);
+        assert!(house.inventory_by_manufacturer("unknown").is_empty());
+    }
+
+    #[test]
+    fn report_is_a_table() {
+        let house = house();
+        assert_eq!(
+            house
+                .create_report(InventoryReportProvider::default())
+                .unwrap(),
+            "manufacturer  model   count  locations\n\
+             Shelly        H&T     1      lust/p4\n\
+             Shelly        Plug S  2      limb/p1, lust/p3\n\
+             TP-Link       HS100   1      lust/p2\n\
+             (unknown)             3      limb/s1, limb/t1, lust/s2\n"
+        );
+        let tp_link = InventoryReportProvider {
+            manufacturer: Some("TP".to_string()),
+        };
+        assert_eq!(
+            house.create_report(tp_link).unwrap(),
+            "manufacturer  model  count  locations\n\
+             TP-Link       HS100  1      lust/p2\n"
+        );
+    }
+}
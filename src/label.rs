@@ -0,0 +1,100 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+
+use crate::{SmartHouse, SmartHouseError, SmartRoom};
+
+/// A human description next to a name, such as "Coffee machine, kitchen counter" for
+/// `sock-b3-07`. Labels need not be unique.
+pub trait Labeled {
+    fn label(&self) -> Option<&str>;
+
+    /// `None` removes the label.
+    fn set_label(&mut self, label: Option<String>);
+}
+
+impl Labeled for SmartHouse {
+    fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    fn set_label(&mut self, label: Option<String>) {
+        self.label = label;
+    }
+}
+
+impl Labeled for SmartRoom {
+    fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    fn set_label(&mut self, label: Option<String>) {
+        self.label = label;
+    }
+}
+
+// Подписи устройств хранятся в комнате: устройства общие и неизменяемые
+pub(crate) type DeviceLabels = BTreeMap<String, String>;
+
+impl SmartRoom {
+    pub fn device_label(&self, device: &str) -> Option<&str> {
+        self.device_labels.get(device).map(String::as_str)
+    }
+
+    /// Labels a device of the room; `None` removes the label. The label is forgotten
+    /// when the device leaves the room.
+    pub fn set_device_label(
+        &mut self,
+        device: &str,
+        label: Option<String>,
+    ) -> Result<(), SmartHouseError> {
+        if self.device(device).is_none() {
+            return Err(SmartHouseError::DeviceNotFound {
+                room: self.name.clone(),
+                device: device.to_string(),
+            });
+        }
+        match label {
+            Some(label) => self.device_labels.insert(device.to_string(), label),
+            None => self.device_labels.remove(device),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SmartSocket;
+
+    #[test]
+    fn labels_on_house_rooms_and_devices() {
+        let mut house = SmartHouse::new("home");
+        house.set_label(Some("Summer house".to_string()));
+        assert_eq!(house.label(), Some("Summer house"));
+        assert_eq!(house.clone().label(), Some("Summer house"));
+
+        let mut kitchen = SmartRoom::new("kitchen");
+        kitchen.set_label(Some("Ground floor".to_string()));
+        kitchen.plug(SmartSocket::new("sock-b3-07")).unwrap();
+        let coffee = Some("Coffee machine".to_string());
+        kitchen
+            .set_device_label("sock-b3-07", coffee.clone())
+            .unwrap();
+        assert_eq!(
+            kitchen.set_device_label("sock-b3-08", coffee),
+            Err(SmartHouseError::DeviceNotFound {
+                room: "kitchen".to_string(),
+                device: "sock-b3-08".to_string(),
+            })
+        );
+        assert_eq!(kitchen.device_label("sock-b3-07"), Some("Coffee machine"));
+
+        kitchen.unplug("sock-b3-07");
+        kitchen.plug(SmartSocket::new("sock-b3-07")).unwrap();
+        assert_eq!(kitchen.device_label("sock-b3-07"), None);
+        kitchen.set_label(None);
+        assert_eq!(kitchen.label(), None);
+    }
+}
@@ -0,0 +1,210 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use crate::{DeviceKind, SmartHouse};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    /// One group per room, in the order the rooms were added. Empty rooms are listed too.
+    #[default]
+    Room,
+    /// Sockets, then thermometers, then devices of no known kind, across all rooms.
+    Kind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sort {
+    /// By device name, then by room name.
+    #[default]
+    NameAsc,
+    /// By kind in the order of [`GroupBy::Kind`], then by name.
+    KindThenName,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ListOptions {
+    pub group_by: GroupBy,
+    pub sort: Sort,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub room: String,
+    pub device: String,
+    pub kind: Option<DeviceKind>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupKey {
+    Room(String),
+    Kind(Option<DeviceKind>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListGroup {
+    pub key: GroupKey,
+    pub entries: Vec<ListEntry>,
+}
+
+/// The result of [`SmartHouse::list`]; `Display` renders it as text with a header and a
+/// device count per group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub group_by: GroupBy,
+    pub groups: Vec<ListGroup>,
+}
+
+fn kind_rank(kind: Option<DeviceKind>) -> u8 {
+    match kind {
+        Some(DeviceKind::Socket) => 0,
+        Some(DeviceKind::Thermometer) => 1,
+        None => 2,
+    }
+}
+
+fn kind_name(kind: Option<DeviceKind>) -> &'static str {
+    match kind {
+        Some(DeviceKind::Socket) => "socket",
+        Some(DeviceKind::Thermometer) => "thermometer",
+        None => "other",
+    }
+}
+
+impl SmartHouse {
+    pub fn list(&self, options: ListOptions) -> Listing {
+        let mut entries: Vec<ListEntry> = self
+            .all_devices()
+            .map(|(room, device)| ListEntry {
+                room: room.name.clone(),
+                device: device.name().into(),
+                kind: device.kind(),
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            let by_name = (&a.device, &a.room).cmp(&(&b.device, &b.room));
+            match options.sort {
+                Sort::NameAsc => by_name,
+                Sort::KindThenName => kind_rank(a.kind).cmp(&kind_rank(b.kind)).then(by_name),
+            }
+        });
+
+        let keys: Vec<GroupKey> = match options.group_by {
+            GroupBy::Room => self
+                .rooms
+                .iter()
+                .map(|room| GroupKey::Room(room.name.clone()))
+                .collect(),
+            GroupBy::Kind => [
+                Some(DeviceKind::Socket),
+                Some(DeviceKind::Thermometer),
+                None,
+            ]
+            .into_iter()
+            .filter(|kind| entries.iter().any(|e| e.kind == *kind))
+            .map(GroupKey::Kind)
+            .collect(),
+        };
+        let groups = keys
+            .into_iter()
+            .map(|key| {
+                let entries = entries
+                    .iter()
+                    .filter(|e| match &key {
+                        GroupKey::Room(room) => e.room == *room,
+                        GroupKey::Kind(kind) => e.kind == *kind,
+                    })
+                    .cloned()
+                    .collect();
+                ListGroup { key, entries }
+            })
+            .collect();
+
+        Listing {
+            group_by: options.group_by,
+            groups,
+        }
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for group in &self.groups {
+            let count = group.entries.len();
+            match &group.key {
+                GroupKey::Room(room) => writeln!(f, "Room {room} ({count})")?,
+                GroupKey::Kind(kind) => writeln!(f, "Kind {} ({count})", kind_name(*kind))?,
+            }
+            for entry in &group.entries {
+                match self.group_by {
+                    GroupBy::Room => writeln!(f, "  {} [{}]", entry.device, kind_name(entry.kind))?,
+                    GroupBy::Kind => writeln!(f, "  {} in {}", entry.device, entry.room)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::{testing::fixtures::small_house, Named, Pluggable, SmartRoom, SmartThermometer};
+
+    #[derive(Clone)]
+    struct Lamp;
+
+    impl Named for Lamp {
+        fn name(&self) -> &str {
+            "lamp"
+        }
+    }
+
+    impl Pluggable for Lamp {
+        crate::boxed_clone!();
+    }
+
+    // small_house, где имя и вид сортируют limb по-разному, с устройством без вида и
+    // пустой комнатой
+    fn house() -> SmartHouse {
+        let mut house = small_house();
+        house
+            .plug("limb", SmartThermometer::new("a-thermo"))
+            .unwrap();
+        house.plug("lust", Lamp).unwrap();
+        house.add(SmartRoom::new("hall")).unwrap();
+        house
+    }
+
+    #[test]
+    fn grouped_by_room() {
+        let listing = house().list(ListOptions::default());
+        assert_eq!(
+            listing.to_string(),
+            "Room limb (3)\n  a-thermo [thermometer]\n  s1 [socket]\n  t1 [thermometer]\nRoom lust (2)\n  lamp [other]\n  s2 [socket]\nRoom hall (0)\n"
+        );
+
+        let listing = house().list(ListOptions {
+            group_by: GroupBy::Room,
+            sort: Sort::KindThenName,
+        });
+        let limb = &listing.groups[0];
+        assert_eq!(limb.key, GroupKey::Room("limb".to_string()));
+        assert_eq!(limb.entries[0].device, "s1");
+    }
+
+    #[test]
+    fn grouped_by_kind_across_rooms() {
+        let listing = house().list(ListOptions {
+            group_by: GroupBy::Kind,
+            sort: Sort::NameAsc,
+        });
+        assert_eq!(
+            listing.to_string(),
+            "Kind socket (2)\n  s1 in limb\n  s2 in lust\nKind thermometer (2)\n  a-thermo in limb\n  t1 in limb\nKind other (1)\n  lamp in lust\n"
+        );
+        assert_eq!(listing.groups[2].key, GroupKey::Kind(None));
+    }
+}
@@ -0,0 +1,305 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{error::Error, fmt, str::FromStr};
+
+use crate::{Pluggable, SmartHouse};
+
+/// A device address in the `house/room/device` form used by configs and CLI arguments.
+///
+/// Shorter forms are `room/device` (any house) and `device` (any house and room; `room`
+/// is then empty). A literal `/` or `\` inside a name is written as `\/` or `\\`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceLocation {
+    pub house: Option<String>,
+    pub room: String,
+    pub device: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    EmptySegment,
+    TooManySegments,
+    DanglingEscape,
+    UnknownEscape(char),
+}
+
+/// Why a location did not parse and the byte offset in the input where it went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocationParseError {
+    pub offset: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for LocationParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::EmptySegment => write!(f, "empty name at byte {}", self.offset),
+            ParseErrorKind::TooManySegments => {
+                write!(f, "more than three segments at byte {}", self.offset)
+            }
+            ParseErrorKind::DanglingEscape => write!(f, "dangling \\ at byte {}", self.offset),
+            ParseErrorKind::UnknownEscape(c) => {
+                write!(f, "unknown escape \\{} at byte {}", c, self.offset)
+            }
+        }
+    }
+}
+
+impl Error for LocationParseError {}
+
+impl FromStr for DeviceLocation {
+    type Err = LocationParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |offset, kind| LocationParseError { offset, kind };
+        let mut segments = Vec::new();
+        let mut current = String::new();
+        let mut start = 0;
+        let mut chars = s.char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, c @ ('/' | '\\'))) => current.push(c),
+                    Some((_, c)) => return Err(error(i, ParseErrorKind::UnknownEscape(c))),
+                    None => return Err(error(i, ParseErrorKind::DanglingEscape)),
+                },
+                '/' => {
+                    if current.is_empty() {
+                        return Err(error(start, ParseErrorKind::EmptySegment));
+                    }
+                    if segments.len() == 2 {
+                        return Err(error(i, ParseErrorKind::TooManySegments));
+                    }
+                    segments.push(core::mem::take(&mut current));
+                    start = i + 1;
+                }
+                c => current.push(c),
+            }
+        }
+        if current.is_empty() {
+            return Err(error(start, ParseErrorKind::EmptySegment));
+        }
+        segments.push(current);
+
+        let mut segments = segments.into_iter();
+        let (house, room, device) = match (segments.next(), segments.next(), segments.next()) {
+            (Some(device), None, None) => (None, String::new(), device),
+            (Some(room), Some(device), None) => (None, room, device),
+            (Some(house), Some(room), Some(device)) => (Some(house), room, device),
+            _ => unreachable!("one to three segments were collected"),
+        };
+
+        Ok(Self {
+            house,
+            room,
+            device,
+        })
+    }
+}
+
+fn escape(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    for c in name.chars() {
+        if matches!(c, '/' | '\\') {
+            f.write_str("\\")?;
+        }
+        write!(f, "{c}")?;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for DeviceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(house) = &self.house {
+            escape(f, house)?;
+            f.write_str("/")?;
+        }
+        if self.house.is_some() || !self.room.is_empty() {
+            escape(f, &self.room)?;
+            f.write_str("/")?;
+        }
+        escape(f, &self.device)
+    }
+}
+
+/// The segment of a [`DeviceLocation`] that did not resolve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocateError {
+    House(String),
+    Room(String),
+    Device { room: String, device: String },
+    // устройство без комнаты нашлось в нескольких комнатах
+    Ambiguous { device: String, rooms: Vec<String> },
+}
+
+impl fmt::Display for LocateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocateError::House(house) => write!(f, "house {house} not found"),
+            LocateError::Room(room) => write!(f, "room {room} not found"),
+            LocateError::Device { room, device } if room.is_empty() => {
+                write!(f, "device {device} not found")
+            }
+            LocateError::Device { room, device } => {
+                write!(f, "device {device} not found in room {room}")
+            }
+            LocateError::Ambiguous { device, rooms } => {
+                write!(
+                    f,
+                    "device {device} is in several rooms: {}",
+                    rooms.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl Error for LocateError {}
+
+impl SmartHouse {
+    pub fn locate(&self, loc: &DeviceLocation) -> Result<Arc<dyn Pluggable>, LocateError> {
+        if let Some(house) = loc.house.as_ref().filter(|house| **house != self.name) {
+            return Err(LocateError::House(house.clone()));
+        }
+
+        let missing = || LocateError::Device {
+            room: loc.room.clone(),
+            device: loc.device.clone(),
+        };
+
+        if !loc.room.is_empty() {
+            return self
+                .room(&loc.room)
+                .ok_or_else(|| LocateError::Room(loc.room.clone()))?
+                .device(&loc.device)
+                .ok_or_else(missing);
+        }
+
+        let mut found = self
+            .get_rooms()
+            .iter()
+            .filter_map(|room| room.device(&loc.device).map(|d| (room, d)));
+        match (found.next(), found.next()) {
+            (None, _) => Err(missing()),
+            (Some((_, device)), None) => Ok(device),
+            (Some((first, _)), Some((second, _))) => Err(LocateError::Ambiguous {
+                device: loc.device.clone(),
+                rooms: [first, second]
+                    .into_iter()
+                    .chain(found.map(|(room, _)| room))
+                    .map(|room| room.name.clone())
+                    .collect(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::fixtures::small_house, SmartSocket};
+
+    fn loc(house: Option<&str>, room: &str, device: &str) -> DeviceLocation {
+        DeviceLocation {
+            house: house.map(str::to_string),
+            room: room.to_string(),
+            device: device.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_forms() {
+        assert_eq!("s1".parse(), Ok(loc(None, "", "s1")));
+        assert_eq!("limb/s1".parse(), Ok(loc(None, "limb", "s1")));
+        assert_eq!("hell/limb/s1".parse(), Ok(loc(Some("hell"), "limb", "s1")));
+        assert_eq!(r"a\/b/c\\d/e".parse(), Ok(loc(Some("a/b"), r"c\d", "e")));
+    }
+
+    #[test]
+    fn parse_errors_carry_offsets() {
+        let err = |s: &str| s.parse::<DeviceLocation>().unwrap_err();
+        let at = |offset, kind| LocationParseError { offset, kind };
+
+        assert_eq!(err(""), at(0, ParseErrorKind::EmptySegment));
+        assert_eq!(err("limb//s1"), at(5, ParseErrorKind::EmptySegment));
+        assert_eq!(err("limb/"), at(5, ParseErrorKind::EmptySegment));
+        assert_eq!(err("a/b/c/d"), at(5, ParseErrorKind::TooManySegments));
+        assert_eq!(err(r"limb\"), at(4, ParseErrorKind::DanglingEscape));
+        assert_eq!(err(r"дом\n"), at(6, ParseErrorKind::UnknownEscape('n')));
+        assert_eq!(err("").to_string(), "empty name at byte 0");
+    }
+
+    #[test]
+    fn round_trip_nasty_names() {
+        let nasty = [
+            loc(Some("a/b"), "c", "d"),
+            loc(Some(r"\"), r"\\/", "/"),
+            loc(None, "комната / 1", "розетка\\2"),
+            loc(None, "", r"just\slash/"),
+            loc(Some(" "), "  ", "\t"),
+        ];
+
+        for location in nasty {
+            let text = location.to_string();
+            assert_eq!(text.parse(), Ok(location), "{text}");
+        }
+    }
+
+    // Ручной перебор того же, что проверяет fuzz/device_location: все короткие строки
+    // из «опасных» символов
+    #[test]
+    fn parse_is_total_and_round_trips() {
+        let alphabet = ['/', '\\', 'a', 'é', ' '];
+        let mut texts = vec![String::new()];
+        let mut longest = texts.clone();
+        for _ in 0..6 {
+            longest = longest
+                .iter()
+                .flat_map(|t| alphabet.iter().map(move |c| format!("{t}{c}")))
+                .collect();
+            texts.extend(longest.iter().cloned());
+        }
+
+        for text in texts {
+            if let Ok(location) = text.parse::<DeviceLocation>() {
+                let printed = location.to_string();
+                assert_eq!(printed.parse(), Ok(location), "{text:?} -> {printed:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn locate_devices() {
+        let mut house = small_house();
+        house.plug("limb", SmartSocket::new("shared")).unwrap();
+        house.plug("lust", SmartSocket::new("shared")).unwrap();
+
+        let find = |s: &str| house.locate(&s.parse().unwrap());
+        assert_eq!(find("hell/limb/s1").unwrap().name(), "s1");
+        assert_eq!(find("lust/s2").unwrap().name(), "s2");
+        assert_eq!(find("s2").unwrap().name(), "s2");
+
+        assert_eq!(
+            find("heaven/limb/s1").err(),
+            Some(LocateError::House("heaven".to_string()))
+        );
+        assert_eq!(
+            find("hall/s1").err(),
+            Some(LocateError::Room("hall".to_string()))
+        );
+        assert_eq!(
+            find("lust/s1").err(),
+            Some(LocateError::Device {
+                room: "lust".to_string(),
+                device: "s1".to_string()
+            })
+        );
+        assert_eq!(
+            find("shared").err(),
+            Some(LocateError::Ambiguous {
+                device: "shared".to_string(),
+                rooms: vec!["limb".to_string(), "lust".to_string()]
+            })
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! A small logging facade shaped like the `log` crate: install a [`Log`] with
+//! [`set_logger`], choose a level with [`set_max_level`], and the crate reports house
+//! changes, rejected operations, report timings and network failures to it.
+//!
+//! Nothing is formatted unless a logger is installed and the level is enabled.
+
+use alloc::boxed::Box;
+use core::{
+    error::Error,
+    fmt, ptr,
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LevelFilter {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata<'a> {
+    level: Level,
+    target: &'a str,
+}
+
+impl<'a> Metadata<'a> {
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// The module that logged the record.
+    pub fn target(&self) -> &'a str {
+        self.target
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Record<'a> {
+    metadata: Metadata<'a>,
+    args: fmt::Arguments<'a>,
+}
+
+impl<'a> Record<'a> {
+    pub fn metadata(&self) -> &Metadata<'a> {
+        &self.metadata
+    }
+
+    pub fn level(&self) -> Level {
+        self.metadata.level
+    }
+
+    pub fn target(&self) -> &'a str {
+        self.metadata.target
+    }
+
+    pub fn args(&self) -> &fmt::Arguments<'a> {
+        &self.args
+    }
+}
+
+pub trait Log: Send + Sync {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool;
+
+    fn log(&self, record: &Record<'_>);
+
+    fn flush(&self);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetLoggerError;
+
+impl fmt::Display for SetLoggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a logger is already installed")
+    }
+}
+
+impl Error for SetLoggerError {}
+
+// Толстый указатель в атомик не положить, поэтому храним указатель на него
+static LOGGER: AtomicPtr<&'static dyn Log> = AtomicPtr::new(ptr::null_mut());
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Off as usize);
+
+/// Installs the logger. Only the first call succeeds.
+pub fn set_logger(logger: &'static dyn Log) -> Result<(), SetLoggerError> {
+    let boxed = Box::into_raw(Box::new(logger));
+    match LOGGER.compare_exchange(ptr::null_mut(), boxed, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            // SAFETY: `boxed` came from Box::into_raw above and was never shared
+            drop(unsafe { Box::from_raw(boxed) });
+            Err(SetLoggerError)
+        }
+    }
+}
+
+/// Records above this level are dropped before they are formatted. Starts at `Off`.
+pub fn set_max_level(level: LevelFilter) {
+    MAX_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+pub fn max_level() -> LevelFilter {
+    match MAX_LEVEL.load(Ordering::Relaxed) {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+#[doc(hidden)]
+pub fn __log(level: Level, target: &'static str, args: fmt::Arguments<'_>) {
+    #[cfg(feature = "tracing")]
+    if crate::tracing::event(level, target, args) {
+        return;
+    }
+    if level as usize > MAX_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    // SAFETY: the pointer is either null or was leaked by set_logger and is never freed
+    let Some(logger) = (unsafe { LOGGER.load(Ordering::Acquire).as_ref() }) else {
+        return;
+    };
+
+    let metadata = Metadata { level, target };
+    if logger.enabled(&metadata) {
+        logger.log(&Record { metadata, args });
+    }
+}
+
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        $crate::log::__log($level, module_path!(), format_args!($($arg)+))
+    };
+}
+
+// Ошибки пишет только сеть, которой без `std` нет
+#[cfg(feature = "std")]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log::log!($crate::log::Level::Error, $($arg)+) };
+}
+
+macro_rules! warn_ {
+    ($($arg:tt)+) => { $crate::log::log!($crate::log::Level::Warn, $($arg)+) };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log::log!($crate::log::Level::Info, $($arg)+) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log::log!($crate::log::Level::Debug, $($arg)+) };
+}
+
+#[cfg(feature = "std")]
+pub(crate) use error;
+pub(crate) use {debug, info, log, warn_ as warn};
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        string::{String, ToString},
+        sync::{Mutex, Once},
+        vec::Vec,
+    };
+
+    use super::*;
+    use crate::{SmartHouse, SmartRoom, SmartSocket};
+
+    struct Capture(Mutex<Vec<(Level, String)>>);
+
+    impl Log for Capture {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            let line = record.args().to_string();
+            self.0.lock().unwrap().push((record.level(), line));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+    // Тесты идут параллельно, поэтому ищем записи по уникальному имени
+    fn captured(level: Level, needle: &str) -> usize {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            set_logger(&CAPTURE).unwrap();
+            set_max_level(LevelFilter::Trace);
+        });
+        CAPTURE
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(l, line)| *l == level && line.contains(needle))
+            .count()
+    }
+
+    #[test]
+    fn duplicate_plug_warns_once() {
+        captured(Level::Warn, "");
+        let mut room = SmartRoom::new("log room");
+        room.plug(SmartSocket::new("log socket 1")).unwrap();
+        assert!(room.plug(SmartSocket::new("log socket 1")).is_err());
+        assert_eq!(captured(Level::Warn, "log socket 1"), 1);
+        assert_eq!(captured(Level::Info, "log socket 1"), 1);
+
+        let mut house = SmartHouse::new("log house");
+        house.add(room).unwrap();
+        assert!(house
+            .plug("log room", SmartSocket::new("log socket 1"))
+            .is_err());
+        assert_eq!(captured(Level::Warn, "log socket 1"), 2);
+        assert_eq!(captured(Level::Info, "room log room to house log house"), 1);
+
+        let report = house.create_report(crate::HouseReport);
+        assert!(report.is_ok());
+        assert_eq!(captured(Level::Debug, "house log house: report"), 1);
+        assert!(set_logger(&CAPTURE).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+/// Builds a house with [`SmartHouseBuilder`](crate::SmartHouseBuilder) and returns its
+/// `Result<SmartHouse, BuildError>`, so duplicates surface as ordinary build errors.
+///
+/// ```
+/// use lesson_3::prelude::*;
+///
+/// let house: SmartHouse = smart_house! {
+///     "hell" => {
+///         "limb" => [socket "s1", thermometer "t1"],
+///         "lust" => [socket "s2"],
+///     }
+/// }
+/// .unwrap();
+/// ```
+///
+/// Only `socket` and `thermometer` are known device kinds:
+///
+/// ```compile_fail
+/// use lesson_3::prelude::*;
+///
+/// let house = smart_house! { "hell" => { "limb" => [lamp "l1"] } };
+/// ```
+///
+/// A device needs both a kind and a name:
+///
+/// ```compile_fail
+/// use lesson_3::prelude::*;
+///
+/// let house = smart_house! { "hell" => { "limb" => ["s1"] } };
+/// ```
+///
+/// Rooms are listed in braces after the house name:
+///
+/// ```compile_fail
+/// use lesson_3::prelude::*;
+///
+/// let house = smart_house! { "hell" => [ "limb" => [socket "s1"] ] };
+/// ```
+/// Writes [`Pluggable::boxed_clone`](crate::Pluggable::boxed_clone) inside an
+/// `impl Pluggable` block for a device that implements `Clone`.
+#[macro_export]
+macro_rules! boxed_clone {
+    () => {
+        fn boxed_clone(&self) -> $crate::__private::Arc<dyn $crate::Pluggable> {
+            $crate::__private::Arc::new(::core::clone::Clone::clone(self))
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! smart_house {
+    ($house:expr => { $($room:expr => [ $($kind:ident $device:expr),* $(,)? ]),* $(,)? }) => {
+        $crate::SmartHouse::builder($house)
+            $(.with_room({
+                let room = $crate::SmartRoomBuilder::new($room);
+                $(let room = $crate::smart_house!(@device room, $kind $device);)*
+                room
+            }))*
+            .build()
+    };
+    (@device $room:ident, socket $device:expr) => {
+        $room.with_socket($device)
+    };
+    (@device $room:ident, thermometer $device:expr) => {
+        $room.with_thermometer($device)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Violation;
+
+    #[test]
+    fn macro_builds_house() {
+        let name = "s2".to_string();
+        let house = smart_house! {
+            "hell" => {
+                "limb" => [socket "s1", thermometer "t1"],
+                "lust" => [socket name],
+                "hall" => [],
+            }
+        }
+        .unwrap();
+
+        assert_eq!(house.room("limb").unwrap().devices(), ["s1", "t1"]);
+        assert_eq!(house.room("lust").unwrap().devices(), ["s2"]);
+        assert_eq!(house.get_rooms().len(), 3);
+    }
+
+    #[test]
+    fn macro_reports_duplicates() {
+        let err = smart_house! {
+            "hell" => {
+                "limb" => [socket "s1", thermometer "s1"],
+                "limb" => [],
+            }
+        }
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            err.0,
+            [
+                Violation::DuplicateDevice {
+                    room: "limb".to_string(),
+                    device: "s1".to_string()
+                },
+                Violation::DuplicateRoom("limb".to_string()),
+            ]
+        );
+    }
+}
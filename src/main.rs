@@ -1,28 +1,23 @@
 // todo: реализация трейта `DeviceInfoProvider` для поставщиков информации
 
-use std::sync::Arc;
-
-use lesson_3::{
-    BorrowingDeviceInfoProvider, OwningDeviceInfoProvider, SmartHouse, SmartRoom, SmartSocket,
-    SmartThermometer,
-};
+use lesson_3::{prelude::*, BorrowingDeviceInfoProvider, OwningDeviceInfoProvider};
 
 fn main() {
     // /**
     // Инициализация устройств
-    let socket1 = SmartSocket::new("foo".to_string());
-    let socket2 = SmartSocket::new("bar".to_string());
-    let thermo = SmartThermometer::new("baz".to_string());
+    let socket1 = SmartSocket::new("foo");
+    let socket2 = SmartSocket::new("bar");
+    let thermo = SmartThermometer::new("baz");
 
     // Инициализация дома
-    let mut house = SmartHouse::new("quix".to_string());
-    let mut room = SmartRoom::new("foobar".to_string());
+    let mut house = SmartHouse::new("quix");
+    let mut room = SmartRoom::new("foobar");
 
-    if let Err(e) = room.plug(Arc::new(socket1.clone())) {
+    if let Err(e) = room.plug(socket1.clone()) {
         panic!("{e}");
     }
 
-    if let Err(e) = room.plug(Arc::new(thermo.clone())) {
+    if let Err(e) = room.plug(thermo.clone()) {
         panic!("{e}");
     }
 
@@ -0,0 +1,142 @@
+use alloc::{
+    collections::BTreeSet,
+    string::{String, ToString},
+    sync::Arc,
+};
+
+use crate::{Pluggable, SmartHouse, SmartHouseError, SmartRoom};
+
+// Отключённые устройства по имени; все остальные включены
+pub(crate) type Disabled = BTreeSet<String>;
+
+impl SmartRoom {
+    /// Takes a device out of service without unplugging it: it keeps its place, label and
+    /// groups, but commands to it are refused and reports, rules, house modes, master
+    /// switches and telemetry leave it out. Returns `false` if it was disabled already.
+    /// The flag is forgotten when the device leaves the room.
+    pub fn disable_device(&mut self, device: &str) -> Result<bool, SmartHouseError> {
+        if self.device(device).is_none() {
+            return Err(SmartHouseError::DeviceNotFound {
+                room: self.name.clone(),
+                device: device.to_string(),
+            });
+        }
+        Ok(self.disabled.insert(device.to_string()))
+    }
+
+    /// Returns `false` if the device was not disabled.
+    pub fn enable_device(&mut self, device: &str) -> bool {
+        self.disabled.remove(device)
+    }
+
+    /// Devices are enabled unless [disabled](Self::disable_device); so is a name that is
+    /// not in the room.
+    pub fn is_enabled(&self, device: &str) -> bool {
+        !self.disabled.contains(device)
+    }
+
+    /// The live devices that are not disabled, in the order they were plugged.
+    pub fn enabled_devices(&self) -> impl Iterator<Item = Arc<dyn Pluggable>> + '_ {
+        self.live_devices()
+            .filter(|device| self.is_enabled(device.name()))
+    }
+}
+
+impl SmartHouse {
+    /// [`SmartRoom::disable_device`] for a room of the house.
+    pub fn disable_device(&mut self, room: &str, device: &str) -> Result<bool, SmartHouseError> {
+        self.room_mut(room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_string()))?
+            .disable_device(device)
+    }
+
+    pub fn enable_device(&mut self, room: &str, device: &str) -> Result<bool, SmartHouseError> {
+        let room = self
+            .room_mut(room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_string()))?;
+        if room.device(device).is_none() {
+            return Err(SmartHouseError::DeviceNotFound {
+                room: room.name.clone(),
+                device: device.to_string(),
+            });
+        }
+        Ok(room.enable_device(device))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::fixtures::{small_house, socket},
+        CommandError, DeviceCommand, HouseMode, ReportBuilder, SmartSocket, SwitchOutcome,
+        Verbosity,
+    };
+
+    // Обе розетки small_house включены, s2 выведена из работы
+    fn house() -> (SmartHouse, [Arc<SmartSocket>; 2]) {
+        let mut house = small_house();
+        let sockets = [socket(&house, "limb", "s1"), socket(&house, "lust", "s2")];
+        for socket in &sockets {
+            socket.turn_on();
+        }
+        assert_eq!(house.disable_device("lust", "s2"), Ok(true));
+        (house, sockets)
+    }
+
+    #[test]
+    fn disabled_device_stays_plugged() {
+        let (mut house, _) = house();
+        assert_eq!(house.disable_device("lust", "s2"), Ok(false));
+        assert_eq!(
+            house.disable_device("lust", "s3"),
+            Err(SmartHouseError::DeviceNotFound {
+                room: "lust".to_string(),
+                device: "s3".to_string(),
+            })
+        );
+        let lust = house.room("lust").unwrap();
+        assert_eq!(lust.devices(), ["s2"]);
+        assert!(!lust.is_enabled("s2") && lust.is_enabled("s1"));
+        assert_eq!(lust.enabled_devices().count(), 0);
+        assert_eq!(house.room("limb").unwrap().enabled_devices().count(), 2);
+
+        assert_eq!(house.enable_device("lust", "s2"), Ok(true));
+        assert_eq!(house.enable_device("lust", "s2"), Ok(false));
+        // вынутое устройство возвращается включённым
+        house.disable_device("lust", "s2").unwrap();
+        let s2 = house.unplug("lust", "s2").unwrap();
+        house.plug("lust", s2).unwrap();
+        assert!(house.room("lust").unwrap().is_enabled("s2"));
+    }
+
+    #[test]
+    fn aggregates_leave_disabled_devices_out() {
+        let (mut house, [s1, s2]) = house();
+        assert_eq!(
+            house.execute(&"lust/s2".parse().unwrap(), DeviceCommand::TurnOff),
+            Err(CommandError::Disabled {
+                device: "s2".to_string()
+            })
+        );
+
+        let report = house.switch_all(false);
+        assert_eq!(report.outcomes[2].1, SwitchOutcome::Disabled);
+        assert!(report.is_complete());
+        assert!(!s1.is_on() && s2.is_on());
+        house.set_mode(HouseMode::Away);
+        assert!(s2.is_on());
+
+        let detailed = ReportBuilder::new().verbosity(Verbosity::Detailed);
+        assert_eq!(
+            house.create_report(detailed.clone()).unwrap(),
+            "-> House: hell\n--> Mode: away\n--> Room: limb\n----> Device: s1 (off, 0.0 W)\n\
+             ----> Device: t1 (no reading)\n--> Room: lust\n"
+        );
+        assert_eq!(
+            house.create_report(detailed.include_disabled()).unwrap(),
+            "-> House: hell\n--> Mode: away\n--> Room: limb\n----> Device: s1 (off, 0.0 W)\n\
+             ----> Device: t1 (no reading)\n--> Room: lust\n----> Device: s2 (on, 0.0 W) [disabled]\n"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+use core::fmt;
+
+use crate::{
+    command::{run, switch},
+    CommandError, DeviceLocation, Pluggable, SmartHouse, SmartRoom,
+};
+
+/// What a master switch did to one device.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwitchOutcome {
+    Switched,
+    /// The device was already on, or off, as asked.
+    Unchanged,
+    Failed(CommandError),
+    NotSwitchable,
+    /// A device with the excluded [tag](Pluggable::has_tag), left alone.
+    Excluded,
+    /// [Disabled](SmartRoom::disable_device) for maintenance, left alone.
+    Disabled,
+}
+
+impl fmt::Display for SwitchOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SwitchOutcome::Switched => f.write_str("switched"),
+            SwitchOutcome::Unchanged => f.write_str("unchanged"),
+            SwitchOutcome::Failed(err) => write!(f, "failed: {err}"),
+            SwitchOutcome::NotSwitchable => f.write_str("not switchable"),
+            SwitchOutcome::Excluded => f.write_str("excluded"),
+            SwitchOutcome::Disabled => f.write_str("disabled"),
+        }
+    }
+}
+
+/// What [`SmartHouse::switch_all`] or [`SmartRoom::switch_all`] did, device by device
+/// in house order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SwitchReport {
+    pub outcomes: Vec<(DeviceLocation, SwitchOutcome)>,
+}
+
+impl SwitchReport {
+    /// Whether every switchable device that was not excluded or disabled is now as asked.
+    pub fn is_complete(&self) -> bool {
+        self.failed().next().is_none()
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = (&DeviceLocation, &CommandError)> {
+        self.outcomes
+            .iter()
+            .filter_map(|(path, outcome)| match outcome {
+                SwitchOutcome::Failed(err) => Some((path, err)),
+                _ => None,
+            })
+    }
+
+    pub fn count(&self, outcome: &SwitchOutcome) -> usize {
+        let same = |other: &SwitchOutcome| match (outcome, other) {
+            (SwitchOutcome::Failed(_), SwitchOutcome::Failed(_)) => true,
+            _ => outcome == other,
+        };
+        self.outcomes
+            .iter()
+            .filter(|(_, other)| same(other))
+            .count()
+    }
+}
+
+impl fmt::Display for SwitchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (path, outcome) in &self.outcomes {
+            writeln!(f, "{path}: {outcome}")?;
+        }
+        Ok(())
+    }
+}
+
+// Исход для одного устройства; сам переключатель передаётся снаружи
+fn outcome(
+    room: &SmartRoom,
+    device: &dyn Pluggable,
+    on: bool,
+    excluded: Option<&str>,
+    apply: impl FnOnce() -> Result<bool, CommandError>,
+) -> SwitchOutcome {
+    if device.as_switchable().is_none() {
+        return SwitchOutcome::NotSwitchable;
+    }
+    if excluded.is_some_and(|tag| device.has_tag(tag)) {
+        return SwitchOutcome::Excluded;
+    }
+    if !room.is_enabled(device.name()) {
+        return SwitchOutcome::Disabled;
+    }
+    match apply() {
+        Ok(was_on) if was_on == on => SwitchOutcome::Unchanged,
+        Ok(_) => SwitchOutcome::Switched,
+        Err(err) => SwitchOutcome::Failed(err),
+    }
+}
+
+impl SmartRoom {
+    /// Turns every device of the room on or off. A device that fails does not stop the
+    /// others; see the report for what happened to each.
+    pub fn switch_all(&self, on: bool) -> SwitchReport {
+        self.switch_every(on, None)
+    }
+
+    /// Like [`switch_all`](Self::switch_all), but leaves devices tagged `tag` alone, such
+    /// as [`CRITICAL`](crate::CRITICAL) sockets.
+    pub fn switch_all_except_tagged(&self, on: bool, tag: &str) -> SwitchReport {
+        self.switch_every(on, Some(tag))
+    }
+
+    fn switch_every(&self, on: bool, excluded: Option<&str>) -> SwitchReport {
+        let outcomes = self
+            .live_devices()
+            .map(|device| {
+                let apply = || run(&*device, switch(on)).map(|was| was == switch(true));
+                let outcome = outcome(self, &*device, on, excluded, apply);
+                (self.location(&device), outcome)
+            })
+            .collect();
+        SwitchReport { outcomes }
+    }
+
+    fn location(&self, device: &Arc<dyn Pluggable>) -> DeviceLocation {
+        DeviceLocation {
+            house: None,
+            room: self.name.clone(),
+            device: device.name().to_string(),
+        }
+    }
+}
+
+impl SmartHouse {
+    /// Turns every device of the house on or off, through [`execute`](Self::execute) so
+    /// budgets and telemetry apply. A device that fails does not stop the others; see the
+    /// report for what happened to each.
+    pub fn switch_all(&self, on: bool) -> SwitchReport {
+        self.switch_every(on, None)
+    }
+
+    /// Like [`switch_all`](Self::switch_all), but leaves devices tagged `tag` alone; the
+    /// panic button passes [`CRITICAL`](crate::CRITICAL).
+    pub fn switch_all_except_tagged(&self, on: bool, tag: &str) -> SwitchReport {
+        self.switch_every(on, Some(tag))
+    }
+
+    fn switch_every(&self, on: bool, excluded: Option<&str>) -> SwitchReport {
+        let mut outcomes = Vec::new();
+        for room in &self.rooms {
+            for device in room.live_devices() {
+                let path = room.location(&device);
+                let apply = || {
+                    self.execute(&path, switch(on))
+                        .map(|done| done.undo == switch(true))
+                };
+                let outcome = outcome(room, &*device, on, excluded, apply);
+                outcomes.push((path, outcome));
+            }
+        }
+        SwitchReport { outcomes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::{
+        testing::fixtures::kitchen, CapabilityError, Named, SmartSocket, Switchable, CRITICAL,
+    };
+
+    // Выключатель, который не выключается
+    #[derive(Default)]
+    struct Stuck(AtomicBool);
+
+    impl Named for Stuck {
+        fn name(&self) -> &str {
+            "stuck"
+        }
+    }
+
+    impl Switchable for Stuck {
+        fn turn_on(&self) -> Result<(), CapabilityError> {
+            self.0.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn turn_off(&self) -> Result<(), CapabilityError> {
+            Err("relay welded".into())
+        }
+
+        fn is_on(&self) -> Result<bool, CapabilityError> {
+            Ok(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    impl Pluggable for Stuck {
+        fn boxed_clone(&self) -> Arc<dyn Pluggable> {
+            Arc::new(Stuck(AtomicBool::new(self.0.load(Ordering::SeqCst))))
+        }
+
+        fn as_switchable(&self) -> Option<&dyn Switchable> {
+            Some(self)
+        }
+    }
+
+    // Кухня из фикстуры с включёнными холодильником и чайником и прихожая с залипшим
+    // выключателем
+    fn house() -> (SmartHouse, [Arc<SmartSocket>; 3]) {
+        let (mut house, sockets) = kitchen();
+        sockets[0].turn_on();
+        sockets[1].turn_on();
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(Stuck::default()).unwrap();
+        house.add(hall).unwrap();
+        house
+            .execute(&"hall/stuck".parse().unwrap(), switch(true))
+            .unwrap();
+        (house, sockets)
+    }
+
+    fn outcomes(report: &SwitchReport) -> Vec<(String, String)> {
+        report
+            .outcomes
+            .iter()
+            .map(|(path, outcome)| (path.to_string(), outcome.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn one_failure_does_not_stop_the_rest() {
+        let (house, [fridge, kettle, lamp]) = house();
+
+        let report = house.switch_all(false);
+        assert!(!fridge.is_on() && !kettle.is_on() && !lamp.is_on());
+        assert_eq!(
+            outcomes(&report),
+            [
+                ("kitchen/fridge", "switched"),
+                ("kitchen/kettle", "switched"),
+                ("kitchen/lamp", "unchanged"),
+                ("kitchen/t1", "not switchable"),
+                ("hall/stuck", "failed: device stuck failed: relay welded"),
+            ]
+            .map(|(path, outcome)| (path.to_string(), outcome.to_string()))
+        );
+        assert!(!report.is_complete());
+        assert_eq!(report.count(&SwitchOutcome::Switched), 2);
+        let failed: Vec<_> = report.failed().map(|(path, _)| path.to_string()).collect();
+        assert_eq!(failed, ["hall/stuck"]);
+    }
+
+    #[test]
+    fn tagged_devices_can_be_excluded() {
+        let (mut house, [fridge, kettle, _]) = house();
+        let kitchen = house.room("kitchen").unwrap();
+
+        let report = kitchen.switch_all_except_tagged(false, CRITICAL);
+        assert!(fridge.is_on() && !kettle.is_on());
+        assert!(report.is_complete());
+        assert_eq!(report.count(&SwitchOutcome::Excluded), 1);
+        assert_eq!(report.outcomes[0].1, SwitchOutcome::Excluded);
+
+        let report = house.switch_all_except_tagged(true, CRITICAL);
+        assert!(kettle.is_on());
+        assert_eq!(report.count(&SwitchOutcome::Switched), 2);
+        assert_eq!(report.count(&SwitchOutcome::Unchanged), 1);
+
+        // любая метка, не только critical
+        let night = Arc::new(SmartSocket::new("night-light").tagged("night"));
+        night.turn_on();
+        house.plug("hall", night.clone()).unwrap();
+        let report = house.switch_all_except_tagged(false, "night");
+        assert!(night.is_on() && !fridge.is_on() && !kettle.is_on());
+        assert_eq!(report.count(&SwitchOutcome::Excluded), 1);
+        assert_eq!(
+            report.outcomes.last().map(|(path, _)| path.to_string()),
+            Some("hall/night-light".to_string())
+        );
+    }
+}
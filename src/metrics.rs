@@ -0,0 +1,222 @@
+use alloc::string::String;
+use core::{fmt::Write, sync::atomic::Ordering};
+
+use crate::{atomic::AtomicU64, devices::downcast, DeviceKind, SmartHouse, SmartSocket};
+
+/// Counters a house keeps about itself, exported by [`SmartHouse::render_prometheus`].
+///
+/// A cloned house starts with zero counters.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    reports: AtomicU64,
+    add_rejections: AtomicU64,
+    plug_rejections: AtomicU64,
+    protocol_errors: AtomicU64,
+}
+
+impl Metrics {
+    pub fn reports(&self) -> u64 {
+        self.reports.load(Ordering::Relaxed)
+    }
+
+    pub fn add_rejections(&self) -> u64 {
+        self.add_rejections.load(Ordering::Relaxed)
+    }
+
+    pub fn plug_rejections(&self) -> u64 {
+        self.plug_rejections.load(Ordering::Relaxed)
+    }
+
+    /// Malformed frames and broken connections seen by a
+    /// [`HouseServer`](crate::net::HouseServer) serving this house.
+    pub fn protocol_errors(&self) -> u64 {
+        self.protocol_errors.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn count_report(&self) {
+        self.reports.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_add_rejection(&self) {
+        self.add_rejections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_plug_rejection(&self) {
+        self.plug_rejections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn count_protocol_errors(&self, errors: u64) {
+        self.protocol_errors.fetch_add(errors, Ordering::Relaxed);
+    }
+}
+
+impl SmartHouse {
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// The gauges, taken from the house right now, and the counters in the Prometheus
+    /// text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        // запись в String не падает
+        let _ = self.write_prometheus(&mut out);
+        out
+    }
+
+    fn write_prometheus(&self, out: &mut String) -> core::fmt::Result {
+        let house = escape(&self.name);
+        let mut sockets = 0;
+        let mut thermometers = 0;
+        let mut other = 0;
+        let mut power = 0.0;
+        for (_, device) in self.all_devices() {
+            match device.kind() {
+                Some(DeviceKind::Socket) => sockets += 1,
+                Some(DeviceKind::Thermometer) => thermometers += 1,
+                None => other += 1,
+            }
+            if let Some(socket) = downcast::<SmartSocket>(device.as_ref()) {
+                power += socket.power();
+            }
+        }
+
+        let gauge = |out: &mut String, name: &str, help: &str, value: &dyn core::fmt::Display| {
+            writeln!(out, "# HELP {name} {help}")?;
+            writeln!(out, "# TYPE {name} gauge")?;
+            writeln!(out, "{name}{{house=\"{house}\"}} {value}")
+        };
+        gauge(
+            out,
+            "smarthouse_rooms",
+            "Rooms in the house.",
+            &self.rooms.len(),
+        )?;
+        gauge(
+            out,
+            "smarthouse_devices",
+            "Live devices in all rooms.",
+            &(sockets + thermometers + other),
+        )?;
+
+        writeln!(
+            out,
+            "# HELP smarthouse_devices_by_kind Live devices by kind."
+        )?;
+        writeln!(out, "# TYPE smarthouse_devices_by_kind gauge")?;
+        for (kind, count) in [
+            ("socket", sockets),
+            ("thermometer", thermometers),
+            ("other", other),
+        ] {
+            writeln!(
+                out,
+                "smarthouse_devices_by_kind{{house=\"{house}\",kind=\"{kind}\"}} {count}"
+            )?;
+        }
+
+        gauge(
+            out,
+            "smarthouse_socket_power_watts",
+            "Power drawn through all smart sockets.",
+            &power,
+        )?;
+
+        let counter = |out: &mut String, name: &str, help: &str, label: &str, value: u64| {
+            writeln!(out, "# HELP {name} {help}")?;
+            writeln!(out, "# TYPE {name} counter")?;
+            writeln!(out, "{name}{{house=\"{house}\"{label}}} {value}")
+        };
+        counter(
+            out,
+            "smarthouse_reports_total",
+            "Reports made.",
+            "",
+            self.metrics.reports(),
+        )?;
+        writeln!(
+            out,
+            "# HELP smarthouse_rejections_total Rejected add and plug operations."
+        )?;
+        writeln!(out, "# TYPE smarthouse_rejections_total counter")?;
+        for (operation, count) in [
+            ("add", self.metrics.add_rejections()),
+            ("plug", self.metrics.plug_rejections()),
+        ] {
+            writeln!(
+                out,
+                "smarthouse_rejections_total{{house=\"{house}\",operation=\"{operation}\"}} {count}"
+            )?;
+        }
+        counter(
+            out,
+            "smarthouse_protocol_errors_total",
+            "Protocol errors on connections serving the house.",
+            "",
+            self.metrics.protocol_errors(),
+        )
+    }
+}
+
+// Экранирование значения метки по формату Prometheus
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HouseReport, SmartRoom, SmartThermometer};
+
+    #[test]
+    fn samples_follow_the_house() {
+        let mut house = SmartHouse::new("my \"home\"");
+        house.add(SmartRoom::new("limb")).unwrap();
+        assert!(house.add(SmartRoom::new("limb")).is_err());
+
+        let socket = SmartSocket::new("s1");
+        socket.set_load(60.5);
+        socket.turn_on();
+        house.plug("limb", socket).unwrap();
+        house.plug("limb", SmartThermometer::new("t1")).unwrap();
+        assert!(house.plug("limb", SmartSocket::new("s1")).is_err());
+        assert!(house.plug("hall", SmartSocket::new("s2")).is_err());
+        house.create_report(HouseReport).unwrap();
+
+        let rendered = house.render_prometheus();
+        let label = r#"house="my \"home\"""#;
+        for sample in [
+            format!("smarthouse_rooms{{{label}}} 1\n"),
+            format!("smarthouse_devices{{{label}}} 2\n"),
+            format!("smarthouse_devices_by_kind{{{label},kind=\"socket\"}} 1\n"),
+            format!("smarthouse_devices_by_kind{{{label},kind=\"thermometer\"}} 1\n"),
+            format!("smarthouse_socket_power_watts{{{label}}} 60.5\n"),
+            format!("smarthouse_reports_total{{{label}}} 1\n"),
+            format!("smarthouse_rejections_total{{{label},operation=\"add\"}} 1\n"),
+            format!("smarthouse_rejections_total{{{label},operation=\"plug\"}} 2\n"),
+            format!("smarthouse_protocol_errors_total{{{label}}} 0\n"),
+            "# TYPE smarthouse_reports_total counter\n".to_string(),
+        ] {
+            assert!(
+                rendered.contains(&sample),
+                "{sample} missing in\n{rendered}"
+            );
+        }
+
+        house.unplug("limb", "s1").unwrap();
+        let rendered = house.render_prometheus();
+        assert!(rendered.contains(&format!("smarthouse_devices{{{label}}} 1\n")));
+        assert!(rendered.contains(&format!("smarthouse_socket_power_watts{{{label}}} 0\n")));
+        assert_eq!(house.clone().metrics().reports(), 0);
+    }
+}
@@ -0,0 +1,205 @@
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::fmt;
+
+use crate::{CommandError, DeviceCommand, DeviceLocation, SmartHouse, CRITICAL};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HouseMode {
+    #[default]
+    Home,
+    /// Every switchable device but the [critical](crate::SmartSocket::critical) ones is off.
+    /// Temperatures are left alone: no device takes a target temperature yet.
+    Away,
+}
+
+impl fmt::Display for HouseMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HouseMode::Home => f.write_str("home"),
+            HouseMode::Away => f.write_str("away"),
+        }
+    }
+}
+
+/// What [`SmartHouse::set_mode`] did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeChange {
+    pub from: HouseMode,
+    pub to: HouseMode,
+    /// Devices switched by the change.
+    pub switched: Vec<DeviceLocation>,
+    /// Devices that did not respond and were left as they were.
+    pub skipped: Vec<(DeviceLocation, CommandError)>,
+}
+
+type Callback = dyn Fn(&ModeChange) + Send + Sync;
+type Listener = Box<Callback>;
+
+#[derive(Default)]
+pub(crate) struct Mode {
+    pub(crate) current: HouseMode,
+    // что вернуть при возвращении домой
+    pub(crate) saved: Vec<(DeviceLocation, DeviceCommand)>,
+    listeners: Vec<Arc<Callback>>,
+}
+
+impl Mode {
+    // режим и сохранённое состояние копируются вместе с домом, подписчики нет
+    pub(crate) fn copy(&self) -> Self {
+        Self {
+            current: self.current,
+            saved: self.saved.clone(),
+            listeners: Vec::new(),
+        }
+    }
+
+    // для HouseCell: подписчики остаются
+    #[cfg(feature = "std")]
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            listeners: self.listeners.clone(),
+            ..self.copy()
+        }
+    }
+}
+
+impl SmartHouse {
+    pub fn mode(&self) -> HouseMode {
+        self.mode.current
+    }
+
+    /// Going `Away` turns off every switchable device not tagged [`CRITICAL`], through
+    /// [`execute`](Self::execute), and remembers which ones were on. Coming back `Home`
+    /// turns those on again. Setting the current mode again does nothing.
+    ///
+    /// Away does not lower thermostats to 16 °C yet. Thermometers only measure, and
+    /// there is no command that sets a target temperature.
+    ///
+    /// A device that fails to respond is skipped and reported in the change; the mode
+    /// changes anyway.
+    pub fn set_mode(&mut self, mode: HouseMode) -> ModeChange {
+        let mut change = ModeChange {
+            from: self.mode.current,
+            to: mode,
+            switched: Vec::new(),
+            skipped: Vec::new(),
+        };
+        if change.from == mode {
+            return change;
+        }
+
+        match mode {
+            HouseMode::Away => {
+                let mut saved = Vec::new();
+                for path in self.switchable() {
+                    match self.execute(&path, DeviceCommand::TurnOff) {
+                        Ok(outcome) if outcome.undo == DeviceCommand::TurnOn => {
+                            saved.push((path.clone(), outcome.undo));
+                            change.switched.push(path);
+                        }
+                        Ok(_) => {}
+                        Err(err) => change.skipped.push((path, err)),
+                    }
+                }
+                self.mode.saved = saved;
+            }
+            HouseMode::Home => {
+                for (path, command) in core::mem::take(&mut self.mode.saved) {
+                    match self.execute(&path, command) {
+                        Ok(_) => change.switched.push(path),
+                        Err(err) => change.skipped.push((path, err)),
+                    }
+                }
+            }
+        }
+
+        self.mode.current = mode;
+        for listener in &self.mode.listeners {
+            listener(&change);
+        }
+        change
+    }
+
+    /// Calls `listener` after every change of mode.
+    pub fn on_mode(&mut self, listener: Listener) {
+        self.mode.listeners.push(Arc::from(listener));
+    }
+
+    // Все устройства, которые можно выключить, кроме помеченных критичными
+    fn switchable(&self) -> Vec<DeviceLocation> {
+        let mut paths = Vec::new();
+        for room in &self.rooms {
+            for device in room.enabled_devices() {
+                if device.as_switchable().is_some() && !device.has_tag(CRITICAL) {
+                    paths.push(DeviceLocation {
+                        house: None,
+                        room: room.name.clone(),
+                        device: device.name().into(),
+                    });
+                }
+            }
+        }
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{testing::fixtures::kitchen, ReportBuilder, Verbosity};
+
+    fn path(text: &str) -> DeviceLocation {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn critical_socket_survives_away_mode() {
+        let (mut house, [fridge, kettle, lamp]) = kitchen();
+        fridge.turn_on();
+        kettle.turn_on();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        house.on_mode(Box::new(move |change| sink.lock().unwrap().push(change.to)));
+
+        let away = house.set_mode(HouseMode::Away);
+        assert_eq!(away.switched, [path("kitchen/kettle")]);
+        assert!(away.skipped.is_empty());
+        assert!(fridge.is_on());
+        assert!(!kettle.is_on() && !lamp.is_on());
+        assert_eq!(house.mode(), HouseMode::Away);
+        let summary = ReportBuilder::new().verbosity(Verbosity::Summary);
+        assert_eq!(
+            house.create_report(summary).unwrap(),
+            "-> House: home\n--> Mode: away\n--> Room: kitchen (4 devices)\n"
+        );
+
+        let home = house.set_mode(HouseMode::Home);
+        assert_eq!(home.switched, [path("kitchen/kettle")]);
+        assert!(fridge.is_on() && kettle.is_on());
+        // выключенная до отъезда лампа так и остаётся выключенной
+        assert!(!lamp.is_on());
+        assert_eq!(*seen.lock().unwrap(), [HouseMode::Away, HouseMode::Home]);
+    }
+
+    #[test]
+    fn unreachable_devices_are_skipped_and_reported() {
+        let (mut house, sockets) = kitchen();
+        sockets.iter().for_each(|socket| socket.turn_on());
+        let kettle = &sockets[1];
+        house.set_mode(HouseMode::Away);
+        house.unplug("kitchen", "kettle").unwrap();
+
+        let home = house.set_mode(HouseMode::Home);
+        assert!(matches!(
+            &home.skipped[..],
+            [(path, CommandError::Missing(_))] if *path == self::path("kitchen/kettle")
+        ));
+        assert_eq!(home.switched, [path("kitchen/lamp")]);
+        assert!(!kettle.is_on());
+        assert_eq!(house.mode(), HouseMode::Home);
+        assert!(house.set_mode(HouseMode::Home).switched.is_empty());
+    }
+}
@@ -0,0 +1,1332 @@
+//! TCP servers and clients speaking the framed [`protocol`](crate::protocol).
+//!
+//! [`SocketServer`] exposes a single smart socket, [`HouseServer`] exposes the layout of a
+//! whole [`SmartHouse`]. Both accept an optional shared-secret token.
+
+use core::fmt;
+use std::{
+    collections::HashMap,
+    error::Error,
+    io,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    atomic::AtomicU64,
+    devices::downcast,
+    log::info,
+    protocol::{
+        read_frame, write_frame, HouseLayout, ProtocolError, Request, Response, RoomLayout,
+    },
+    CapabilityError, CommandError, DeviceCommand, DeviceDriver, DeviceInfo, DeviceKind,
+    DeviceLocation, ErrorSink, HouseReport, LocateError, LogSink, Named, Pluggable, SmartHouse,
+    UNKNOWN_MAKER,
+};
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    Protocol(ProtocolError),
+    Remote(String),
+    NotFound(String),
+    Unauthorized,
+    UnexpectedResponse(Response),
+    RetriesExhausted { attempts: u32, last: Box<NetError> },
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::Protocol(e) => write!(f, "protocol error: {e}"),
+            Self::Remote(message) => write!(f, "remote error: {message}"),
+            Self::NotFound(what) => write!(f, "{what} not found"),
+            Self::Unauthorized => write!(f, "unauthorized"),
+            Self::UnexpectedResponse(response) => write!(f, "unexpected response {response:?}"),
+            Self::RetriesExhausted { attempts, last } => {
+                write!(f, "gave up after {attempts} attempts: {last}")
+            }
+        }
+    }
+}
+
+impl NetError {
+    /// Whether trying again may help: a dropped or refused connection may, a missing
+    /// device, a refused token or a garbled answer will not.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Io(e) => crate::retry::is_transient_io(e),
+            Self::Protocol(e) => e.is_transient(),
+            Self::RetriesExhausted { last, .. } => last.is_transient(),
+            Self::Remote(_)
+            | Self::NotFound(_)
+            | Self::Unauthorized
+            | Self::UnexpectedResponse(_) => false,
+        }
+    }
+}
+
+impl Error for NetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Protocol(e) => Some(e),
+            Self::RetriesExhausted { last, .. } => Some(last.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ProtocolError> for NetError {
+    fn from(e: ProtocolError) -> Self {
+        match e {
+            ProtocolError::Io(e) => Self::Io(e),
+            e => Self::Protocol(e),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Gate {
+    token: Option<String>,
+    rejected: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl Gate {
+    fn authorize(&self, request: &Request) -> bool {
+        match (&self.token, request) {
+            (None, _) => true,
+            (Some(expected), Request::Auth { token }) => tokens_match(expected, token),
+            (Some(_), _) => false,
+        }
+    }
+
+    fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::SeqCst)
+    }
+
+    fn errors(&self) -> u64 {
+        self.errors.load(Ordering::SeqCst)
+    }
+}
+
+fn tokens_match(expected: &str, actual: &str) -> bool {
+    let (expected, actual) = (expected.as_bytes(), actual.as_bytes());
+
+    // Сравнение без раннего выхода, чтобы не выдавать длину совпавшего префикса
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// Ошибкой протокола считается и кадр, из которого не разобрался запрос, и оборванное соединение
+fn serve_connection(
+    stream: TcpStream,
+    gate: &Gate,
+    handle: impl FnMut(Request) -> Response,
+) -> Result<(), ProtocolError> {
+    let served = serve_frames(stream, gate, handle);
+    if served.is_err() {
+        gate.errors.fetch_add(1, Ordering::SeqCst);
+    }
+    served
+}
+
+fn serve_frames(
+    mut stream: TcpStream,
+    gate: &Gate,
+    mut handle: impl FnMut(Request) -> Response,
+) -> Result<(), ProtocolError> {
+    let mut authorized = gate.token.is_none();
+
+    loop {
+        let request = match read_frame(&mut stream) {
+            Ok(frame) => Request::from_frame(frame),
+            Err(ProtocolError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        #[cfg(feature = "tracing")]
+        let span = {
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "unknown".to_string(), |addr| addr.to_string());
+            let command = request.as_ref().map_or("invalid", Request::command);
+            crate::tracing::Span::new("request", &[("peer", &peer), ("command", &command)])
+        };
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
+        let response = match request {
+            Ok(request) if !authorized => {
+                if !gate.authorize(&request) {
+                    gate.rejected.fetch_add(1, Ordering::SeqCst);
+                    write_frame(&mut stream, &Response::Unauthorized.to_frame())?;
+                    let _ = stream.shutdown(Shutdown::Both);
+                    return Ok(());
+                }
+
+                authorized = true;
+                Response::Authorized
+            }
+            Ok(Request::Auth { .. }) => Response::Authorized,
+            Ok(request) => handle(request),
+            Err(e) => {
+                gate.errors.fetch_add(1, Ordering::SeqCst);
+                Response::Error(e.to_string())
+            }
+        };
+
+        write_frame(&mut stream, &response.to_frame())?;
+    }
+}
+
+struct ServedSocket {
+    name: String,
+    on: AtomicBool,
+    gate: Gate,
+    connections: Mutex<HashMap<u64, TcpStream>>,
+    sink: Arc<dyn ErrorSink>,
+}
+
+impl ServedSocket {
+    fn handle(&self, request: Request) -> Response {
+        match request {
+            Request::GetState { device } if device == self.name => Response::State {
+                on: self.on.load(Ordering::SeqCst),
+            },
+            Request::SetState { device, on } if device == self.name => {
+                self.on.store(on, Ordering::SeqCst);
+                info!(
+                    "socket {} turned {}",
+                    self.name,
+                    if on { "on" } else { "off" }
+                );
+                Response::State { on }
+            }
+            Request::GetReading { device } if device == self.name => {
+                Response::Error(format!("Socket {} has no readings", self.name))
+            }
+            Request::Report => Response::Report(format!(
+                "Socket[{}]: {}",
+                self.name,
+                if self.on.load(Ordering::SeqCst) {
+                    "on"
+                } else {
+                    "off"
+                }
+            )),
+            Request::GetState { device }
+            | Request::SetState { device, .. }
+            | Request::GetReading { device } => Response::NotFound(format!("device {device}")),
+            other => Response::Error(format!("Socket server cannot answer {other:?}")),
+        }
+    }
+
+    fn lock_connections(&self) -> MutexGuard<'_, HashMap<u64, TcpStream>> {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub struct SocketServer {
+    listener: TcpListener,
+    device: ServedSocket,
+}
+
+impl SocketServer {
+    pub fn bind(name: impl Into<String>, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            device: ServedSocket {
+                name: name.into(),
+                on: AtomicBool::new(false),
+                gate: Gate::default(),
+                connections: Mutex::default(),
+                sink: Arc::new(LogSink),
+            },
+        })
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.device.gate.token = Some(token.into());
+        self
+    }
+
+    /// Receives accept and connection errors as the `socket-server` component.
+    pub fn with_error_sink(mut self, sink: Arc<dyn ErrorSink>) -> Self {
+        self.device.sink = sink;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn spawn(self) -> io::Result<ServerHandle> {
+        let addr = self.local_addr()?;
+        let device = Arc::new(self.device);
+        let listener = self.listener;
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stopped = Arc::clone(&stopped);
+            let device = Arc::clone(&device);
+            thread::spawn(move || {
+                for (id, stream) in (0u64..).zip(listener.incoming()) {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            device.sink.report("socket-server", &e);
+                            continue;
+                        }
+                    };
+                    if let Ok(tracked) = stream.try_clone() {
+                        device.lock_connections().insert(id, tracked);
+                    }
+
+                    let device = Arc::clone(&device);
+                    thread::spawn(move || {
+                        if let Err(e) = serve_connection(stream, &device.gate, |r| device.handle(r))
+                        {
+                            device.sink.report("socket-server", &e);
+                        }
+                        device.lock_connections().remove(&id);
+                    });
+                }
+            })
+        };
+
+        Ok(ServerHandle {
+            device,
+            addr,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl fmt::Debug for SocketServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SocketServer")
+            .field("name", &self.device.name)
+            .field("addr", &self.listener.local_addr().ok())
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct ServerHandle {
+    device: Arc<ServedSocket>,
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    pub fn name(&self) -> &str {
+        &self.device.name
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn rejected_attempts(&self) -> u64 {
+        self.device.gate.rejected()
+    }
+
+    pub fn protocol_errors(&self) -> u64 {
+        self.device.gate.errors()
+    }
+
+    pub fn connections(&self) -> usize {
+        self.device.lock_connections().len()
+    }
+
+    pub fn drop_connections(&self) {
+        for (_, stream) in self.device.lock_connections().drain() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stopped.store(true, Ordering::SeqCst);
+            // Разбудить accept, чтобы поток увидел флаг остановки
+            let _ = TcpStream::connect(self.addr);
+            let _ = thread.join();
+            self.drop_connections();
+        }
+    }
+}
+
+impl fmt::Debug for ServerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerHandle")
+            .field("name", &self.device.name)
+            .field("addr", &self.addr)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl From<&SmartHouse> for HouseLayout {
+    fn from(house: &SmartHouse) -> Self {
+        Self {
+            name: house.name.clone(),
+            rooms: house
+                .get_rooms()
+                .iter()
+                .map(|room| RoomLayout {
+                    name: room.name().to_string(),
+                    devices: room.device_names().map(str::to_string).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Serves the layout of a house. Connections are handled one at a time on the calling thread.
+pub struct HouseServer {
+    listener: TcpListener,
+    gate: Gate,
+    sink: Arc<dyn ErrorSink>,
+}
+
+impl fmt::Debug for HouseServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HouseServer")
+            .field("addr", &self.listener.local_addr().ok())
+            .finish_non_exhaustive()
+    }
+}
+
+impl HouseServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            gate: Gate::default(),
+            sink: Arc::new(LogSink),
+        })
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.gate.token = Some(token.into());
+        self
+    }
+
+    /// Receives connection errors as the `house-server` component.
+    pub fn with_error_sink(mut self, sink: Arc<dyn ErrorSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn rejected_attempts(&self) -> u64 {
+        self.gate.rejected()
+    }
+
+    pub fn protocol_errors(&self) -> u64 {
+        self.gate.errors()
+    }
+
+    pub fn serve(&self, house: &SmartHouse) -> io::Result<()> {
+        loop {
+            self.serve_once(house)?;
+        }
+    }
+
+    pub fn serve_once(&self, house: &SmartHouse) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        #[cfg(feature = "metrics")]
+        let errors = self.gate.errors();
+        if let Err(e) = serve_connection(stream, &self.gate, |r| Self::handle(house, r)) {
+            self.sink.report("house-server", &e);
+        }
+        #[cfg(feature = "metrics")]
+        house
+            .metrics
+            .count_protocol_errors(self.gate.errors() - errors);
+
+        Ok(())
+    }
+
+    fn handle(house: &SmartHouse, request: Request) -> Response {
+        match request {
+            Request::Layout => Response::Layout(house.into()),
+            Request::Report => match house.create_report(HouseReport) {
+                Ok(report) => Response::Report(report),
+                Err(e) => Response::Error(e.to_string()),
+            },
+            // устройство без описания состояния хотя бы на месте
+            Request::GetDeviceState { room, device } => match Self::find(house, &room, &device) {
+                Ok(device) => Response::Status(device.status().unwrap_or("connected".to_string())),
+                Err(missing) => missing,
+            },
+            Request::SetDeviceState { room, device, on } => {
+                let path = DeviceLocation {
+                    house: None,
+                    room,
+                    device,
+                };
+                let command = match on {
+                    true => DeviceCommand::TurnOn,
+                    false => DeviceCommand::TurnOff,
+                };
+                match house.execute(&path, command) {
+                    Ok(_) => Response::State { on },
+                    Err(CommandError::Missing(LocateError::Room(room))) => {
+                        Response::NotFound(format!("room {room}"))
+                    }
+                    Err(CommandError::Missing(_)) => {
+                        Response::NotFound(format!("device {} in room {}", path.device, path.room))
+                    }
+                    Err(CommandError::WrongKind { device, .. }) => {
+                        Response::Error(format!("Device {device} cannot be switched"))
+                    }
+                    Err(err) => Response::Error(err.to_string()),
+                }
+            }
+            other => Response::Error(format!("House server cannot answer {other:?}")),
+        }
+    }
+
+    fn find(house: &SmartHouse, room: &str, device: &str) -> Result<Arc<dyn Pluggable>, Response> {
+        house
+            .room(room)
+            .ok_or_else(|| Response::NotFound(format!("room {room}")))?
+            .device(device)
+            .ok_or_else(|| Response::NotFound(format!("device {device} in room {room}")))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub max_retries: u32,
+    pub initial_delay: Duration,
+    pub multiplier: f64,
+    pub max_delay: Duration,
+    pub retry_non_idempotent: bool,
+}
+
+impl ReconnectPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt as i32);
+        Duration::try_from_secs_f64(self.initial_delay.as_secs_f64() * factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(2),
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+struct Connection {
+    addr: SocketAddr,
+    token: Option<String>,
+    policy: ReconnectPolicy,
+    timeout: Option<Duration>,
+    stream: Mutex<Option<TcpStream>>,
+}
+
+impl Connection {
+    fn open(addr: impl ToSocketAddrs) -> Result<Self, NetError> {
+        let stream = TcpStream::connect(addr)?;
+
+        Ok(Self {
+            addr: stream.peer_addr()?,
+            token: None,
+            policy: ReconnectPolicy::default(),
+            timeout: None,
+            stream: Mutex::new(Some(stream)),
+        })
+    }
+
+    // Действует и на открытое соединение, и на следующие
+    fn set_timeout(&mut self, timeout: Duration) -> Result<(), NetError> {
+        self.timeout = Some(timeout);
+        let stream = self
+            .stream
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(stream) = stream {
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+        }
+        Ok(())
+    }
+
+    fn authenticate(&mut self, token: String) -> Result<(), NetError> {
+        let request = Request::Auth {
+            token: token.clone(),
+        };
+        let response = self.request(&request, false)?;
+        self.token = Some(token);
+
+        match response {
+            Response::Authorized => Ok(()),
+            other => Err(NetError::UnexpectedResponse(other)),
+        }
+    }
+
+    fn request(&self, request: &Request, idempotent: bool) -> Result<Response, NetError> {
+        let mut stream = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+        let retries = match idempotent || self.policy.retry_non_idempotent {
+            true => self.policy.max_retries,
+            false => 0,
+        };
+
+        let mut attempt = 0;
+        loop {
+            match self.exchange(&mut stream, request) {
+                Err(NetError::Io(e)) => {
+                    // Соединение считается потерянным: следующий вызов откроет новое
+                    *stream = None;
+
+                    if attempt == retries {
+                        return Err(match retries {
+                            0 => NetError::Io(e),
+                            _ => NetError::RetriesExhausted {
+                                attempts: attempt + 1,
+                                last: Box::new(NetError::Io(e)),
+                            },
+                        });
+                    }
+
+                    thread::sleep(self.policy.delay(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn exchange(
+        &self,
+        slot: &mut Option<TcpStream>,
+        request: &Request,
+    ) -> Result<Response, NetError> {
+        let stream = match slot {
+            Some(stream) => stream,
+            None => slot.insert(self.reconnect(self.timeout)?),
+        };
+
+        write_frame(stream, &request.to_frame())?;
+        match Response::from_frame(read_frame(stream)?)? {
+            Response::Error(message) => Err(NetError::Remote(message)),
+            Response::NotFound(what) => Err(NetError::NotFound(what)),
+            Response::Unauthorized => Err(NetError::Unauthorized),
+            response => Ok(response),
+        }
+    }
+
+    // С таймаутом соединение не ждёт мёртвый сервер дольше него ни на каком шаге
+    fn reconnect(&self, timeout: Option<Duration>) -> Result<TcpStream, NetError> {
+        let mut stream = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&self.addr, timeout)?,
+            None => TcpStream::connect(self.addr)?,
+        };
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+
+        if let Some(token) = &self.token {
+            let auth = Request::Auth {
+                token: token.clone(),
+            };
+            write_frame(&mut stream, &auth.to_frame())?;
+
+            match Response::from_frame(read_frame(&mut stream)?)? {
+                Response::Authorized => {}
+                Response::Unauthorized => return Err(NetError::Unauthorized),
+                other => return Err(NetError::UnexpectedResponse(other)),
+            }
+        }
+
+        Ok(stream)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let stream = self
+            .stream
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(stream) = stream {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+pub struct SocketClient {
+    name: String,
+    conn: Connection,
+}
+
+impl SocketClient {
+    pub fn connect(name: impl Into<String>, addr: impl ToSocketAddrs) -> Result<Self, NetError> {
+        Ok(Self {
+            name: name.into(),
+            conn: Connection::open(addr)?,
+        })
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Result<Self, NetError> {
+        self.conn.authenticate(token.into())?;
+        Ok(self)
+    }
+
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.conn.policy = policy;
+        self
+    }
+
+    /// Gives up on a connect, read or write that takes longer than `timeout`, with an
+    /// I/O error of kind `TimedOut` or `WouldBlock`, instead of waiting for a hung server
+    /// forever. Without it a request waits as long as the server takes.
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self, NetError> {
+        self.conn.set_timeout(timeout)?;
+        Ok(self)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.conn.addr
+    }
+
+    pub fn is_on(&self) -> Result<bool, NetError> {
+        let request = Request::GetState {
+            device: self.name.clone(),
+        };
+
+        match self.conn.request(&request, true)? {
+            Response::State { on } => Ok(on),
+            other => Err(NetError::UnexpectedResponse(other)),
+        }
+    }
+
+    /// Closes the client's connection to the server; a later request opens a new one.
+    pub fn close(&self) {
+        let mut stream = self
+            .conn
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(stream) = stream.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Asks for the state over a separate connection that gives up after `timeout`,
+    /// without retries and without touching the client's own connection.
+    pub fn ping(&self, timeout: Duration) -> Result<bool, NetError> {
+        let request = Request::GetState {
+            device: self.name.clone(),
+        };
+        let mut stream = Some(self.conn.reconnect(Some(timeout))?);
+
+        match self.conn.exchange(&mut stream, &request)? {
+            Response::State { on } => Ok(on),
+            other => Err(NetError::UnexpectedResponse(other)),
+        }
+    }
+
+    pub fn turn_on(&self) -> Result<(), NetError> {
+        self.set_state(true)
+    }
+
+    pub fn turn_off(&self) -> Result<(), NetError> {
+        self.set_state(false)
+    }
+
+    pub fn report(&self) -> Result<String, NetError> {
+        match self.conn.request(&Request::Report, true)? {
+            Response::Report(text) => Ok(text),
+            other => Err(NetError::UnexpectedResponse(other)),
+        }
+    }
+
+    fn set_state(&self, on: bool) -> Result<(), NetError> {
+        let request = Request::SetState {
+            device: self.name.clone(),
+            on,
+        };
+
+        match self.conn.request(&request, false)? {
+            Response::State { on: actual } if actual == on => Ok(()),
+            other => Err(NetError::UnexpectedResponse(other)),
+        }
+    }
+}
+
+impl Named for SocketClient {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The copy talks to the same server with the same token and policy, over a connection
+/// of its own opened on first use.
+impl Clone for SocketClient {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            conn: Connection {
+                addr: self.conn.addr,
+                token: self.conn.token.clone(),
+                policy: self.conn.policy.clone(),
+                timeout: self.conn.timeout,
+                stream: Mutex::new(None),
+            },
+        }
+    }
+}
+
+impl Pluggable for SocketClient {
+    crate::boxed_clone!();
+
+    fn as_switchable(&self) -> Option<&dyn crate::Switchable> {
+        Some(self)
+    }
+
+    fn kind(&self) -> Option<DeviceKind> {
+        Some(DeviceKind::Socket)
+    }
+}
+
+impl fmt::Debug for SocketClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SocketClient")
+            .field("name", &self.name)
+            .field("addr", &self.conn.addr)
+            .finish_non_exhaustive()
+    }
+}
+
+/// How long [`SocketDriver::probe`] waits for an answer unless told otherwise.
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Connects a [`SocketClient`] to the socket `name` of a [`SocketServer`].
+#[derive(Debug, Clone)]
+pub struct SocketDriver {
+    name: String,
+    addr: SocketAddr,
+    token: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl SocketDriver {
+    pub fn new(name: impl Into<String>, addr: SocketAddr) -> Self {
+        Self {
+            name: name.into(),
+            addr,
+            token: None,
+            timeout: None,
+        }
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// See [`SocketClient::with_timeout`]; the probe waits this long instead of
+    /// [`DEFAULT_PROBE_TIMEOUT`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn client(&self) -> Result<SocketClient, NetError> {
+        let mut client = SocketClient::connect(self.name.clone(), self.addr)?;
+        if let Some(token) = &self.token {
+            client = client.with_token(token.clone())?;
+        }
+        if let Some(timeout) = self.timeout {
+            client = client.with_timeout(timeout)?;
+        }
+        Ok(client)
+    }
+}
+
+impl DeviceDriver for SocketDriver {
+    fn connect(&self) -> Result<Arc<dyn Pluggable>, CapabilityError> {
+        Ok(Arc::new(self.client()?))
+    }
+
+    fn disconnect(&self, device: Arc<dyn Pluggable>) {
+        if let Some(client) = downcast::<SocketClient>(&*device) {
+            client.close();
+        }
+    }
+
+    /// Asks the server for the socket's state; the server does not tell who made it.
+    fn probe(&self) -> Result<DeviceInfo, CapabilityError> {
+        let client = self.client()?;
+        client.ping(self.timeout.unwrap_or(DEFAULT_PROBE_TIMEOUT))?;
+        client.close();
+        Ok(DeviceInfo::new(UNKNOWN_MAKER, "tcp socket"))
+    }
+}
+
+pub struct HouseClient {
+    conn: Connection,
+}
+
+impl fmt::Debug for HouseClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HouseClient")
+            .field("addr", &self.conn.addr)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HouseClient {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, NetError> {
+        Ok(Self {
+            conn: Connection::open(addr)?,
+        })
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Result<Self, NetError> {
+        self.conn.authenticate(token.into())?;
+        Ok(self)
+    }
+
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.conn.policy = policy;
+        self
+    }
+
+    /// See [`SocketClient::with_timeout`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self, NetError> {
+        self.conn.set_timeout(timeout)?;
+        Ok(self)
+    }
+
+    pub fn layout(&self) -> Result<HouseLayout, NetError> {
+        match self.conn.request(&Request::Layout, true)? {
+            Response::Layout(layout) => Ok(layout),
+            other => Err(NetError::UnexpectedResponse(other)),
+        }
+    }
+
+    /// The device's [status](Pluggable::status) as in detailed reports, such as
+    /// `on, 60.0 W`, or `connected` for a device that has none.
+    pub fn status(&self, room: &str, device: &str) -> Result<String, NetError> {
+        let request = Request::GetDeviceState {
+            room: room.to_string(),
+            device: device.to_string(),
+        };
+
+        match self.conn.request(&request, true)? {
+            Response::Status(status) => Ok(status),
+            other => Err(NetError::UnexpectedResponse(other)),
+        }
+    }
+
+    pub fn set_state(&self, room: &str, device: &str, on: bool) -> Result<bool, NetError> {
+        let request = Request::SetDeviceState {
+            room: room.to_string(),
+            device: device.to_string(),
+            on,
+        };
+
+        match self.conn.request(&request, false)? {
+            Response::State { on } => Ok(on),
+            other => Err(NetError::UnexpectedResponse(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::small_house;
+
+    fn spawn_server(name: &str) -> ServerHandle {
+        SocketServer::bind(name.to_string(), "127.0.0.1:0")
+            .unwrap()
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn switch_remote_socket() {
+        let server = spawn_server("Main socket");
+        let client = SocketClient::connect("Main socket", server.local_addr()).unwrap();
+
+        assert!(!client.is_on().unwrap(), "Socket starts switched off");
+        client.turn_on().unwrap();
+        assert!(client.is_on().unwrap(), "Socket switched on");
+        assert_eq!(client.report().unwrap(), "Socket[Main socket]: on");
+        client.turn_off().unwrap();
+        assert!(!client.is_on().unwrap(), "Socket switched off");
+    }
+
+    #[test]
+    fn unknown_device_is_not_found() {
+        let server = spawn_server("Main socket");
+        let client = SocketClient::connect("Other", server.local_addr()).unwrap();
+
+        assert!(matches!(client.is_on(), Err(NetError::NotFound(_))));
+    }
+
+    fn spawn_protected_server(name: &str, token: &str) -> ServerHandle {
+        SocketServer::bind(name.to_string(), "127.0.0.1:0")
+            .unwrap()
+            .with_token(token.to_string())
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn missing_token_is_rejected() {
+        let server = spawn_protected_server("Main socket", "s3cr3t");
+        let client = SocketClient::connect("Main socket", server.local_addr()).unwrap();
+
+        assert!(matches!(client.turn_on(), Err(NetError::Unauthorized)));
+        assert_eq!(server.rejected_attempts(), 1);
+        assert!(
+            matches!(client.is_on(), Err(NetError::Unauthorized)),
+            "Reconnecting without a token is rejected again"
+        );
+        assert_eq!(server.rejected_attempts(), 2);
+    }
+
+    #[test]
+    fn wrong_token_is_rejected() {
+        let server = spawn_protected_server("Main socket", "s3cr3t");
+        let client = SocketClient::connect("Main socket", server.local_addr()).unwrap();
+
+        assert!(matches!(
+            client.with_token("guess"),
+            Err(NetError::Unauthorized)
+        ));
+        assert_eq!(server.rejected_attempts(), 1);
+    }
+
+    #[test]
+    fn correct_token_is_accepted() {
+        let server = spawn_protected_server("Main socket", "s3cr3t");
+        let client = SocketClient::connect("Main socket", server.local_addr())
+            .unwrap()
+            .with_token("s3cr3t")
+            .unwrap();
+
+        client.turn_on().unwrap();
+        assert!(client.is_on().unwrap());
+        assert_eq!(server.rejected_attempts(), 0);
+    }
+
+    #[test]
+    fn token_is_ignored_by_open_server() {
+        let server = spawn_server("Main socket");
+        let client = SocketClient::connect("Main socket", server.local_addr())
+            .unwrap()
+            .with_token("anything")
+            .unwrap();
+
+        assert!(!client.is_on().unwrap());
+    }
+
+    fn fast_policy(max_retries: u32) -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_retries,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+            ..ReconnectPolicy::default()
+        }
+    }
+
+    fn restart(server: ServerHandle) -> ServerHandle {
+        let (name, addr) = (server.name().to_string(), server.local_addr());
+        server.shutdown();
+        SocketServer::bind(name, addr).unwrap().spawn().unwrap()
+    }
+
+    #[test]
+    fn reads_reconnect_after_restart() {
+        let server = spawn_server("Main socket");
+        let client = SocketClient::connect("Main socket", server.local_addr())
+            .unwrap()
+            .with_reconnect(fast_policy(3));
+        assert!(!client.is_on().unwrap());
+
+        let _server = restart(server);
+        assert!(
+            !client.is_on().unwrap(),
+            "Read is retried on a new connection"
+        );
+    }
+
+    #[test]
+    fn writes_are_not_retried_by_default() {
+        let server = spawn_server("Main socket");
+        let client = SocketClient::connect("Main socket", server.local_addr())
+            .unwrap()
+            .with_reconnect(fast_policy(3));
+        client.turn_on().unwrap();
+
+        let _server = restart(server);
+        assert!(matches!(client.turn_on(), Err(NetError::Io(_))));
+        client.turn_on().unwrap();
+        assert!(client.is_on().unwrap(), "Next call reconnects");
+    }
+
+    #[test]
+    fn writes_retry_when_allowed() {
+        let server = spawn_server("Main socket");
+        let policy = ReconnectPolicy {
+            retry_non_idempotent: true,
+            ..fast_policy(3)
+        };
+        let client = SocketClient::connect("Main socket", server.local_addr())
+            .unwrap()
+            .with_reconnect(policy);
+        client.turn_off().unwrap();
+
+        let _server = restart(server);
+        client.turn_on().unwrap();
+        assert!(client.is_on().unwrap());
+    }
+
+    #[test]
+    fn reconnect_reauthenticates() {
+        let server = spawn_protected_server("Main socket", "s3cr3t");
+        let client = SocketClient::connect("Main socket", server.local_addr())
+            .unwrap()
+            .with_token("s3cr3t")
+            .unwrap()
+            .with_reconnect(fast_policy(3));
+
+        let (name, addr) = (server.name().to_string(), server.local_addr());
+        server.shutdown();
+        let server = SocketServer::bind(name, addr)
+            .unwrap()
+            .with_token("s3cr3t")
+            .spawn()
+            .unwrap();
+
+        assert!(!client.is_on().unwrap());
+        assert_eq!(server.rejected_attempts(), 0);
+    }
+
+    #[test]
+    fn retries_are_exhausted() {
+        let server = spawn_server("Main socket");
+        let client = SocketClient::connect("Main socket", server.local_addr())
+            .unwrap()
+            .with_reconnect(fast_policy(2));
+        server.shutdown();
+
+        match client.is_on() {
+            Err(NetError::RetriesExhausted { attempts, .. }) => assert_eq!(attempts, 3),
+            other => panic!("expected exhausted retries, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            multiplier: 3.0,
+            max_delay: Duration::from_millis(500),
+            ..ReconnectPolicy::default()
+        };
+
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(300));
+        assert_eq!(policy.delay(2), Duration::from_millis(500));
+        assert_eq!(policy.delay(40), Duration::from_millis(500));
+    }
+
+    fn serve_house(server: HouseServer, connections: usize) -> JoinHandle<()> {
+        thread::spawn(move || {
+            let house = small_house();
+
+            for _ in 0..connections {
+                server.serve_once(&house).unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn house_layout_over_network() {
+        let server = HouseServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let serving = serve_house(server, 1);
+
+        let client = HouseClient::connect(addr).unwrap();
+        let layout = client.layout().unwrap();
+        assert_eq!(layout.name, "hell");
+        assert_eq!(layout.rooms.len(), 2);
+        assert_eq!(layout.rooms[0].devices, ["s1", "t1"]);
+        assert_eq!(client.status("limb", "s1").unwrap(), "off, 0.0 W");
+        assert_eq!(client.status("limb", "t1").unwrap(), "no reading");
+        assert!(matches!(
+            client.status("limb", "nope"),
+            Err(NetError::NotFound(_))
+        ));
+        assert!(matches!(
+            client.status("hall", "s1"),
+            Err(NetError::NotFound(_))
+        ));
+
+        assert!(client.set_state("limb", "s1", true).unwrap());
+        assert!(!client.set_state("limb", "s1", false).unwrap());
+        assert!(client.set_state("limb", "t1", true).is_err());
+        assert!(matches!(
+            client.set_state("limb", "nope", true),
+            Err(NetError::NotFound(_))
+        ));
+
+        drop(client);
+        serving.join().unwrap();
+    }
+
+    #[test]
+    fn house_server_requires_token() {
+        let server = HouseServer::bind("127.0.0.1:0")
+            .unwrap()
+            .with_token("s3cr3t");
+        let addr = server.local_addr().unwrap();
+        let serving = serve_house(server, 2);
+
+        let client = HouseClient::connect(addr)
+            .unwrap()
+            .with_reconnect(ReconnectPolicy::none());
+        assert!(matches!(client.layout(), Err(NetError::Unauthorized)));
+        drop(client);
+
+        let client = HouseClient::connect(addr)
+            .unwrap()
+            .with_token("s3cr3t")
+            .unwrap();
+        assert_eq!(client.layout().unwrap().name, "hell");
+
+        drop(client);
+        serving.join().unwrap();
+    }
+
+    #[test]
+    fn unknown_frames_are_protocol_errors() {
+        let server = spawn_server("Main socket");
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        let junk = crate::protocol::Frame {
+            kind: 0xEE,
+            payload: vec![1, 2, 3],
+        };
+        write_frame(&mut stream, &junk).unwrap();
+        assert!(matches!(
+            Response::from_frame(read_frame(&mut stream).unwrap()),
+            Ok(Response::Error(_))
+        ));
+        assert_eq!(server.protocol_errors(), 1);
+    }
+
+    #[test]
+    fn dropped_connections_are_reopened() {
+        let server = spawn_server("Main socket");
+        let client = SocketClient::connect("Main socket", server.local_addr())
+            .unwrap()
+            .with_reconnect(fast_policy(3));
+        client.turn_on().unwrap();
+        assert_eq!(server.connections(), 1);
+
+        server.drop_connections();
+        assert_eq!(server.connections(), 0);
+        assert!(
+            client.is_on().unwrap(),
+            "State survives a dropped connection"
+        );
+    }
+
+    #[test]
+    fn names_with_newlines_survive_framing() {
+        let server = spawn_server("line\nbreak");
+        let client = SocketClient::connect("line\nbreak", server.local_addr()).unwrap();
+
+        client.turn_on().unwrap();
+        assert!(client.is_on().unwrap());
+    }
+
+    #[test]
+    fn driver_disconnects_on_unplug() {
+        let server = spawn_server("s1");
+        let driver = SocketDriver::new("s1", server.local_addr());
+        assert_eq!(driver.probe().unwrap().model, "tcp socket");
+        assert!(SocketDriver::new("s2", server.local_addr())
+            .probe()
+            .is_err());
+
+        let mut room = crate::SmartRoom::new("hall");
+        room.plug_driver(Box::new(driver)).unwrap();
+        let s1 = room.device("s1").unwrap();
+        s1.as_switchable().unwrap().turn_on().unwrap();
+        assert_eq!(server.connections(), 1);
+
+        room.unplug("s1").unwrap();
+        // сервер замечает закрытое соединение не сразу
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while server.connections() > 0 && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(server.connections(), 0);
+
+        let addr = server.local_addr();
+        server.shutdown();
+        assert!(matches!(
+            room.plug_driver(Box::new(SocketDriver::new("s1", addr))),
+            Err(crate::SmartHouseError::Driver(_))
+        ));
+        assert!(room.devices().is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{DeviceKind, SmartHouse};
+
+/// One slice of a longer listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items in the whole listing.
+    pub total: usize,
+    /// Whether items follow this page.
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSummary {
+    pub room: String,
+    pub device: String,
+    pub kind: Option<DeviceKind>,
+    pub status: Option<String>,
+}
+
+impl SmartHouse {
+    /// Up to `limit` live devices starting at `offset`, ordered by room name and then
+    /// device name, both compared as strings. The order does not depend on how the house
+    /// was built, so consecutive pages neither repeat nor skip devices while the house
+    /// stays the same. An offset past the end gives an empty page.
+    pub fn devices_page(&self, offset: usize, limit: usize) -> Page<DeviceSummary> {
+        let mut devices: Vec<_> = self.all_devices().collect();
+        let total = devices.len();
+        devices.sort_by(|(a_room, a), (b_room, b)| {
+            (a_room.name.as_str(), a.name()).cmp(&(b_room.name.as_str(), b.name()))
+        });
+
+        let items: Vec<_> = devices
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(room, device)| DeviceSummary {
+                room: room.name.clone(),
+                device: device.name().into(),
+                kind: device.kind(),
+                status: device.status(),
+            })
+            .collect();
+        let has_more = offset.saturating_add(items.len()) < total;
+
+        Page {
+            items,
+            total,
+            has_more,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SmartRoom, SmartSocket, SmartThermometer};
+
+    #[test]
+    fn pages_are_sorted_and_bounded() {
+        let mut lust = SmartRoom::new("lust");
+        lust.plug(SmartSocket::new("s2")).unwrap();
+        lust.plug(SmartSocket::new("s1")).unwrap();
+        let mut limb = SmartRoom::new("limb");
+        limb.plug(SmartThermometer::new("t1")).unwrap();
+        limb.plug(SmartSocket::new("a0")).unwrap();
+        let mut house = SmartHouse::new("hell");
+        house.add(lust).unwrap();
+        house.add(SmartRoom::new("hall")).unwrap();
+        house.add(limb).unwrap();
+
+        let names = |page: &Page<DeviceSummary>| -> Vec<String> {
+            page.items
+                .iter()
+                .map(|d| alloc::format!("{}/{}", d.room, d.device))
+                .collect()
+        };
+        let first = house.devices_page(0, 3);
+        assert_eq!(names(&first), ["limb/a0", "limb/t1", "lust/s1"]);
+        assert_eq!((first.total, first.has_more), (4, true));
+        assert_eq!(first.items[0].kind, Some(DeviceKind::Socket));
+        assert_eq!(first.items[0].status.as_deref(), Some("off, 0.0 W"));
+
+        let last = house.devices_page(3, 3);
+        assert_eq!(names(&last), ["lust/s2"]);
+        assert!(!last.has_more);
+        assert!(!house.devices_page(0, 4).has_more);
+
+        for offset in [4, 5, usize::MAX] {
+            let past = house.devices_page(offset, 10);
+            assert!(past.items.is_empty());
+            assert_eq!((past.total, past.has_more), (4, false));
+        }
+        assert!(house.devices_page(1, 0).items.is_empty());
+        assert!(house.devices_page(1, 0).has_more);
+    }
+}
@@ -0,0 +1,205 @@
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::{error::Error, fmt};
+
+use crate::{Pluggable, SmartHouse, SmartRoom};
+
+/// The change a policy is asked about. Nothing has been changed yet.
+#[derive(Clone, Copy)]
+pub enum PolicyContext<'a> {
+    AddRoom {
+        house: &'a SmartHouse,
+        room: &'a SmartRoom,
+    },
+    /// Also sent for the target room of `move_device`.
+    PlugDevice {
+        house: &'a SmartHouse,
+        room: &'a SmartRoom,
+        device: &'a dyn Pluggable,
+    },
+}
+
+impl fmt::Debug for PolicyContext<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyContext::AddRoom { house, room } => f
+                .debug_struct("AddRoom")
+                .field("house", &house.name)
+                .field("room", &room.name)
+                .finish(),
+            PolicyContext::PlugDevice {
+                house,
+                room,
+                device,
+            } => f
+                .debug_struct("PlugDevice")
+                .field("house", &house.name)
+                .field("room", &room.name)
+                .field("device", &device.name())
+                .finish(),
+        }
+    }
+}
+
+/// A policy's reason for refusing a change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation(pub String);
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "policy violation: {}", self.0)
+    }
+}
+
+impl Error for PolicyViolation {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PolicyId(u64);
+
+type Check = dyn Fn(&PolicyContext<'_>) -> Result<(), PolicyViolation> + Send + Sync;
+type Policy = Box<Check>;
+
+#[derive(Default, Clone)]
+pub(crate) struct Policies {
+    next: u64,
+    policies: Vec<(PolicyId, Arc<Check>)>,
+}
+
+impl Policies {
+    // Первое же вето останавливает проверку
+    pub(crate) fn check(&self, context: &PolicyContext<'_>) -> Result<(), PolicyViolation> {
+        self.policies
+            .iter()
+            .try_for_each(|(_, policy)| policy(context))
+    }
+}
+
+impl SmartHouse {
+    /// Registers a policy that `add`, `plug` and `move_device` consult after their own
+    /// checks pass and before they change anything. Policies run in registration order and
+    /// the first veto is returned as [`SmartHouseError::Policy`](crate::SmartHouseError::Policy).
+    pub fn add_policy(&mut self, policy: Policy) -> PolicyId {
+        let id = PolicyId(self.policies.next);
+        self.policies.next += 1;
+        self.policies.policies.push((id, Arc::from(policy)));
+        id
+    }
+
+    /// Returns whether the policy was registered.
+    pub fn remove_policy(&mut self, id: PolicyId) -> bool {
+        let before = self.policies.policies.len();
+        self.policies.policies.retain(|(other, _)| *other != id);
+        self.policies.policies.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SmartHouseError, SmartSocket, SmartThermometer};
+
+    fn max_sockets(limit: usize) -> Policy {
+        Box::new(move |context| match context {
+            PolicyContext::PlugDevice { room, device, .. }
+                if device.name().starts_with("socket")
+                    && room
+                        .device_names()
+                        .filter(|n| n.starts_with("socket"))
+                        .count()
+                        >= limit =>
+            {
+                Err(PolicyViolation(format!(
+                    "{} already has {limit} sockets",
+                    room.name
+                )))
+            }
+            _ => Ok(()),
+        })
+    }
+
+    fn lowercase_rooms(context: &PolicyContext<'_>) -> Result<(), PolicyViolation> {
+        match context {
+            PolicyContext::AddRoom { room, .. } if room.name.chars().any(char::is_uppercase) => {
+                Err(PolicyViolation(format!(
+                    "room {} is not lowercase",
+                    room.name
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn vetoes_leave_house_unchanged() {
+        let mut house = SmartHouse::new("home");
+        house.add(SmartRoom::new("boiler")).unwrap();
+        house.add(SmartRoom::new("kitchen")).unwrap();
+        let limit = house.add_policy(max_sockets(1));
+        house.add_policy(Box::new(lowercase_rooms));
+
+        house.plug("boiler", SmartSocket::new("socket 1")).unwrap();
+        let vetoed = house.plug("boiler", SmartSocket::new("socket 2"));
+        assert_eq!(
+            vetoed.err(),
+            Some(SmartHouseError::Policy(PolicyViolation(
+                "boiler already has 1 sockets".to_string()
+            )))
+        );
+        house.plug("boiler", SmartThermometer::new("t1")).unwrap();
+        house.plug("kitchen", SmartSocket::new("socket 3")).unwrap();
+        assert!(house.move_device("socket 3", "kitchen", "boiler").is_err());
+        assert!(house.add(SmartRoom::new("Hall")).is_err());
+
+        assert_eq!(house.room("boiler").unwrap().devices(), ["socket 1", "t1"]);
+        assert_eq!(house.room("kitchen").unwrap().devices(), ["socket 3"]);
+        assert!(house.room("Hall").is_none());
+
+        assert!(house.remove_policy(limit));
+        assert!(!house.remove_policy(limit));
+        house.plug("boiler", SmartSocket::new("socket 2")).unwrap();
+        house.move_device("socket 3", "kitchen", "boiler").unwrap();
+        assert_eq!(house.room("boiler").unwrap().device_names().count(), 4);
+        assert!(house.add(SmartRoom::new("Hall")).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn policies_hold_in_a_house_cell() {
+        let mut house = SmartHouse::new("home");
+        house.add(SmartRoom::new("boiler")).unwrap();
+        let limit = house.add_policy(max_sockets(1));
+        let cell = crate::HouseCell::new(house);
+
+        cell.update(|house| house.plug("boiler", SmartSocket::new("socket 1")))
+            .unwrap();
+        let before = cell.load();
+        assert!(matches!(
+            cell.try_update(|house| house.plug("boiler", SmartSocket::new("socket 2"))),
+            Err(SmartHouseError::Policy(_))
+        ));
+        assert!(Arc::ptr_eq(&before, &cell.load()));
+        assert_eq!(before.room("boiler").unwrap().devices(), ["socket 1"]);
+
+        assert!(cell.update(|house| house.remove_policy(limit)));
+        cell.try_update(|house| house.plug("boiler", SmartSocket::new("socket 2")))
+            .unwrap();
+        assert_eq!(
+            cell.load().room("boiler").unwrap().device_names().count(),
+            2
+        );
+    }
+
+    #[test]
+    fn first_veto_wins_and_duplicates_come_first() {
+        let mut house = SmartHouse::new("home");
+        house.add_policy(Box::new(|_| Err(PolicyViolation("first".to_string()))));
+        house.add_policy(Box::new(|_| Err(PolicyViolation("second".to_string()))));
+        assert_eq!(
+            house.add(SmartRoom::new("boiler")).unwrap_err().to_string(),
+            "policy violation: first"
+        );
+        assert_eq!(
+            house.plug("boiler", SmartSocket::new("s1")).err(),
+            Some(SmartHouseError::RoomNotFound("boiler".to_string()))
+        );
+    }
+}
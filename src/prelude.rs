@@ -0,0 +1,31 @@
+//! The traits and types most code needs, in one import.
+//!
+//! ```
+//! use lesson_3::prelude::*;
+//!
+//! fn build() -> Result<String, Box<dyn std::error::Error>> {
+//!     let mut room = SmartRoom::new("Boiler");
+//!     room.plug_chain(SmartSocket::new("Main socket"))?
+//!         .plug_chain(SmartThermometer::new("T1"))?;
+//!
+//!     let mut house = SmartHouse::new("Home");
+//!     house
+//!         .add_chain(room)?
+//!         .add_chain(SmartRoom::new("Kitchen"))?;
+//!     let socket = house.locate(&"Boiler/Main socket".parse()?)?;
+//!     assert_eq!(socket.name(), "Main socket");
+//!
+//!     house.create_report(HouseReport)
+//! }
+//!
+//! assert!(build().unwrap().contains("Main socket"));
+//! ```
+
+#[cfg(feature = "async")]
+pub use crate::async_report::AsyncReportable;
+pub use crate::{
+    boxed_clone, smart_house, BuildError, DeviceId, DeviceKind, DeviceLocation, DeviceSliceExt,
+    HouseEvent, HouseReport, IntoDevice, Named, Pluggable, ReportBuilder, Reportable, RoomId,
+    SmartHouse, SmartHouseBuilder, SmartHouseError, SmartRoom, SmartRoomBuilder, SmartSocket,
+    SmartThermometer,
+};
@@ -0,0 +1,764 @@
+//! Length-prefixed binary framing for the network servers and clients.
+//!
+//! Every frame is `[u32 BE payload length][u8 message type][payload]`.
+
+use core::fmt;
+use std::{
+    error::Error,
+    io::{self, Read, Write},
+};
+
+pub const HEADER_LEN: usize = 5;
+pub const MAX_PAYLOAD_LEN: usize = 1 << 20;
+
+const GET_STATE: u8 = 0x01;
+const SET_STATE: u8 = 0x02;
+const GET_READING: u8 = 0x03;
+const REPORT: u8 = 0x04;
+const AUTH: u8 = 0x05;
+const LAYOUT: u8 = 0x06;
+const GET_DEVICE_STATE: u8 = 0x07;
+const SET_DEVICE_STATE: u8 = 0x08;
+
+const STATE: u8 = 0x81;
+const READING: u8 = 0x83;
+const REPORT_TEXT: u8 = 0x84;
+const AUTHORIZED: u8 = 0x85;
+const HOUSE_LAYOUT: u8 = 0x86;
+const STATUS: u8 = 0x87;
+const NOT_FOUND: u8 = 0xfd;
+const UNAUTHORIZED: u8 = 0xfe;
+const ERROR: u8 = 0xff;
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Truncated { expected: usize, actual: usize },
+    PayloadTooLarge(usize),
+    UnknownMessageType(u8),
+    MalformedPayload(&'static str),
+    TrailingBytes(usize),
+    Io(io::Error),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { expected, actual } => {
+                write!(
+                    f,
+                    "truncated frame: expected {expected} bytes, got {actual}"
+                )
+            }
+            Self::PayloadTooLarge(len) => {
+                write!(f, "payload of {len} bytes exceeds {MAX_PAYLOAD_LEN}")
+            }
+            Self::UnknownMessageType(kind) => write!(f, "unknown message type {kind:#04x}"),
+            Self::MalformedPayload(reason) => write!(f, "malformed payload: {reason}"),
+            Self::TrailingBytes(len) => write!(f, "{len} trailing bytes after frame"),
+            Self::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+impl ProtocolError {
+    /// Only I/O errors may go away on their own; a bad frame stays bad.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Io(e) => crate::retry::is_transient_io(e),
+            _ => false,
+        }
+    }
+}
+
+impl Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ProtocolError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: u8,
+    pub payload: Vec<u8>,
+}
+
+pub fn encode(kind: u8, payload: &[u8]) -> Result<Vec<u8>, ProtocolError> {
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(ProtocolError::PayloadTooLarge(payload.len()));
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    out.push(kind);
+    out.extend_from_slice(payload);
+
+    Ok(out)
+}
+
+/// Decodes one frame from the start of `buf`, returning it with the number of bytes consumed.
+pub fn decode(buf: &[u8]) -> Result<(Frame, usize), ProtocolError> {
+    if buf.len() < HEADER_LEN {
+        return Err(ProtocolError::Truncated {
+            expected: HEADER_LEN,
+            actual: buf.len(),
+        });
+    }
+
+    let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    if len > MAX_PAYLOAD_LEN {
+        return Err(ProtocolError::PayloadTooLarge(len));
+    }
+
+    let total = HEADER_LEN + len;
+    if buf.len() < total {
+        return Err(ProtocolError::Truncated {
+            expected: total,
+            actual: buf.len(),
+        });
+    }
+
+    let frame = Frame {
+        kind: buf[4],
+        payload: buf[HEADER_LEN..total].to_vec(),
+    };
+
+    Ok((frame, total))
+}
+
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Frame, ProtocolError> {
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    if len > MAX_PAYLOAD_LEN {
+        return Err(ProtocolError::PayloadTooLarge(len));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+
+    Ok(Frame {
+        kind: header[4],
+        payload,
+    })
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, frame: &Frame) -> Result<(), ProtocolError> {
+    writer.write_all(&encode(frame.kind, &frame.payload)?)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn decode_exact(buf: &[u8]) -> Result<Frame, ProtocolError> {
+    let (frame, used) = decode(buf)?;
+    match buf.len() - used {
+        0 => Ok(frame),
+        rest => Err(ProtocolError::TrailingBytes(rest)),
+    }
+}
+
+fn text(payload: Vec<u8>) -> Result<String, ProtocolError> {
+    String::from_utf8(payload).map_err(|_| ProtocolError::MalformedPayload("invalid utf-8"))
+}
+
+fn push_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_be_bytes());
+}
+
+fn push_str(out: &mut Vec<u8>, value: &str) {
+    push_u32(out, value.len());
+    out.extend_from_slice(value.as_bytes());
+}
+
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.0.len() < len {
+            return Err(ProtocolError::MalformedPayload("field exceeds payload"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn flag(&mut self) -> Result<bool, ProtocolError> {
+        match self.take(1)? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(ProtocolError::MalformedPayload("invalid state flag")),
+        }
+    }
+
+    fn u32(&mut self) -> Result<usize, ProtocolError> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    }
+
+    // Количество элементов, каждый из которых занимает не меньше `min_len` байт.
+    // Такому счётчику можно доверять при предвыделении.
+    fn count(&mut self, min_len: usize) -> Result<usize, ProtocolError> {
+        let count = self.u32()?;
+        match count.checked_mul(min_len) {
+            Some(len) if len <= self.0.len() => Ok(count),
+            _ => Err(ProtocolError::MalformedPayload("count exceeds payload")),
+        }
+    }
+
+    fn str(&mut self) -> Result<String, ProtocolError> {
+        let len = self.u32()?;
+        text(self.take(len)?.to_vec())
+    }
+
+    fn finish(self) -> Result<(), ProtocolError> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(ProtocolError::MalformedPayload(
+                "unexpected trailing fields",
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomLayout {
+    pub name: String,
+    pub devices: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HouseLayout {
+    pub name: String,
+    pub rooms: Vec<RoomLayout>,
+}
+
+impl fmt::Display for HouseLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "-> House: {}", self.name)?;
+        for room in &self.rooms {
+            writeln!(f, "--> Room: {}", room.name)?;
+            for device in &room.devices {
+                writeln!(f, "----> Device: {device}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl HouseLayout {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        push_str(out, &self.name);
+        push_u32(out, self.rooms.len());
+        for room in &self.rooms {
+            push_str(out, &room.name);
+            push_u32(out, room.devices.len());
+            for device in &room.devices {
+                push_str(out, device);
+            }
+        }
+    }
+
+    fn decode_from(fields: &mut Fields<'_>) -> Result<Self, ProtocolError> {
+        let name = fields.str()?;
+        // комната: имя и счётчик устройств, устройство: имя
+        let room_count = fields.count(8)?;
+        let mut rooms = Vec::with_capacity(room_count);
+        for _ in 0..room_count {
+            let name = fields.str()?;
+            let device_count = fields.count(4)?;
+            let mut devices = Vec::with_capacity(device_count);
+            for _ in 0..device_count {
+                devices.push(fields.str()?);
+            }
+            rooms.push(RoomLayout { name, devices });
+        }
+
+        Ok(Self { name, rooms })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    GetState {
+        device: String,
+    },
+    SetState {
+        device: String,
+        on: bool,
+    },
+    GetReading {
+        device: String,
+    },
+    Report,
+    Auth {
+        token: String,
+    },
+    Layout,
+    GetDeviceState {
+        room: String,
+        device: String,
+    },
+    SetDeviceState {
+        room: String,
+        device: String,
+        on: bool,
+    },
+}
+
+impl Request {
+    /// Short name of the request, as used in logs and traces.
+    pub fn command(&self) -> &'static str {
+        match self {
+            Self::GetState { .. } => "get_state",
+            Self::SetState { .. } => "set_state",
+            Self::GetReading { .. } => "get_reading",
+            Self::Report => "report",
+            Self::Auth { .. } => "auth",
+            Self::Layout => "layout",
+            Self::GetDeviceState { .. } => "get_device_state",
+            Self::SetDeviceState { .. } => "set_device_state",
+        }
+    }
+
+    pub fn to_frame(&self) -> Frame {
+        let (kind, payload) = match self {
+            Self::GetState { device } => (GET_STATE, device.as_bytes().to_vec()),
+            Self::SetState { device, on } => {
+                let mut payload = Vec::with_capacity(1 + device.len());
+                payload.push(u8::from(*on));
+                payload.extend_from_slice(device.as_bytes());
+                (SET_STATE, payload)
+            }
+            Self::GetReading { device } => (GET_READING, device.as_bytes().to_vec()),
+            Self::Report => (REPORT, Vec::new()),
+            Self::Auth { token } => (AUTH, token.as_bytes().to_vec()),
+            Self::Layout => (LAYOUT, Vec::new()),
+            Self::GetDeviceState { room, device } => {
+                let mut payload = Vec::new();
+                push_str(&mut payload, room);
+                push_str(&mut payload, device);
+                (GET_DEVICE_STATE, payload)
+            }
+            Self::SetDeviceState { room, device, on } => {
+                let mut payload = vec![u8::from(*on)];
+                push_str(&mut payload, room);
+                push_str(&mut payload, device);
+                (SET_DEVICE_STATE, payload)
+            }
+        };
+
+        Frame { kind, payload }
+    }
+
+    pub fn from_frame(frame: Frame) -> Result<Self, ProtocolError> {
+        let Frame { kind, mut payload } = frame;
+
+        match kind {
+            GET_STATE => Ok(Self::GetState {
+                device: text(payload)?,
+            }),
+            SET_STATE => {
+                let on = match payload.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    Some(_) => return Err(ProtocolError::MalformedPayload("invalid state flag")),
+                    None => return Err(ProtocolError::MalformedPayload("missing state flag")),
+                };
+                payload.remove(0);
+
+                Ok(Self::SetState {
+                    device: text(payload)?,
+                    on,
+                })
+            }
+            GET_READING => Ok(Self::GetReading {
+                device: text(payload)?,
+            }),
+            REPORT if payload.is_empty() => Ok(Self::Report),
+            REPORT => Err(ProtocolError::MalformedPayload(
+                "report request carries no payload",
+            )),
+            AUTH => Ok(Self::Auth {
+                token: text(payload)?,
+            }),
+            LAYOUT if payload.is_empty() => Ok(Self::Layout),
+            LAYOUT => Err(ProtocolError::MalformedPayload(
+                "layout request carries no payload",
+            )),
+            GET_DEVICE_STATE => {
+                let mut fields = Fields(&payload);
+                let request = Self::GetDeviceState {
+                    room: fields.str()?,
+                    device: fields.str()?,
+                };
+                fields.finish()?;
+                Ok(request)
+            }
+            SET_DEVICE_STATE => {
+                let mut fields = Fields(&payload);
+                let on = fields.flag()?;
+                let request = Self::SetDeviceState {
+                    room: fields.str()?,
+                    device: fields.str()?,
+                    on,
+                };
+                fields.finish()?;
+                Ok(request)
+            }
+            other => Err(ProtocolError::UnknownMessageType(other)),
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        let frame = self.to_frame();
+        encode(frame.kind, &frame.payload)
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, ProtocolError> {
+        Self::from_frame(decode_exact(buf)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    State { on: bool },
+    Reading(f64),
+    Report(String),
+    Authorized,
+    Unauthorized,
+    Layout(HouseLayout),
+    Status(String),
+    NotFound(String),
+    Error(String),
+}
+
+impl Response {
+    pub fn to_frame(&self) -> Frame {
+        let (kind, payload) = match self {
+            Self::State { on } => (STATE, vec![u8::from(*on)]),
+            Self::Reading(value) => (READING, value.to_be_bytes().to_vec()),
+            Self::Report(text) => (REPORT_TEXT, text.as_bytes().to_vec()),
+            Self::Authorized => (AUTHORIZED, Vec::new()),
+            Self::Unauthorized => (UNAUTHORIZED, Vec::new()),
+            Self::Layout(layout) => {
+                let mut payload = Vec::new();
+                layout.encode_into(&mut payload);
+                (HOUSE_LAYOUT, payload)
+            }
+            Self::Status(text) => (STATUS, text.as_bytes().to_vec()),
+            Self::NotFound(what) => (NOT_FOUND, what.as_bytes().to_vec()),
+            Self::Error(message) => (ERROR, message.as_bytes().to_vec()),
+        };
+
+        Frame { kind, payload }
+    }
+
+    pub fn from_frame(frame: Frame) -> Result<Self, ProtocolError> {
+        let Frame { kind, payload } = frame;
+
+        match kind {
+            STATE => match payload.as_slice() {
+                [0] => Ok(Self::State { on: false }),
+                [1] => Ok(Self::State { on: true }),
+                _ => Err(ProtocolError::MalformedPayload("invalid state flag")),
+            },
+            READING => {
+                let bytes: [u8; 8] = payload
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| ProtocolError::MalformedPayload("reading must be 8 bytes"))?;
+                Ok(Self::Reading(f64::from_be_bytes(bytes)))
+            }
+            REPORT_TEXT => Ok(Self::Report(text(payload)?)),
+            AUTHORIZED | UNAUTHORIZED if !payload.is_empty() => Err(
+                ProtocolError::MalformedPayload("authorization response carries no payload"),
+            ),
+            AUTHORIZED => Ok(Self::Authorized),
+            UNAUTHORIZED => Ok(Self::Unauthorized),
+            HOUSE_LAYOUT => {
+                let mut fields = Fields(&payload);
+                let layout = HouseLayout::decode_from(&mut fields)?;
+                fields.finish()?;
+                Ok(Self::Layout(layout))
+            }
+            STATUS => Ok(Self::Status(text(payload)?)),
+            NOT_FOUND => Ok(Self::NotFound(text(payload)?)),
+            ERROR => Ok(Self::Error(text(payload)?)),
+            other => Err(ProtocolError::UnknownMessageType(other)),
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        let frame = self.to_frame();
+        encode(frame.kind, &frame.payload)
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, ProtocolError> {
+        Self::from_frame(decode_exact(buf)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requests() -> Vec<Request> {
+        vec![
+            Request::GetState {
+                device: "Main socket".to_string(),
+            },
+            Request::GetState {
+                device: String::new(),
+            },
+            Request::SetState {
+                device: "line\nbreak".to_string(),
+                on: true,
+            },
+            Request::SetState {
+                device: "Розетка".to_string(),
+                on: false,
+            },
+            Request::GetReading {
+                device: "Thermometer 1".to_string(),
+            },
+            Request::Report,
+            Request::Auth {
+                token: "s3cr3t".to_string(),
+            },
+            Request::Layout,
+            Request::GetDeviceState {
+                room: "Boiler".to_string(),
+                device: String::new(),
+            },
+            Request::SetDeviceState {
+                room: "Boiler".to_string(),
+                device: "Main socket".to_string(),
+                on: true,
+            },
+        ]
+    }
+
+    fn responses() -> Vec<Response> {
+        vec![
+            Response::State { on: true },
+            Response::State { on: false },
+            Response::Reading(-273.15),
+            Response::Reading(f64::MAX),
+            Response::Report("-> House: hell\n--> Room: limb\n".to_string()),
+            Response::Report(String::new()),
+            Response::Authorized,
+            Response::Unauthorized,
+            Response::Layout(HouseLayout {
+                name: "hell".to_string(),
+                rooms: vec![
+                    RoomLayout {
+                        name: "limb".to_string(),
+                        devices: vec!["s1".to_string(), "t1".to_string()],
+                    },
+                    RoomLayout {
+                        name: "lust".to_string(),
+                        devices: Vec::new(),
+                    },
+                ],
+            }),
+            Response::Layout(HouseLayout {
+                name: String::new(),
+                rooms: Vec::new(),
+            }),
+            Response::Status("on".to_string()),
+            Response::NotFound("room limb".to_string()),
+            Response::Error("Device not found".to_string()),
+        ]
+    }
+
+    #[test]
+    fn request_round_trip() {
+        for request in requests() {
+            let bytes = request.encode().unwrap();
+            assert_eq!(Request::decode(&bytes).unwrap(), request);
+        }
+    }
+
+    #[test]
+    fn response_round_trip() {
+        for response in responses() {
+            let bytes = response.encode().unwrap();
+            assert_eq!(Response::decode(&bytes).unwrap(), response);
+        }
+    }
+
+    #[test]
+    fn zero_length_payload() {
+        let bytes = encode(REPORT, &[]).unwrap();
+        assert_eq!(bytes, [0, 0, 0, 0, REPORT]);
+
+        let (frame, used) = decode(&bytes).unwrap();
+        assert_eq!(used, HEADER_LEN);
+        assert!(frame.payload.is_empty());
+        assert_eq!(Request::decode(&bytes).unwrap(), Request::Report);
+    }
+
+    #[test]
+    fn maximum_length_payload() {
+        let text = "x".repeat(MAX_PAYLOAD_LEN);
+        let response = Response::Report(text);
+        let bytes = response.encode().unwrap();
+        assert_eq!(bytes.len(), HEADER_LEN + MAX_PAYLOAD_LEN);
+        assert_eq!(Response::decode(&bytes).unwrap(), response);
+
+        let oversized = vec![0u8; MAX_PAYLOAD_LEN + 1];
+        assert!(matches!(
+            encode(REPORT_TEXT, &oversized),
+            Err(ProtocolError::PayloadTooLarge(_))
+        ));
+
+        let mut header = ((MAX_PAYLOAD_LEN + 1) as u32).to_be_bytes().to_vec();
+        header.push(REPORT_TEXT);
+        assert!(matches!(
+            decode(&header),
+            Err(ProtocolError::PayloadTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        for request in requests() {
+            let bytes = request.encode().unwrap();
+            for cut in 0..bytes.len() {
+                assert!(
+                    matches!(
+                        Request::decode(&bytes[..cut]),
+                        Err(ProtocolError::Truncated { .. })
+                    ),
+                    "prefix of {cut} bytes should be truncated"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn garbage_never_panics() {
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..2000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+
+            let len = (seed % 24) as usize;
+            let bytes: Vec<u8> = (0..len).map(|i| (seed >> (i % 8 * 8)) as u8).collect();
+
+            let _ = Request::decode(&bytes);
+            let _ = Response::decode(&bytes);
+        }
+    }
+
+    // Ручной перебор полей внутри кадра с верным заголовком. Сам кадр цел, поэтому
+    // декодер его стороны даёт сообщение или MalformedPayload, а другой стороны —
+    // UnknownMessageType
+    #[test]
+    fn damaged_payloads_are_typed_errors() {
+        let mut malformed = 0;
+        let mut check = |kind: u8, payload: &[u8], request: bool| {
+            let bytes = encode(kind, payload).unwrap();
+            let requested = Request::decode(&bytes).map(drop);
+            let responded = Response::decode(&bytes).map(drop);
+            let (own, other) = match request {
+                true => (requested, responded),
+                false => (responded, requested),
+            };
+            match own {
+                Ok(()) => {}
+                Err(ProtocolError::MalformedPayload(_)) => malformed += 1,
+                Err(err) => panic!("{kind:#04x} {payload:?}: {err}"),
+            }
+            assert!(
+                matches!(other, Err(ProtocolError::UnknownMessageType(k)) if k == kind),
+                "{kind:#04x} {payload:?}: {other:?}"
+            );
+        };
+
+        let frames = requests()
+            .iter()
+            .map(|r| (r.to_frame(), true))
+            .chain(responses().iter().map(|r| (r.to_frame(), false)))
+            .collect::<Vec<_>>();
+        for (Frame { kind, payload }, request) in frames {
+            for cut in 0..payload.len() {
+                check(kind, &payload[..cut], request);
+            }
+            for at in 0..payload.len() {
+                for value in [0x00, 0x01, 0x7f, 0xff] {
+                    let mut damaged = payload.clone();
+                    damaged[at] = value;
+                    check(kind, &damaged, request);
+                }
+            }
+        }
+        assert!(malformed > 0);
+    }
+
+    #[test]
+    fn malformed_payloads() {
+        assert!(matches!(
+            Request::decode(&encode(SET_STATE, &[]).unwrap()),
+            Err(ProtocolError::MalformedPayload(_))
+        ));
+        assert!(matches!(
+            Request::decode(&encode(SET_STATE, &[7, b'a']).unwrap()),
+            Err(ProtocolError::MalformedPayload(_))
+        ));
+        assert!(matches!(
+            Request::decode(&encode(GET_STATE, &[0xff, 0xfe]).unwrap()),
+            Err(ProtocolError::MalformedPayload(_))
+        ));
+        assert!(matches!(
+            Response::decode(&encode(READING, &[1, 2, 3]).unwrap()),
+            Err(ProtocolError::MalformedPayload(_))
+        ));
+        assert!(matches!(
+            Request::decode(&encode(GET_DEVICE_STATE, &[0, 0, 0, 9, b'a']).unwrap()),
+            Err(ProtocolError::MalformedPayload(_))
+        ));
+        assert!(matches!(
+            Response::decode(&encode(HOUSE_LAYOUT, &[0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]).unwrap()),
+            Err(ProtocolError::MalformedPayload(_))
+        ));
+        assert!(matches!(
+            Request::decode(&encode(0x42, &[]).unwrap()),
+            Err(ProtocolError::UnknownMessageType(0x42))
+        ));
+
+        let mut bytes = Request::Report.encode().unwrap();
+        bytes.push(0);
+        assert!(matches!(
+            Request::decode(&bytes),
+            Err(ProtocolError::TrailingBytes(1))
+        ));
+    }
+
+    #[test]
+    fn stream_read_write() {
+        let mut wire = Vec::new();
+        for request in requests() {
+            write_frame(&mut wire, &request.to_frame()).unwrap();
+        }
+
+        let mut reader = wire.as_slice();
+        for request in requests() {
+            let frame = read_frame(&mut reader).unwrap();
+            assert_eq!(Request::from_frame(frame).unwrap(), request);
+        }
+        assert!(matches!(read_frame(&mut reader), Err(ProtocolError::Io(_))));
+    }
+}
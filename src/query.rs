@@ -0,0 +1,213 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{DeviceKind, Pluggable, SmartHouse, SmartRoom};
+
+type Predicate = Arc<dyn Fn(&SmartRoom, &dyn Pluggable) -> bool + Send + Sync>;
+
+/// Narrows the live devices of a house step by step, see [`SmartHouse::query`].
+///
+/// A query only stores its filters, so it is cheap to clone and refine in different
+/// ways. Nothing is looked at until a terminal operation runs, and [`first`](Self::first)
+/// stops at the first match.
+#[derive(Clone)]
+pub struct DeviceQuery<'a> {
+    house: &'a SmartHouse,
+    room: Option<String>,
+    kind: Option<DeviceKind>,
+    name_contains: Vec<String>,
+    predicates: Vec<Predicate>,
+}
+
+impl fmt::Debug for DeviceQuery<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceQuery")
+            .field("house", &self.house.name)
+            .field("room", &self.room)
+            .field("kind", &self.kind)
+            .field("name_contains", &self.name_contains)
+            .field("predicates", &self.predicates.len())
+            .finish()
+    }
+}
+
+impl SmartHouse {
+    /// A query over every live device of the house.
+    ///
+    /// ```
+    /// use lesson_3::{DeviceKind, SmartHouse};
+    ///
+    /// let house = SmartHouse::builder("Home")
+    ///     .room("Boiler", |r| r.thermometer("outdoor-1").thermometer("indoor").socket("outdoor-2"))
+    ///     .build()
+    ///     .unwrap();
+    /// let outdoor = house.query().in_room("Boiler").name_contains("outdoor");
+    /// assert_eq!(outdoor.clone().of_kind(DeviceKind::Thermometer).names(), ["outdoor-1"]);
+    /// assert_eq!(outdoor.count(), 2);
+    /// ```
+    pub fn query(&self) -> DeviceQuery<'_> {
+        DeviceQuery {
+            house: self,
+            room: None,
+            kind: None,
+            name_contains: Vec::new(),
+            predicates: Vec::new(),
+        }
+    }
+}
+
+impl<'a> DeviceQuery<'a> {
+    /// Only devices of the room called `room`; a later call replaces the room.
+    pub fn in_room(mut self, room: impl Into<String>) -> Self {
+        self.room = Some(room.into());
+        self
+    }
+
+    /// Only devices of `kind`; a later call replaces the kind.
+    pub fn of_kind(mut self, kind: DeviceKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Only devices whose name contains `part`. Calls add up.
+    pub fn name_contains(mut self, part: impl Into<String>) -> Self {
+        self.name_contains.push(part.into());
+        self
+    }
+
+    /// Only devices for which `predicate` holds. It runs after the other filters.
+    pub fn filter(
+        mut self,
+        predicate: impl Fn(&SmartRoom, &dyn Pluggable) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.predicates.push(Arc::new(predicate));
+        self
+    }
+
+    fn matches(&self, room: &SmartRoom, device: &dyn Pluggable) -> bool {
+        self.kind.is_none_or(|kind| device.kind() == Some(kind))
+            && self
+                .name_contains
+                .iter()
+                .all(|part| device.name().contains(part.as_str()))
+            && self.predicates.iter().all(|p| p(room, device))
+    }
+
+    /// The matching devices with their rooms, room by room; other rooms are skipped
+    /// without looking at their devices.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a SmartRoom, Arc<dyn Pluggable>)> + '_ {
+        self.house
+            .rooms
+            .iter()
+            .filter(|room| self.room.as_ref().is_none_or(|name| room.name == *name))
+            .flat_map(|room| room.live_devices().map(move |device| (room, device)))
+            .filter(|(room, device)| self.matches(room, device.as_ref()))
+    }
+
+    pub fn collect(&self) -> Vec<(&'a SmartRoom, Arc<dyn Pluggable>)> {
+        self.iter().collect()
+    }
+
+    pub fn count(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn first(&self) -> Option<(&'a SmartRoom, Arc<dyn Pluggable>)> {
+        self.iter().next()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.iter()
+            .map(|(_, device)| device.name().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::fixtures::small_house, SmartSocket, SmartThermometer};
+
+    // small_house с уличными датчиками; t1 в limb остаётся термометром не с улицы
+    fn house() -> SmartHouse {
+        let mut house = small_house();
+        house
+            .plug("limb", SmartThermometer::new("outdoor north"))
+            .unwrap();
+        house
+            .plug("limb", SmartSocket::new("outdoor pump"))
+            .unwrap();
+        house
+            .plug("limb", SmartThermometer::new("outdoor south"))
+            .unwrap();
+        house
+            .plug("lust", SmartThermometer::new("outdoor porch"))
+            .unwrap();
+        house
+    }
+
+    #[test]
+    fn three_filters_and_refinements() {
+        let house = house();
+        let query = house
+            .query()
+            .in_room("limb")
+            .of_kind(DeviceKind::Thermometer)
+            .name_contains("outdoor");
+        assert_eq!(query.names(), ["outdoor north", "outdoor south"]);
+        assert_eq!(query.count(), 2);
+        assert_eq!(query.first().unwrap().1.name(), "outdoor north");
+        assert_eq!(query.collect()[1].0.name, "limb");
+
+        let base = house.query().name_contains("outdoor");
+        assert_eq!(base.count(), 4);
+        assert_eq!(base.clone().in_room("lust").names(), ["outdoor porch"]);
+        assert_eq!(
+            base.clone().name_contains("south").names(),
+            ["outdoor south"]
+        );
+        assert_eq!(base.of_kind(DeviceKind::Socket).names(), ["outdoor pump"]);
+        assert_eq!(house.query().in_room("Attic").count(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn first_stops_at_the_first_match() {
+        use crate::{devices::downcast, testing::MockDevice};
+
+        let mocks: Vec<_> = (0..4)
+            .map(|i| Arc::new(MockDevice::builder(alloc::format!("m{i}")).build()))
+            .collect();
+        mocks[1].turn_on().unwrap();
+        mocks[3].turn_on().unwrap();
+        let mut room = SmartRoom::new("Boiler");
+        for mock in &mocks {
+            room.plug(mock.clone()).unwrap();
+        }
+        let mut house = SmartHouse::new("Home");
+        house.add(room).unwrap();
+
+        let on = house.query().filter(|_, device| {
+            downcast::<MockDevice>(device).is_some_and(|mock| mock.is_on().unwrap())
+        });
+        assert_eq!(on.first().unwrap().1.name(), "m1");
+        let reads: Vec<_> = mocks.iter().map(|m| m.times_state_read()).collect();
+        assert_eq!(reads, [1, 1, 0, 0]);
+
+        assert_eq!(on.names(), ["m1", "m3"]);
+        let reads: Vec<_> = mocks.iter().map(|m| m.times_state_read()).collect();
+        assert_eq!(reads, [2, 2, 1, 1]);
+
+        // отсеянные ранее фильтры не дают предикату смотреть на устройство
+        let named = house.query().name_contains("m2").filter(|_, device| {
+            downcast::<MockDevice>(device).is_some_and(|mock| mock.is_on().unwrap())
+        });
+        assert_eq!(named.count(), 0);
+        assert_eq!(mocks[0].times_state_read(), 2);
+        assert_eq!(mocks[2].times_state_read(), 2);
+    }
+}
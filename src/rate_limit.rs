@@ -0,0 +1,275 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use crate::{CommandError, SmartHouse};
+
+/// At most `commands` commands per `window` to one device, as a token bucket: a device
+/// that has been quiet can take `commands` at once, after which one more is allowed
+/// every `window / commands`. A limit of zero commands is taken as one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub commands: u32,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    pub fn new(commands: u32, window: Duration) -> Self {
+        Self { commands, window }
+    }
+}
+
+// Уровень в наносекундах окна на команду: одна команда стоит window, за наносекунду
+// добавляется commands, так пополнение считается без округлений
+#[derive(Debug, Clone)]
+struct Bucket {
+    level: u128,
+    at: SystemTime,
+}
+
+impl Bucket {
+    fn take(&mut self, limit: RateLimit, now: SystemTime) -> Result<(), Duration> {
+        let commands = u128::from(limit.commands.max(1));
+        let cost = limit.window.as_nanos();
+        let elapsed = now.duration_since(self.at).unwrap_or_default().as_nanos();
+        self.level = (self.level + elapsed * commands).min(cost * commands);
+        self.at = self.at.max(now);
+        if self.level < cost {
+            let wait = (cost - self.level).div_ceil(commands);
+            return Err(Duration::from_nanos(wait as u64));
+        }
+        self.level -= cost;
+        Ok(())
+    }
+
+    fn refund(&mut self, limit: RateLimit) {
+        let commands = u128::from(limit.commands.max(1));
+        let cost = limit.window.as_nanos();
+        self.level = (self.level + cost).min(cost * commands);
+    }
+}
+
+type Key = (String, String);
+
+#[derive(Debug, Default)]
+pub(crate) struct RateLimits {
+    default: Option<RateLimit>,
+    devices: BTreeMap<Key, Option<RateLimit>>,
+    buckets: Mutex<BTreeMap<Key, Bucket>>,
+}
+
+// Копия помнит лимиты, но начинает с полными корзинами
+impl Clone for RateLimits {
+    fn clone(&self) -> Self {
+        Self {
+            default: self.default,
+            devices: self.devices.clone(),
+            buckets: Mutex::default(),
+        }
+    }
+}
+
+impl RateLimits {
+    // для HouseCell: корзины переходят в копию как есть
+    pub(crate) fn fork(&self) -> Self {
+        let buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        Self {
+            buckets: Mutex::new(buckets.clone()),
+            ..self.clone()
+        }
+    }
+}
+
+impl SmartHouse {
+    /// Limits the commands [`execute`](Self::execute) lets through to each device;
+    /// `None`, the default, lets everything through. Devices with a limit of their own
+    /// keep it. Reading a device is never limited, and neither a command of the wrong
+    /// kind nor one the device fails uses up the limit.
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.rate_limits.default = limit;
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limits.default
+    }
+
+    /// Overrides the house limit for one device; `None` exempts it. The override is kept
+    /// by name, so it applies to whatever device of that name is in the room.
+    pub fn set_device_rate_limit(&mut self, room: &str, device: &str, limit: Option<RateLimit>) {
+        let key = (room.to_string(), device.to_string());
+        self.rate_limits.devices.insert(key, limit);
+    }
+
+    /// Puts the device back under the house limit.
+    pub fn clear_device_rate_limit(&mut self, room: &str, device: &str) {
+        let key = (room.to_string(), device.to_string());
+        self.rate_limits.devices.remove(&key);
+    }
+
+    /// The limit that applies to the device, its own or the house's.
+    pub fn device_rate_limit(&self, room: &str, device: &str) -> Option<RateLimit> {
+        let key = (room.to_string(), device.to_string());
+        match self.rate_limits.devices.get(&key) {
+            Some(limit) => *limit,
+            None => self.rate_limits.default,
+        }
+    }
+
+    // Берёт токен из корзины устройства по часам дома
+    pub(crate) fn check_rate(&self, room: &str, device: &str) -> Result<(), CommandError> {
+        let Some(limit) = self.device_rate_limit(room, device) else {
+            return Ok(());
+        };
+        let now = self.now();
+        let mut buckets = self
+            .rate_limits
+            .buckets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let key = (room.to_string(), device.to_string());
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket {
+            level: limit.window.as_nanos() * u128::from(limit.commands.max(1)),
+            at: now,
+        });
+        bucket
+            .take(limit, now)
+            .map_err(|retry_after| CommandError::RateLimited {
+                device: device.to_string(),
+                retry_after,
+            })
+    }
+
+    // Возвращает токен, взятый check_rate, если устройство отказало
+    pub(crate) fn refund_rate(&self, room: &str, device: &str) {
+        let Some(limit) = self.device_rate_limit(room, device) else {
+            return;
+        };
+        let mut buckets = self
+            .rate_limits
+            .buckets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let key = (room.to_string(), device.to_string());
+        if let Some(bucket) = buckets.get_mut(&key) {
+            bucket.refund(limit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{ManualClock, MockDevice},
+        DeviceCommand, DeviceLocation, SmartRoom, SmartSocket,
+    };
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn house() -> (SmartHouse, ManualClock) {
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(SmartSocket::new("relay")).unwrap();
+        hall.plug(SmartSocket::new("lamp")).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+        let clock = ManualClock::default();
+        house.set_clock(clock.clone());
+        house.set_rate_limit(Some(RateLimit::new(2, SECOND)));
+        (house, clock)
+    }
+
+    fn toggle(house: &SmartHouse, device: &str) -> Result<(), CommandError> {
+        let path: DeviceLocation = format!("hall/{device}").parse().unwrap();
+        house.execute(&path, DeviceCommand::TurnOn).map(|_| ())
+    }
+
+    fn limited(device: &str, retry_after: Duration) -> Result<(), CommandError> {
+        Err(CommandError::RateLimited {
+            device: device.to_string(),
+            retry_after,
+        })
+    }
+
+    #[test]
+    fn bucket_refills_with_the_clock() {
+        let (house, clock) = house();
+        toggle(&house, "relay").unwrap();
+        toggle(&house, "relay").unwrap();
+        assert_eq!(toggle(&house, "relay"), limited("relay", SECOND / 2));
+        // у каждого устройства своя корзина
+        toggle(&house, "lamp").unwrap();
+
+        // полтокена за четверть секунды ещё не хватает на команду
+        clock.advance(SECOND / 4);
+        assert_eq!(toggle(&house, "relay"), limited("relay", SECOND / 4));
+        clock.advance(SECOND / 4);
+        toggle(&house, "relay").unwrap();
+        assert_eq!(toggle(&house, "relay"), limited("relay", SECOND / 2));
+
+        // корзина не копит больше своего размера
+        clock.advance(SECOND * 10);
+        toggle(&house, "relay").unwrap();
+        toggle(&house, "relay").unwrap();
+        assert!(toggle(&house, "relay").is_err());
+        assert_eq!(
+            limited("relay", SECOND / 2).unwrap_err().to_string(),
+            "device relay is rate limited, retry in 500ms"
+        );
+    }
+
+    #[test]
+    fn refused_commands_leave_the_bucket_alone() {
+        let (mut house, _) = house();
+        let brightness = DeviceCommand::SetBrightness(50);
+        for _ in 0..5 {
+            assert!(matches!(
+                house.execute(&"hall/relay".parse().unwrap(), brightness),
+                Err(CommandError::WrongKind { .. })
+            ));
+        }
+        toggle(&house, "relay").unwrap();
+        toggle(&house, "relay").unwrap();
+        assert_eq!(toggle(&house, "relay"), limited("relay", SECOND / 2));
+
+        // отказавшее устройство токен получает обратно
+        let flaky = MockDevice::builder("flaky").fail_next(3).build();
+        house.plug("hall", flaky).unwrap();
+        for _ in 0..3 {
+            assert!(matches!(
+                toggle(&house, "flaky"),
+                Err(CommandError::Failed { .. })
+            ));
+        }
+        toggle(&house, "flaky").unwrap();
+        toggle(&house, "flaky").unwrap();
+        assert_eq!(toggle(&house, "flaky"), limited("flaky", SECOND / 2));
+    }
+
+    #[test]
+    fn devices_override_the_house_limit() {
+        let (mut house, _) = house();
+        house.set_device_rate_limit("hall", "relay", Some(RateLimit::new(1, SECOND * 60)));
+        house.set_device_rate_limit("hall", "lamp", None);
+        assert_eq!(
+            house.device_rate_limit("hall", "relay"),
+            Some(RateLimit::new(1, SECOND * 60))
+        );
+
+        toggle(&house, "relay").unwrap();
+        assert_eq!(toggle(&house, "relay"), limited("relay", SECOND * 60));
+        for _ in 0..10 {
+            toggle(&house, "lamp").unwrap();
+        }
+        // чтение не тратит токенов
+        let relay = house.room("hall").unwrap().device("relay").unwrap();
+        assert!(relay.status().is_some());
+
+        house.clear_device_rate_limit("hall", "lamp");
+        assert_eq!(house.device_rate_limit("hall", "lamp"), house.rate_limit());
+        house.set_rate_limit(None);
+        assert_eq!(house.device_rate_limit("hall", "lamp"), None);
+        assert!(house.clone().device_rate_limit("hall", "relay").is_some());
+    }
+}
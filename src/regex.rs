@@ -0,0 +1,448 @@
+//! A small backtracking regular expression engine shaped like the `regex` crate's
+//! [`Regex`], enough for checking device naming conventions.
+//!
+//! Supported: literals, `.`, classes such as `[a-z_]` and `[^0-9]`, `\d \w \s` and their
+//! negations, `^` and `$`, groups `( )` and `(?: )` with `|`, the quantifiers `* + ?
+//! {n} {n,} {n,m}` and their lazy forms, and a leading `(?i)` for ignoring case.
+//! Like `regex::Regex::is_match`, a match may start anywhere unless anchored.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// Byte offset in the pattern.
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "regex parse error at byte {}: {}",
+            self.offset, self.message
+        )
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+        ignore_case: bool,
+    },
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+// Верхняя граница для {n,m}: имена устройств короче
+const MAX_REPEAT: u32 = 1000;
+
+struct Parser<'a> {
+    pattern: &'a str,
+    pos: usize,
+    ignore_case: bool,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> Error {
+        Error {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.pattern[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let found = self.pattern[self.pos..].starts_with(s);
+        if found {
+            self.pos += s.len();
+        }
+        found
+    }
+
+    // альтернативы до `)` или конца шаблона
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, Error> {
+        let mut alternatives = Vec::from([Vec::new()]);
+        while let Some(c) = self.peek() {
+            match c {
+                ')' => break,
+                '|' => {
+                    self.bump();
+                    alternatives.push(Vec::new());
+                }
+                _ => {
+                    let atom = self.atom()?;
+                    let atom = self.quantified(atom)?;
+                    alternatives.last_mut().unwrap().push(atom);
+                }
+            }
+        }
+        Ok(alternatives)
+    }
+
+    fn atom(&mut self) -> Result<Node, Error> {
+        let start = self.pos;
+        Ok(match self.bump().unwrap() {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                self.eat("?:");
+                let group = self.alternatives()?;
+                if !self.eat(")") {
+                    self.pos = start;
+                    return Err(self.error("unclosed group"));
+                }
+                Node::Group(group)
+            }
+            '[' => self.class()?,
+            '\\' => self.escape()?,
+            '*' | '+' | '?' | '{' => {
+                self.pos = start;
+                return Err(self.error("quantifier without anything to repeat"));
+            }
+            c => self.char(c),
+        })
+    }
+
+    fn char(&self, c: char) -> Node {
+        match self.ignore_case && c.to_lowercase().ne(c.to_uppercase()) {
+            true => Node::Class {
+                ranges: Vec::from([(c, c)]),
+                negated: false,
+                ignore_case: true,
+            },
+            false => Node::Char(c),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Node, Error> {
+        let class = |ranges: &[(char, char)], negated| Node::Class {
+            ranges: ranges.to_vec(),
+            negated,
+            ignore_case: false,
+        };
+        Ok(match self.bump() {
+            Some('d') => class(DIGIT, false),
+            Some('D') => class(DIGIT, true),
+            Some('w') => class(WORD, false),
+            Some('W') => class(WORD, true),
+            Some('s') => class(SPACE, false),
+            Some('S') => class(SPACE, true),
+            Some('n') => Node::Char('\n'),
+            Some('t') => Node::Char('\t'),
+            Some(c) if c.is_ascii_punctuation() || c == ' ' => Node::Char(c),
+            Some(_) => return Err(self.error("unknown escape")),
+            None => return Err(self.error("dangling \\")),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, Error> {
+        let start = self.pos - 1;
+        let negated = self.eat("^");
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.bump() {
+                Some(']') if !first => break,
+                Some('\\') => match self.escape()? {
+                    Node::Char(c) => c,
+                    Node::Class {
+                        ranges: inner,
+                        negated: false,
+                        ..
+                    } => {
+                        ranges.extend(inner);
+                        first = false;
+                        continue;
+                    }
+                    _ => return Err(self.error("negated escape inside a class")),
+                },
+                Some(c) => c,
+                None => {
+                    self.pos = start;
+                    return Err(self.error("unclosed class"));
+                }
+            };
+            first = false;
+            let end = match self.pattern[self.pos..].starts_with('-')
+                && !self.pattern[self.pos..].starts_with("-]")
+            {
+                true => {
+                    self.bump();
+                    self.bump().ok_or_else(|| self.error("unclosed class"))?
+                }
+                false => c,
+            };
+            if end < c {
+                return Err(self.error("range out of order"));
+            }
+            ranges.push((c, end));
+        }
+        Ok(Node::Class {
+            ranges,
+            negated,
+            ignore_case: self.ignore_case,
+        })
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let digits = self.pattern[self.pos..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        let value = self.pattern[self.pos..self.pos + digits].parse().ok()?;
+        self.pos += digits;
+        Some(value)
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, Error> {
+        let (min, max) = match self.peek() {
+            Some(c @ ('*' | '+' | '?')) => {
+                self.bump();
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            Some('{') => {
+                let start = self.pos;
+                self.bump();
+                let min = self
+                    .number()
+                    .ok_or_else(|| self.error("expected a number"))?;
+                let max = match self.eat(",") {
+                    true => self.number(),
+                    false => Some(min),
+                };
+                if !self.eat("}") {
+                    return Err(self.error("unclosed repetition"));
+                }
+                if min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT || max < min) {
+                    self.pos = start;
+                    return Err(self.error("invalid repetition count"));
+                }
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+        if matches!(atom, Node::Start | Node::End) {
+            return Err(self.error("anchors cannot be repeated"));
+        }
+        let greedy = !self.eat("?");
+        Ok(Node::Repeat {
+            node: Box::new(atom),
+            min,
+            max,
+            greedy,
+        })
+    }
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+/// A compiled pattern. Compile once and reuse it: [`Regex::new`] does all the parsing.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    pattern: String,
+    nodes: Vec<Vec<Node>>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            pattern,
+            pos: 0,
+            ignore_case: false,
+        };
+        parser.ignore_case = parser.eat("(?i)");
+        let nodes = parser.alternatives()?;
+        if parser.pos < pattern.len() {
+            return Err(parser.error("unopened group"));
+        }
+
+        Ok(Self {
+            pattern: pattern.into(),
+            nodes,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let top = [Node::Group(self.nodes.clone())];
+        (0..=text.len()).any(|start| matches(&top, &text, start, &mut |_| true))
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl core::str::FromStr for Regex {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::new(s)
+    }
+}
+
+fn single(node: &Node, c: char) -> bool {
+    match node {
+        Node::Char(expected) => *expected == c,
+        Node::Any => c != '\n',
+        Node::Class {
+            ranges,
+            negated,
+            ignore_case,
+        } => {
+            let hit = |c: char| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+            let found = hit(c)
+                || (*ignore_case && (c.to_lowercase().any(hit) || c.to_uppercase().any(hit)));
+            found != *negated
+        }
+        _ => false,
+    }
+}
+
+// Сопоставляет `seq` с позиции `pos` и передаёт конец совпадения в `then`
+fn matches(seq: &[Node], text: &[char], pos: usize, then: &mut dyn FnMut(usize) -> bool) -> bool {
+    let Some((node, rest)) = seq.split_first() else {
+        return then(pos);
+    };
+    match node {
+        Node::Start => pos == 0 && matches(rest, text, pos, then),
+        Node::End => pos == text.len() && matches(rest, text, pos, then),
+        Node::Group(alternatives) => alternatives.iter().any(|alternative| {
+            matches(alternative, text, pos, &mut |end| {
+                matches(rest, text, end, then)
+            })
+        }),
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => repeat(node, *min, *max, *greedy, 0, rest, text, pos, then),
+        node => pos < text.len() && single(node, text[pos]) && matches(rest, text, pos + 1, then),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn repeat(
+    node: &Node,
+    min: u32,
+    max: Option<u32>,
+    greedy: bool,
+    count: u32,
+    rest: &[Node],
+    text: &[char],
+    pos: usize,
+    then: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    let more = |then: &mut dyn FnMut(usize) -> bool| {
+        max.is_none_or(|max| count < max)
+            && matches(core::slice::from_ref(node), text, pos, &mut |end| {
+                // пустое повторение ничего не продвигает и зациклило бы разбор
+                (end > pos || count < min)
+                    && repeat(node, min, max, greedy, count + 1, rest, text, end, then)
+            })
+    };
+    let done = count >= min;
+    match greedy {
+        true => more(then) || (done && matches(rest, text, pos, then)),
+        false => (done && matches(rest, text, pos, then)) || more(then),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn re(pattern: &str) -> Regex {
+        Regex::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn matching() {
+        assert!(re("").is_match(""));
+        assert!(re("ket").is_match("kettle"));
+        assert!(!re("^ket$").is_match("kettle"));
+        assert!(re(r"^socket-\d+$").is_match("socket-12"));
+        assert!(!re(r"^socket-\d+$").is_match("socket-"));
+        assert!(re(r"^[a-z]+(-[a-z0-9]+)*$").is_match("socket-kitchen-1"));
+        assert!(!re(r"^[a-z]+(-[a-z0-9]+)*$").is_match("Socket kitchen"));
+        assert!(re("^(?:socket|thermo)-.{2,3}$").is_match("thermo-abc"));
+        assert!(!re("^(?:socket|thermo)-.{2,3}$").is_match("thermo-abcd"));
+        assert!(re("^a{3}$").is_match("aaa"));
+        assert!(re("^a{2,}$").is_match("aaaaa"));
+        assert!(re(r"^[^\s]+$").is_match("no_spaces"));
+        assert!(!re(r"^\S+$").is_match("one space"));
+        assert!(re("^(a|ab)(c|bcd)$").is_match("abcd"));
+        assert!(re("^a*?b$").is_match("aaab"));
+        assert!(re("^(a*)*$").is_match("aaaa"));
+        assert!(!re("^(a*)*b$").is_match("aaaaaaaaaa"));
+        assert!(re("^x[-a]$").is_match("x-"));
+        assert!(re("^[]a]$").is_match("]"));
+        assert!(re(r"^\.$").is_match("."));
+        assert!(!re(r"^\.$").is_match("a"));
+        assert!(re("^комната-?$").is_match("комната"));
+    }
+
+    #[test]
+    fn ignore_case() {
+        assert!(re("(?i)^THERMO-[a-c]$").is_match("thermo-B"));
+        assert!(!re("^THERMO$").is_match("thermo"));
+        assert!(re("(?i)^РОЗЕТКА$").is_match("розетка"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |pattern: &str| Regex::new(pattern).unwrap_err();
+        assert_eq!(error("a(b").offset, 1);
+        assert_eq!(error("a)").message, "unopened group");
+        assert_eq!(error("[a-").message, "unclosed class");
+        assert_eq!(error("[z-a]").message, "range out of order");
+        assert_eq!(error("*a").message, "quantifier without anything to repeat");
+        assert_eq!(error("a{2,1}").message, "invalid repetition count");
+        assert_eq!(error("a{9999}").message, "invalid repetition count");
+        assert_eq!(error(r"\q").message, "unknown escape");
+        assert_eq!(error("\\").message, "dangling \\");
+        assert_eq!(
+            error("a(b").to_string(),
+            "regex parse error at byte 1: unclosed group"
+        );
+        assert_eq!(re("a+").as_str(), "a+");
+    }
+}
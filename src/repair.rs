@@ -0,0 +1,378 @@
+use alloc::{
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{DeviceLocation, SmartHouse, ValidationIssue};
+
+/// What [`SmartHouse::repair`] does with a second room or device of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepairPolicy {
+    /// The first one stays, later ones are removed.
+    #[default]
+    DropDuplicates,
+    /// Later rooms get a numeric suffix, `hall-2`, `hall-3` and so on. Devices name
+    /// themselves and cannot be renamed, so duplicate devices are still removed.
+    RenameDuplicates,
+    /// Nothing is changed if there are duplicates.
+    Fail,
+}
+
+/// One change made by [`SmartHouse::repair`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepairChange {
+    RoomDropped(String),
+    RoomRenamed {
+        from: String,
+        to: String,
+    },
+    DeviceDropped {
+        room: String,
+        device: String,
+    },
+    /// The room got handles of its own; the ones it issued before are foreign now.
+    HandlesRenewed(String),
+    /// A name index was rebuilt; `room` is `None` for the house's index of rooms.
+    Reindexed {
+        room: Option<String>,
+    },
+    PositionCleared {
+        room: String,
+        device: String,
+    },
+    LabelCleared {
+        room: String,
+        device: String,
+    },
+    MemberDropped {
+        group: String,
+        member: DeviceLocation,
+    },
+    DoorRemoved {
+        rooms: (String, String),
+    },
+}
+
+impl fmt::Display for RepairChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairChange::RoomDropped(room) => write!(f, "dropped room {room}"),
+            RepairChange::RoomRenamed { from, to } => write!(f, "renamed room {from} to {to}"),
+            RepairChange::DeviceDropped { room, device } => {
+                write!(f, "dropped device {device} from room {room}")
+            }
+            RepairChange::HandlesRenewed(room) => write!(f, "renewed handles of room {room}"),
+            RepairChange::Reindexed { room: None } => f.write_str("reindexed rooms"),
+            RepairChange::Reindexed { room: Some(room) } => write!(f, "reindexed room {room}"),
+            RepairChange::PositionCleared { room, device } => {
+                write!(f, "cleared position of {device} in room {room}")
+            }
+            RepairChange::LabelCleared { room, device } => {
+                write!(f, "cleared label of {device} in room {room}")
+            }
+            RepairChange::MemberDropped { group, member } => {
+                write!(f, "dropped {member} from group {group}")
+            }
+            RepairChange::DoorRemoved { rooms: (a, b) } => {
+                write!(f, "removed door between {a} and {b}")
+            }
+        }
+    }
+}
+
+/// Every change [`SmartHouse::repair`] made, in the order it made them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RepairReport {
+    pub changes: Vec<RepairChange>,
+}
+
+impl RepairReport {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+impl SmartHouse {
+    /// Fixes what [`validate`](Self::validate) finds, other than blank names: duplicates
+    /// are handled by `policy`, indexes are rebuilt, and positions, labels, group members
+    /// and doors that refer to nothing are removed. Repairing a repaired house changes
+    /// nothing.
+    ///
+    /// With [`RepairPolicy::Fail`], duplicates are returned as the error and the house is
+    /// left as it was. Subscribers are not notified and nothing is recorded for undo.
+    pub fn repair(&mut self, policy: RepairPolicy) -> Result<RepairReport, Vec<ValidationIssue>> {
+        if policy == RepairPolicy::Fail {
+            let duplicates: Vec<_> = self
+                .validate()
+                .err()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|issue| {
+                    matches!(
+                        issue,
+                        ValidationIssue::DuplicateRoom(_) | ValidationIssue::DuplicateDevice { .. }
+                    )
+                })
+                .collect();
+            if !duplicates.is_empty() {
+                return Err(duplicates);
+            }
+        }
+
+        let mut changes = Vec::new();
+        self.repair_rooms(policy, &mut changes);
+        for room in &mut self.rooms {
+            let name = room.name.clone();
+            for device in room.drop_duplicates() {
+                changes.push(RepairChange::DeviceDropped {
+                    room: name.clone(),
+                    device,
+                });
+            }
+            if room.reindex() {
+                changes.push(RepairChange::Reindexed {
+                    room: Some(name.clone()),
+                });
+            }
+
+            let devices: BTreeSet<String> = room.plugged_names().map(str::to_string).collect();
+            let bounds = room.bounds();
+            let stray: Vec<String> = room
+                .geometry
+                .positions
+                .iter()
+                .filter(|(device, position)| {
+                    !devices.contains(*device) || bounds.is_some_and(|b| !b.contains(**position))
+                })
+                .map(|(device, _)| device.clone())
+                .collect();
+            for device in stray {
+                room.clear_position(&device);
+                changes.push(RepairChange::PositionCleared {
+                    room: name.clone(),
+                    device,
+                });
+            }
+            let stray: Vec<String> = room
+                .device_labels
+                .keys()
+                .filter(|device| !devices.contains(*device))
+                .cloned()
+                .collect();
+            for device in stray {
+                room.device_labels.remove(&device);
+                changes.push(RepairChange::LabelCleared {
+                    room: name.clone(),
+                    device,
+                });
+            }
+        }
+
+        let mut groups = core::mem::take(&mut self.groups);
+        for group in &mut groups {
+            group.members.retain(|member| {
+                let found = self.locate(member).is_ok();
+                if !found {
+                    changes.push(RepairChange::MemberDropped {
+                        group: group.name.clone(),
+                        member: member.clone(),
+                    });
+                }
+                found
+            });
+        }
+        self.groups = groups;
+        let dangling: Vec<_> = self
+            .doors
+            .iter()
+            .filter(|(a, b)| self.room(a).is_none() || self.room(b).is_none())
+            .cloned()
+            .collect();
+        for rooms in dangling {
+            self.doors.remove(&rooms);
+            changes.push(RepairChange::DoorRemoved { rooms });
+        }
+        Ok(RepairReport { changes })
+    }
+
+    fn repair_rooms(&mut self, policy: RepairPolicy, changes: &mut Vec<RepairChange>) {
+        let mut names: BTreeSet<String> = BTreeSet::new();
+        let mut i = 0;
+        while i < self.rooms.len() {
+            let name = self.rooms[i].name.clone();
+            if names.insert(name.clone()) {
+                i += 1;
+                continue;
+            }
+            if policy == RepairPolicy::RenameDuplicates {
+                let taken = |to: &String| {
+                    names.contains(to) || self.rooms.iter().any(|room| room.name == *to)
+                };
+                let to = (2..)
+                    .map(|n| format!("{name}-{n}"))
+                    .find(|to| !taken(to))
+                    .unwrap_or_default();
+                names.insert(to.clone());
+                self.rooms[i].name = to.clone();
+                changes.push(RepairChange::RoomRenamed { from: name, to });
+                i += 1;
+            } else {
+                self.rooms.remove(i);
+                changes.push(RepairChange::RoomDropped(name));
+            }
+        }
+
+        let mut owners = BTreeSet::new();
+        for room in &mut self.rooms {
+            if !owners.insert(room.owner()) {
+                room.renew_owner();
+                changes.push(RepairChange::HandlesRenewed(room.name.clone()));
+            }
+        }
+        if self.reindex() {
+            changes.push(RepairChange::Reindexed { room: None });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::{Named, Pluggable, Position, SmartRoom, SmartSocket};
+
+    // Устройство r1, которое уже в комнате называет себя s1
+    #[derive(Default)]
+    struct Renamable(AtomicBool);
+
+    impl Named for Renamable {
+        fn name(&self) -> &str {
+            match self.0.load(Ordering::SeqCst) {
+                true => "s1",
+                false => "r1",
+            }
+        }
+    }
+
+    impl Pluggable for Renamable {
+        fn boxed_clone(&self) -> Arc<dyn Pluggable> {
+            Arc::new(Renamable(AtomicBool::new(self.0.load(Ordering::SeqCst))))
+        }
+    }
+
+    // Старый дом: две комнаты hall и повторяющееся устройство s1
+    fn legacy() -> SmartHouse {
+        let renamable = Arc::new(Renamable::default());
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(SmartSocket::new("s1")).unwrap();
+        hall.plug(renamable.clone()).unwrap();
+        hall.set_position("r1", Position::new(1.0, 1.0)).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+        house.add(SmartRoom::new("kitchen")).unwrap();
+        house.connect_rooms("hall", "kitchen").unwrap();
+        house.create_group("all").unwrap();
+        house
+            .add_to_group("all", &"hall/r1".parse().unwrap())
+            .unwrap();
+        renamable.0.store(true, Ordering::SeqCst);
+        let copy = house.rooms[0].clone();
+        house.rooms.push(copy);
+        house
+    }
+
+    fn changes(report: &RepairReport) -> Vec<String> {
+        report.changes.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn dropping_duplicates() {
+        let mut house = legacy();
+        assert!(house.validate().is_err());
+
+        let report = house.repair(RepairPolicy::DropDuplicates).unwrap();
+        assert_eq!(
+            changes(&report),
+            [
+                "dropped room hall",
+                "dropped device s1 from room hall",
+                "reindexed room hall",
+                "cleared position of r1 in room hall",
+                "dropped hall/r1 from group all",
+            ]
+        );
+        assert_eq!(house.validate(), Ok(()));
+        assert_eq!(house.room("hall").unwrap().devices(), ["s1"]);
+        assert!(house
+            .repair(RepairPolicy::DropDuplicates)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn renaming_duplicate_rooms() {
+        let mut house = legacy();
+        house.add(SmartRoom::new("hall-2")).unwrap();
+
+        let report = house.repair(RepairPolicy::RenameDuplicates).unwrap();
+        assert_eq!(
+            report.changes[..2],
+            [
+                RepairChange::RoomRenamed {
+                    from: "hall".to_string(),
+                    to: "hall-3".to_string(),
+                },
+                RepairChange::Reindexed { room: None },
+            ]
+        );
+        assert_eq!(house.validate(), Ok(()));
+        assert_eq!(house.room("hall-3").unwrap().devices(), ["s1"]);
+        assert!(house
+            .repair(RepairPolicy::RenameDuplicates)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn failing_leaves_the_house_alone() {
+        let mut house = legacy();
+        let issues = house.repair(RepairPolicy::Fail).unwrap_err();
+        assert_eq!(
+            issues,
+            [
+                ValidationIssue::DuplicateRoom("hall".to_string()),
+                ValidationIssue::DuplicateDevice {
+                    room: "hall".to_string(),
+                    device: "s1".to_string(),
+                },
+                ValidationIssue::DuplicateDevice {
+                    room: "hall".to_string(),
+                    device: "s1".to_string(),
+                },
+            ]
+        );
+        assert_eq!(house.rooms.len(), 3);
+
+        // без повторов Fail чинит остальное, как и другие политики
+        house.repair(RepairPolicy::DropDuplicates).unwrap();
+        house
+            .doors
+            .insert(("attic".to_string(), "hall".to_string()));
+        let report = house.repair(RepairPolicy::Fail).unwrap();
+        assert_eq!(changes(&report), ["removed door between attic and hall"]);
+    }
+}
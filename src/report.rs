@@ -0,0 +1,377 @@
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::{error::Error, fmt::Write};
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::{
+    HouseMode, Labeled, Named, Pluggable, SmartHouse, SmartRoom, SmartSocket, SmartThermometer,
+};
+
+pub trait Reportable {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>>;
+}
+
+/// The whole house as a tree of rooms and device names.
+#[derive(Debug)]
+pub struct HouseReport;
+
+impl Reportable for HouseReport {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        ReportBuilder::new().run(house)
+    }
+}
+
+#[derive(Debug)]
+pub struct OwningDeviceInfoProvider {
+    pub socket: SmartSocket,
+}
+
+impl Reportable for OwningDeviceInfoProvider {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        let (room, _) = house
+            .find_device(self.socket.name())
+            .ok_or("Device not found")?;
+        let mut out = String::new();
+        write!(out, "{:#} {:#} {:#}", house, room, &self.socket)?;
+
+        Ok(out)
+    }
+}
+
+#[derive(Debug)]
+pub struct BorrowingDeviceInfoProvider<'a, 'b> {
+    pub socket: &'a SmartSocket,
+    pub thermo: &'b SmartThermometer,
+}
+
+impl Reportable for BorrowingDeviceInfoProvider<'_, '_> {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        let mut plugged_socket_room = None;
+        let mut plugged_thermo_room = None;
+
+        for room in house.get_rooms().iter() {
+            if room.is_connected(self.socket) {
+                plugged_socket_room = Some(room);
+            }
+
+            if room.is_connected(self.thermo) {
+                plugged_thermo_room = Some(room);
+            }
+        }
+
+        if plugged_thermo_room.is_none() && plugged_socket_room.is_none() {
+            return Err("Devices not found".into());
+        }
+
+        let mut out = String::new();
+
+        if let (Some(plugged_socket_room), Some(plugged_thermo_room)) =
+            (plugged_socket_room, plugged_thermo_room)
+        {
+            write!(
+                out,
+                "{:#} {:#} {:#} ",
+                house, plugged_socket_room, self.socket
+            )?;
+            if plugged_socket_room.name() != plugged_thermo_room.name() {
+                write!(out, "{:#} ", plugged_thermo_room)?;
+            }
+            write!(out, "{:#}", self.thermo)?;
+        } else {
+            match plugged_socket_room {
+                Some(room) => write!(out, "{:#} {:#} {:#}", house, room, self.socket)?,
+                None => write!(out, "not found {:#}", self.socket)?,
+            }
+
+            match plugged_thermo_room {
+                Some(room) => write!(out, "\n {:#} {:#} {:#}", house, room, self.thermo)?,
+                None => write!(out, " not found {:#}", self.thermo)?,
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// Rooms with a device count.
+    Summary,
+    /// Rooms and device names.
+    #[default]
+    Normal,
+    /// Device names with their current status.
+    Detailed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// The arrow tree used by every other report.
+    #[default]
+    Text,
+    Markdown,
+}
+
+/// Options for a whole-house report.
+///
+/// The default options produce exactly the [`HouseReport`](crate::HouseReport) tree.
+#[derive(Debug, Clone, Default)]
+pub struct ReportBuilder {
+    sorted: bool,
+    verbosity: Verbosity,
+    skip_empty_rooms: bool,
+    include_disabled: bool,
+    format: Format,
+}
+
+impl ReportBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lists rooms and devices by name instead of in the order they were added.
+    pub fn sorted(mut self) -> Self {
+        self.sorted = true;
+        self
+    }
+
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    pub fn skip_empty_rooms(mut self) -> Self {
+        self.skip_empty_rooms = true;
+        self
+    }
+
+    /// Lists [disabled](SmartRoom::disable_device) devices too, marked as such in a
+    /// detailed report; they are left out by default.
+    pub fn include_disabled(mut self) -> Self {
+        self.include_disabled = true;
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn run(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        #[cfg(feature = "tracing")]
+        let span = crate::tracing::Span::new("render", &[("rooms", &house.get_rooms().len())]);
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        let mut out = String::new();
+        self.render(house, &mut out)?;
+        Ok(out)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, house: &SmartHouse, out: &mut impl io::Write) -> io::Result<()> {
+        let mut report = String::new();
+        self.render(house, &mut report)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        out.write_all(report.as_bytes())
+    }
+
+    fn rooms<'a>(&self, house: &'a SmartHouse) -> Vec<(&'a SmartRoom, Vec<Arc<dyn Pluggable>>)> {
+        let mut rooms: Vec<_> = house
+            .get_rooms()
+            .iter()
+            .map(|room| {
+                let devices = room
+                    .live_devices()
+                    .filter(|device| self.include_disabled || room.is_enabled(device.name()))
+                    .collect::<Vec<_>>();
+                (room, devices)
+            })
+            .filter(|(_, devices)| !(self.skip_empty_rooms && devices.is_empty()))
+            .collect();
+
+        if self.sorted {
+            rooms.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+            for (_, devices) in rooms.iter_mut() {
+                devices.sort_by(|a, b| a.name().cmp(b.name()));
+            }
+        }
+        rooms
+    }
+
+    // подписи показывает только подробный отчёт
+    fn label<'a>(&self, label: Option<&'a str>) -> Option<&'a str> {
+        label.filter(|_| self.verbosity == Verbosity::Detailed)
+    }
+
+    fn render(&self, house: &SmartHouse, out: &mut String) -> core::fmt::Result {
+        match (self.format, self.label(house.label())) {
+            (Format::Text, None) => write!(out, "{:#}", house)?,
+            (Format::Text, Some(label)) => writeln!(out, "-> House: {} - {}", house.name, label)?,
+            (Format::Markdown, None) => writeln!(out, "# House: {}", house.name)?,
+            (Format::Markdown, Some(label)) => {
+                writeln!(out, "# House: {} - {}", house.name, label)?
+            }
+        }
+        // режим отличный от обычного виден уже в кратком отчёте
+        if house.mode() != HouseMode::Home {
+            match self.format {
+                Format::Text => writeln!(out, "--> Mode: {}", house.mode())?,
+                Format::Markdown => writeln!(out, "Mode: {}", house.mode())?,
+            }
+        }
+
+        for (room, devices) in self.rooms(house) {
+            match (self.format, self.verbosity) {
+                (Format::Text, Verbosity::Summary) => {
+                    writeln!(out, "--> Room: {} ({} devices)", room.name, devices.len())?
+                }
+                (Format::Text, _) => match self.label(room.label()) {
+                    Some(label) => writeln!(out, "--> Room: {} - {}", room.name, label)?,
+                    None => write!(out, "{:#}", room)?,
+                },
+                (Format::Markdown, Verbosity::Summary) => {
+                    writeln!(out, "## Room: {} ({} devices)", room.name, devices.len())?
+                }
+                (Format::Markdown, _) => match self.label(room.label()) {
+                    Some(label) => writeln!(out, "## Room: {} - {}", room.name, label)?,
+                    None => writeln!(out, "## Room: {}", room.name)?,
+                },
+            }
+            if self.verbosity == Verbosity::Summary {
+                continue;
+            }
+
+            for device in devices {
+                let prefix = match self.format {
+                    Format::Text => "----> Device: ",
+                    Format::Markdown => "- ",
+                };
+                write!(out, "{}{}", prefix, device.name())?;
+                if let Some(label) = self.label(room.device_label(device.name())) {
+                    write!(out, " - {}", label)?;
+                }
+                if self.verbosity == Verbosity::Detailed {
+                    if let Some(status) = device.status() {
+                        write!(out, " ({})", status)?;
+                    }
+                    if !room.is_enabled(device.name()) {
+                        write!(out, " [disabled]")?;
+                    }
+                }
+                writeln!(out)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Reportable for ReportBuilder {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        self.run(house)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::house_with_state;
+
+    // Розетка s1 переезжает в конец lust, а пустая hall встаёт последней, чтобы
+    // сортировке было что переставлять
+    fn house() -> SmartHouse {
+        let mut house = house_with_state();
+        house.move_device("s1", "limb", "lust").unwrap();
+        house.add(SmartRoom::new("hall")).unwrap();
+        house
+    }
+
+    #[test]
+    fn labels_only_in_detailed_reports() {
+        let mut house = house();
+        house.set_label(Some("Summer house".into()));
+        let lust = house.room_mut("lust").unwrap();
+        lust.set_label(Some("Upstairs".into()));
+        lust.set_device_label("s1", Some("Kettle".into())).unwrap();
+
+        assert_eq!(
+            house.create_report(HouseReport).unwrap(),
+            self::house().create_report(HouseReport).unwrap()
+        );
+        let detailed = ReportBuilder::new()
+            .verbosity(Verbosity::Detailed)
+            .skip_empty_rooms();
+        assert_eq!(
+            detailed.run(&house).unwrap(),
+            "-> House: hell - Summer house\n\
+             --> Room: limb\n\
+             ----> Device: t1 (21.5 °C)\n\
+             --> Room: lust - Upstairs\n\
+             ----> Device: s2 (off, 0.0 W)\n\
+             ----> Device: s1 - Kettle (on, 60.0 W)\n"
+        );
+    }
+
+    #[test]
+    fn default_is_house_report() {
+        assert_eq!(
+            ReportBuilder::new().run(&house()).unwrap(),
+            house().create_report(HouseReport).unwrap()
+        );
+    }
+
+    // Ожидаемые отчёты лежат в tests/golden, UPDATE_GOLDEN=1 их перезаписывает
+    #[cfg(feature = "std")]
+    mod golden {
+        use super::*;
+        use crate::testing::assert_report_matches;
+
+        #[test]
+        fn house_report() {
+            assert_report_matches(HouseReport, &house(), "tests/golden/house_report.txt");
+        }
+
+        #[test]
+        fn sorted_detailed_markdown() {
+            let builder = ReportBuilder::new()
+                .sorted()
+                .verbosity(Verbosity::Detailed)
+                .skip_empty_rooms()
+                .format(Format::Markdown);
+            assert_report_matches(builder, &house(), "tests/golden/sorted_detailed.md");
+        }
+
+        #[test]
+        fn summary_text() {
+            let builder = ReportBuilder::new().verbosity(Verbosity::Summary);
+            assert_report_matches(builder, &house(), "tests/golden/summary.txt");
+        }
+
+        #[test]
+        fn detailed_text_to_writer() {
+            let builder = ReportBuilder::new()
+                .verbosity(Verbosity::Detailed)
+                .skip_empty_rooms();
+            let mut out = Vec::new();
+            builder.write_to(&house(), &mut out).unwrap();
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                builder.run(&house()).unwrap()
+            );
+            assert_report_matches(builder, &house(), "tests/golden/detailed.txt");
+        }
+
+        #[test]
+        fn summary_markdown_through_create_report() {
+            let builder = ReportBuilder::new()
+                .verbosity(Verbosity::Summary)
+                .format(Format::Markdown)
+                .sorted();
+            assert_eq!(
+                house().create_report(builder.clone()).unwrap(),
+                builder.run(&house()).unwrap()
+            );
+            assert_report_matches(builder, &house(), "tests/golden/summary.md");
+        }
+    }
+}
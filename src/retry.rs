@@ -0,0 +1,289 @@
+use std::{error::Error, io, thread, time::Duration};
+
+use crate::{
+    net::NetError, protocol::ProtocolError, BatteryPowered, CapabilityError, DeviceInfo,
+    DeviceKind, Measurable, Named, Pluggable, Switchable,
+};
+
+/// How often [`Retrying`] tries a command before giving up, and how long it waits
+/// between tries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries in all, the first one included; zero is taken as one.
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(attempts: u32, backoff: Duration) -> Self {
+        Self { attempts, backoff }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(100))
+    }
+}
+
+// Сбои связи, которые могут пройти сами
+pub(crate) fn is_transient_io(err: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(
+        err.kind(),
+        ConnectionRefused
+            | ConnectionReset
+            | ConnectionAborted
+            | NotConnected
+            | BrokenPipe
+            | TimedOut
+            | WouldBlock
+            | Interrupted
+            | UnexpectedEof
+    )
+}
+
+/// Whether a device error may go away if the command is tried again, such as a dropped
+/// connection. Errors of unknown types are taken as permanent.
+pub fn is_transient(err: &(dyn Error + 'static)) -> bool {
+    if let Some(err) = err.downcast_ref::<NetError>() {
+        err.is_transient()
+    } else if let Some(err) = err.downcast_ref::<ProtocolError>() {
+        err.is_transient()
+    } else if let Some(err) = err.downcast_ref::<io::Error>() {
+        is_transient_io(err)
+    } else {
+        false
+    }
+}
+
+/// A device whose `turn_on` and `turn_off` are tried again after a
+/// [transient](is_transient) error, by `policy`. Reads and other errors go through once.
+///
+/// The house sees the wrapper, not `D`, so anything that looks for a built-in device
+/// type, such as [`SetLoad`](crate::DeviceCommand::SetLoad), does not find it. Tags are
+/// passed through.
+#[derive(Debug, Clone)]
+pub struct Retrying<D> {
+    device: D,
+    policy: RetryPolicy,
+}
+
+impl<D> Retrying<D> {
+    pub fn new(device: D, policy: RetryPolicy) -> Self {
+        Self { device, policy }
+    }
+
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    pub fn policy(&self) -> RetryPolicy {
+        self.policy
+    }
+
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    fn retry(
+        &self,
+        mut op: impl FnMut() -> Result<(), CapabilityError>,
+    ) -> Result<(), CapabilityError> {
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Err(err) if attempt < self.policy.attempts && is_transient(&*err) => {
+                    attempt += 1;
+                    thread::sleep(self.policy.backoff);
+                }
+                done => return done,
+            }
+        }
+    }
+}
+
+impl<D: Named> Named for Retrying<D> {
+    fn name(&self) -> &str {
+        self.device.name()
+    }
+}
+
+impl<D: Pluggable> Switchable for Retrying<D> {
+    fn turn_on(&self) -> Result<(), CapabilityError> {
+        let switchable = self.device.as_switchable().ok_or("not switchable")?;
+        self.retry(|| switchable.turn_on())
+    }
+
+    fn turn_off(&self) -> Result<(), CapabilityError> {
+        let switchable = self.device.as_switchable().ok_or("not switchable")?;
+        self.retry(|| switchable.turn_off())
+    }
+
+    fn is_on(&self) -> Result<bool, CapabilityError> {
+        self.device.as_switchable().ok_or("not switchable")?.is_on()
+    }
+}
+
+impl<D: Pluggable + Clone> Pluggable for Retrying<D> {
+    crate::boxed_clone!();
+
+    fn status(&self) -> Option<String> {
+        self.device.status()
+    }
+
+    fn kind(&self) -> Option<DeviceKind> {
+        self.device.kind()
+    }
+
+    fn state_stamp(&self) -> u64 {
+        self.device.state_stamp()
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.device.has_tag(tag)
+    }
+
+    fn as_switchable(&self) -> Option<&dyn Switchable> {
+        self.device.as_switchable().map(|_| self as &dyn Switchable)
+    }
+
+    fn as_measurable(&self) -> Option<&dyn Measurable> {
+        self.device.as_measurable()
+    }
+
+    fn as_battery_powered(&self) -> Option<&dyn BatteryPowered> {
+        self.device.as_battery_powered()
+    }
+
+    fn info(&self) -> Option<DeviceInfo> {
+        self.device.info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::{CommandError, DeviceCommand, SmartHouse, SmartRoom};
+
+    // Устройство отказывает первые `failures` раз ошибкой `error`
+    #[derive(Clone)]
+    struct Flaky {
+        failures: u32,
+        error: fn() -> NetError,
+        attempts: Arc<AtomicU32>,
+        on: Arc<AtomicBool>,
+    }
+
+    impl Flaky {
+        fn new(failures: u32, error: fn() -> NetError) -> Self {
+            Self {
+                failures,
+                error,
+                attempts: Arc::default(),
+                on: Arc::default(),
+            }
+        }
+    }
+
+    impl Named for Flaky {
+        fn name(&self) -> &str {
+            "relay"
+        }
+    }
+
+    impl Switchable for Flaky {
+        fn turn_on(&self) -> Result<(), CapabilityError> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(Box::new((self.error)()));
+            }
+            self.on.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn turn_off(&self) -> Result<(), CapabilityError> {
+            self.on.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn is_on(&self) -> Result<bool, CapabilityError> {
+            Ok(self.on.load(Ordering::SeqCst))
+        }
+    }
+
+    impl Pluggable for Flaky {
+        crate::boxed_clone!();
+
+        fn as_switchable(&self) -> Option<&dyn Switchable> {
+            Some(self)
+        }
+    }
+
+    fn reset() -> NetError {
+        NetError::Io(io::ErrorKind::ConnectionReset.into())
+    }
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        attempts: 3,
+        backoff: Duration::ZERO,
+    };
+
+    #[test]
+    fn transient_failures_are_retried() {
+        let flaky = Flaky::new(2, reset);
+        let attempts = flaky.attempts.clone();
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(Retrying::new(flaky, POLICY)).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+
+        house
+            .execute(&"hall/relay".parse().unwrap(), DeviceCommand::TurnOn)
+            .unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn retries_run_out() {
+        let flaky = Retrying::new(Flaky::new(5, reset), POLICY);
+        let err = flaky.turn_on().unwrap_err();
+        assert!(is_transient(&*err));
+        assert_eq!(flaky.device().attempts.load(Ordering::SeqCst), 3);
+        assert!(!flaky.is_on().unwrap());
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let permanent: [fn() -> NetError; 3] = [
+            || NetError::NotFound("device relay".to_string()),
+            || NetError::Unauthorized,
+            || NetError::Protocol(ProtocolError::MalformedPayload("state")),
+        ];
+        for error in permanent {
+            let flaky = Retrying::new(Flaky::new(1, error), POLICY);
+            assert!(!is_transient(&*flaky.turn_on().unwrap_err()));
+            assert_eq!(flaky.device().attempts.load(Ordering::SeqCst), 1);
+        }
+        assert!(NetError::RetriesExhausted {
+            attempts: 2,
+            last: Box::new(reset()),
+        }
+        .is_transient());
+
+        // не поддерживающее команду устройство отказывает без повторов
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(Retrying::new(crate::SmartThermometer::new("t1"), POLICY))
+            .unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+        assert!(matches!(
+            house.execute(&"hall/t1".parse().unwrap(), DeviceCommand::TurnOn),
+            Err(CommandError::WrongKind { .. })
+        ));
+    }
+}
@@ -0,0 +1,411 @@
+use std::{
+    fmt,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    devices::downcast,
+    scheduler::{RunOutcome, Task},
+    udp::ThermometerReceiver,
+    ActionError, CommandError, DeviceLocation, SmartHouse, SmartSocket, SmartThermometer,
+};
+
+/// Which value of a device a [`Condition`] looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Reading {
+    /// Degrees Celsius of a thermometer or a thermometer receiver.
+    Temperature,
+    /// Watts drawn through a socket.
+    Power,
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reading::Temperature => f.write_str("temperature"),
+            Reading::Power => f.write_str("power"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    Above(f64),
+    Below(f64),
+}
+
+impl Threshold {
+    fn crossed(self, value: f64) -> bool {
+        match self {
+            Threshold::Above(limit) => value > limit,
+            Threshold::Below(limit) => value < limit,
+        }
+    }
+
+    fn reset(self, value: f64, hysteresis: f64) -> bool {
+        match self {
+            Threshold::Above(limit) => value <= limit - hysteresis,
+            Threshold::Below(limit) => value >= limit + hysteresis,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub path: DeviceLocation,
+    pub reading: Reading,
+    pub threshold: Threshold,
+    /// How long the threshold has to stay crossed before the rule fires.
+    pub debounce: Duration,
+    /// How far back past the threshold the reading has to go before the rule can fire
+    /// again.
+    pub hysteresis: f64,
+}
+
+impl Condition {
+    pub fn new(path: DeviceLocation, reading: Reading, threshold: Threshold) -> Self {
+        Self {
+            path,
+            reading,
+            threshold,
+            debounce: Duration::ZERO,
+            hysteresis: 0.0,
+        }
+    }
+
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    pub fn hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+}
+
+/// Runs `then` once each time `when` becomes true, see [`SmartHouse::evaluate_rules`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub when: Condition,
+    pub then: Task,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RuleId(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleState {
+    /// The threshold is not crossed.
+    Armed,
+    /// The threshold is crossed, but not yet for the whole debounce.
+    Pending { since: SystemTime },
+    /// The rule has fired and waits for the reading to go back past the hysteresis.
+    Fired,
+}
+
+/// What one evaluation of a rule saw and did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleEvaluation {
+    pub rule: RuleId,
+    pub at: SystemTime,
+    pub value: Result<f64, ActionError>,
+    /// The state after this evaluation.
+    pub state: RuleState,
+    /// What running the action did, if the rule fired.
+    pub fired: Option<RunOutcome>,
+}
+
+impl fmt::Display for RuleEvaluation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Ok(value) => write!(f, "rule {}: {value}", self.rule.0)?,
+            Err(err) => write!(f, "rule {}: {err}", self.rule.0)?,
+        }
+        match self.state {
+            RuleState::Armed => f.write_str(", armed"),
+            RuleState::Pending { .. } => f.write_str(", pending"),
+            RuleState::Fired if self.fired.is_some() => f.write_str(", fired"),
+            RuleState::Fired => f.write_str(", waiting for reset"),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Entry {
+    id: RuleId,
+    rule: Rule,
+    state: RuleState,
+    last: Option<RuleEvaluation>,
+}
+
+// Состояние правил меняется при оценке, а оценка идёт через &SmartHouse
+#[derive(Default)]
+pub(crate) struct Rules {
+    next: u64,
+    entries: Mutex<Vec<Entry>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Clone for Rules {
+    fn clone(&self) -> Self {
+        Self {
+            next: self.next,
+            entries: Mutex::new(lock(&self.entries).clone()),
+        }
+    }
+}
+
+impl SmartHouse {
+    pub fn add_rule(&mut self, rule: Rule) -> RuleId {
+        let id = RuleId(self.rules.next);
+        self.rules.next += 1;
+        lock(&self.rules.entries).push(Entry {
+            id,
+            rule,
+            state: RuleState::Armed,
+            last: None,
+        });
+        id
+    }
+
+    pub fn remove_rule(&mut self, id: RuleId) -> Option<Rule> {
+        let mut entries = lock(&self.rules.entries);
+        let position = entries.iter().position(|entry| entry.id == id)?;
+        Some(entries.remove(position).rule)
+    }
+
+    pub fn rule_state(&self, id: RuleId) -> Option<RuleState> {
+        let entries = lock(&self.rules.entries);
+        entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| entry.state)
+    }
+
+    /// The latest evaluation of every rule that has been evaluated, in registration order.
+    pub fn rule_evaluations(&self) -> Vec<RuleEvaluation> {
+        let entries = lock(&self.rules.entries);
+        entries
+            .iter()
+            .filter_map(|entry| entry.last.clone())
+            .collect()
+    }
+
+    /// Reads every rule's device once and runs the action of each rule whose condition
+    /// has held for its debounce. A fired rule stays quiet until its reading goes back past
+    /// the hysteresis, however long the condition stays true. A device that cannot be
+    /// read interrupts the debounce but does not reset a fired rule.
+    ///
+    /// Devices do not report their readings to the house, so nothing calls this on its
+    /// own: call it on a timer or after feeding new readings.
+    pub fn evaluate_rules(&self) -> Vec<RuleEvaluation> {
+        let now = self.now();
+        let mut fire = Vec::new();
+        let mut evaluations: Vec<_> = {
+            let mut entries = lock(&self.rules.entries);
+            entries
+                .iter_mut()
+                .enumerate()
+                .map(|(position, entry)| {
+                    let value = self.read(&entry.rule.when.path, entry.rule.when.reading);
+                    let before = entry.state;
+                    entry.state = step(&entry.rule.when, before, value.as_ref().ok(), now);
+                    if entry.state == RuleState::Fired && before != RuleState::Fired {
+                        fire.push((position, entry.rule.then.clone()));
+                    }
+                    RuleEvaluation {
+                        rule: entry.id,
+                        at: now,
+                        value,
+                        state: entry.state,
+                        fired: None,
+                    }
+                })
+                .collect()
+        };
+
+        // действия выполняются без блокировки: сцена может читать правила
+        for (position, task) in fire {
+            evaluations[position].fired = Some(self.run_task(&task));
+        }
+
+        let mut entries = lock(&self.rules.entries);
+        for evaluation in &evaluations {
+            if let Some(entry) = entries.iter_mut().find(|entry| entry.id == evaluation.rule) {
+                entry.last = Some(evaluation.clone());
+            }
+        }
+        evaluations
+    }
+
+    pub(crate) fn read(&self, path: &DeviceLocation, reading: Reading) -> Result<f64, ActionError> {
+        let device = self.locate(path).map_err(ActionError::Missing)?;
+        if self
+            .room(&path.room)
+            .is_some_and(|room| !room.is_enabled(device.name()))
+        {
+            return Err(CommandError::Disabled {
+                device: device.name().to_string(),
+            }
+            .into());
+        }
+        let value = match reading {
+            Reading::Temperature => downcast::<SmartThermometer>(&*device)
+                .map(SmartThermometer::temperature)
+                .or_else(|| {
+                    downcast::<ThermometerReceiver>(&*device).map(ThermometerReceiver::temperature)
+                }),
+            Reading::Power => downcast::<SmartSocket>(&*device).map(|socket| Some(socket.load())),
+        };
+        match value {
+            Some(Some(value)) => Ok(value),
+            Some(None) => Err(ActionError::Failed(String::from("no reading yet"))),
+            None => Err(ActionError::WrongKind {
+                device: device.name().to_string(),
+            }),
+        }
+    }
+}
+
+fn step(
+    condition: &Condition,
+    state: RuleState,
+    value: Option<&f64>,
+    now: SystemTime,
+) -> RuleState {
+    let Some(&value) = value else {
+        return match state {
+            RuleState::Fired => RuleState::Fired,
+            _ => RuleState::Armed,
+        };
+    };
+    let crossed = condition.threshold.crossed(value);
+    match state {
+        RuleState::Fired if condition.threshold.reset(value, condition.hysteresis) => {
+            RuleState::Armed
+        }
+        RuleState::Fired => RuleState::Fired,
+        _ if !crossed => RuleState::Armed,
+        RuleState::Armed => settle(condition, now, now),
+        RuleState::Pending { since } => settle(condition, since, now),
+    }
+}
+
+fn settle(condition: &Condition, since: SystemTime, now: SystemTime) -> RuleState {
+    match now.duration_since(since).unwrap_or_default() >= condition.debounce {
+        true => RuleState::Fired,
+        false => RuleState::Pending { since },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{testing::ManualClock, Clock, SceneAction, SmartRoom};
+
+    fn boiler_room() -> (SmartHouse, Arc<SmartThermometer>, Arc<SmartSocket>) {
+        let thermometer = Arc::new(SmartThermometer::new("t1"));
+        let boiler = Arc::new(SmartSocket::new("boiler"));
+        boiler.turn_on();
+        let mut room = SmartRoom::new("boiler-room");
+        room.plug(thermometer.clone()).unwrap();
+        room.plug(boiler.clone()).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(room).unwrap();
+        (house, thermometer, boiler)
+    }
+
+    fn overheat() -> Rule {
+        Rule {
+            name: "boiler overheat".to_string(),
+            when: Condition::new(
+                "boiler-room/t1".parse().unwrap(),
+                Reading::Temperature,
+                Threshold::Above(75.0),
+            )
+            .debounce(Duration::from_secs(30))
+            .hysteresis(5.0),
+            then: Task::Device(SceneAction::SetSocket {
+                path: "boiler-room/boiler".parse().unwrap(),
+                on: false,
+            }),
+        }
+    }
+
+    #[test]
+    fn rule_fires_once_and_resets_past_hysteresis() {
+        let (mut house, thermometer, boiler) = boiler_room();
+        let clock = ManualClock::default();
+        house.set_clock(clock.clone());
+        let rule = house.add_rule(overheat());
+
+        let step = |celsius: f64, after: u64| {
+            clock.advance(Duration::from_secs(after));
+            thermometer.set_temperature(celsius);
+            house.evaluate_rules().remove(0)
+        };
+
+        // короткий выброс не проходит дребезг
+        assert_eq!(
+            step(80.0, 0).state,
+            RuleState::Pending { since: clock.now() }
+        );
+        assert_eq!(step(70.0, 10).state, RuleState::Armed);
+
+        assert!(matches!(step(80.0, 10).state, RuleState::Pending { .. }));
+        let fired = step(81.0, 30);
+        assert_eq!(fired.state, RuleState::Fired);
+        assert_eq!(fired.fired, Some(RunOutcome::Device(Ok(()))));
+        assert!(!boiler.is_on());
+
+        // пока условие держится, правило молчит
+        boiler.turn_on();
+        assert_eq!(step(82.0, 60).fired, None);
+        assert_eq!(step(72.0, 60).state, RuleState::Fired);
+        assert!(boiler.is_on());
+
+        assert_eq!(step(70.0, 60).state, RuleState::Armed);
+        step(80.0, 0);
+        assert!(step(80.0, 30).fired.is_some());
+        assert!(!boiler.is_on());
+
+        assert_eq!(house.rule_state(rule), Some(RuleState::Fired));
+        assert_eq!(house.rule_evaluations().len(), 1);
+        assert_eq!(house.rule_evaluations()[0].value, Ok(80.0));
+    }
+
+    #[test]
+    fn unreadable_devices_are_reported() {
+        let (mut house, _, _) = boiler_room();
+        let mut rule = overheat();
+        house.add_rule(rule.clone());
+        rule.when.path = "boiler-room/boiler".parse().unwrap();
+        house.add_rule(rule.clone());
+        rule.when.path = "boiler-room/kettle".parse().unwrap();
+        house.add_rule(rule);
+
+        let values: Vec<_> = house
+            .evaluate_rules()
+            .into_iter()
+            .map(|evaluation| evaluation.value)
+            .collect();
+        assert_eq!(
+            values[..2],
+            [
+                Err(ActionError::Failed("no reading yet".to_string())),
+                Err(ActionError::WrongKind {
+                    device: "boiler".to_string()
+                }),
+            ]
+        );
+        assert!(matches!(values[2], Err(ActionError::Missing(_))));
+    }
+}
@@ -0,0 +1,314 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{CommandError, DeviceCommand, DeviceLocation, LocateError, SmartHouse};
+
+/// One step of a [`Scene`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneAction {
+    SetSocket { path: DeviceLocation, on: bool },
+    SetLoad { path: DeviceLocation, watts: f64 },
+}
+
+impl SceneAction {
+    pub fn path(&self) -> &DeviceLocation {
+        match self {
+            SceneAction::SetSocket { path, .. } | SceneAction::SetLoad { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for SceneAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneAction::SetSocket { path, on: true } => write!(f, "turn on {path}"),
+            SceneAction::SetSocket { path, on: false } => write!(f, "turn off {path}"),
+            SceneAction::SetLoad { path, watts } => write!(f, "set {path} to {watts} W"),
+        }
+    }
+}
+
+/// A named list of actions applied in order by [`SmartHouse::activate_scene`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene {
+    pub name: String,
+    pub actions: Vec<SceneAction>,
+}
+
+impl Scene {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, action: SceneAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+}
+
+/// Why one action of a scene was not applied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionError {
+    Missing(LocateError),
+    // устройство нашлось, но действие к нему неприменимо
+    WrongKind { device: String },
+    // устройство само отказало, например сетевая розетка недоступна
+    Failed(String),
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::Missing(err) => err.fmt(f),
+            ActionError::WrongKind { device } => write!(f, "{device} does not support this"),
+            ActionError::Failed(reason) => write!(f, "device failed: {reason}"),
+        }
+    }
+}
+
+impl core::error::Error for ActionError {}
+
+impl From<CommandError> for ActionError {
+    fn from(err: CommandError) -> Self {
+        match err {
+            CommandError::Missing(err) => ActionError::Missing(err),
+            CommandError::WrongKind { device, .. } => ActionError::WrongKind { device },
+            CommandError::Failed { reason, .. } => ActionError::Failed(reason),
+            err @ (CommandError::OverBudget { .. }
+            | CommandError::Disabled { .. }
+            | CommandError::RateLimited { .. }
+            | CommandError::Timeout { .. }) => ActionError::Failed(err.to_string()),
+        }
+    }
+}
+
+/// What activating a scene did, one outcome per action in scene order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneReport {
+    pub scene: String,
+    pub outcomes: Vec<(SceneAction, Result<(), ActionError>)>,
+}
+
+impl SceneReport {
+    pub fn is_ok(&self) -> bool {
+        self.outcomes.iter().all(|(_, outcome)| outcome.is_ok())
+    }
+
+    pub fn succeeded(&self) -> impl Iterator<Item = &SceneAction> {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| outcome.is_ok())
+            .map(|(action, _)| action)
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = (&SceneAction, &ActionError)> {
+        self.outcomes
+            .iter()
+            .filter_map(|(action, outcome)| outcome.as_ref().err().map(|err| (action, err)))
+    }
+}
+
+impl fmt::Display for SceneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scene {}", self.scene)?;
+        for (action, outcome) in &self.outcomes {
+            match outcome {
+                Ok(()) => write!(f, "\n  ok: {action}")?,
+                Err(err) => write!(f, "\n  failed: {action}: {err}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SmartHouse {
+    /// Stores the scene, replacing and returning an earlier one with the same name.
+    pub fn register_scene(&mut self, scene: Scene) -> Option<Scene> {
+        match self
+            .scenes
+            .iter_mut()
+            .find(|known| known.name == scene.name)
+        {
+            Some(known) => Some(core::mem::replace(known, scene)),
+            None => {
+                self.scenes.push(scene);
+                None
+            }
+        }
+    }
+
+    pub fn remove_scene(&mut self, name: &str) -> Option<Scene> {
+        let position = self.scenes.iter().position(|scene| scene.name == name)?;
+        Some(self.scenes.remove(position))
+    }
+
+    pub fn scene(&self, name: &str) -> Option<&Scene> {
+        self.scenes.iter().find(|scene| scene.name == name)
+    }
+
+    pub fn scenes(&self) -> impl Iterator<Item = &Scene> {
+        self.scenes.iter()
+    }
+
+    /// Applies every action of the named scene in order, or gives `None` for an unknown
+    /// name. A failed action does not stop the ones after it; the report says which
+    /// actions went through.
+    pub fn activate_scene(&self, name: &str) -> Option<SceneReport> {
+        let scene = self.scene(name)?;
+        let outcomes = scene
+            .actions
+            .iter()
+            .map(|action| (action.clone(), self.apply_action(action)))
+            .collect();
+        Some(SceneReport {
+            scene: scene.name.clone(),
+            outcomes,
+        })
+    }
+
+    /// Applies one action outside of any scene, through [`execute`](SmartHouse::execute).
+    pub fn apply_action(&self, action: &SceneAction) -> Result<(), ActionError> {
+        let command = match *action {
+            SceneAction::SetSocket { on: true, .. } => DeviceCommand::TurnOn,
+            SceneAction::SetSocket { on: false, .. } => DeviceCommand::TurnOff,
+            SceneAction::SetLoad { watts, .. } => DeviceCommand::SetLoad(watts),
+        };
+        self.execute(action.path(), command)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::*;
+    use crate::testing::fixtures::kitchen;
+
+    fn path(text: &str) -> DeviceLocation {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn scene_applies_actions_and_reports_failures() {
+        let (mut house, [_, kettle, lamp]) = kitchen();
+        kettle.turn_on();
+        house.register_scene(
+            Scene::new("tea time")
+                .with(SceneAction::SetSocket {
+                    path: path("kitchen/kettle"),
+                    on: false,
+                })
+                .with(SceneAction::SetSocket {
+                    path: path("kitchen/t1"),
+                    on: true,
+                })
+                .with(SceneAction::SetSocket {
+                    path: path("living/tv"),
+                    on: true,
+                })
+                .with(SceneAction::SetLoad {
+                    path: path("lamp"),
+                    watts: 40.0,
+                }),
+        );
+
+        let report = house.activate_scene("tea time").unwrap();
+
+        assert!(!kettle.is_on());
+        assert_eq!(lamp.load(), 40.0);
+        assert!(!report.is_ok());
+        assert_eq!(report.succeeded().count(), 2);
+
+        let failed: Vec<_> = report.failed().map(|(_, err)| err.clone()).collect();
+        assert_eq!(
+            failed,
+            [
+                ActionError::WrongKind {
+                    device: String::from("t1")
+                },
+                ActionError::Missing(LocateError::Room(String::from("living"))),
+            ]
+        );
+        assert!(house.activate_scene("breakfast").is_none());
+    }
+
+    #[test]
+    fn any_switchable_device_takes_part_in_scenes() {
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        use crate::{CapabilityError, Named, Pluggable, Switchable};
+
+        // устройство, о котором дом ничего не знает, кроме способности включаться
+        #[derive(Default)]
+        struct Blinds(AtomicBool);
+
+        impl Named for Blinds {
+            fn name(&self) -> &str {
+                "blinds"
+            }
+        }
+
+        impl Switchable for Blinds {
+            fn turn_on(&self) -> Result<(), CapabilityError> {
+                self.0.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+
+            fn turn_off(&self) -> Result<(), CapabilityError> {
+                self.0.store(false, Ordering::SeqCst);
+                Ok(())
+            }
+
+            fn is_on(&self) -> Result<bool, CapabilityError> {
+                Ok(self.0.load(Ordering::SeqCst))
+            }
+        }
+
+        impl Pluggable for Blinds {
+            fn boxed_clone(&self) -> Arc<dyn Pluggable> {
+                Arc::new(Blinds(AtomicBool::new(self.0.load(Ordering::SeqCst))))
+            }
+
+            fn as_switchable(&self) -> Option<&dyn Switchable> {
+                Some(self)
+            }
+        }
+
+        let (mut house, _) = kitchen();
+        let blinds = Arc::new(Blinds::default());
+        house.plug("kitchen", blinds.clone()).unwrap();
+        house.register_scene(Scene::new("morning").with(SceneAction::SetSocket {
+            path: path("kitchen/blinds"),
+            on: true,
+        }));
+
+        assert!(house.activate_scene("morning").unwrap().is_ok());
+        assert!(blinds.0.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn registering_a_scene_again_replaces_it() {
+        let (mut house, [_, kettle, _]) = kitchen();
+        let off = SceneAction::SetSocket {
+            path: path("kitchen/kettle"),
+            on: false,
+        };
+        let on = SceneAction::SetSocket {
+            path: path("kitchen/kettle"),
+            on: true,
+        };
+        assert!(house.register_scene(Scene::new("s").with(off)).is_none());
+        assert!(house.register_scene(Scene::new("s").with(on)).is_some());
+        assert_eq!(house.scenes().count(), 1);
+
+        assert!(house.activate_scene("s").unwrap().is_ok());
+        assert!(kettle.is_on());
+    }
+}
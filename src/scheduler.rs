@@ -0,0 +1,605 @@
+//! Running scenes and device actions at set times.
+//!
+//! A [`Scheduler`] owns a list of [`ScheduledAction`]s over a [`SharedSmartHouse`].
+//! [`tick`](Scheduler::tick) runs whatever is due by the scheduler's [`Clock`];
+//! [`start`](Scheduler::start) does that on a background thread until
+//! [`stop`](Scheduler::stop). Triggers that fall while the scheduler is stopped are
+//! skipped, not replayed when it starts again.
+
+use core::fmt;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    ActionError, Clock, SceneAction, SceneReport, SharedSmartHouse, SmartHouse, SystemClock,
+};
+
+/// Runs kept for [`Scheduler::recent_runs`].
+pub const RUN_HISTORY: usize = 32;
+
+// Поток не спит дольше этого: часы могут быть ручными или прыгнуть
+const MAX_WAIT: Duration = Duration::from_millis(200);
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// A minute of the day, from 00:00 to 23:59.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeOfDay {
+    hour: u8,
+    minute: u8,
+}
+
+impl TimeOfDay {
+    pub fn new(hour: u8, minute: u8) -> Result<Self, InvalidTimeOfDay> {
+        if hour > 23 || minute > 59 {
+            return Err(InvalidTimeOfDay { hour, minute });
+        }
+        Ok(Self { hour, minute })
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// An hour over 23 or a minute over 59.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl fmt::Display for InvalidTimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02} is not a time of day",
+            self.hour, self.minute
+        )
+    }
+}
+
+impl Error for InvalidTimeOfDay {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    Once(SystemTime),
+    /// Every `interval` counted from `start`; a zero interval fires once, at `start`.
+    Every {
+        start: SystemTime,
+        interval: Duration,
+    },
+    /// Every day at the time UTC, see [`Schedule::daily`].
+    Daily(TimeOfDay),
+}
+
+impl Schedule {
+    /// Every day at `hour:minute` UTC; fails unless that is a time of day.
+    pub fn daily(hour: u8, minute: u8) -> Result<Self, InvalidTimeOfDay> {
+        TimeOfDay::new(hour, minute).map(Schedule::Daily)
+    }
+
+    /// The first trigger at or after `from`, or `None` when none is left.
+    pub fn next_at_or_after(&self, from: SystemTime) -> Option<SystemTime> {
+        match *self {
+            Schedule::Once(at) => (at >= from).then_some(at),
+            Schedule::Every { start, interval } => {
+                let Ok(late) = from.duration_since(start) else {
+                    return Some(start);
+                };
+                if interval.is_zero() {
+                    return late.is_zero().then_some(start);
+                }
+                let periods = late.as_nanos().div_ceil(interval.as_nanos());
+                let offset = interval.as_nanos().checked_mul(periods)?;
+                start.checked_add(Duration::from_nanos(u64::try_from(offset).ok()?))
+            }
+            Schedule::Daily(time) => {
+                let since_epoch = from.duration_since(UNIX_EPOCH).ok()?;
+                let midnight = since_epoch.as_secs() / DAY * DAY;
+                let time = u64::from(time.hour) * 60 * 60 + u64::from(time.minute) * 60;
+                let mut at = UNIX_EPOCH + Duration::from_secs(midnight + time);
+                if at < from {
+                    at += Duration::from_secs(DAY);
+                }
+                Some(at)
+            }
+        }
+    }
+}
+
+/// What a [`ScheduledAction`] does when it fires.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Task {
+    /// A scene registered on the house, looked up by name when the task runs.
+    Scene(String),
+    Device(SceneAction),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledAction {
+    pub when: Schedule,
+    pub action: Task,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(u64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome {
+    Scene(SceneReport),
+    UnknownScene(String),
+    Device(Result<(), ActionError>),
+}
+
+/// One fired job: when it was due and what running it did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub job: JobId,
+    pub due: SystemTime,
+    pub outcome: RunOutcome,
+}
+
+struct Job {
+    id: JobId,
+    scheduled: ScheduledAction,
+    next: Option<SystemTime>,
+}
+
+struct State {
+    clock: Arc<dyn Clock>,
+    jobs: Vec<Job>,
+    next_id: u64,
+    running: bool,
+    history: VecDeque<Run>,
+}
+
+struct Shared {
+    house: SharedSmartHouse,
+    state: Mutex<State>,
+    wakeup: Condvar,
+}
+
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Scheduler {
+    pub fn new(house: SharedSmartHouse) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                house,
+                state: Mutex::new(State {
+                    clock: Arc::new(SystemClock),
+                    jobs: Vec::new(),
+                    next_id: 0,
+                    running: false,
+                    history: VecDeque::new(),
+                }),
+                wakeup: Condvar::new(),
+            }),
+            worker: Mutex::new(None),
+        }
+    }
+
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        lock(&self.shared.state).clock = Arc::new(clock);
+        self
+    }
+
+    /// Registers an action; its first trigger is the first one from now on.
+    pub fn add(&self, scheduled: ScheduledAction) -> JobId {
+        let mut state = lock(&self.shared.state);
+        let id = JobId(state.next_id);
+        state.next_id += 1;
+        let next = scheduled.when.next_at_or_after(state.clock.now());
+        state.jobs.push(Job {
+            id,
+            scheduled,
+            next,
+        });
+        id
+    }
+
+    pub fn remove(&self, id: JobId) -> Option<ScheduledAction> {
+        let mut state = lock(&self.shared.state);
+        let position = state.jobs.iter().position(|job| job.id == id)?;
+        Some(state.jobs.remove(position).scheduled)
+    }
+
+    /// The next trigger of every job that has one, earliest first.
+    pub fn upcoming(&self) -> Vec<(JobId, SystemTime)> {
+        let state = lock(&self.shared.state);
+        let mut upcoming: Vec<_> = state
+            .jobs
+            .iter()
+            .filter_map(|job| Some((job.id, job.next?)))
+            .collect();
+        upcoming.sort_by_key(|&(id, at)| (at, id));
+        upcoming
+    }
+
+    /// The last [`RUN_HISTORY`] runs, oldest first.
+    pub fn recent_runs(&self) -> Vec<Run> {
+        lock(&self.shared.state).history.iter().cloned().collect()
+    }
+
+    pub fn is_running(&self) -> bool {
+        lock(&self.shared.state).running
+    }
+
+    /// Runs every job that is due now, once each, however many of its triggers have
+    /// passed since the last tick. Jobs run in the order they were due.
+    pub fn tick(&self) -> Vec<Run> {
+        self.shared.tick()
+    }
+
+    /// Starts ticking on a background thread. Triggers missed since the scheduler was
+    /// stopped are dropped first. Does nothing if it is already running.
+    pub fn start(&self) {
+        let mut worker = lock(&self.worker);
+        if !self.shared.resume() {
+            return;
+        }
+        let shared = Arc::clone(&self.shared);
+        *worker = Some(thread::spawn(move || loop {
+            shared.tick();
+            if !shared.wait() {
+                break;
+            }
+        }));
+    }
+
+    /// Stops the background thread and waits for the tick in progress.
+    pub fn stop(&self) {
+        lock(&self.shared.state).running = false;
+        self.shared.wakeup.notify_all();
+        if let Some(worker) = lock(&self.worker).take() {
+            let _ = worker.join();
+        }
+    }
+
+    /// Ticks until [`stop`](Self::stop), like [`start`](Self::start) but inside the
+    /// caller's executor; each wait runs on a helper thread.
+    #[cfg(feature = "async")]
+    pub async fn run(&self) {
+        if !self.shared.resume() {
+            return;
+        }
+        loop {
+            self.shared.tick();
+            let shared = Arc::clone(&self.shared);
+            if !crate::async_report::spawn_blocking(move || shared.wait()).await {
+                break;
+            }
+        }
+    }
+}
+
+impl Shared {
+    // false, если планировщик уже запущен
+    fn resume(&self) -> bool {
+        let mut state = lock(&self.state);
+        if state.running {
+            return false;
+        }
+        state.running = true;
+        let now = state.clock.now();
+        for job in &mut state.jobs {
+            job.next = job.scheduled.when.next_at_or_after(now);
+        }
+        true
+    }
+
+    fn tick(&self) -> Vec<Run> {
+        let mut due: Vec<_> = {
+            let mut state = lock(&self.state);
+            let now = state.clock.now();
+            let due = state
+                .jobs
+                .iter_mut()
+                .filter_map(|job| {
+                    let at = job.next.filter(|at| *at <= now)?;
+                    // пропущенные срабатывания не накапливаются
+                    job.next = now
+                        .checked_add(Duration::from_nanos(1))
+                        .and_then(|after| job.scheduled.when.next_at_or_after(after));
+                    Some((job.id, at, job.scheduled.action.clone()))
+                })
+                .collect();
+            state.jobs.retain(|job| job.next.is_some());
+            due
+        };
+        // раньше назначенное выполняется раньше
+        due.sort_by_key(|&(job, at, _)| (at, job));
+
+        let runs: Vec<_> = due
+            .into_iter()
+            .map(|(job, due, task)| Run {
+                job,
+                due,
+                outcome: self.house.with_house(|house| house.run_task(&task)),
+            })
+            .collect();
+
+        let mut state = lock(&self.state);
+        for run in &runs {
+            if state.history.len() == RUN_HISTORY {
+                state.history.pop_front();
+            }
+            state.history.push_back(run.clone());
+        }
+        runs
+    }
+
+    // Ждёт следующего срабатывания; false, если планировщик остановили
+    fn wait(&self) -> bool {
+        let state = lock(&self.state);
+        let now = state.clock.now();
+        let wait = state
+            .jobs
+            .iter()
+            .filter_map(|job| job.next)
+            .min()
+            .map_or(MAX_WAIT, |at| {
+                at.duration_since(now).unwrap_or_default().min(MAX_WAIT)
+            });
+        let state = self
+            .wakeup
+            .wait_timeout_while(state, wait, |state| state.running)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0;
+        state.running
+    }
+}
+
+impl SmartHouse {
+    pub(crate) fn run_task(&self, task: &Task) -> RunOutcome {
+        match task {
+            Task::Scene(name) => match self.activate_scene(name) {
+                Some(report) => RunOutcome::Scene(report),
+                None => RunOutcome::UnknownScene(name.clone()),
+            },
+            Task::Device(action) => RunOutcome::Device(self.apply_action(action)),
+        }
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = lock(&self.shared.state);
+        let jobs: Vec<_> = state
+            .jobs
+            .iter()
+            .map(|job| (job.id, &job.scheduled, job.next))
+            .collect();
+        f.debug_struct("Scheduler")
+            .field("jobs", &jobs)
+            .field("running", &state.running)
+            .finish()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{testing::ManualClock, Scene, SmartRoom, SmartSocket};
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn garden() -> (SharedSmartHouse, Arc<SmartSocket>) {
+        let socket = Arc::new(SmartSocket::new("socket"));
+        socket.turn_on();
+        let mut garden = SmartRoom::new("garden");
+        garden.plug(socket.clone()).unwrap();
+        let mut house = SmartHouse::new("home");
+        house.add(garden).unwrap();
+        house.register_scene(Scene::new("lights on").with(SceneAction::SetSocket {
+            path: "garden/socket".parse().unwrap(),
+            on: true,
+        }));
+        (SharedSmartHouse::new(house), socket)
+    }
+
+    fn turn_off() -> Task {
+        Task::Device(SceneAction::SetSocket {
+            path: "garden/socket".parse().unwrap(),
+            on: false,
+        })
+    }
+
+    // 2023-11-14 22:13:20 UTC, как у ManualClock по умолчанию
+    fn start() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    #[test]
+    fn schedules_find_their_next_trigger() {
+        let eleven = Schedule::daily(23, 0).unwrap();
+        let tonight = UNIX_EPOCH + Duration::from_secs(1_699_916_400 + DAY);
+        assert_eq!(eleven.next_at_or_after(start()), Some(tonight));
+        assert_eq!(eleven.next_at_or_after(tonight), Some(tonight));
+        assert_eq!(
+            eleven.next_at_or_after(tonight + Duration::from_secs(1)),
+            Some(tonight + Duration::from_secs(DAY))
+        );
+
+        let every = Schedule::Every {
+            start: start(),
+            interval: HOUR,
+        };
+        assert_eq!(every.next_at_or_after(start() - HOUR), Some(start()));
+        assert_eq!(
+            every.next_at_or_after(start() + HOUR / 2),
+            Some(start() + HOUR)
+        );
+
+        assert_eq!(
+            Schedule::Once(start()).next_at_or_after(start() + HOUR),
+            None
+        );
+    }
+
+    #[test]
+    fn daily_times_are_checked() {
+        for (hour, minute) in [(24, 0), (25, 99), (12, 60), (u8::MAX, u8::MAX)] {
+            assert_eq!(
+                Schedule::daily(hour, minute),
+                Err(InvalidTimeOfDay { hour, minute })
+            );
+        }
+        assert_eq!(
+            InvalidTimeOfDay {
+                hour: 25,
+                minute: 99
+            }
+            .to_string(),
+            "25:99 is not a time of day"
+        );
+
+        let last = TimeOfDay::new(23, 59).unwrap();
+        assert_eq!((last.hour(), last.minute()), (23, 59));
+        assert_eq!(TimeOfDay::new(0, 5).unwrap().to_string(), "00:05");
+        // последняя минута дня всё ещё сегодня
+        let midnight = UNIX_EPOCH + Duration::from_secs(1_699_920_000);
+        assert_eq!(
+            Schedule::Daily(last).next_at_or_after(midnight),
+            Some(midnight + Duration::from_secs(DAY - 60))
+        );
+    }
+
+    #[test]
+    fn tick_runs_due_jobs_once() {
+        let (house, socket) = garden();
+        let clock = ManualClock::new(start());
+        let scheduler = Scheduler::new(house).with_clock(clock.clone());
+        let nightly = scheduler.add(ScheduledAction {
+            when: Schedule::daily(23, 0).unwrap(),
+            action: turn_off(),
+        });
+        let once = scheduler.add(ScheduledAction {
+            when: Schedule::Once(start() + HOUR / 4),
+            action: Task::Scene("lights on".to_string()),
+        });
+        let tonight = UNIX_EPOCH + Duration::from_secs(1_699_916_400 + DAY);
+        assert_eq!(
+            scheduler.upcoming(),
+            [(once, start() + HOUR / 4), (nightly, tonight)]
+        );
+
+        assert!(scheduler.tick().is_empty());
+        clock.set(tonight);
+        let runs = scheduler.tick();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].job, nightly);
+        assert_eq!(runs[1].outcome, RunOutcome::Device(Ok(())));
+        assert!(!socket.is_on());
+
+        // разовое задание выполнено и удалено
+        assert_eq!(
+            scheduler.upcoming(),
+            [(nightly, tonight + Duration::from_secs(DAY))]
+        );
+        assert!(scheduler.tick().is_empty());
+
+        // три пропущенных дня дают один запуск, а не три
+        clock.advance(Duration::from_secs(3 * DAY));
+        assert_eq!(scheduler.tick().len(), 1);
+        assert_eq!(scheduler.recent_runs().len(), 3);
+    }
+
+    #[test]
+    fn triggers_missed_while_stopped_are_skipped() {
+        let (house, socket) = garden();
+        let clock = ManualClock::new(start());
+        let scheduler = Scheduler::new(house).with_clock(clock.clone());
+        let job = scheduler.add(ScheduledAction {
+            when: Schedule::Every {
+                start: start() + HOUR,
+                interval: HOUR,
+            },
+            action: turn_off(),
+        });
+
+        clock.advance(5 * HOUR + HOUR / 2);
+        scheduler.start();
+        assert!(scheduler.is_running());
+        assert_eq!(scheduler.upcoming(), [(job, start() + 6 * HOUR)]);
+        scheduler.stop();
+
+        assert!(scheduler.recent_runs().is_empty());
+        assert!(socket.is_on());
+    }
+
+    #[test]
+    fn background_thread_runs_due_jobs() {
+        let (house, socket) = garden();
+        let clock = ManualClock::new(start());
+        let scheduler = Scheduler::new(house).with_clock(clock.clone());
+        scheduler.add(ScheduledAction {
+            when: Schedule::Once(start() + HOUR),
+            action: turn_off(),
+        });
+        scheduler.start();
+        clock.advance(HOUR);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while socket.is_on() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        scheduler.stop();
+        assert!(!socket.is_on());
+        assert!(!scheduler.is_running());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn run_stops_with_the_scheduler() {
+        let (house, socket) = garden();
+        let clock = ManualClock::new(start());
+        let scheduler = Arc::new(Scheduler::new(house).with_clock(clock.clone()));
+        scheduler.add(ScheduledAction {
+            when: Schedule::Once(start()),
+            action: turn_off(),
+        });
+
+        let stopper = Arc::clone(&scheduler);
+        let stop = thread::spawn(move || {
+            while stopper.recent_runs().is_empty() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            stopper.stop();
+        });
+        crate::async_report::block_on(scheduler.run());
+        stop.join().unwrap();
+        assert!(!socket.is_on());
+    }
+}
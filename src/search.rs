@@ -0,0 +1,362 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{DeviceKind, Labeled, SmartHouse, SmartRoom};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    // `?`
+    One,
+    // `*`
+    Many,
+}
+
+/// A name pattern where `*` matches any run of characters and `?` a single one; `\*`,
+/// `\?` and `\\` stand for the characters themselves. The whole name must match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    ignore_case: bool,
+    labels: bool,
+}
+
+impl Glob {
+    /// A `\` at the end of the pattern stands for itself.
+    pub fn new(pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '\\' => Token::Char(chars.next().unwrap_or('\\')),
+                '?' => Token::One,
+                // подряд идущие звёздочки ничего не добавляют
+                '*' if tokens.last() == Some(&Token::Many) => continue,
+                '*' => Token::Many,
+                c => Token::Char(c),
+            };
+            tokens.push(token);
+        }
+
+        Self {
+            tokens,
+            ignore_case: false,
+            labels: false,
+        }
+    }
+
+    /// Compares letters ignoring case.
+    pub fn ignore_case(self) -> Self {
+        let tokens = self
+            .tokens
+            .into_iter()
+            .flat_map(|token| match token {
+                Token::Char(c) => c.to_lowercase().map(Token::Char).collect(),
+                other => Vec::from([other]),
+            })
+            .collect();
+        Self {
+            tokens,
+            ignore_case: true,
+            ..self
+        }
+    }
+
+    /// Makes the house searches match labels as well as names, see
+    /// [`Labeled`](crate::Labeled).
+    pub fn include_labels(self) -> Self {
+        Self {
+            labels: true,
+            ..self
+        }
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        let pattern = &self.tokens;
+        let name: Vec<char> = match self.ignore_case {
+            true => name.chars().flat_map(char::to_lowercase).collect(),
+            false => name.chars().collect(),
+        };
+
+        // Жадный проход с откатом к последней звёздочке
+        let (mut p, mut n) = (0, 0);
+        let mut star: Option<(usize, usize)> = None;
+        while n < name.len() {
+            match pattern.get(p) {
+                Some(Token::Char(c)) if *c == name[n] => (p, n) = (p + 1, n + 1),
+                Some(Token::One) => (p, n) = (p + 1, n + 1),
+                Some(Token::Many) => {
+                    star = Some((p, n));
+                    p += 1;
+                }
+                _ => match star {
+                    Some((star_p, star_n)) => {
+                        star = Some((star_p, star_n + 1));
+                        (p, n) = (star_p + 1, star_n + 1);
+                    }
+                    None => return false,
+                },
+            }
+        }
+        pattern[p..].iter().all(|token| *token == Token::Many)
+    }
+}
+
+impl Glob {
+    fn matches_labeled(&self, name: &str, label: Option<&str>) -> bool {
+        self.is_match(name) || (self.labels && label.is_some_and(|label| self.is_match(label)))
+    }
+}
+
+impl From<&str> for Glob {
+    fn from(pattern: &str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+/// A device found by [`SmartHouse::search`] or the regex searches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceMatch {
+    pub room: String,
+    pub device: String,
+    pub kind: Option<DeviceKind>,
+}
+
+impl SmartHouse {
+    /// Every live device whose name matches, room by room.
+    ///
+    /// ```
+    /// use lesson_3::{Glob, SmartHouse};
+    ///
+    /// let house = SmartHouse::builder("Home")
+    ///     .room("Kitchen", |r| r.thermometer("Thermo-kitchen-1").socket("kettle"))
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(house.search("therm*").len(), 0);
+    /// assert_eq!(house.search(Glob::new("therm*").ignore_case()).len(), 1);
+    /// assert_eq!(house.search("*-kitchen-?")[0].device, "Thermo-kitchen-1");
+    /// ```
+    pub fn search(&self, pattern: impl Into<Glob>) -> Vec<DeviceMatch> {
+        let glob = pattern.into();
+        self.all_matching(|name, label| glob.matches_labeled(name, label))
+    }
+
+    #[cfg(feature = "regex")]
+    fn matching(&self, mut f: impl FnMut(&str) -> bool) -> Vec<DeviceMatch> {
+        self.all_matching(|name, _| f(name))
+    }
+
+    fn all_matching(&self, mut f: impl FnMut(&str, Option<&str>) -> bool) -> Vec<DeviceMatch> {
+        self.all_devices()
+            .filter(|(room, device)| f(device.name(), room.device_label(device.name())))
+            .map(|(room, device)| DeviceMatch {
+                room: room.name.clone(),
+                device: device.name().into(),
+                kind: device.kind(),
+            })
+            .collect()
+    }
+
+    /// Every live device whose name matches `re` anywhere, room by room. Anchor the
+    /// pattern with `^…$` to match whole names.
+    #[cfg(feature = "regex")]
+    pub fn search_regex(&self, re: &crate::regex::Regex) -> Vec<DeviceMatch> {
+        self.matching(|name| re.is_match(name))
+    }
+
+    /// Every live device whose name does not match `re`: the devices breaking a naming
+    /// convention.
+    ///
+    /// ```
+    /// use lesson_3::{regex::Regex, SmartHouse};
+    ///
+    /// let convention = Regex::new(r"^[a-z]+(-[a-z0-9]+)*$").unwrap();
+    /// let house = SmartHouse::builder("Home")
+    ///     .room("Kitchen", |r| r.socket("socket-kitchen-1").socket("Kettle"))
+    ///     .build()
+    ///     .unwrap();
+    /// let offenders = house.find_nonconforming(&convention);
+    /// assert_eq!(offenders[0].device, "Kettle");
+    /// assert_eq!(offenders[0].room, "Kitchen");
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn find_nonconforming(&self, re: &crate::regex::Regex) -> Vec<DeviceMatch> {
+        self.matching(|name| !re.is_match(name))
+    }
+
+    /// The rooms whose names match, in the order they were added.
+    pub fn search_rooms(&self, pattern: impl Into<Glob>) -> Vec<&SmartRoom> {
+        let glob = pattern.into();
+        self.rooms
+            .iter()
+            .filter(|room| glob.matches_labeled(&room.name, room.label()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SmartSocket, SmartThermometer};
+
+    #[test]
+    fn empty_and_wildcard_only_patterns() {
+        assert!(Glob::new("").is_match(""));
+        assert!(!Glob::new("").is_match("a"));
+        for pattern in ["*", "**", "***"] {
+            assert!(Glob::new(pattern).is_match(""));
+            assert!(Glob::new(pattern).is_match("anything at all"));
+        }
+        assert_eq!(Glob::new("a**b"), Glob::new("a*b"));
+        assert!(Glob::new("???").is_match("abc"));
+        assert!(Glob::new("???").is_match("日本語"));
+        assert!(!Glob::new("???").is_match("ab"));
+        assert!(Glob::new("?*").is_match("a"));
+        assert!(!Glob::new("?*").is_match(""));
+    }
+
+    #[test]
+    fn backtracking_and_anchoring() {
+        let glob = Glob::new("*-kitchen-*");
+        assert!(glob.is_match("socket-kitchen-1"));
+        assert!(glob.is_match("-kitchen-"));
+        assert!(glob.is_match("a-kitchen-b-kitchen-c"));
+        assert!(!glob.is_match("socket-kitchen"));
+        assert!(Glob::new("a*b*c").is_match("aXbYbZc"));
+        assert!(!Glob::new("a*b*c").is_match("aXbYbZ"));
+        assert!(!Glob::new("therm").is_match("thermometer"));
+    }
+
+    #[test]
+    fn escapes_and_case() {
+        assert!(Glob::new(r"\*").is_match("*"));
+        assert!(!Glob::new(r"\*").is_match("a"));
+        assert!(Glob::new(r"what\?").is_match("what?"));
+        assert!(!Glob::new(r"what\?").is_match("whats"));
+        assert!(Glob::new(r"a\\b").is_match(r"a\b"));
+        assert!(Glob::new(r"end\").is_match(r"end\"));
+
+        assert!(!Glob::new("THERM*").is_match("thermometer"));
+        assert!(Glob::new("THERM*").ignore_case().is_match("thermometer"));
+        assert!(Glob::new("КОМ?АТА").ignore_case().is_match("комната"));
+        assert!(Glob::new("straße").ignore_case().is_match("STRAẞE"));
+    }
+
+    #[test]
+    fn labels_are_searched_on_request() {
+        let mut kitchen = SmartRoom::new("kitchen");
+        kitchen.set_label(Some("Ground floor".into()));
+        kitchen.plug(SmartSocket::new("sock-b3-07")).unwrap();
+        kitchen
+            .set_device_label("sock-b3-07", Some("Coffee machine".into()))
+            .unwrap();
+        let mut house = SmartHouse::new("Home");
+        house.add(kitchen).unwrap();
+
+        assert!(house.search("coffee*").is_empty());
+        let coffee = Glob::new("coffee*").ignore_case().include_labels();
+        assert_eq!(house.search(coffee)[0].device, "sock-b3-07");
+        // имя по-прежнему находится и с подписями
+        assert_eq!(house.search(Glob::new("sock-*").include_labels()).len(), 1);
+
+        assert!(house.search_rooms("ground*").is_empty());
+        let ground = Glob::new("Ground*").include_labels();
+        assert_eq!(house.search_rooms(ground)[0].name, "kitchen");
+    }
+
+    #[test]
+    fn search_a_populated_house() {
+        let mut kitchen = SmartRoom::new("kitchen");
+        kitchen.plug(SmartSocket::new("socket-kitchen-1")).unwrap();
+        kitchen
+            .plug(SmartThermometer::new("thermo-kitchen"))
+            .unwrap();
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(SmartThermometer::new("Thermo-hall")).unwrap();
+        hall.plug(SmartSocket::new("socket-hall-1")).unwrap();
+        let mut house = SmartHouse::new("Home");
+        house.add(kitchen).unwrap();
+        house.add(hall).unwrap();
+        house.add(SmartRoom::new("kitchenette")).unwrap();
+
+        let found = |matches: Vec<DeviceMatch>| -> Vec<(String, String)> {
+            matches.into_iter().map(|m| (m.room, m.device)).collect()
+        };
+        assert_eq!(
+            found(house.search("therm*")),
+            [("kitchen".into(), "thermo-kitchen".into())]
+        );
+        assert_eq!(house.search(Glob::new("therm*").ignore_case()).len(), 2);
+        assert_eq!(
+            house.search("*-?-*"),
+            Vec::<DeviceMatch>::new(),
+            "single-character segments only"
+        );
+        assert_eq!(
+            house.search("socket-*-1"),
+            [
+                DeviceMatch {
+                    room: "kitchen".into(),
+                    device: "socket-kitchen-1".into(),
+                    kind: Some(DeviceKind::Socket),
+                },
+                DeviceMatch {
+                    room: "hall".into(),
+                    device: "socket-hall-1".into(),
+                    kind: Some(DeviceKind::Socket),
+                },
+            ]
+        );
+
+        let rooms: Vec<_> = house
+            .search_rooms("kitchen*")
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(rooms, ["kitchen", "kitchenette"]);
+        assert!(house.search_rooms("?").is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn regex_search_and_its_inverse() {
+        use crate::regex::Regex;
+
+        let mut kitchen = SmartRoom::new("kitchen");
+        kitchen.plug(SmartSocket::new("socket-kitchen-1")).unwrap();
+        kitchen.plug(SmartSocket::new("Kettle")).unwrap();
+        let mut hall = SmartRoom::new("hall");
+        hall.plug(SmartThermometer::new("thermo-hall")).unwrap();
+        hall.plug(SmartThermometer::new("thermo hall 2")).unwrap();
+        let mut house = SmartHouse::new("Home");
+        house.add(kitchen).unwrap();
+        house.add(hall).unwrap();
+
+        let convention = Regex::new(r"^(socket|thermo)-[a-z]+(-\d+)?$").unwrap();
+        let names = |matches: Vec<DeviceMatch>| -> Vec<(String, String)> {
+            matches.into_iter().map(|m| (m.room, m.device)).collect()
+        };
+        assert_eq!(
+            names(house.search_regex(&convention)),
+            [
+                ("kitchen".into(), "socket-kitchen-1".into()),
+                ("hall".into(), "thermo-hall".into())
+            ]
+        );
+        assert_eq!(
+            house.find_nonconforming(&convention),
+            [
+                DeviceMatch {
+                    room: "kitchen".into(),
+                    device: "Kettle".into(),
+                    kind: Some(DeviceKind::Socket),
+                },
+                DeviceMatch {
+                    room: "hall".into(),
+                    device: "thermo hall 2".into(),
+                    kind: Some(DeviceKind::Thermometer),
+                },
+            ]
+        );
+        assert_eq!(house.search_regex(&Regex::new("hall").unwrap()).len(), 2);
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    convert::Infallible,
+    error::Error,
+    sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{
+    events::Deferred, DeviceId, IntoDevice, Pluggable, Reportable, RoomId, SmartHouse,
+    SmartHouseError, SmartRoom,
+};
+
+/// A house shared between threads.
+///
+/// Every method takes the lock for the duration of one call only, and closures passed to
+/// `with_*` run while it is held, so no guard can escape (or live across an `.await`).
+#[derive(Debug, Clone)]
+pub struct SharedSmartHouse {
+    inner: Arc<RwLock<SmartHouse>>,
+}
+
+impl SharedSmartHouse {
+    pub fn new(house: SmartHouse) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(house)),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, SmartHouse> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, SmartHouse> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn with_house<R>(&self, f: impl FnOnce(&SmartHouse) -> R) -> R {
+        f(&self.read())
+    }
+
+    pub fn with_room<R>(&self, room: &str, f: impl FnOnce(&SmartRoom) -> R) -> Option<R> {
+        let house = self.read();
+        house.room(room).map(f)
+    }
+
+    pub fn with_device<R>(
+        &self,
+        room: &str,
+        device: &str,
+        f: impl FnOnce(&dyn Pluggable) -> R,
+    ) -> Option<R> {
+        self.with_room(room, |r| r.device(device).map(|d| f(d.as_ref())))
+            .flatten()
+    }
+
+    pub fn add_room(&self, room: SmartRoom) -> Result<RoomId, SmartHouseError> {
+        self.write().add(room)
+    }
+
+    pub fn plug(&self, room: &str, device: impl IntoDevice) -> Result<DeviceId, SmartHouseError> {
+        self.write().plug(room, device)
+    }
+
+    pub fn report<T: Reportable>(&self, report: T) -> Result<String, Box<dyn Error>> {
+        self.read().create_report(report)
+    }
+}
+
+impl From<SmartHouse> for SharedSmartHouse {
+    fn from(house: SmartHouse) -> Self {
+        Self::new(house)
+    }
+}
+
+/// A copy-on-write house for read-heavy use.
+///
+/// [`load`](Self::load) hands out an immutable snapshot; the internal lock is held only
+/// for the `Arc` clone, so readers never wait for a running update. Writers clone the
+/// current house, change the copy and swap it in, one writer at a time. A reader may
+/// keep working with a snapshot that an update has already replaced, but it never sees
+/// a half-applied update.
+///
+/// The copy an update works on is the same house, not a [clone](SmartHouse#impl-Clone-for-SmartHouse):
+/// handles, subscriptions, policies, rules, alerts, budget settings, undo history, the
+/// audit log and rate-limit buckets all carry over, and telemetry and metrics are shared.
+/// Rule states, alerts and rate-limit tokens changed through an old snapshot after it
+/// was replaced stay with that snapshot. Devices are always shared.
+///
+/// Subscribers hear about an update's changes once readers can see them, and not at all
+/// if the update fails. They run while the next update waits, so they must not update
+/// the cell themselves.
+#[derive(Debug)]
+pub struct HouseCell {
+    current: RwLock<Arc<SmartHouse>>,
+    writer: Mutex<()>,
+}
+
+impl HouseCell {
+    pub fn new(house: SmartHouse) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(house)),
+            writer: Mutex::new(()),
+        }
+    }
+
+    pub fn load(&self) -> Arc<SmartHouse> {
+        let current = self.current.read().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(&current)
+    }
+
+    pub fn update<R>(&self, f: impl FnOnce(&mut SmartHouse) -> R) -> R {
+        let Ok(result) = self.try_update(|house| Ok::<_, Infallible>(f(house)));
+        result
+    }
+
+    /// Like [`update`](Self::update), but the copy is thrown away if `f` fails.
+    pub fn try_update<R, E>(
+        &self,
+        f: impl FnOnce(&mut SmartHouse) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut house = self.load().fork();
+        house.listeners.deferred = Some(Deferred::default());
+        let result = f(&mut house)?;
+        let deferred = house.listeners.deferred.take().unwrap_or_default();
+        for event in &deferred.events {
+            house.commit_change(event);
+        }
+        let house = Arc::new(house);
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::clone(&house);
+        // подписчики и драйверы узнают об изменениях, когда читатели уже видят новый дом
+        for event in &deferred.events {
+            house.listeners.emit(event);
+        }
+        for (driver, device) in deferred.releases {
+            driver.disconnect(device);
+        }
+        Ok(result)
+    }
+}
+
+impl From<SmartHouse> for HouseCell {
+    fn from(house: SmartHouse) -> Self {
+        Self::new(house)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+    };
+
+    use super::*;
+    use crate::{
+        testing::fixtures::{large_house, small_house},
+        HouseEvent, Named, OwningDeviceInfoProvider, PolicyContext, PolicyViolation, SmartSocket,
+    };
+
+    #[test]
+    fn concurrent_plugging() {
+        const ROOMS: usize = 8;
+        const DEVICES: usize = 100;
+
+        let shared = SharedSmartHouse::new(large_house(ROOMS, 0));
+
+        let workers: Vec<_> = (0..ROOMS)
+            .map(|r| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for d in 0..DEVICES {
+                        let socket = SmartSocket::new(format!("socket {d}"));
+                        shared.plug(&format!("room-{r}"), Arc::new(socket)).unwrap();
+                        shared.with_room(&format!("room-{r}"), |room| room.device_names().count());
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        for r in 0..ROOMS {
+            let count = shared.with_room(&format!("room-{r}"), |room| room.device_names().count());
+            assert_eq!(count, Some(DEVICES));
+        }
+        let total = shared.with_house(|house| house.get_rooms().len());
+        assert_eq!(total, ROOMS);
+    }
+
+    #[test]
+    fn lookups_and_errors() {
+        let shared = SharedSmartHouse::from(small_house());
+        assert!(shared.add_room(SmartRoom::new("limb")).is_err());
+        assert!(shared
+            .plug("attic", Arc::new(SmartSocket::new("s3")))
+            .is_err());
+
+        let name = shared.with_device("limb", "t1", |d| d.name().to_string());
+        assert_eq!(name.as_deref(), Some("t1"));
+        assert!(shared.with_device("limb", "nope", |_| ()).is_none());
+        assert!(shared.with_device("lust", "t1", |_| ()).is_none());
+    }
+
+    #[test]
+    fn report_through_lock() {
+        let shared = SharedSmartHouse::new(small_house());
+        let socket = SmartSocket::new("s1");
+        let report = shared.report(OwningDeviceInfoProvider { socket }).unwrap();
+        assert!(report.contains("Socket[s1]"));
+    }
+
+    #[test]
+    fn cell_readers_see_whole_updates() {
+        const UPDATES: usize = 200;
+
+        let cell = Arc::new(HouseCell::new(SmartHouse::new("hell")));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let cell = Arc::clone(&cell);
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    let mut seen = 0;
+                    while !done.load(Ordering::SeqCst) {
+                        let house = cell.load();
+                        let rooms = house.get_rooms().len();
+                        assert!(rooms >= seen, "Snapshots never go back");
+                        // каждое обновление добавляет комнату вместе с розеткой
+                        for room in house.get_rooms() {
+                            assert_eq!(room.device_names().count(), 1);
+                        }
+                        seen = rooms;
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..UPDATES {
+            cell.update(|house| {
+                let mut room = SmartRoom::new(format!("room {i}"));
+                room.plug(SmartSocket::new(format!("socket {i}"))).unwrap();
+                house.add(room).unwrap();
+            });
+        }
+        done.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_eq!(cell.load().get_rooms().len(), UPDATES);
+    }
+
+    #[test]
+    fn updates_keep_subscribers_and_policies() {
+        let mut house = small_house();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        house.subscribe(Box::new(move |event| {
+            sink.lock().unwrap().push(event.clone());
+        }));
+        house.add_policy(Box::new(|context| match context {
+            PolicyContext::AddRoom { room, .. } if room.name() == "attic" => {
+                Err(PolicyViolation("no attic".to_string()))
+            }
+            _ => Ok(()),
+        }));
+        let hall = house.add(SmartRoom::new("hall")).unwrap();
+
+        let cell = HouseCell::new(house);
+        cell.update(|house| house.plug("hall", SmartSocket::new("s3")))
+            .unwrap();
+        assert!(matches!(
+            cell.update(|house| house.add(SmartRoom::new("attic"))),
+            Err(SmartHouseError::Policy(_))
+        ));
+        // хэндлы старого снимка тоже в силе
+        assert_eq!(cell.load().room_by_id(hall).map(Named::name), Ok("hall"));
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                HouseEvent::RoomAdded {
+                    room: "hall".to_string()
+                },
+                HouseEvent::DevicePlugged {
+                    room: "hall".to_string(),
+                    device: "s3".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_update_is_discarded() {
+        let cell = HouseCell::from(small_house());
+        let before = cell.load();
+        let result = cell.try_update(|house| {
+            house.add(SmartRoom::new("hall"))?;
+            house.add(SmartRoom::new("limb"))
+        });
+        assert!(result.is_err());
+        assert!(Arc::ptr_eq(&before, &cell.load()));
+        assert_eq!(cell.load().get_rooms().len(), 2);
+    }
+}
@@ -0,0 +1,49 @@
+use std::{error::Error, sync::mpsc};
+
+use crate::log::error;
+
+/// Where background threads (servers, UDP emitters and receivers, the heartbeat
+/// monitor) send the errors they cannot return to anyone.
+///
+/// `component` names the kind of thread: `socket-server`, `house-server`,
+/// `thermo-emitter`, `thermo-receiver` or `heartbeat`.
+pub trait ErrorSink: Send + Sync {
+    fn report(&self, component: &str, error: &dyn Error);
+}
+
+/// Logs every error at error level. Background components use it unless given another sink.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl ErrorSink for LogSink {
+    fn report(&self, component: &str, e: &dyn Error) {
+        error!("{}: {}", component, e);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportedError {
+    pub component: String,
+    pub message: String,
+}
+
+/// Sends every error to a channel, so tests can wait for and count them.
+#[derive(Debug, Clone)]
+pub struct ChannelSink(mpsc::Sender<ReportedError>);
+
+impl ChannelSink {
+    pub fn new() -> (Self, mpsc::Receiver<ReportedError>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self(sender), receiver)
+    }
+}
+
+impl ErrorSink for ChannelSink {
+    fn report(&self, component: &str, error: &dyn Error) {
+        // получатель мог уже уйти, ошибка тогда никому не нужна
+        let _ = self.0.send(ReportedError {
+            component: component.to_string(),
+            message: error.to_string(),
+        });
+    }
+}
@@ -0,0 +1,293 @@
+//! Feeding sensors from an outside source of readings.
+//!
+//! A [`SourceDriver`] asks its [`ReadingSource`] for a reading for every registered
+//! sensor and records it, once per [`step`](SourceDriver::step) or once per interval on
+//! a background thread. [`SineDaySource`] makes up a daily temperature curve for demos.
+
+use core::{f64::consts::TAU, fmt};
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{udp::Sensor, Clock, SystemClock};
+
+/// Where readings come from, such as a weather service or a simulation.
+pub trait ReadingSource: Send {
+    /// The reading for `device` at `now`, or `None` to leave the device as it is.
+    fn next_reading(&mut self, device: &str, now: SystemTime) -> Option<f64>;
+}
+
+/// A temperature that follows a cosine over the UTC day, highest at the peak hour and
+/// lowest twelve hours later, with uniform noise on top. The noise is pseudo-random from
+/// a seed, so a run can be repeated.
+#[derive(Debug, Clone)]
+pub struct SineDaySource {
+    pub mean: f64,
+    pub amplitude: f64,
+    pub peak_hour: f64,
+    pub noise: f64,
+    state: u64,
+}
+
+impl Default for SineDaySource {
+    /// 15 to 25 °C, warmest at 15:00, with 0.3 °C of noise.
+    fn default() -> Self {
+        Self::new(20.0, 5.0).noise(0.3, 0x5eed)
+    }
+}
+
+impl SineDaySource {
+    pub fn new(mean: f64, amplitude: f64) -> Self {
+        Self {
+            mean,
+            amplitude,
+            peak_hour: 15.0,
+            noise: 0.0,
+            state: 1,
+        }
+    }
+
+    pub fn peak_at(mut self, hour: f64) -> Self {
+        self.peak_hour = hour;
+        self
+    }
+
+    /// Adds up to `celsius` of noise either way.
+    pub fn noise(mut self, celsius: f64, seed: u64) -> Self {
+        self.noise = celsius;
+        // у xorshift нулевое состояние так и остаётся нулём
+        self.state = seed.max(1);
+        self
+    }
+
+    /// The curve without noise.
+    pub fn curve(&self, now: SystemTime) -> f64 {
+        let secs = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |since| since.as_secs_f64());
+        let hours = (secs / 3600.0).rem_euclid(24.0);
+        self.mean + self.amplitude * (TAU * (hours - self.peak_hour) / 24.0).cos()
+    }
+
+    // xorshift64*, равномерно в [-1, 1)
+    fn jitter(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        bits as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
+
+impl ReadingSource for SineDaySource {
+    fn next_reading(&mut self, _device: &str, now: SystemTime) -> Option<f64> {
+        let noise = match self.noise == 0.0 {
+            true => 0.0,
+            false => self.noise * self.jitter(),
+        };
+        Some(self.curve(now) + noise)
+    }
+}
+
+struct Fed {
+    name: String,
+    sensor: Arc<dyn Sensor>,
+}
+
+/// Pulls readings from a source into sensors.
+pub struct SourceDriver {
+    source: Box<dyn ReadingSource>,
+    sensors: Vec<Fed>,
+    clock: Arc<dyn Clock>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl SourceDriver {
+    pub fn new(source: impl ReadingSource + 'static) -> Self {
+        Self {
+            source: Box::new(source),
+            sensors: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// `name` is what the source is asked about; it does not have to be the device name.
+    pub fn register(&mut self, name: impl Into<String>, sensor: Arc<dyn Sensor>) {
+        self.sensors.push(Fed {
+            name: name.into(),
+            sensor,
+        });
+    }
+
+    /// Asks the source once for every sensor, in the order they were registered, and
+    /// returns the readings recorded.
+    pub fn step(&mut self) -> Vec<(String, f64)> {
+        let now = self.clock.now();
+        let mut pushed = Vec::new();
+        for fed in &self.sensors {
+            if let Some(value) = self.source.next_reading(&fed.name, now) {
+                fed.sensor.record(value, now);
+                pushed.push((fed.name.clone(), value));
+            }
+        }
+        pushed
+    }
+
+    /// Steps on a background thread every `interval` until the handle is stopped or
+    /// dropped.
+    pub fn spawn(mut self, interval: Duration) -> DriverHandle {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || loop {
+                self.step();
+
+                let (stopped, wakeup) = &*stop;
+                let stopped = wakeup
+                    .wait_timeout_while(lock(stopped), interval, |stopped| !*stopped)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+                if *stopped {
+                    break;
+                }
+            })
+        };
+        DriverHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl fmt::Debug for SourceDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sensors: Vec<_> = self.sensors.iter().map(|fed| &fed.name).collect();
+        f.debug_struct("SourceDriver")
+            .field("sensors", &sensors)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A running [`SourceDriver`]. Dropping the handle stops it as well.
+#[derive(Debug)]
+pub struct DriverHandle {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DriverHandle {
+    /// Stops the driver and waits for the step in progress.
+    pub fn stop(self) {}
+}
+
+impl Drop for DriverHandle {
+    fn drop(&mut self) {
+        let (stopped, wakeup) = &*self.stop;
+        *lock(stopped) = true;
+        wakeup.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, time::Instant};
+
+    use super::*;
+    use crate::{testing::ManualClock, SmartThermometer};
+
+    // Отдаёт заранее заданные значения и запоминает, о чём спрашивали
+    struct Script {
+        values: VecDeque<f64>,
+        asked: Arc<Mutex<Vec<(String, SystemTime)>>>,
+    }
+
+    impl ReadingSource for Script {
+        fn next_reading(&mut self, device: &str, now: SystemTime) -> Option<f64> {
+            lock(&self.asked).push((device.to_string(), now));
+            self.values.pop_front()
+        }
+    }
+
+    #[test]
+    fn steps_push_what_the_source_gives() {
+        let clock = ManualClock::default();
+        let start = clock.now();
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let mut driver = SourceDriver::new(Script {
+            values: VecDeque::from([21.0, 5.5, 22.0]),
+            asked: Arc::clone(&asked),
+        })
+        .with_clock(clock.clone());
+        let indoor = Arc::new(SmartThermometer::new("indoor"));
+        let outdoor = Arc::new(SmartThermometer::new("outdoor"));
+        driver.register("indoor", indoor.clone());
+        driver.register("outdoor", outdoor.clone());
+
+        assert_eq!(
+            driver.step(),
+            [("indoor".to_string(), 21.0), ("outdoor".to_string(), 5.5)]
+        );
+        assert_eq!(indoor.temperature(), Some(21.0));
+        assert_eq!(outdoor.temperature(), Some(5.5));
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(driver.step(), [("indoor".to_string(), 22.0)]);
+        // источник промолчал: прежнее показание остаётся
+        assert_eq!(outdoor.temperature(), Some(5.5));
+        assert_eq!(
+            lock(&asked)[2..],
+            [
+                ("indoor".to_string(), start + Duration::from_secs(60)),
+                ("outdoor".to_string(), start + Duration::from_secs(60)),
+            ]
+        );
+    }
+
+    #[test]
+    fn sine_day_peaks_at_the_peak_hour() {
+        let day = UNIX_EPOCH + Duration::from_secs(19_675 * 86_400);
+        let hour = |h: u64| day + Duration::from_secs(h * 3600);
+        let mut source = SineDaySource::new(20.0, 5.0).peak_at(15.0);
+        assert!((source.next_reading("t1", hour(15)).unwrap() - 25.0).abs() < 1e-9);
+        assert!((source.next_reading("t1", hour(3)).unwrap() - 15.0).abs() < 1e-9);
+        assert!((source.curve(hour(9)) - 20.0).abs() < 1e-9);
+
+        let mut noisy = SineDaySource::new(20.0, 5.0).noise(0.5, 42);
+        let readings: Vec<_> = (0..100)
+            .map(|_| noisy.next_reading("t1", hour(15)).unwrap())
+            .collect();
+        assert!(readings.iter().all(|c| (24.5..=25.5).contains(c)));
+        assert!(readings.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn spawned_driver_stops_on_request() {
+        let thermometer = Arc::new(SmartThermometer::new("t1"));
+        let mut driver = SourceDriver::new(SineDaySource::default());
+        driver.register("t1", thermometer.clone());
+        let handle = driver.spawn(Duration::from_millis(5));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while thermometer.temperature().is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        handle.stop();
+        let last = thermometer.temperature();
+        assert!(last.is_some());
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(thermometer.temperature(), last);
+    }
+}
@@ -0,0 +1,1016 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    io,
+    sync::{atomic::Ordering, Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    atomic::AtomicU64, audit::json_string, devices::downcast, udp::ThermometerReceiver,
+    DeviceLocation, Pluggable, Reading, SmartHouse, SmartRoom, SmartSocket, SmartThermometer,
+};
+
+/// Points a query returns unless it asks for another cap.
+pub const DEFAULT_QUERY_LIMIT: usize = 1000;
+
+/// Points buffered before they are handed to the backend.
+pub const DEFAULT_TELEMETRY_BATCH: usize = 64;
+
+/// One reading of one device, or a minute of readings after compaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub room: String,
+    pub device: String,
+    pub metric: Reading,
+    /// The reading's time, or the start of the minute for a downsampled point.
+    pub at: SystemTime,
+    /// The reading, or the mean of the minute.
+    pub value: f64,
+    pub resolution: Resolution,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Raw,
+    /// Every raw point of the minute, replaced by one point, see [`Retention`].
+    Minute(Bucket),
+}
+
+/// The readings a downsampled point stands for. `sum` is kept so that buckets merge
+/// exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bucket {
+    pub min: f64,
+    pub max: f64,
+    pub sum: f64,
+    pub count: usize,
+}
+
+impl Bucket {
+    fn of(value: f64) -> Self {
+        Self {
+            min: value,
+            max: value,
+            sum: value,
+            count: 1,
+        }
+    }
+
+    fn merge(&mut self, other: Bucket) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+}
+
+/// How long a [`Telemetry`] store keeps its points: raw for `raw`, then downsampled to
+/// one point per minute for another `downsampled`, then dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    pub raw: Duration,
+    pub downsampled: Duration,
+}
+
+impl Retention {
+    pub fn new(raw: Duration, downsampled: Duration) -> Self {
+        Self { raw, downsampled }
+    }
+}
+
+/// Which points to return: every filter left unset matches everything. The range is
+/// half-open, `from` included and `until` not, so adjacent ranges never share a point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryQuery {
+    pub device: Option<DeviceLocation>,
+    pub metric: Option<Reading>,
+    pub from: Option<SystemTime>,
+    pub until: Option<SystemTime>,
+    pub limit: usize,
+}
+
+impl Default for TelemetryQuery {
+    fn default() -> Self {
+        Self {
+            device: None,
+            metric: None,
+            from: None,
+            until: None,
+            limit: DEFAULT_QUERY_LIMIT,
+        }
+    }
+}
+
+impl TelemetryQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A path without a room matches the device name in any room.
+    pub fn device(mut self, path: DeviceLocation) -> Self {
+        self.device = Some(path);
+        self
+    }
+
+    pub fn metric(mut self, metric: Reading) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+
+    pub fn from(mut self, from: SystemTime) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    pub fn until(mut self, until: SystemTime) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn matches(&self, point: &Point) -> bool {
+        self.device.as_ref().is_none_or(|path| {
+            path.device == point.device && (path.room.is_empty() || path.room == point.room)
+        }) && self.metric.is_none_or(|metric| metric == point.metric)
+            && self.from.is_none_or(|from| point.at >= from)
+            && self.until.is_none_or(|until| point.at < until)
+    }
+}
+
+/// Where a [`Telemetry`] store keeps its points.
+pub trait TelemetryBackend: Send {
+    /// Stores a batch of points. Batches come in the order they were recorded, but points
+    /// may be out of order when the clock was set back.
+    fn append(&mut self, points: Vec<Point>);
+
+    /// Calls `visit` for the points matching `query`, oldest first, at most `query.limit`
+    /// of them.
+    fn scan(&self, query: &TelemetryQuery, visit: &mut dyn FnMut(&Point));
+
+    /// Applies `retention` as of `now`. Only whole minutes older than `retention.raw` are
+    /// downsampled, and downsampled points are never downsampled again, so compacting
+    /// twice with the same `now` changes nothing.
+    fn compact(&mut self, retention: &Retention, now: SystemTime);
+}
+
+fn minute(at: SystemTime) -> SystemTime {
+    let secs = at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    UNIX_EPOCH + Duration::from_secs(secs - secs % 60)
+}
+
+/// Points kept in memory, sorted by time.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    points: Vec<Point>,
+}
+
+impl TelemetryBackend for MemoryBackend {
+    fn append(&mut self, points: Vec<Point>) {
+        for point in points {
+            // обычно точка новее всех, и вставка сводится к push
+            let at = self.points.partition_point(|stored| stored.at <= point.at);
+            self.points.insert(at, point);
+        }
+    }
+
+    fn scan(&self, query: &TelemetryQuery, visit: &mut dyn FnMut(&Point)) {
+        let start = match query.from {
+            Some(from) => self.points.partition_point(|point| point.at < from),
+            None => 0,
+        };
+        self.points[start..]
+            .iter()
+            .take_while(|point| query.until.is_none_or(|until| point.at < until))
+            .filter(|point| query.matches(point))
+            .take(query.limit)
+            .for_each(visit);
+    }
+
+    fn compact(&mut self, retention: &Retention, now: SystemTime) {
+        let raw_until = now.checked_sub(retention.raw).unwrap_or(UNIX_EPOCH);
+        let drop_until = raw_until
+            .checked_sub(retention.downsampled)
+            .unwrap_or(UNIX_EPOCH);
+        let due = |point: &Point| {
+            point.at < drop_until
+                || point.resolution == Resolution::Raw
+                    && minute(point.at) + Duration::from_secs(60) <= raw_until
+        };
+        if !self.points.iter().any(due) {
+            return;
+        }
+
+        // все минутные точки идут в общую кучу, чтобы опоздавшие сырые точки слились с ними
+        let mut buckets = BTreeMap::new();
+        let mut kept = Vec::with_capacity(self.points.len());
+        for point in self.points.drain(..) {
+            if point.at < drop_until {
+                continue;
+            }
+            let bucket = match point.resolution {
+                Resolution::Minute(bucket) => bucket,
+                Resolution::Raw if due(&point) => Bucket::of(point.value),
+                Resolution::Raw => {
+                    kept.push(point);
+                    continue;
+                }
+            };
+            let key = (minute(point.at), point.room, point.device, point.metric);
+            buckets
+                .entry(key)
+                .and_modify(|merged: &mut Bucket| merged.merge(bucket))
+                .or_insert(bucket);
+        }
+        for ((at, room, device, metric), bucket) in buckets {
+            if at < drop_until {
+                continue;
+            }
+            kept.push(Point {
+                room,
+                device,
+                metric,
+                at,
+                value: bucket.mean(),
+                resolution: Resolution::Minute(bucket),
+            });
+        }
+        kept.sort_by_key(|point| point.at);
+        self.points = kept;
+    }
+}
+
+/// A history of device readings for the whole house, see [`SmartHouse::with_telemetry`].
+///
+/// Recording only appends to a buffer; the buffer goes to the backend once it holds a
+/// batch of points, on [`flush`](Self::flush), and before every query.
+pub struct Telemetry {
+    buffer: Mutex<Vec<Point>>,
+    batch: usize,
+    backend: Mutex<Box<dyn TelemetryBackend>>,
+    retention: Option<Retention>,
+    // поколение состояния устройств на момент последнего опроса
+    sampled: AtomicU64,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::with_backend(MemoryBackend::default())
+    }
+}
+
+impl fmt::Debug for Telemetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Telemetry")
+            .field("buffered", &lock(&self.buffer).len())
+            .field("batch", &self.batch)
+            .field("retention", &self.retention)
+            .finish_non_exhaustive()
+    }
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Telemetry {
+    /// A store kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_backend(backend: impl TelemetryBackend + 'static) -> Self {
+        Self {
+            buffer: Mutex::new(Vec::new()),
+            batch: DEFAULT_TELEMETRY_BATCH,
+            backend: Mutex::new(Box::new(backend)),
+            retention: None,
+            sampled: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// How many points to buffer before writing them to the backend.
+    pub fn batch(mut self, points: usize) -> Self {
+        self.batch = points.max(1);
+        self
+    }
+
+    /// Points are kept forever unless a retention is set and
+    /// [`compact`](Self::compact) runs.
+    pub fn retention(mut self, retention: Retention) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Downsamples and drops points as the retention says, as of `now`. Does nothing
+    /// without a retention.
+    pub fn compact(&self, now: SystemTime) {
+        let Some(retention) = &self.retention else {
+            return;
+        };
+        self.flush();
+        lock(&self.backend).compact(retention, now);
+    }
+
+    pub fn record(&self, point: Point) {
+        let mut buffer = lock(&self.buffer);
+        buffer.push(point);
+        if buffer.len() >= self.batch {
+            let points = std::mem::take(&mut *buffer);
+            drop(buffer);
+            lock(&self.backend).append(points);
+        }
+    }
+
+    pub fn flush(&self) {
+        let points = std::mem::take(&mut *lock(&self.buffer));
+        if !points.is_empty() {
+            lock(&self.backend).append(points);
+        }
+    }
+
+    /// Matching points, oldest first, at most `query.limit` of them. Raw and downsampled
+    /// points come together, told apart by their [`resolution`](Point::resolution).
+    pub fn query(&self, query: &TelemetryQuery) -> Vec<Point> {
+        let mut points = Vec::new();
+        self.scan(query, &mut |point| points.push(point.clone()));
+        points
+    }
+
+    /// Like [`query`](Self::query), handing the points over one by one instead of
+    /// collecting them.
+    pub fn scan(&self, query: &TelemetryQuery, visit: &mut dyn FnMut(&Point)) {
+        self.flush();
+        lock(&self.backend).scan(query, visit);
+    }
+
+    fn record_device(&self, room: &SmartRoom, device: &dyn Pluggable, at: SystemTime) -> usize {
+        let readings = readings(device);
+        for &(metric, value) in &readings {
+            self.record(Point {
+                room: room.name.clone(),
+                device: device.name().to_string(),
+                metric,
+                at,
+                value,
+                resolution: Resolution::Raw,
+            });
+        }
+        readings.len()
+    }
+}
+
+// Что можно снять с устройства прямо сейчас
+fn readings(device: &dyn Pluggable) -> Vec<(Reading, f64)> {
+    if let Some(socket) = downcast::<SmartSocket>(device) {
+        return vec![(Reading::Power, socket.power())];
+    }
+    let temperature = downcast::<SmartThermometer>(device)
+        .map(SmartThermometer::temperature)
+        .or_else(|| downcast::<ThermometerReceiver>(device).map(ThermometerReceiver::temperature));
+    match temperature {
+        Some(Some(celsius)) => vec![(Reading::Temperature, celsius)],
+        _ => Vec::new(),
+    }
+}
+
+impl Telemetry {
+    /// Writes the matching points as CSV, one point per line after a header, in the
+    /// columns `timestamp,room,device,metric,value`. Timestamps are RFC 3339 in UTC;
+    /// fields with commas, quotes or line breaks are quoted. Points are written as they
+    /// are read, without collecting them first.
+    pub fn export_csv(&self, query: &TelemetryQuery, out: &mut impl io::Write) -> io::Result<()> {
+        out.write_all(b"timestamp,room,device,metric,value\n")?;
+        self.export(query, out, |line, point| {
+            let _ = write!(line, "{},", rfc3339(point.at));
+            csv_field(line, &point.room);
+            line.push(',');
+            csv_field(line, &point.device);
+            let _ = writeln!(line, ",{},{}", point.metric, point.value);
+        })
+    }
+
+    /// Writes the matching points as one JSON object per line, with the same fields as
+    /// [`export_csv`](Self::export_csv), e.g.
+    /// `{"timestamp":"2023-11-14T22:13:20Z","room":"kitchen","device":"t1","metric":"temperature","value":21.5}`.
+    /// A value that is not a finite number is written as `null`.
+    pub fn export_jsonl(&self, query: &TelemetryQuery, out: &mut impl io::Write) -> io::Result<()> {
+        self.export(query, out, |line, point| {
+            let _ = write!(line, "{{\"timestamp\":\"{}\",\"room\":", rfc3339(point.at));
+            json_string(line, &point.room);
+            line.push_str(",\"device\":");
+            json_string(line, &point.device);
+            let _ = write!(line, ",\"metric\":\"{}\",\"value\":", point.metric);
+            match point.value.is_finite() {
+                true => {
+                    let _ = write!(line, "{}", point.value);
+                }
+                false => line.push_str("null"),
+            }
+            line.push_str("}\n");
+        })
+    }
+
+    // После первой ошибки записи остальные точки пропускаются
+    fn export(
+        &self,
+        query: &TelemetryQuery,
+        out: &mut impl io::Write,
+        format: impl Fn(&mut String, &Point),
+    ) -> io::Result<()> {
+        let mut line = String::new();
+        let mut written = Ok(());
+        self.scan(query, &mut |point| {
+            if written.is_ok() {
+                line.clear();
+                format(&mut line, point);
+                written = out.write_all(line.as_bytes());
+            }
+        });
+        written
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    Min,
+    Max,
+    Mean,
+    /// The latest value in the window.
+    Last,
+    /// How many readings the window holds; a downsampled point counts as its readings.
+    Count,
+}
+
+/// What [`Telemetry::aggregate`] does with a window that holds no points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyWindows {
+    #[default]
+    Skip,
+    /// Emitted with no value.
+    Emit,
+}
+
+/// One window of one series, a series being the readings of one metric of one device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub room: String,
+    pub device: String,
+    pub metric: Reading,
+    pub start: SystemTime,
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Accumulator {
+    bucket: Bucket,
+    last: f64,
+}
+
+impl Accumulator {
+    fn of(point: &Point) -> Self {
+        let bucket = match point.resolution {
+            Resolution::Raw => Bucket::of(point.value),
+            Resolution::Minute(bucket) => bucket,
+        };
+        Self {
+            bucket,
+            last: point.value,
+        }
+    }
+
+    fn value(&self, aggregation: Aggregation) -> f64 {
+        match aggregation {
+            Aggregation::Min => self.bucket.min,
+            Aggregation::Max => self.bucket.max,
+            Aggregation::Mean => self.bucket.mean(),
+            Aggregation::Last => self.last,
+            Aggregation::Count => self.bucket.count as f64,
+        }
+    }
+}
+
+impl Telemetry {
+    /// Aggregates the matching points per series over windows of `window`, series sorted
+    /// by room, device and metric, windows oldest first. Downsampled points enter with
+    /// all the readings they stand for.
+    ///
+    /// Windows are aligned to the Unix epoch, so hourly windows start on the hour and
+    /// daily ones at midnight UTC. With [`EmptyWindows::Emit`] a series gets every window
+    /// from its first point to its last, or over the query's range when it has one.
+    /// `query.limit` caps the windows returned, not the points read.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is zero.
+    pub fn aggregate(
+        &self,
+        query: &TelemetryQuery,
+        window: Duration,
+        aggregation: Aggregation,
+        empty: EmptyWindows,
+    ) -> Vec<Window> {
+        assert!(!window.is_zero(), "aggregation window must not be zero");
+        let width = window.as_nanos();
+        let index = |at: SystemTime| {
+            at.duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_nanos() / width)
+        };
+
+        let mut series: BTreeMap<_, BTreeMap<u128, Accumulator>> = BTreeMap::new();
+        let everything = TelemetryQuery {
+            limit: usize::MAX,
+            ..query.clone()
+        };
+        self.scan(&everything, &mut |point| {
+            let key = (point.room.clone(), point.device.clone(), point.metric);
+            let windows = series.entry(key).or_default();
+            let next = Accumulator::of(point);
+            windows
+                .entry(index(point.at))
+                .and_modify(|acc| {
+                    acc.bucket.merge(next.bucket);
+                    acc.last = next.last;
+                })
+                .or_insert(next);
+        });
+
+        let mut out = Vec::new();
+        for ((room, device, metric), windows) in series {
+            let (Some(&first), Some(&last)) = (windows.keys().next(), windows.keys().last()) else {
+                continue;
+            };
+            let first = query.from.map_or(first, index);
+            let last = query
+                .until
+                .and_then(|until| until.checked_sub(Duration::from_nanos(1)))
+                .map_or(last, index);
+            let indices: Box<dyn Iterator<Item = u128>> = match empty {
+                EmptyWindows::Skip => Box::new(windows.keys().copied()),
+                EmptyWindows::Emit => Box::new(first..=last),
+            };
+            for i in indices {
+                let start = UNIX_EPOCH + Duration::from_nanos((i * width) as u64);
+                out.push(Window {
+                    room: room.clone(),
+                    device: device.clone(),
+                    metric,
+                    start,
+                    value: windows.get(&i).map(|acc| acc.value(aggregation)),
+                });
+                if out.len() == query.limit {
+                    return out;
+                }
+            }
+        }
+        out
+    }
+}
+
+fn csv_field(out: &mut String, value: &str) {
+    if !value.contains([',', '"', '\n', '\r']) {
+        out.push_str(value);
+        return;
+    }
+    out.push('"');
+    out.push_str(&value.replace('"', "\"\""));
+    out.push('"');
+}
+
+// Секунды с долями, если они есть: 2023-11-14T22:13:20.5Z
+fn rfc3339(at: SystemTime) -> String {
+    let since = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (year, month, day) = civil(secs / 86_400);
+    let time = secs % 86_400;
+    let mut out = format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+    if since.subsec_nanos() != 0 {
+        let fraction = format!("{:09}", since.subsec_nanos());
+        let _ = write!(out, ".{}", fraction.trim_end_matches('0'));
+    }
+    out.push('Z');
+    out
+}
+
+// Дата по номеру дня от эпохи, алгоритм Говарда Хиннанта
+pub(crate) fn civil(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+// Обратное к civil, для дат не раньше эпохи
+pub(crate) fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+impl SmartHouse {
+    /// Records device readings into `telemetry`, stamped by the house's clock (see
+    /// [`set_clock`](Self::set_clock)). Every command through [`execute`](Self::execute)
+    /// records the device it changed; readings that change on their own, such as
+    /// temperatures, are recorded by [`sample_telemetry`](Self::sample_telemetry).
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = Some(Arc::new(telemetry));
+        self
+    }
+
+    pub fn telemetry(&self) -> Option<&Telemetry> {
+        self.telemetry.as_deref()
+    }
+
+    /// Records the current readings of every device, unless no device has changed since
+    /// the last sample. Returns how many points were recorded. A tick with nothing to
+    /// record still reads every device's stamp once, see
+    /// [`state_generation`](Self::state_generation).
+    pub fn sample_telemetry(&self) -> usize {
+        let Some(telemetry) = &self.telemetry else {
+            return 0;
+        };
+        let generation = self.state_generation();
+        if telemetry.sampled.swap(generation, Ordering::Relaxed) == generation {
+            return 0;
+        }
+        let at = self.now();
+        self.rooms
+            .iter()
+            .flat_map(|room| room.enabled_devices().map(move |device| (room, device)))
+            .map(|(room, device)| telemetry.record_device(room, &*device, at))
+            .sum()
+    }
+
+    /// [`Telemetry::compact`] as of the house's clock.
+    pub fn compact_telemetry(&self) {
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.compact(self.now());
+        }
+    }
+
+    pub(crate) fn record_telemetry(&self, room: &SmartRoom, device: &dyn Pluggable) {
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.record_device(room, device, self.now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use super::*;
+    use crate::{testing::ManualClock, Clock, DeviceCommand};
+
+    fn house(clock: &ManualClock) -> (SmartHouse, Arc<SmartThermometer>) {
+        let thermometer = Arc::new(SmartThermometer::new("t1"));
+        let mut kitchen = SmartRoom::new("kitchen");
+        kitchen.plug(thermometer.clone()).unwrap();
+        kitchen.plug(SmartSocket::new("kettle")).unwrap();
+        let mut house = SmartHouse::new("home").with_telemetry(Telemetry::new().batch(2));
+        house.add(kitchen).unwrap();
+        house.set_clock(clock.clone());
+        (house, thermometer)
+    }
+
+    fn path(text: &str) -> DeviceLocation {
+        text.parse().unwrap()
+    }
+
+    fn values(points: &[Point]) -> Vec<f64> {
+        points.iter().map(|point| point.value).collect()
+    }
+
+    #[test]
+    fn range_includes_from_and_excludes_until() {
+        let clock = ManualClock::default();
+        let start = clock.now();
+        let (house, thermometer) = house(&clock);
+        for celsius in [20.0, 21.0, 22.0, 23.0] {
+            thermometer.set_temperature(celsius);
+            house.sample_telemetry();
+            clock.advance(Duration::from_secs(10));
+        }
+        let telemetry = house.telemetry().unwrap();
+        let temperature = TelemetryQuery::new().metric(Reading::Temperature);
+
+        let at = |secs| start + Duration::from_secs(secs);
+        let range = |from, until| {
+            values(&telemetry.query(&temperature.clone().from(at(from)).until(at(until))))
+        };
+        assert_eq!(range(10, 30), [21.0, 22.0]);
+        assert_eq!(range(9, 11), [21.0]);
+        assert!(range(11, 20).is_empty());
+        assert_eq!(
+            values(&telemetry.query(&temperature)),
+            [20.0, 21.0, 22.0, 23.0]
+        );
+        assert_eq!(
+            values(&telemetry.query(&temperature.clone().from(at(30)))),
+            [23.0]
+        );
+        assert!(telemetry.query(&temperature.until(UNIX_EPOCH)).is_empty());
+    }
+
+    #[test]
+    fn commands_and_samples_are_recorded_once() {
+        let clock = ManualClock::default();
+        let (house, thermometer) = house(&clock);
+        thermometer.set_temperature(19.5);
+        assert_eq!(house.sample_telemetry(), 2);
+
+        clock.advance(Duration::from_secs(1));
+        house
+            .execute(&path("kettle"), DeviceCommand::SetLoad(1500.0))
+            .unwrap();
+        house
+            .execute(&path("kettle"), DeviceCommand::TurnOn)
+            .unwrap();
+
+        let power = house
+            .telemetry()
+            .unwrap()
+            .query(&TelemetryQuery::new().device(path("kitchen/kettle")));
+        assert_eq!(values(&power), [0.0, 0.0, 1500.0]);
+        assert!(power.iter().all(|point| point.metric == Reading::Power));
+    }
+
+    #[test]
+    fn queries_are_chronological_and_capped() {
+        let telemetry = Telemetry::new().batch(3);
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        // часы отвели назад: точки пришли не по порядку
+        for secs in [5, 1, 4, 2, 3] {
+            telemetry.record(Point {
+                room: "kitchen".to_string(),
+                device: "t1".to_string(),
+                metric: Reading::Temperature,
+                at: start + Duration::from_secs(secs),
+                value: secs as f64,
+                resolution: Resolution::Raw,
+            });
+        }
+        let all = TelemetryQuery::new();
+        assert_eq!(values(&telemetry.query(&all)), [1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(values(&telemetry.query(&all.limit(2))), [1.0, 2.0]);
+    }
+
+    fn point(start: SystemTime, secs: u64, value: f64) -> Point {
+        Point {
+            room: "kitchen".to_string(),
+            device: "t1".to_string(),
+            metric: Reading::Temperature,
+            at: start + Duration::from_secs(secs),
+            value,
+            resolution: Resolution::Raw,
+        }
+    }
+
+    #[test]
+    fn compaction_downsamples_old_minutes_exactly() {
+        // начало минуты, чтобы границы корзин были видны
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_040);
+        let telemetry = Telemetry::new().retention(Retention::new(
+            Duration::from_secs(120),
+            Duration::from_secs(300),
+        ));
+        for (secs, celsius) in [(0, 20.0), (20, 23.0), (59, 21.5), (60, 30.0), (150, 25.0)] {
+            telemetry.record(point(start, secs, celsius));
+        }
+
+        // первая минута целиком старше двух минут, вторая ещё нет на секунду
+        telemetry.compact(start + Duration::from_secs(239));
+        let points = telemetry.query(&TelemetryQuery::new());
+        assert_eq!(
+            points[0],
+            Point {
+                value: 21.5,
+                resolution: Resolution::Minute(Bucket {
+                    min: 20.0,
+                    max: 23.0,
+                    sum: 64.5,
+                    count: 3,
+                }),
+                ..point(start, 0, 0.0)
+            }
+        );
+        assert_eq!(values(&points[1..]), [30.0, 25.0]);
+        assert!(points[1..]
+            .iter()
+            .all(|point| point.resolution == Resolution::Raw));
+
+        // после хранения сжатых точек пропадает и первая минута
+        telemetry.compact(start + Duration::from_secs(60 + 120 + 300));
+        let points = telemetry.query(&TelemetryQuery::new());
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].at, start + Duration::from_secs(60));
+        assert!(matches!(points[0].resolution, Resolution::Minute(_)));
+    }
+
+    #[test]
+    fn compacting_twice_changes_nothing() {
+        let clock = ManualClock::default();
+        let start = clock.now();
+        let telemetry = Telemetry::new().retention(Retention::new(
+            Duration::from_secs(60),
+            Duration::from_secs(3600),
+        ));
+        for secs in 0..300 {
+            telemetry.record(point(start, secs, (secs % 7) as f64));
+        }
+        clock.advance(Duration::from_secs(300));
+
+        telemetry.compact(clock.now());
+        let once = telemetry.query(&TelemetryQuery::new());
+        telemetry.compact(clock.now());
+        assert_eq!(telemetry.query(&TelemetryQuery::new()), once);
+        assert!(once.len() < 300);
+    }
+
+    fn series() -> Telemetry {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let telemetry = Telemetry::new();
+        telemetry.record(point(start, 0, 21.5));
+        telemetry.record(Point {
+            room: "living, \"big\" room".to_string(),
+            metric: Reading::Power,
+            at: start + Duration::from_millis(86_400_250),
+            ..point(start, 0, -0.125)
+        });
+        telemetry
+    }
+
+    // Разбор одной строки CSV с кавычками, только для проверки экспорта
+    fn csv_fields(line: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    fields.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn csv_export_parses_back() {
+        let telemetry = series();
+        let mut out = Vec::new();
+        telemetry
+            .export_csv(&TelemetryQuery::new(), &mut out)
+            .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let rows: Vec<_> = text.lines().map(csv_fields).collect();
+
+        assert_eq!(rows[0], ["timestamp", "room", "device", "metric", "value"]);
+        let points = telemetry.query(&TelemetryQuery::new());
+        assert_eq!(rows.len(), points.len() + 1);
+        for (row, point) in rows[1..].iter().zip(&points) {
+            assert_eq!(row[1], point.room);
+            assert_eq!(row[2], point.device);
+            assert_eq!(row[3], point.metric.to_string());
+            assert_eq!(row[4].parse::<f64>().unwrap(), point.value);
+        }
+        assert_eq!(rows[1][0], "2023-11-14T22:13:20Z");
+        assert_eq!(rows[2][0], "2023-11-15T22:13:20.25Z");
+        assert_eq!(rows[2][1], "living, \"big\" room");
+    }
+
+    #[test]
+    fn jsonl_export_writes_a_line_per_point() {
+        let mut out = Vec::new();
+        series()
+            .export_jsonl(
+                &TelemetryQuery::new().metric(Reading::Temperature),
+                &mut out,
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"timestamp\":\"2023-11-14T22:13:20Z\",\"room\":\"kitchen\",\"device\":\"t1\",\
+             \"metric\":\"temperature\",\"value\":21.5}\n"
+        );
+    }
+
+    #[test]
+    fn empty_range_exports_only_the_header() {
+        let telemetry = series();
+        let nothing = TelemetryQuery::new().until(UNIX_EPOCH);
+        let mut csv = Vec::new();
+        telemetry.export_csv(&nothing, &mut csv).unwrap();
+        assert_eq!(csv, b"timestamp,room,device,metric,value\n");
+        let mut jsonl = Vec::new();
+        telemetry.export_jsonl(&nothing, &mut jsonl).unwrap();
+        assert!(jsonl.is_empty());
+    }
+
+    #[test]
+    fn timestamps_cross_leap_days() {
+        let at = |secs| rfc3339(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(1_709_251_199), "2024-02-29T23:59:59Z");
+    }
+
+    #[test]
+    fn hourly_windows_start_on_the_hour_across_gaps() {
+        // 22:13:20 UTC; окно начинается в 22:00:00
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let hour = UNIX_EPOCH + Duration::from_secs(1_699_999_200);
+        let telemetry = Telemetry::new();
+        for (minutes, celsius) in [(0, 20.0), (30, 22.0), (50, 19.0), (230, 25.0)] {
+            telemetry.record(point(start, minutes * 60, celsius));
+        }
+        let query = TelemetryQuery::new().metric(Reading::Temperature);
+        let hours = Duration::from_secs(3600);
+        let starts = |windows: &[Window]| -> Vec<u64> {
+            windows
+                .iter()
+                .map(|window| window.start.duration_since(hour).unwrap().as_secs() / 3600)
+                .collect()
+        };
+
+        let skipped = telemetry.aggregate(&query, hours, Aggregation::Mean, EmptyWindows::Skip);
+        assert_eq!(starts(&skipped), [0, 1, 4]);
+        assert_eq!(
+            skipped.iter().map(|w| w.value).collect::<Vec<_>>(),
+            [Some(21.0), Some(19.0), Some(25.0)]
+        );
+
+        let emitted = telemetry.aggregate(&query, hours, Aggregation::Count, EmptyWindows::Emit);
+        assert_eq!(starts(&emitted), [0, 1, 2, 3, 4]);
+        assert_eq!(
+            emitted.iter().map(|w| w.value).collect::<Vec<_>>(),
+            [Some(2.0), Some(1.0), None, None, Some(1.0)]
+        );
+
+        let within = query.from(hour).until(hour + hours * 7).limit(6);
+        let capped = telemetry.aggregate(&within, hours, Aggregation::Last, EmptyWindows::Emit);
+        assert_eq!(starts(&capped), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(capped[0].value, Some(22.0));
+    }
+
+    #[test]
+    fn aggregates_keep_series_apart_and_use_buckets() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_040);
+        let telemetry = Telemetry::new().retention(Retention::new(
+            Duration::from_secs(60),
+            Duration::from_secs(3600),
+        ));
+        for (secs, celsius) in [(0, 10.0), (10, 30.0), (20, 20.0)] {
+            telemetry.record(point(start, secs, celsius));
+            telemetry.record(Point {
+                device: "t2".to_string(),
+                ..point(start, secs, celsius + 1.0)
+            });
+        }
+        telemetry.compact(start + Duration::from_secs(600));
+
+        let windows = telemetry.aggregate(
+            &TelemetryQuery::new(),
+            Duration::from_secs(3600),
+            Aggregation::Max,
+            EmptyWindows::Skip,
+        );
+        let values: Vec<_> = windows
+            .iter()
+            .map(|window| (window.device.as_str(), window.value))
+            .collect();
+        assert_eq!(values, [("t1", Some(30.0)), ("t2", Some(31.0))]);
+    }
+}
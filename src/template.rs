@@ -0,0 +1,231 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::{
+    DeviceKind, Labeled, Pluggable, RoomId, SmartHouse, SmartHouseError, SmartRoom, SmartSocket,
+    SmartThermometer,
+};
+
+/// The placeholder in template names and labels that stands for the room name.
+pub const ROOM_PLACEHOLDER: &str = "{room}";
+
+/// One device of a [`RoomTemplate`]: its kind, a name and label in which
+/// [`ROOM_PLACEHOLDER`] is replaced by the room name, and the state it starts in.
+/// Settings that do not apply to the kind, such as a temperature for a socket, are
+/// ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceSpec {
+    pub kind: DeviceKind,
+    pub name: String,
+    pub label: Option<String>,
+    pub on: bool,
+    /// The socket's load, in watts.
+    pub load: Option<f64>,
+    pub critical: bool,
+    /// The thermometer's first reading, in Celsius.
+    pub temperature: Option<f64>,
+}
+
+impl DeviceSpec {
+    pub fn new(kind: DeviceKind, name: impl Into<String>) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+            label: None,
+            on: false,
+            load: None,
+            critical: false,
+            temperature: None,
+        }
+    }
+
+    pub fn socket(name: impl Into<String>) -> Self {
+        Self::new(DeviceKind::Socket, name)
+    }
+
+    pub fn thermometer(name: impl Into<String>) -> Self {
+        Self::new(DeviceKind::Thermometer, name)
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn on(mut self) -> Self {
+        self.on = true;
+        self
+    }
+
+    pub fn load(mut self, watts: f64) -> Self {
+        self.load = Some(watts);
+        self
+    }
+
+    pub fn critical(mut self) -> Self {
+        self.critical = true;
+        self
+    }
+
+    pub fn temperature(mut self, celsius: f64) -> Self {
+        self.temperature = Some(celsius);
+        self
+    }
+
+    fn instantiate(&self, room: &str) -> Arc<dyn Pluggable> {
+        let name = self.name.replace(ROOM_PLACEHOLDER, room);
+        match self.kind {
+            DeviceKind::Socket => {
+                let mut socket = SmartSocket::new(name);
+                if self.critical {
+                    socket = socket.critical();
+                }
+                if let Some(watts) = self.load {
+                    socket.set_load(watts);
+                }
+                if self.on {
+                    socket.turn_on();
+                }
+                Arc::new(socket)
+            }
+            DeviceKind::Thermometer => {
+                let thermometer = SmartThermometer::new(name);
+                if let Some(celsius) = self.temperature {
+                    thermometer.set_temperature(celsius);
+                }
+                Arc::new(thermometer)
+            }
+        }
+    }
+}
+
+/// A room layout to stamp out many times, such as identical hotel rooms.
+///
+/// ```
+/// use lesson_3::{DeviceSpec, RoomTemplate, SmartHouse};
+///
+/// let template = RoomTemplate::new()
+///     .device(DeviceSpec::socket("{room}-lamp").label("Lamp in {room}"))
+///     .device(DeviceSpec::thermometer("{room}-temp"));
+/// let mut house = SmartHouse::new("hotel");
+/// house.add_from_template("101", &template).unwrap();
+/// assert_eq!(house.room("101").unwrap().devices(), ["101-lamp", "101-temp"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RoomTemplate {
+    pub label: Option<String>,
+    pub devices: Vec<DeviceSpec>,
+}
+
+impl RoomTemplate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The room's label; [`ROOM_PLACEHOLDER`] is replaced here too.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn device(mut self, device: DeviceSpec) -> Self {
+        self.devices.push(device);
+        self
+    }
+
+    /// A new room named `name` with fresh devices, not yet in any house.
+    pub fn instantiate(&self, name: &str) -> Result<SmartRoom, SmartHouseError> {
+        let mut room = SmartRoom::new(name);
+        room.set_label(
+            self.label
+                .as_ref()
+                .map(|l| l.replace(ROOM_PLACEHOLDER, name)),
+        );
+        for spec in &self.devices {
+            let device = spec.instantiate(name);
+            let device_name = String::from(device.name());
+            room.plug(device)?;
+            if let Some(label) = &spec.label {
+                room.set_device_label(&device_name, Some(label.replace(ROOM_PLACEHOLDER, name)))?;
+            }
+        }
+        Ok(room)
+    }
+}
+
+impl SmartHouse {
+    /// Adds a room named `room` laid out by `template`. Names follow the usual rules: two
+    /// template devices that come out with the same name are a
+    /// [`DuplicateDevice`](SmartHouseError::DuplicateDevice), and an existing room a
+    /// [`DuplicateRoom`](SmartHouseError::DuplicateRoom). On error the house is unchanged.
+    pub fn add_from_template(
+        &mut self,
+        room: &str,
+        template: &RoomTemplate,
+    ) -> Result<RoomId, SmartHouseError> {
+        let room = template.instantiate(room)?;
+        self.add(room)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::devices::downcast;
+
+    fn template() -> RoomTemplate {
+        RoomTemplate::new()
+            .label("Guest room {room}")
+            .device(
+                DeviceSpec::socket("{room}-fridge")
+                    .critical()
+                    .load(90.0)
+                    .on(),
+            )
+            .device(DeviceSpec::socket("lamp").label("Lamp of {room}"))
+            .device(DeviceSpec::thermometer("{room}-temp").temperature(21.5))
+    }
+
+    #[test]
+    fn rooms_get_their_own_devices() {
+        let mut house = SmartHouse::new("hotel");
+        for room in ["101", "102"] {
+            house.add_from_template(room, &template()).unwrap();
+        }
+
+        let room = house.room("102").unwrap();
+        assert_eq!(room.devices(), ["102-fridge", "lamp", "102-temp"]);
+        assert_eq!(room.label(), Some("Guest room 102"));
+        assert_eq!(room.device_label("lamp"), Some("Lamp of 102"));
+        let fridge = room.device("102-fridge").unwrap();
+        let fridge = downcast::<SmartSocket>(&*fridge).unwrap();
+        assert!(fridge.is_critical() && fridge.is_on());
+        assert_eq!(fridge.power(), 90.0);
+        let temp = room.device("102-temp").unwrap();
+        let temp = downcast::<SmartThermometer>(&*temp).unwrap();
+        assert_eq!(temp.temperature(), Some(21.5));
+
+        // устройства не общие: каждая комната получает свои
+        let other = house.room("101").unwrap().device("lamp").unwrap();
+        assert!(!Arc::ptr_eq(&other, &room.device("lamp").unwrap()));
+    }
+
+    #[test]
+    fn collisions_follow_duplicate_rules() {
+        let mut house = SmartHouse::new("hotel");
+        house.add_from_template("101", &template()).unwrap();
+        assert_eq!(
+            house.add_from_template("101", &template()),
+            Err(SmartHouseError::DuplicateRoom("101".to_string()))
+        );
+
+        let clashing = template().device(DeviceSpec::thermometer("{room}-fridge"));
+        assert_eq!(
+            house.add_from_template("102", &clashing),
+            Err(SmartHouseError::DuplicateDevice("102-fridge".to_string()))
+        );
+        assert!(house.room("102").is_none());
+        assert_eq!(house.get_rooms().len(), 1);
+    }
+}
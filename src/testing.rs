@@ -0,0 +1,715 @@
+//! Fakes for testing code that works with devices, such as report providers, and
+//! generated houses for property tests.
+//!
+//! Available with the `test-util` feature.
+
+use core::fmt;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+mod assertions;
+pub mod fixtures;
+mod golden;
+
+pub use assertions::{assert_device_count, assert_house_contains, assert_room_devices};
+pub use golden::assert_report_matches;
+
+use crate::{
+    heartbeat::{BeatError, Heartbeat},
+    udp::Sensor,
+    CapabilityError, Clock, DeviceKind, Named, Pluggable, SmartHouse, SmartRoom, SmartSocket,
+    SmartThermometer, Switchable,
+};
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The failure a [`MockDevice`] was told to produce.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockError {
+    pub device: String,
+    pub operation: &'static str,
+}
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed on mock device {}",
+            self.operation, self.device
+        )
+    }
+}
+
+impl Error for MockError {}
+
+/// A switchable device whose kind, status and health are set by the test.
+///
+/// State operations (`turn_on`, `turn_off`, `is_on`) fail while failures are injected
+/// with [`fail_next`](Self::fail_next) or [`fail_always`](Self::fail_always). The call
+/// counters include failed calls.
+pub struct MockDevice {
+    name: String,
+    kind: Option<DeviceKind>,
+    status: Mutex<Option<String>>,
+    healthy: AtomicBool,
+    on: AtomicBool,
+    fail_next: AtomicU32,
+    fail_always: AtomicBool,
+    turned_on: AtomicUsize,
+    turned_off: AtomicUsize,
+    state_reads: AtomicUsize,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockDeviceBuilder {
+    name: String,
+    kind: Option<DeviceKind>,
+    status: Option<String>,
+    healthy: bool,
+    on: bool,
+    fail_next: u32,
+    fail_always: bool,
+}
+
+impl MockDeviceBuilder {
+    pub fn kind(mut self, kind: DeviceKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Reported by [`Pluggable::status`] instead of the default `on` / `off`.
+    pub fn status(mut self, status: impl Into<String>) -> Self {
+        self.status = Some(status.into());
+        self
+    }
+
+    pub fn healthy(mut self, healthy: bool) -> Self {
+        self.healthy = healthy;
+        self
+    }
+
+    pub fn on(mut self) -> Self {
+        self.on = true;
+        self
+    }
+
+    pub fn fail_next(mut self, calls: u32) -> Self {
+        self.fail_next = calls;
+        self
+    }
+
+    pub fn fail_always(mut self) -> Self {
+        self.fail_always = true;
+        self
+    }
+
+    pub fn build(self) -> MockDevice {
+        MockDevice {
+            name: self.name,
+            kind: self.kind,
+            status: Mutex::new(self.status),
+            healthy: AtomicBool::new(self.healthy),
+            on: AtomicBool::new(self.on),
+            fail_next: AtomicU32::new(self.fail_next),
+            fail_always: AtomicBool::new(self.fail_always),
+            turned_on: AtomicUsize::new(0),
+            turned_off: AtomicUsize::new(0),
+            state_reads: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl MockDevice {
+    /// A healthy device that is off, has no kind and never fails.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self::builder(name).build()
+    }
+
+    pub fn builder(name: impl Into<String>) -> MockDeviceBuilder {
+        MockDeviceBuilder {
+            name: name.into(),
+            kind: None,
+            status: None,
+            healthy: true,
+            on: false,
+            fail_next: 0,
+            fail_always: false,
+        }
+    }
+
+    fn lock_status(&self) -> MutexGuard<'_, Option<String>> {
+        lock(&self.status)
+    }
+
+    fn attempt(&self, operation: &'static str) -> Result<(), MockError> {
+        let injected = self.fail_always.load(Ordering::SeqCst)
+            || self
+                .fail_next
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+        match injected {
+            true => Err(MockError {
+                device: self.name.clone(),
+                operation,
+            }),
+            false => Ok(()),
+        }
+    }
+
+    pub fn turn_on(&self) -> Result<(), MockError> {
+        self.turned_on.fetch_add(1, Ordering::SeqCst);
+        self.attempt("turn_on")?;
+        self.on.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn turn_off(&self) -> Result<(), MockError> {
+        self.turned_off.fetch_add(1, Ordering::SeqCst);
+        self.attempt("turn_off")?;
+        self.on.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn is_on(&self) -> Result<bool, MockError> {
+        self.state_reads.fetch_add(1, Ordering::SeqCst);
+        self.attempt("is_on")?;
+        Ok(self.on.load(Ordering::SeqCst))
+    }
+
+    /// Makes the next `calls` state operations fail.
+    pub fn fail_next(&self, calls: u32) {
+        self.fail_next.store(calls, Ordering::SeqCst);
+    }
+
+    /// Makes every state operation fail until [`recover`](Self::recover).
+    pub fn fail_always(&self) {
+        self.fail_always.store(true, Ordering::SeqCst);
+    }
+
+    /// Stops injecting failures.
+    pub fn recover(&self) {
+        self.fail_always.store(false, Ordering::SeqCst);
+        self.fail_next.store(0, Ordering::SeqCst);
+    }
+
+    pub fn set_status(&self, status: Option<String>) {
+        *self.lock_status() = status;
+    }
+
+    /// An unhealthy device misses its heartbeats.
+    pub fn set_healthy(&self, healthy: bool) {
+        self.healthy.store(healthy, Ordering::SeqCst);
+    }
+
+    pub fn times_turned_on(&self) -> usize {
+        self.turned_on.load(Ordering::SeqCst)
+    }
+
+    pub fn times_turned_off(&self) -> usize {
+        self.turned_off.load(Ordering::SeqCst)
+    }
+
+    pub fn times_state_read(&self) -> usize {
+        self.state_reads.load(Ordering::SeqCst)
+    }
+}
+
+/// The copy has the same configuration, state, injected failures and call counters.
+impl Clone for MockDevice {
+    fn clone(&self) -> Self {
+        let load = |n: &AtomicUsize| AtomicUsize::new(n.load(Ordering::SeqCst));
+        Self {
+            name: self.name.clone(),
+            kind: self.kind,
+            status: Mutex::new(self.lock_status().clone()),
+            healthy: AtomicBool::new(self.healthy.load(Ordering::SeqCst)),
+            on: AtomicBool::new(self.on.load(Ordering::SeqCst)),
+            fail_next: AtomicU32::new(self.fail_next.load(Ordering::SeqCst)),
+            fail_always: AtomicBool::new(self.fail_always.load(Ordering::SeqCst)),
+            turned_on: load(&self.turned_on),
+            turned_off: load(&self.turned_off),
+            state_reads: load(&self.state_reads),
+        }
+    }
+}
+
+impl fmt::Debug for MockDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockDevice")
+            .field("name", &self.name)
+            .field("kind", &self.kind)
+            .field("on", &self.on.load(Ordering::SeqCst))
+            .field("healthy", &self.healthy.load(Ordering::SeqCst))
+            .finish_non_exhaustive()
+    }
+}
+
+impl Named for MockDevice {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Switchable for MockDevice {
+    fn turn_on(&self) -> Result<(), CapabilityError> {
+        Ok(MockDevice::turn_on(self)?)
+    }
+
+    fn turn_off(&self) -> Result<(), CapabilityError> {
+        Ok(MockDevice::turn_off(self)?)
+    }
+
+    fn is_on(&self) -> Result<bool, CapabilityError> {
+        Ok(MockDevice::is_on(self)?)
+    }
+}
+
+impl Pluggable for MockDevice {
+    crate::boxed_clone!();
+
+    fn as_switchable(&self) -> Option<&dyn Switchable> {
+        Some(self)
+    }
+
+    fn status(&self) -> Option<String> {
+        let on = self.on.load(Ordering::SeqCst);
+        let status = self.lock_status().clone();
+        Some(status.unwrap_or_else(|| if on { "on" } else { "off" }.to_string()))
+    }
+
+    fn kind(&self) -> Option<DeviceKind> {
+        self.kind
+    }
+}
+
+impl Heartbeat for MockDevice {
+    fn beat(&self, _: Duration, _: SystemTime) -> Result<(), BeatError> {
+        match self.healthy.load(Ordering::SeqCst) {
+            true => Ok(()),
+            false => Err(format!("mock device {} is unhealthy", self.name).into()),
+        }
+    }
+}
+
+/// A small seeded generator (SplitMix64), so a failing case can be replayed from its seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..=max`.
+    pub fn below(&mut self, max: usize) -> usize {
+        (self.next_u64() % (max as u64 + 1)) as usize
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() - 1)]
+    }
+}
+
+// Куски имён: юникод, пробелы по краям, разделители путей и кавычки
+const NAME_PARTS: &[&str] = &[
+    "hall",
+    "s1",
+    "Kitchen",
+    " ",
+    "\t",
+    "é",
+    "комната",
+    "日本",
+    "🔌",
+    "a b",
+    " lead",
+    "trail ",
+    "\u{200B}",
+    "/",
+    "\\",
+    "\"",
+    "\n",
+    "#",
+    "0",
+];
+
+/// Generates houses with up to `max_rooms` rooms of up to `max_devices` sockets and
+/// thermometers each. Room names are unique in a house and device names in a room.
+#[derive(Debug, Clone)]
+pub struct HouseStrategy {
+    max_rooms: usize,
+    max_devices: usize,
+}
+
+impl Default for HouseStrategy {
+    fn default() -> Self {
+        Self {
+            max_rooms: 6,
+            max_devices: 6,
+        }
+    }
+}
+
+impl HouseStrategy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_rooms(mut self, max_rooms: usize) -> Self {
+        self.max_rooms = max_rooms;
+        self
+    }
+
+    pub fn max_devices(mut self, max_devices: usize) -> Self {
+        self.max_devices = max_devices;
+        self
+    }
+
+    /// A non-empty name of one to three parts, made unique among `taken` with a suffix.
+    pub fn name(&self, rng: &mut Rng, taken: &[String]) -> String {
+        let mut name: String = (0..=rng.below(2)).map(|_| *rng.pick(NAME_PARTS)).collect();
+        if taken.contains(&name) {
+            let mut n = 2;
+            while taken.contains(&format!("{name}~{n}")) {
+                n += 1;
+            }
+            name = format!("{name}~{n}");
+        }
+        name
+    }
+
+    pub fn device(&self, rng: &mut Rng, name: String) -> Box<dyn Pluggable> {
+        match rng.below(1) {
+            0 => {
+                let socket = SmartSocket::new(name);
+                socket.set_load(rng.below(3000) as f64 / 2.0);
+                if rng.below(1) == 1 {
+                    socket.turn_on();
+                }
+                Box::new(socket)
+            }
+            _ => {
+                let thermo = SmartThermometer::new(name);
+                if rng.below(3) > 0 {
+                    thermo.set_temperature(rng.below(800) as f64 / 10.0 - 30.0);
+                }
+                Box::new(thermo)
+            }
+        }
+    }
+
+    pub fn room(&self, rng: &mut Rng, name: String) -> SmartRoom {
+        let mut room = SmartRoom::new(name);
+        let mut names = Vec::new();
+        for _ in 0..rng.below(self.max_devices) {
+            let name = self.name(rng, &names);
+            names.push(name.clone());
+            let device: std::sync::Arc<dyn Pluggable> = self.device(rng, name).into();
+            room.plug(device).expect("device names are unique");
+        }
+        room
+    }
+
+    pub fn house(&self, rng: &mut Rng) -> SmartHouse {
+        let mut house = SmartHouse::new(self.name(rng, &[]));
+        let mut names = Vec::new();
+        for _ in 0..rng.below(self.max_rooms) {
+            let name = self.name(rng, &names);
+            names.push(name.clone());
+            let room = self.room(rng, name);
+            house.add(room).expect("room names are unique");
+        }
+        house
+    }
+
+    /// Runs `property` on `cases` houses. A failing case panics with the seed that
+    /// [`Rng::new`] and [`house`](Self::house) reproduce it from.
+    pub fn check(&self, seed: u64, cases: usize, mut property: impl FnMut(&SmartHouse)) {
+        let mut seeds = Rng::new(seed);
+        for _ in 0..cases {
+            let case = seeds.next_u64();
+            let house = self.house(&mut Rng::new(case));
+            let outcome =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| property(&house)));
+            if let Err(panic) = outcome {
+                eprintln!("property failed for the house from seed {case:#x}:\n{house:?}");
+                std::panic::resume_unwind(panic);
+            }
+        }
+    }
+}
+
+/// A [`Clock`] that only moves when told to. Clones share the time.
+#[derive(Debug, Clone)]
+pub struct ManualClock(Arc<Mutex<SystemTime>>);
+
+impl Default for ManualClock {
+    /// Starts at 2023-11-14 22:13:20 UTC, so stamps are far from the epoch and easy to read.
+    fn default() -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+    }
+}
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self(Arc::new(Mutex::new(start)))
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *lock(&self.0) = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *lock(&self.0) += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *lock(&self.0)
+    }
+}
+
+/// Feeds a fixed script of `(offset, celsius)` readings into a [`Sensor`], with offsets
+/// counted from `start`. Readings go in one at a time with [`advance`](Self::advance), up
+/// to a point with [`advance_to`](Self::advance_to), or all at once with
+/// [`finish`](Self::finish).
+pub struct ScriptedSensor {
+    sensor: Arc<dyn Sensor>,
+    start: SystemTime,
+    script: VecDeque<(Duration, f64)>,
+}
+
+impl ScriptedSensor {
+    /// The script is played in order of offsets; readings with equal offsets keep their order.
+    pub fn new(
+        sensor: Arc<dyn Sensor>,
+        start: SystemTime,
+        script: impl IntoIterator<Item = (Duration, f64)>,
+    ) -> Self {
+        let mut script: Vec<_> = script.into_iter().collect();
+        script.sort_by_key(|(offset, _)| *offset);
+        Self {
+            sensor,
+            start,
+            script: script.into(),
+        }
+    }
+
+    /// Feeds the next reading and returns the moment it was recorded at.
+    pub fn advance(&mut self) -> Option<SystemTime> {
+        let (offset, celsius) = self.script.pop_front()?;
+        let at = self.start + offset;
+        self.sensor.record(celsius, at);
+        Some(at)
+    }
+
+    /// Feeds every reading up to and including `offset`; returns how many there were.
+    pub fn advance_to(&mut self, offset: Duration) -> usize {
+        let mut fed = 0;
+        while self.script.front().is_some_and(|(next, _)| *next <= offset) {
+            self.advance();
+            fed += 1;
+        }
+        fed
+    }
+
+    /// Feeds the rest of the script.
+    pub fn finish(&mut self) -> usize {
+        self.advance_to(Duration::MAX)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.script.len()
+    }
+}
+
+impl fmt::Debug for ScriptedSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptedSensor")
+            .field("start", &self.start)
+            .field("remaining", &self.script)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        protocol::{decode, encode, HouseLayout, Response},
+        DeviceSliceExt, ReportBuilder, Verbosity,
+    };
+
+    #[test]
+    fn injected_failures_and_counters() {
+        let device = MockDevice::builder("lamp").fail_next(1).build();
+        assert!(device.turn_on().is_err());
+        assert_eq!(device.is_on(), Ok(false));
+        device.turn_on().unwrap();
+        assert_eq!(device.is_on(), Ok(true));
+
+        device.fail_always();
+        let error = device.turn_off().unwrap_err();
+        assert_eq!(error.to_string(), "turn_off failed on mock device lamp");
+        device.recover();
+        device.turn_off().unwrap();
+
+        assert_eq!(device.times_turned_on(), 2);
+        assert_eq!(device.times_turned_off(), 2);
+        assert_eq!(device.times_state_read(), 2);
+    }
+
+    #[test]
+    fn mocks_drop_into_rooms_and_reports() {
+        let lamp = Arc::new(MockDevice::builder("lamp").on().build());
+        let mut room = SmartRoom::new("limb");
+        room.plug(lamp.clone()).unwrap();
+        room.plug(
+            MockDevice::builder("boiler")
+                .kind(DeviceKind::Thermometer)
+                .status("62 °C")
+                .build(),
+        )
+        .unwrap();
+        let mut house = SmartHouse::new("hell");
+        house.add(room).unwrap();
+
+        let report = ReportBuilder::new()
+            .verbosity(Verbosity::Detailed)
+            .run(&house)
+            .unwrap();
+        assert_eq!(
+            report,
+            "-> House: hell\n--> Room: limb\n----> Device: lamp (on)\n----> Device: boiler (62 °C)\n"
+        );
+
+        lamp.set_status(Some("flickering".to_string()));
+        let devices = house.get_rooms()[0].devices_raw();
+        assert_eq!(
+            devices.by_kind(DeviceKind::Thermometer).count(),
+            1,
+            "Mocks report the kind they were built with"
+        );
+        assert_eq!(devices[0].status().as_deref(), Some("flickering"));
+    }
+
+    #[test]
+    fn generated_houses_are_valid() {
+        let strategy = HouseStrategy::new().max_rooms(3).max_devices(2);
+        strategy.check(1, 200, |house| {
+            assert!(house.get_rooms().len() <= 3);
+            assert!(house
+                .get_rooms()
+                .iter()
+                .all(|r| r.device_names().count() <= 2));
+            assert!(!house.name.is_empty());
+        });
+    }
+
+    #[test]
+    fn layout_survives_the_wire() {
+        HouseStrategy::new().check(2, 200, |house| {
+            let layout = HouseLayout::from(house);
+            let frame = Response::Layout(layout.clone()).to_frame();
+            let bytes = encode(frame.kind, &frame.payload).unwrap();
+            let (frame, _) = decode(&bytes).unwrap();
+            assert_eq!(
+                Response::from_frame(frame).unwrap(),
+                Response::Layout(layout)
+            );
+        });
+    }
+
+    #[test]
+    fn sorted_reports_ignore_insertion_order() {
+        HouseStrategy::new().check(3, 200, |house| {
+            let mut reversed = SmartHouse::new(house.name.clone());
+            for room in house.get_rooms().iter().rev() {
+                let mut copy = SmartRoom::new(room.name.clone());
+                for device in room.devices_raw().into_iter().rev() {
+                    copy.plug(device).unwrap();
+                }
+                reversed.add(copy).unwrap();
+            }
+
+            let sorted = ReportBuilder::new().sorted().verbosity(Verbosity::Detailed);
+            assert_eq!(sorted.run(house).unwrap(), sorted.run(&reversed).unwrap());
+
+            let counts = |builder: ReportBuilder| {
+                let mut lines: Vec<_> = builder
+                    .verbosity(Verbosity::Summary)
+                    .run(house)
+                    .unwrap()
+                    .lines()
+                    .map(str::to_string)
+                    .collect();
+                lines.sort();
+                lines
+            };
+            assert_eq!(
+                counts(ReportBuilder::new()),
+                counts(ReportBuilder::new().sorted())
+            );
+        });
+    }
+
+    #[test]
+    fn scripted_readings_go_stale_and_recover() {
+        let fake = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver = Arc::new(
+            crate::udp::ThermometerReceiver::subscribe_every(
+                "t1",
+                fake.local_addr().unwrap(),
+                Duration::from_secs(60),
+            )
+            .unwrap(),
+        );
+        let start = ManualClock::default().now();
+        let secs = Duration::from_secs;
+        let mut script = ScriptedSensor::new(
+            receiver.clone(),
+            start,
+            [(secs(10), 22.0), (secs(0), 20.5), (secs(1), 21.0)],
+        );
+        let timeout = secs(5);
+
+        assert_eq!(script.advance(), Some(start));
+        assert_eq!(receiver.temperature(), Some(20.5));
+        assert!(receiver.beat(timeout, start + secs(5)).is_ok());
+
+        assert_eq!(script.advance_to(secs(9)), 1);
+        assert_eq!(receiver.temperature(), Some(21.0));
+        let stale = receiver.beat(timeout, start + secs(9)).unwrap_err();
+        assert_eq!(stale.to_string(), "last reading 8s ago");
+
+        assert_eq!(script.finish(), 1);
+        assert_eq!(receiver.last_received(), Some(start + secs(10)));
+        assert!(receiver.beat(timeout, start + secs(10)).is_ok());
+        assert_eq!(script.remaining(), 0);
+        assert_eq!(script.advance(), None);
+
+        let thermo = Arc::new(SmartThermometer::new("t2"));
+        ScriptedSensor::new(thermo.clone(), start, [(secs(0), -1.5), (secs(3), 4.0)]).finish();
+        assert_eq!(thermo.temperature(), Some(4.0));
+    }
+}
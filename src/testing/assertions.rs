@@ -0,0 +1,114 @@
+//! Assertions on the structure of a house.
+//!
+//! They use the public API only. A failure prints the whole house tree, so the
+//! difference is visible at a glance.
+
+use crate::{HouseReport, SmartHouse};
+
+fn tree(house: &SmartHouse) -> String {
+    house
+        .create_report(HouseReport)
+        .unwrap_or_else(|e| format!("<report failed: {e}>\n"))
+}
+
+/// Asserts that `device` is plugged into `room`. [`assert_house_contains!`] reads better.
+///
+/// [`assert_house_contains!`]: crate::assert_house_contains!
+#[track_caller]
+pub fn assert_house_contains(house: &SmartHouse, room: &str, device: &str) {
+    if house.get_device(room, device).is_none() {
+        panic!(
+            "no device {device:?} in room {room:?}; the house is:\n{}",
+            tree(house)
+        );
+    }
+}
+
+#[track_caller]
+pub fn assert_device_count(house: &SmartHouse, expected: usize) {
+    let actual = house.device_count();
+    if actual != expected {
+        panic!(
+            "expected {expected} devices, found {actual}; the house is:\n{}",
+            tree(house)
+        );
+    }
+}
+
+/// Asserts that `room` holds exactly `expected`, in that order.
+#[track_caller]
+pub fn assert_room_devices<S: AsRef<str>>(
+    house: &SmartHouse,
+    room: &str,
+    expected: impl IntoIterator<Item = S>,
+) {
+    let expected: Vec<String> = expected
+        .into_iter()
+        .map(|s| s.as_ref().to_string())
+        .collect();
+    let Some(found) = house.room(room) else {
+        panic!("no room {room:?}; the house is:\n{}", tree(house));
+    };
+    let actual = found.devices();
+    if actual != expected {
+        panic!(
+            "room {room:?} holds {actual:?}, expected {expected:?}; the house is:\n{}",
+            tree(house)
+        );
+    }
+}
+
+/// Asserts that a device is plugged into a room of the house.
+///
+/// ```
+/// use lesson_3::{assert_house_contains, testing::fixtures::small_house};
+///
+/// let house = small_house();
+/// assert_house_contains!(house, "limb" / "t1");
+/// ```
+#[macro_export]
+macro_rules! assert_house_contains {
+    ($house:expr, $room:tt / $device:tt $(,)?) => {
+        $crate::testing::assert_house_contains(&$house, $room, $device)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::catch_unwind;
+
+    use super::*;
+    use crate::testing::fixtures::small_house;
+
+    fn message(f: impl FnOnce() + std::panic::UnwindSafe) -> String {
+        let panic = catch_unwind(f).unwrap_err();
+        match panic.downcast::<String>() {
+            Ok(message) => *message,
+            Err(_) => panic!("assertion should panic with a formatted message"),
+        }
+    }
+
+    #[test]
+    fn passing_and_failing_assertions() {
+        let house = small_house();
+        crate::assert_house_contains!(house, "limb" / "s1");
+        let room = "lust";
+        crate::assert_house_contains!(house, room / "s2");
+        assert_device_count(&house, 3);
+        assert_room_devices(&house, "limb", ["s1", "t1"]);
+        assert_room_devices(&house, "lust", vec!["s2".to_string()]);
+
+        let tree = "the house is:\n-> House: hell\n--> Room: limb\n----> Device: s1\n----> Device: t1\n--> Room: lust\n----> Device: s2\n";
+        let failures = [
+            message(|| crate::assert_house_contains!(small_house(), "lust" / "s1")),
+            message(|| assert_device_count(&small_house(), 2)),
+            message(|| assert_room_devices(&small_house(), "limb", ["t1", "s1"])),
+            message(|| assert_room_devices(&small_house(), "hall", ["s1"])),
+        ];
+        assert!(failures[0].starts_with("no device \"s1\" in room \"lust\""));
+        assert!(failures[1].starts_with("expected 2 devices, found 3"));
+        assert!(failures[2].starts_with(r#"room "limb" holds ["s1", "t1"], expected ["t1", "s1"]"#));
+        assert!(failures[3].starts_with("no room \"hall\""));
+        assert!(failures.iter().all(|m| m.ends_with(tree)), "{failures:#?}");
+    }
+}
@@ -0,0 +1,148 @@
+//! Ready-made houses for tests and examples.
+//!
+//! Every fixture is put together with [`SmartHouse::builder`], so it passes the same
+//! validation as a house built by hand.
+
+use alloc::{format, sync::Arc};
+
+use crate::{devices::downcast_arc, SmartHouse, SmartRoom, SmartSocket, SmartThermometer};
+
+/// `hell` with two rooms: `limb` holds socket `s1` and thermometer `t1`, `lust` holds
+/// socket `s2`. All devices are in their initial state.
+pub fn small_house() -> SmartHouse {
+    SmartHouse::builder("hell")
+        .room("limb", |r| r.socket("s1").thermometer("t1"))
+        .room("lust", |r| r.socket("s2"))
+        .build()
+        .expect("fixture is valid")
+}
+
+/// `rooms` rooms named `room-0`, `room-1`, … with `devices_per_room` devices each.
+/// Devices are named `device-<room>-<n>` and alternate between sockets and thermometers,
+/// starting with a socket.
+pub fn large_house(rooms: usize, devices_per_room: usize) -> SmartHouse {
+    let mut builder = SmartHouse::builder("large");
+    for room in 0..rooms {
+        builder = builder.room(format!("room-{room}"), |mut r| {
+            for device in 0..devices_per_room {
+                let name = format!("device-{room}-{device}");
+                r = if device % 2 == 0 {
+                    r.socket(name)
+                } else {
+                    r.thermometer(name)
+                };
+            }
+            r
+        });
+    }
+    builder.build().expect("fixture is valid")
+}
+
+/// The layout of [`small_house`] with known readings: `s1` is on at 60 W, `t1` reads
+/// 21.5 °C and `s2` is off at 0 W.
+pub fn house_with_state() -> SmartHouse {
+    let s1 = SmartSocket::new("s1");
+    s1.set_load(60.0);
+    s1.turn_on();
+    let t1 = SmartThermometer::new("t1");
+    t1.set_temperature(21.5);
+
+    SmartHouse::builder("hell")
+        .room("limb", |r| r.device(Arc::new(s1)).device(Arc::new(t1)))
+        .room("lust", |r| r.socket("s2"))
+        .build()
+        .expect("fixture is valid")
+}
+
+/// `home` with a `kitchen` of three switched-off sockets, the
+/// [critical](SmartSocket::critical) `fridge`, `kettle` and `lamp`, and thermometer
+/// `t1`. The sockets come back too, in that order, to be switched and checked directly.
+pub fn kitchen() -> (SmartHouse, [Arc<SmartSocket>; 3]) {
+    let sockets = [
+        Arc::new(SmartSocket::new("fridge").critical()),
+        Arc::new(SmartSocket::new("kettle")),
+        Arc::new(SmartSocket::new("lamp")),
+    ];
+    let house = SmartHouse::builder("home")
+        .room("kitchen", |r| {
+            sockets
+                .iter()
+                .fold(r, |r, socket| r.device(socket.clone()))
+                .thermometer("t1")
+        })
+        .build()
+        .expect("fixture is valid");
+    (house, sockets)
+}
+
+/// The smart socket `device` of a fixture's `room`, shared with the house.
+///
+/// # Panics
+///
+/// If there is no such device or it is not a [`SmartSocket`].
+#[track_caller]
+pub fn socket(house: &SmartHouse, room: &str, device: &str) -> Arc<SmartSocket> {
+    house
+        .get_device(room, device)
+        .and_then(downcast_arc)
+        .unwrap_or_else(|| panic!("no socket {device:?} in room {room:?}"))
+}
+
+/// Like [`socket`], for a [`SmartThermometer`].
+#[track_caller]
+pub fn thermometer(house: &SmartHouse, room: &str, device: &str) -> Arc<SmartThermometer> {
+    house
+        .get_device(room, device)
+        .and_then(downcast_arc)
+        .unwrap_or_else(|| panic!("no thermometer {device:?} in room {room:?}"))
+}
+
+/// A lone room for tests that work below the house level: `limb` as in [`small_house`].
+pub fn small_room() -> SmartRoom {
+    SmartRoom::builder("limb")
+        .socket("s1")
+        .thermometer("t1")
+        .build()
+        .expect("fixture is valid")
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{assert_device_count, assert_room_devices},
+        ReportBuilder, Verbosity,
+    };
+
+    #[test]
+    fn fixtures_have_the_documented_shape() {
+        let house = small_house();
+        assert_room_devices(&house, "limb", ["s1", "t1"]);
+        assert_room_devices(&house, "lust", ["s2"]);
+        assert_eq!(small_room().devices(), ["s1", "t1"]);
+
+        let large = large_house(3, 4);
+        assert_eq!(large.get_rooms().len(), 3);
+        assert_device_count(&large, 12);
+        assert_room_devices(
+            &large,
+            "room-2",
+            ["device-2-0", "device-2-1", "device-2-2", "device-2-3"],
+        );
+
+        let report = ReportBuilder::new()
+            .verbosity(Verbosity::Detailed)
+            .run(&house_with_state())
+            .unwrap();
+        assert_eq!(
+            report,
+            "-> House: hell\n--> Room: limb\n----> Device: s1 (on, 60.0 W)\n----> Device: t1 (21.5 °C)\n--> Room: lust\n----> Device: s2 (off, 0.0 W)\n"
+        );
+
+        let (house, [fridge, ..]) = kitchen();
+        assert_room_devices(&house, "kitchen", ["fridge", "kettle", "lamp", "t1"]);
+        assert!(fridge.is_critical() && !fridge.is_on());
+        assert!(Arc::ptr_eq(&fridge, &socket(&house, "kitchen", "fridge")));
+        thermometer(&house, "kitchen", "t1");
+    }
+}
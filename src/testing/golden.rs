@@ -0,0 +1,62 @@
+//! Reports compared against checked-in golden files.
+
+use std::{env, fs, path::PathBuf};
+
+use crate::{Reportable, SmartHouse};
+
+// Отчёты и файлы сравниваются с переводами строк как в Unix
+fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+// Относительные пути считаются от корня пакета, который запускает тесты
+fn resolve(path: &str) -> PathBuf {
+    match env::var_os("CARGO_MANIFEST_DIR") {
+        Some(root) => PathBuf::from(root).join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Renders `provider` for `house` and compares the result with the file at `path`,
+/// relative to the package root. With `UPDATE_GOLDEN=1` in the environment the file is
+/// rewritten instead.
+#[track_caller]
+pub fn assert_report_matches(provider: impl Reportable, house: &SmartHouse, path: &str) {
+    let report = match provider.make(house) {
+        Ok(report) => normalize(&report),
+        Err(e) => panic!("report for {path} failed: {e}"),
+    };
+    let file = resolve(path);
+
+    if env::var_os("UPDATE_GOLDEN").is_some_and(|v| v == "1") {
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(&file, report).unwrap();
+        return;
+    }
+
+    let expected = match fs::read_to_string(&file) {
+        Ok(expected) => normalize(&expected),
+        Err(e) => panic!(
+            "cannot read golden file {}: {e}; run with UPDATE_GOLDEN=1 to create it",
+            file.display()
+        ),
+    };
+    assert!(
+        report == expected,
+        "report differs from {}\n--- expected\n{expected}--- actual\n{report}",
+        file.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_endings_do_not_matter() {
+        assert_eq!(normalize("a\r\nb\n"), "a\nb\n");
+        assert!(resolve("tests/golden/x.txt").ends_with("tests/golden/x.txt"));
+    }
+}
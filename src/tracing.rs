@@ -0,0 +1,302 @@
+//! Spans and events shaped like the `tracing` crate, for services that collect traces
+//! rather than logs.
+//!
+//! Reports run inside a `report` span, every request served over TCP inside a `request`
+//! span. The crate's [`log`](crate::log) records become events of the current span while
+//! a [`Subscriber`] is installed, and are not passed to the logger then, so nothing is
+//! emitted twice.
+
+use core::fmt;
+use std::{
+    cell::RefCell,
+    error::Error,
+    future::Future,
+    pin::Pin,
+    sync::{atomic::Ordering, Arc, OnceLock},
+    task::{Context, Poll},
+};
+
+use crate::{atomic::AtomicU64, log::Level};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpanId(u64);
+
+pub type Fields<'a> = [(&'static str, &'a dyn fmt::Display)];
+
+pub struct SpanInfo<'a> {
+    pub id: SpanId,
+    pub parent: Option<SpanId>,
+    pub name: &'static str,
+    pub fields: &'a Fields<'a>,
+}
+
+impl fmt::Debug for SpanInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut info = f.debug_struct("SpanInfo");
+        info.field("id", &self.id)
+            .field("parent", &self.parent)
+            .field("name", &self.name);
+        for (name, value) in self.fields {
+            info.field(name, &format_args!("{value}"));
+        }
+        info.finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct Event<'a> {
+    pub level: Level,
+    pub target: &'a str,
+    /// The innermost span entered on this thread.
+    pub parent: Option<SpanId>,
+    pub message: fmt::Arguments<'a>,
+}
+
+pub trait Subscriber: Send + Sync {
+    fn new_span(&self, span: &SpanInfo<'_>);
+
+    fn enter(&self, span: SpanId);
+
+    fn exit(&self, span: SpanId);
+
+    /// The span was dropped and will not be entered again.
+    fn close(&self, span: SpanId);
+
+    fn event(&self, event: &Event<'_>);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetGlobalDefaultError;
+
+impl fmt::Display for SetGlobalDefaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a global subscriber is already installed")
+    }
+}
+
+impl Error for SetGlobalDefaultError {}
+
+static GLOBAL: OnceLock<Arc<dyn Subscriber>> = OnceLock::new();
+static NEXT_SPAN: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static SCOPED: RefCell<Option<Arc<dyn Subscriber>>> = const { RefCell::new(None) };
+    static ENTERED: RefCell<Vec<SpanId>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Installs the subscriber for every thread. Only the first call succeeds.
+pub fn set_global_default(
+    subscriber: impl Subscriber + 'static,
+) -> Result<(), SetGlobalDefaultError> {
+    GLOBAL
+        .set(Arc::new(subscriber))
+        .map_err(|_| SetGlobalDefaultError)
+}
+
+/// Runs `f` with `subscriber` in place of the global one on the current thread.
+pub fn with_default<T>(subscriber: Arc<dyn Subscriber>, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Arc<dyn Subscriber>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            SCOPED.with(|scoped| *scoped.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(SCOPED.with(|scoped| scoped.borrow_mut().replace(subscriber)));
+    f()
+}
+
+fn current() -> Option<Arc<dyn Subscriber>> {
+    SCOPED
+        .with(|scoped| scoped.borrow().clone())
+        .or_else(|| GLOBAL.get().cloned())
+}
+
+fn parent() -> Option<SpanId> {
+    ENTERED.with(|entered| entered.borrow().last().copied())
+}
+
+/// A span is reported to the subscriber that was current when it was created.
+pub struct Span {
+    inner: Option<(SpanId, Arc<dyn Subscriber>)>,
+}
+
+impl Span {
+    /// Without a subscriber the span is disabled and costs nothing.
+    pub fn new(name: &'static str, fields: &Fields<'_>) -> Self {
+        let inner = current().map(|subscriber| {
+            let id = SpanId(NEXT_SPAN.fetch_add(1, Ordering::Relaxed));
+            subscriber.new_span(&SpanInfo {
+                id,
+                parent: parent(),
+                name,
+                fields,
+            });
+            (id, subscriber)
+        });
+        Self { inner }
+    }
+
+    pub fn id(&self) -> Option<SpanId> {
+        self.inner.as_ref().map(|(id, _)| *id)
+    }
+
+    pub fn enter(&self) -> Entered<'_> {
+        if let Some((id, subscriber)) = &self.inner {
+            ENTERED.with(|entered| entered.borrow_mut().push(*id));
+            subscriber.enter(*id);
+        }
+        Entered { span: self }
+    }
+
+    /// Enters the span on every poll of `future`, so it stays the parent across awaits.
+    pub fn instrument<F: Future>(self, future: F) -> Instrumented<F> {
+        Instrumented {
+            future: Box::pin(future),
+            span: self,
+        }
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Span").field("id", &self.id()).finish()
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some((id, subscriber)) = &self.inner {
+            subscriber.close(*id);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Entered<'a> {
+    span: &'a Span,
+}
+
+impl Drop for Entered<'_> {
+    fn drop(&mut self) {
+        if let Some((id, subscriber)) = &self.span.inner {
+            ENTERED.with(|entered| entered.borrow_mut().pop());
+            subscriber.exit(*id);
+        }
+    }
+}
+
+/// Future returned by [`Span::instrument`].
+#[derive(Debug)]
+pub struct Instrumented<F> {
+    future: Pin<Box<F>>,
+    span: Span,
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        let _entered = this.span.enter();
+        this.future.as_mut().poll(cx)
+    }
+}
+
+// Запись лога уходит подписчику, если он есть; тогда логгер её уже не получает
+pub(crate) fn event(level: Level, target: &str, message: fmt::Arguments<'_>) -> bool {
+    let Some(subscriber) = current() else {
+        return false;
+    };
+    subscriber.event(&Event {
+        level,
+        target,
+        parent: parent(),
+        message,
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{testing::fixtures::small_house, HouseReport};
+
+    #[derive(Debug, PartialEq)]
+    enum Traced {
+        Span(SpanId, Option<SpanId>, &'static str, String),
+        Event(Option<SpanId>, String),
+        Close(SpanId),
+    }
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<Traced>>);
+
+    impl Subscriber for Collect {
+        fn new_span(&self, span: &SpanInfo<'_>) {
+            let fields: Vec<_> = span
+                .fields
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            self.0.lock().unwrap().push(Traced::Span(
+                span.id,
+                span.parent,
+                span.name,
+                fields.join(" "),
+            ));
+        }
+
+        fn enter(&self, _: SpanId) {}
+
+        fn exit(&self, _: SpanId) {}
+
+        fn close(&self, span: SpanId) {
+            self.0.lock().unwrap().push(Traced::Close(span));
+        }
+
+        fn event(&self, event: &Event<'_>) {
+            let message = event.message.to_string();
+            self.0
+                .lock()
+                .unwrap()
+                .push(Traced::Event(event.parent, message));
+        }
+    }
+
+    #[test]
+    fn report_span_hierarchy() {
+        let house = small_house();
+
+        let collect = Arc::new(Collect::default());
+        with_default(collect.clone(), || {
+            house.create_report(HouseReport).unwrap();
+        });
+
+        let traced = std::mem::take(&mut *collect.0.lock().unwrap());
+        let (report, render) = match &traced[..2] {
+            [Traced::Span(report, None, "report", _), Traced::Span(render, Some(parent), "render", _)]
+                if parent == report =>
+            {
+                (*report, *render)
+            }
+            other => panic!("unexpected spans: {other:?}"),
+        };
+        assert_eq!(
+            traced[0],
+            Traced::Span(report, None, "report", "house=hell devices=3".to_string())
+        );
+        match &traced[2..] {
+            [Traced::Close(closed), Traced::Event(Some(parent), message), Traced::Close(last)]
+                if *closed == render
+                    && *parent == report
+                    && message.starts_with("house hell: report made")
+                    && *last == report => {}
+            other => panic!("unexpected trace: {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use alloc::sync::Arc;
+use core::{mem, ops::Deref};
+
+use crate::{
+    events::Deferred, house::Snapshot, undo::History, DeviceId, IntoDevice, Pluggable, RoomId,
+    SmartHouse, SmartHouseError, SmartRoom,
+};
+
+/// The house inside [`SmartHouse::transaction`]. Reads go to the house as it is so far;
+/// changes are the usual house changes, checked by policies as they are made.
+///
+/// The handle does not give the house out mutably, so transactions cannot nest.
+pub struct Transaction<'a> {
+    house: &'a mut SmartHouse,
+    // None после фиксации; иначе Drop откатывает дом к этому состоянию
+    snapshot: Option<Snapshot>,
+    history: History,
+    // отложенное снаружи, если транзакция идёт внутри обновления HouseCell
+    outer: Option<Deferred>,
+}
+
+impl Transaction<'_> {
+    pub fn add_room(&mut self, room: SmartRoom) -> Result<RoomId, SmartHouseError> {
+        self.house.add(room)
+    }
+
+    pub fn remove_room(&mut self, name: &str) -> Option<SmartRoom> {
+        self.house.remove_room(name)
+    }
+
+    pub fn plug(
+        &mut self,
+        room: &str,
+        device: impl IntoDevice,
+    ) -> Result<DeviceId, SmartHouseError> {
+        self.house.plug(room, device)
+    }
+
+    pub fn unplug(
+        &mut self,
+        room: &str,
+        device: &str,
+    ) -> Result<Arc<dyn Pluggable>, SmartHouseError> {
+        self.house.unplug(room, device)
+    }
+
+    pub fn move_device(
+        &mut self,
+        device: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<DeviceId, SmartHouseError> {
+        self.house.move_device(device, from, to)
+    }
+
+    fn commit(mut self) {
+        self.snapshot = None;
+        let inside = mem::replace(&mut self.house.history, mem::take(&mut self.history));
+        self.house.history.merge(inside);
+        let deferred = mem::replace(&mut self.house.listeners.deferred, self.outer.take());
+        let deferred = deferred.unwrap_or_default();
+        for event in deferred.events {
+            self.house.changed(event);
+        }
+        for (driver, device) in deferred.releases {
+            self.house.disconnect(driver, device);
+        }
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = SmartHouse;
+
+    fn deref(&self) -> &SmartHouse {
+        self.house
+    }
+}
+
+// Откат и при ошибке, и при панике внутри замыкания
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            self.house.restore(snapshot);
+            self.house.listeners.deferred = self.outer.take();
+            self.house.history = mem::take(&mut self.history);
+        }
+    }
+}
+
+impl SmartHouse {
+    /// Runs `f` and keeps its changes only if it returns `Ok`. On `Err` (or a panic) the
+    /// rooms and devices are put back exactly as they were, handles included, and the
+    /// error is passed through.
+    ///
+    /// Subscribers, the audit log and the undo history see the changes only when the
+    /// transaction commits, all at once and in order. Devices unplugged inside it are
+    /// [disconnected](crate::DeviceDriver::disconnect) only then too.
+    pub fn transaction<R, E>(
+        &mut self,
+        f: impl FnOnce(&mut Transaction<'_>) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let snapshot = self.snapshot();
+        let fresh = self.history.fresh();
+        let history = mem::replace(&mut self.history, fresh);
+        let outer = self.listeners.deferred.replace(Deferred::default());
+
+        let mut tx = Transaction {
+            house: self,
+            snapshot: Some(snapshot),
+            history,
+            outer,
+        };
+        let result = f(&mut tx);
+        if result.is_ok() {
+            tx.commit();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        string::{String, ToString},
+        vec,
+    };
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{HouseEvent, SmartSocket, SmartThermometer};
+
+    fn house() -> (SmartHouse, RoomId) {
+        let mut limb = SmartRoom::new("limb");
+        limb.plug(SmartSocket::new("s1")).unwrap();
+        let mut house = SmartHouse::new("hell").with_undo(10);
+        let limb = house.add(limb).unwrap();
+        (house, limb)
+    }
+
+    fn layout(house: &SmartHouse) -> Vec<(String, Vec<String>)> {
+        house
+            .rooms
+            .iter()
+            .map(|room| (room.name.clone(), room.devices()))
+            .collect()
+    }
+
+    #[test]
+    fn failed_transaction_leaves_the_house_untouched() {
+        let (mut house, limb) = house();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        house.subscribe(Box::new(move |event| {
+            seen.lock().unwrap().push(event.clone())
+        }));
+        let before = layout(&house);
+        let generation = house.generation();
+
+        let result = house.transaction(|tx| {
+            tx.add_room(SmartRoom::new("boiler"))?;
+            tx.plug("boiler", SmartThermometer::new("t1"))?;
+            tx.move_device("s1", "limb", "boiler")?;
+            assert_eq!(tx.room("boiler").unwrap().devices(), ["t1", "s1"]);
+            tx.remove_room("limb");
+            tx.plug("limb", SmartSocket::new("s2"))?;
+            Ok(())
+        });
+
+        assert_eq!(
+            result,
+            Err(SmartHouseError::RoomNotFound("limb".to_string()))
+        );
+        assert_eq!(layout(&house), before);
+        assert_eq!(house.generation(), generation);
+        assert!(house.room_by_id(limb).is_ok());
+        assert!(events.lock().unwrap().is_empty());
+        assert!(!house.can_redo());
+        house.undo().unwrap();
+        assert!(house.rooms.is_empty());
+    }
+
+    #[test]
+    fn committed_transaction_sends_its_changes() {
+        let (mut house, _) = house();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        house.subscribe(Box::new(move |event| {
+            seen.lock().unwrap().push(event.clone())
+        }));
+
+        let moved = house.transaction(|tx| {
+            tx.add_room(SmartRoom::new("boiler"))?;
+            assert!(events.lock().unwrap().is_empty());
+            tx.move_device("s1", "limb", "boiler")
+        });
+
+        assert!(moved.is_ok());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                HouseEvent::RoomAdded {
+                    room: "boiler".to_string()
+                },
+                HouseEvent::DeviceMoved {
+                    device: "s1".to_string(),
+                    from: "limb".to_string(),
+                    to: "boiler".to_string(),
+                },
+            ]
+        );
+
+        house.undo().unwrap();
+        house.undo().unwrap();
+        assert_eq!(
+            layout(&house),
+            [("limb".to_string(), vec!["s1".to_string()])]
+        );
+    }
+}
@@ -0,0 +1,663 @@
+//! UDP thermometer: an emitter streaming readings to subscribers and a receiver device.
+//!
+//! A receiver subscribes by sending a `GetReading` frame to the emitter and repeats
+//! the subscription periodically, so it picks the stream up again after an emitter restart.
+
+use core::fmt;
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    devices::{downcast, next_stamp},
+    protocol::{decode, encode, ProtocolError, Request, Response},
+    CapabilityError, Clock, DeviceDriver, DeviceInfo, DeviceKind, ErrorSink, LogSink, Named,
+    Pluggable, SmartThermometer, SystemClock, UNKNOWN_MAKER,
+};
+
+const MAX_DATAGRAM_LEN: usize = 1024;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn recv_frame(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<Option<(Vec<u8>, SocketAddr)>> {
+    match socket.recv_from(buf) {
+        Ok((len, from)) => Ok(Some((buf[..len].to_vec(), from))),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub struct ThermometerEmitter {
+    name: String,
+    socket: UdpSocket,
+    interval: Duration,
+    source: Box<dyn FnMut() -> f64 + Send>,
+    sink: Arc<dyn ErrorSink>,
+}
+
+impl ThermometerEmitter {
+    pub fn bind(name: impl Into<String>, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            name: name.into(),
+            socket: UdpSocket::bind(addr)?,
+            interval: Duration::from_secs(1),
+            source: Box::new(|| 20.0),
+            sink: Arc::new(LogSink),
+        })
+    }
+
+    /// Receives send and receive errors and malformed requests as the `thermo-emitter`
+    /// component.
+    pub fn with_error_sink(mut self, sink: Arc<dyn ErrorSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_source(mut self, source: impl FnMut() -> f64 + Send + 'static) -> Self {
+        self.source = Box::new(source);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn spawn(mut self) -> io::Result<EmitterHandle> {
+        let addr = self.local_addr()?;
+        self.socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+
+        let thread = {
+            let stopped = Arc::clone(&stopped);
+            let subscribers = Arc::clone(&subscribers);
+            thread::spawn(move || self.run(&stopped, &subscribers))
+        };
+
+        Ok(EmitterHandle {
+            addr,
+            stopped,
+            subscribers,
+            thread: Some(thread),
+        })
+    }
+
+    fn send(&self, reading: &[u8], to: SocketAddr) {
+        if let Err(e) = self.socket.send_to(reading, to) {
+            self.sink.report("thermo-emitter", &e);
+        }
+    }
+
+    fn reading(&mut self) -> Vec<u8> {
+        let frame = Response::Reading((self.source)()).to_frame();
+        encode(frame.kind, &frame.payload).unwrap_or_default()
+    }
+
+    fn run(&mut self, stopped: &AtomicBool, subscribers: &Mutex<Vec<SocketAddr>>) {
+        let mut buf = [0u8; MAX_DATAGRAM_LEN];
+        let mut next_tick = Instant::now() + self.interval;
+
+        while !stopped.load(Ordering::SeqCst) {
+            let received = recv_frame(&self.socket, &mut buf)
+                .inspect_err(|e| self.sink.report("thermo-emitter", e));
+            if let Ok(Some((datagram, from))) = received {
+                let subscribe = decode(&datagram)
+                    .and_then(|(frame, _)| Request::from_frame(frame))
+                    .inspect_err(|e| self.sink.report("thermo-emitter", e))
+                    .is_ok_and(
+                        |r| matches!(r, Request::GetReading { device } if device == self.name),
+                    );
+
+                if subscribe {
+                    let mut subscribers = lock(subscribers);
+                    if !subscribers.contains(&from) {
+                        subscribers.push(from);
+                    }
+                    drop(subscribers);
+
+                    let reading = self.reading();
+                    self.send(&reading, from);
+                }
+            }
+
+            if Instant::now() >= next_tick {
+                next_tick += self.interval;
+                let reading = self.reading();
+                for subscriber in lock(subscribers).iter() {
+                    self.send(&reading, *subscriber);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ThermometerEmitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThermometerEmitter")
+            .field("name", &self.name)
+            .field("addr", &self.socket.local_addr().ok())
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct EmitterHandle {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    subscribers: Arc<Mutex<Vec<SocketAddr>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EmitterHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn subscribers(&self) -> usize {
+        lock(&self.subscribers).len()
+    }
+
+    pub fn drop_subscribers(&self) {
+        lock(&self.subscribers).clear();
+    }
+
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stopped.store(true, Ordering::SeqCst);
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for EmitterHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmitterHandle")
+            .field("addr", &self.addr)
+            .field("subscribers", &self.subscribers())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for EmitterHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Takes temperature readings. The receiving thread of a [`ThermometerReceiver`] feeds it
+/// through this trait, and so can tests.
+pub trait Sensor: Send + Sync {
+    fn record(&self, celsius: f64, at: SystemTime);
+}
+
+impl Sensor for SmartThermometer {
+    fn record(&self, celsius: f64, _at: SystemTime) {
+        self.set_temperature(celsius);
+    }
+}
+
+struct Latest {
+    value: Option<f64>,
+    received: Option<SystemTime>,
+    clock: Arc<dyn Clock>,
+    // метка последнего показания, см. Pluggable::state_stamp
+    changed_at: u64,
+}
+
+// Без `at` время берётся с часов приёмника
+fn record(latest: &Mutex<Latest>, celsius: f64, at: Option<SystemTime>) {
+    let mut latest = lock(latest);
+    latest.value = Some(celsius);
+    latest.received = Some(at.unwrap_or_else(|| latest.clock.now()));
+    latest.changed_at = next_stamp();
+}
+
+pub struct ThermometerReceiver {
+    name: String,
+    emitter: SocketAddr,
+    latest: Arc<Mutex<Latest>>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ThermometerReceiver {
+    pub fn subscribe(name: impl Into<String>, emitter: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::subscribe_every(name, emitter, Duration::from_secs(1))
+    }
+
+    pub fn subscribe_every(
+        name: impl Into<String>,
+        emitter: impl ToSocketAddrs,
+        resubscribe: Duration,
+    ) -> io::Result<Self> {
+        Self::subscribe_with_sink(name, emitter, resubscribe, Arc::new(LogSink))
+    }
+
+    /// Like [`subscribe_every`](Self::subscribe_every); errors of the receiving thread and
+    /// malformed readings go to `sink` as the `thermo-receiver` component.
+    pub fn subscribe_with_sink(
+        name: impl Into<String>,
+        emitter: impl ToSocketAddrs,
+        resubscribe: Duration,
+        sink: Arc<dyn ErrorSink>,
+    ) -> io::Result<Self> {
+        let name = name.into();
+        let emitter = emitter
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no emitter address"))?;
+
+        let local = match emitter {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let frame = Request::GetReading {
+            device: name.clone(),
+        }
+        .to_frame();
+        let subscription = encode(frame.kind, &frame.payload).map_err(|e| match e {
+            ProtocolError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidInput, e.to_string()),
+        })?;
+        socket.send_to(&subscription, emitter)?;
+
+        let latest = Arc::new(Mutex::new(Latest {
+            value: None,
+            received: None,
+            clock: Arc::new(SystemClock),
+            changed_at: 0,
+        }));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let latest = Arc::clone(&latest);
+            let stopped = Arc::clone(&stopped);
+            thread::spawn(move || {
+                let mut buf = [0u8; MAX_DATAGRAM_LEN];
+                let mut last_subscription = Instant::now();
+
+                while !stopped.load(Ordering::SeqCst) {
+                    if last_subscription.elapsed() >= resubscribe {
+                        if let Err(e) = socket.send_to(&subscription, emitter) {
+                            sink.report("thermo-receiver", &e);
+                        }
+                        last_subscription = Instant::now();
+                    }
+
+                    let (datagram, from) = match recv_frame(&socket, &mut buf) {
+                        Ok(Some(received)) => received,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            sink.report("thermo-receiver", &e);
+                            continue;
+                        }
+                    };
+                    if from != emitter {
+                        continue;
+                    }
+
+                    let reading =
+                        decode(&datagram).and_then(|(frame, _)| Response::from_frame(frame));
+                    if let Err(e) = &reading {
+                        sink.report("thermo-receiver", e);
+                    }
+                    if let Ok(Response::Reading(value)) = reading {
+                        record(&latest, value, None);
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            name,
+            emitter,
+            latest,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// Stamps readings received from now on with `clock` instead of the system time.
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        lock(&self.latest).clock = Arc::new(clock);
+        self
+    }
+
+    pub fn emitter(&self) -> SocketAddr {
+        self.emitter
+    }
+
+    pub fn temperature(&self) -> Option<f64> {
+        lock(&self.latest).value
+    }
+
+    pub fn last_received(&self) -> Option<SystemTime> {
+        lock(&self.latest).received
+    }
+
+    /// Stops listening to the emitter; the latest reading stays.
+    pub fn unsubscribe(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_subscribed(&self) -> bool {
+        !self.stopped.load(Ordering::SeqCst)
+    }
+
+    pub fn wait_for_reading(&self, timeout: Duration) -> Option<f64> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(value) = self.temperature() {
+                return Some(value);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Subscribes a [`ThermometerReceiver`] to the thermometer `name` of a
+/// [`ThermometerEmitter`].
+#[derive(Debug, Clone)]
+pub struct ThermometerDriver {
+    name: String,
+    emitter: SocketAddr,
+    resubscribe: Duration,
+    probe_timeout: Duration,
+}
+
+impl ThermometerDriver {
+    pub fn new(name: impl Into<String>, emitter: SocketAddr) -> Self {
+        Self {
+            name: name.into(),
+            emitter,
+            resubscribe: Duration::from_secs(1),
+            probe_timeout: crate::net::DEFAULT_PROBE_TIMEOUT,
+        }
+    }
+
+    /// See [`ThermometerReceiver::subscribe_every`].
+    pub fn with_resubscribe(mut self, resubscribe: Duration) -> Self {
+        self.resubscribe = resubscribe;
+        self
+    }
+
+    /// How long the probe waits for a reading.
+    pub fn with_probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    fn receiver(&self) -> io::Result<ThermometerReceiver> {
+        ThermometerReceiver::subscribe_every(self.name.clone(), self.emitter, self.resubscribe)
+    }
+}
+
+impl DeviceDriver for ThermometerDriver {
+    /// Over UDP nothing answers a subscription, so this succeeds even if no emitter
+    /// listens; [`probe`](Self::probe) first to find out.
+    fn connect(&self) -> Result<Arc<dyn Pluggable>, CapabilityError> {
+        Ok(Arc::new(self.receiver()?))
+    }
+
+    fn disconnect(&self, device: Arc<dyn Pluggable>) {
+        if let Some(receiver) = downcast::<ThermometerReceiver>(&*device) {
+            receiver.unsubscribe();
+        }
+    }
+
+    /// Waits for one reading; the emitter does not tell who made the thermometer.
+    fn probe(&self) -> Result<DeviceInfo, CapabilityError> {
+        self.receiver()?
+            .wait_for_reading(self.probe_timeout)
+            .ok_or_else(|| {
+                format!(
+                    "no reading from {} within {:?}",
+                    self.name, self.probe_timeout
+                )
+            })?;
+        Ok(DeviceInfo::new(UNKNOWN_MAKER, "udp thermometer"))
+    }
+}
+
+impl fmt::Debug for ThermometerReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThermometerReceiver")
+            .field("name", &self.name)
+            .field("emitter", &self.emitter)
+            .field("temperature", &self.temperature())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ThermometerReceiver {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Sensor for ThermometerReceiver {
+    fn record(&self, celsius: f64, at: SystemTime) {
+        record(&self.latest, celsius, Some(at));
+    }
+}
+
+impl Named for ThermometerReceiver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Pluggable for ThermometerReceiver {
+    fn as_measurable(&self) -> Option<&dyn crate::Measurable> {
+        Some(self)
+    }
+
+    /// The copy keeps the latest reading but does not subscribe to the emitter.
+    fn boxed_clone(&self) -> Arc<dyn Pluggable> {
+        let latest = lock(&self.latest);
+        Arc::new(Self {
+            name: self.name.clone(),
+            emitter: self.emitter,
+            latest: Arc::new(Mutex::new(Latest {
+                value: latest.value,
+                received: latest.received,
+                clock: Arc::clone(&latest.clock),
+                changed_at: latest.changed_at,
+            })),
+            stopped: Arc::new(AtomicBool::new(true)),
+            thread: None,
+        })
+    }
+
+    fn kind(&self) -> Option<DeviceKind> {
+        Some(DeviceKind::Thermometer)
+    }
+
+    fn state_stamp(&self) -> u64 {
+        lock(&self.latest).changed_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::small_house;
+
+    fn spawn_emitter(name: &str, value: f64) -> EmitterHandle {
+        ThermometerEmitter::bind(name.to_string(), "127.0.0.1:0")
+            .unwrap()
+            .with_interval(Duration::from_millis(20))
+            .with_source(move || value)
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn malformed_reading_is_reported_once() {
+        let fake = UdpSocket::bind("127.0.0.1:0").unwrap();
+        fake.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (sink, errors) = crate::ChannelSink::new();
+        let receiver = ThermometerReceiver::subscribe_with_sink(
+            "t1",
+            fake.local_addr().unwrap(),
+            Duration::from_secs(60),
+            Arc::new(sink),
+        )
+        .unwrap();
+
+        let mut buf = [0u8; MAX_DATAGRAM_LEN];
+        let (_, subscriber) = fake.recv_from(&mut buf).unwrap();
+        fake.send_to(b"not a frame", subscriber).unwrap();
+        let reported = errors.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(reported.component, "thermo-receiver");
+
+        let frame = Response::Reading(19.5).to_frame();
+        fake.send_to(&encode(frame.kind, &frame.payload).unwrap(), subscriber)
+            .unwrap();
+        assert_eq!(
+            receiver.wait_for_reading(Duration::from_secs(5)),
+            Some(19.5)
+        );
+        assert_eq!(errors.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn telemetry_samples_each_new_reading() {
+        let fake = UdpSocket::bind("127.0.0.1:0").unwrap();
+        fake.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let receiver = Arc::new(
+            ThermometerReceiver::subscribe_every(
+                "t2".to_string(),
+                fake.local_addr().unwrap(),
+                Duration::from_secs(60),
+            )
+            .unwrap(),
+        );
+        let mut house = small_house().with_telemetry(crate::Telemetry::new());
+        house.plug("lust", receiver.clone()).unwrap();
+
+        let mut buf = [0u8; MAX_DATAGRAM_LEN];
+        let (_, subscriber) = fake.recv_from(&mut buf).unwrap();
+        let send = |celsius: f64| {
+            let frame = Response::Reading(celsius).to_frame();
+            fake.send_to(&encode(frame.kind, &frame.payload).unwrap(), subscriber)
+                .unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while receiver.temperature() != Some(celsius) && Instant::now() < deadline {
+                thread::sleep(POLL_INTERVAL);
+            }
+            assert_eq!(receiver.temperature(), Some(celsius));
+        };
+
+        send(19.5);
+        let first = house.sample_telemetry();
+        assert!(first > 0);
+        assert_eq!(house.sample_telemetry(), 0);
+        // новое показание по UDP сдвигает state_generation, и дом снова снимается
+        send(20.0);
+        assert_eq!(house.sample_telemetry(), first);
+    }
+
+    #[test]
+    fn receiver_gets_readings() {
+        let emitter = spawn_emitter("Thermometer 1", 21.5);
+        let receiver =
+            ThermometerReceiver::subscribe("Thermometer 1", emitter.local_addr()).unwrap();
+
+        assert_eq!(
+            receiver.wait_for_reading(Duration::from_secs(2)),
+            Some(21.5)
+        );
+        assert!(receiver.last_received().is_some());
+        assert_eq!(emitter.subscribers(), 1);
+    }
+
+    #[test]
+    fn wrong_name_is_not_subscribed() {
+        let emitter = spawn_emitter("Thermometer 1", 21.5);
+        let receiver = ThermometerReceiver::subscribe("Other", emitter.local_addr()).unwrap();
+
+        assert_eq!(receiver.wait_for_reading(Duration::from_millis(200)), None);
+        assert_eq!(emitter.subscribers(), 0);
+    }
+
+    #[test]
+    fn receiver_resubscribes_after_drop() {
+        let emitter = spawn_emitter("Thermometer 1", 21.5);
+        let receiver = ThermometerReceiver::subscribe_every(
+            "Thermometer 1".to_string(),
+            emitter.local_addr(),
+            Duration::from_millis(30),
+        )
+        .unwrap();
+        receiver.wait_for_reading(Duration::from_secs(2)).unwrap();
+
+        emitter.drop_subscribers();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while emitter.subscribers() == 0 && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert_eq!(emitter.subscribers(), 1, "Receiver subscribed again");
+    }
+
+    #[test]
+    fn driver_subscribes_and_unsubscribes() {
+        let emitter = spawn_emitter("t1", 21.0);
+        let driver = ThermometerDriver::new("t1", emitter.local_addr())
+            .with_probe_timeout(Duration::from_secs(5));
+        assert_eq!(driver.probe().unwrap().model, "udp thermometer");
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let nobody = ThermometerDriver::new("t1", silent.local_addr().unwrap())
+            .with_probe_timeout(Duration::from_millis(50));
+        assert!(nobody.probe().is_err());
+
+        let mut room = crate::SmartRoom::new("hall");
+        room.plug_driver(Box::new(driver)).unwrap();
+        let t1 = room.device("t1").unwrap();
+        let receiver = downcast::<ThermometerReceiver>(&*t1).unwrap();
+        assert_eq!(
+            receiver.wait_for_reading(Duration::from_secs(5)),
+            Some(21.0)
+        );
+
+        room.unplug("t1").unwrap();
+        assert!(!receiver.is_subscribed());
+        assert_eq!(receiver.temperature(), Some(21.0));
+    }
+}
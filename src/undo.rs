@@ -0,0 +1,285 @@
+use alloc::{
+    boxed::Box,
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use crate::{HouseEvent, Pluggable, SmartHouse, SmartHouseError, SmartRoom};
+
+// Правка, которая отменяет записанное изменение; её применение даёт обратную ей
+#[derive(Clone)]
+pub(crate) enum Edit {
+    // комната большая, в истории она лежит в Box
+    AddRoom(Box<SmartRoom>),
+    RemoveRoom(String),
+    Plug {
+        room: String,
+        device: Arc<dyn Pluggable>,
+    },
+    Unplug {
+        room: String,
+        device: String,
+    },
+    Move {
+        device: String,
+        from: String,
+        to: String,
+    },
+}
+
+#[derive(Default, Clone)]
+pub(crate) struct History {
+    depth: usize,
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    // отмена и повтор идут через обычные методы, которые не должны писать в историю
+    replaying: bool,
+}
+
+impl History {
+    // пустая история с той же глубиной, для правок внутри транзакции
+    pub(crate) fn fresh(&self) -> Self {
+        Self {
+            depth: self.depth,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn merge(&mut self, later: History) {
+        if later.undo.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        for edit in later.undo {
+            self.push_undo(edit);
+        }
+    }
+
+    fn push_undo(&mut self, edit: Edit) {
+        self.undo.push_back(edit);
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+}
+
+impl SmartHouse {
+    /// Keeps the last `depth` structural changes (`add`, `remove_room`, `plug`, `unplug`
+    /// and `move_device`) for [`undo`](Self::undo). Device state is not recorded.
+    pub fn with_undo(mut self, depth: usize) -> Self {
+        self.history.depth = depth;
+        while self.history.undo.len() > depth {
+            self.history.undo.pop_front();
+        }
+        self
+    }
+
+    pub(crate) fn record(&mut self, inverse: Edit) {
+        if self.history.depth == 0 || self.history.replaying {
+            return;
+        }
+        self.history.redo.clear();
+        self.history.push_undo(inverse);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Reverts the latest recorded change and returns the change the revert made, as sent
+    /// to subscribers, or `None` with nothing to undo. A room or device brought back goes
+    /// to the end of the house or room, plugged strongly.
+    ///
+    /// A revert refused by the house (a policy veto, or changes made behind the house's
+    /// back through a room) is dropped from the history and its error returned.
+    pub fn undo(&mut self) -> Result<Option<HouseEvent>, SmartHouseError> {
+        let Some(edit) = self.history.undo.pop_back() else {
+            return Ok(None);
+        };
+        let (inverse, event) = self.replay(edit)?;
+        self.history.redo.push(inverse);
+        Ok(Some(event))
+    }
+
+    /// Applies the latest undone change again. Any new change clears what can be redone.
+    pub fn redo(&mut self) -> Result<Option<HouseEvent>, SmartHouseError> {
+        let Some(edit) = self.history.redo.pop() else {
+            return Ok(None);
+        };
+        let (inverse, event) = self.replay(edit)?;
+        self.history.push_undo(inverse);
+        Ok(Some(event))
+    }
+
+    fn replay(&mut self, edit: Edit) -> Result<(Edit, HouseEvent), SmartHouseError> {
+        self.history.replaying = true;
+        let replayed = self.apply_edit(edit);
+        self.history.replaying = false;
+        replayed
+    }
+
+    fn apply_edit(&mut self, edit: Edit) -> Result<(Edit, HouseEvent), SmartHouseError> {
+        match edit {
+            Edit::AddRoom(room) => {
+                let name = room.name.clone();
+                self.add(*room)?;
+                Ok((
+                    Edit::RemoveRoom(name.clone()),
+                    HouseEvent::RoomAdded { room: name },
+                ))
+            }
+            Edit::RemoveRoom(name) => {
+                let room = self
+                    .remove_room(&name)
+                    .ok_or_else(|| SmartHouseError::RoomNotFound(name.clone()))?;
+                Ok((
+                    Edit::AddRoom(Box::new(room)),
+                    HouseEvent::RoomRemoved { room: name },
+                ))
+            }
+            Edit::Plug { room, device } => {
+                let name = device.name().to_string();
+                self.plug(&room, device)?;
+                Ok((
+                    Edit::Unplug {
+                        room: room.clone(),
+                        device: name.clone(),
+                    },
+                    HouseEvent::DevicePlugged { room, device: name },
+                ))
+            }
+            Edit::Unplug { room, device } => {
+                let unplugged = self.unplug(&room, &device)?;
+                Ok((
+                    Edit::Plug {
+                        room: room.clone(),
+                        device: unplugged,
+                    },
+                    HouseEvent::DeviceUnplugged { room, device },
+                ))
+            }
+            Edit::Move { device, from, to } => {
+                self.move_device(&device, &from, &to)?;
+                Ok((
+                    Edit::Move {
+                        device: device.clone(),
+                        from: to.clone(),
+                        to: from.clone(),
+                    },
+                    HouseEvent::DeviceMoved { device, from, to },
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SmartSocket, SmartThermometer};
+
+    fn house(depth: usize) -> SmartHouse {
+        let mut limb = SmartRoom::new("limb");
+        limb.plug(SmartSocket::new("s1")).unwrap();
+        let mut house = SmartHouse::new("hell").with_undo(depth);
+        house.add(limb).unwrap();
+        house.add(SmartRoom::new("lust")).unwrap();
+        house
+    }
+
+    // порядок комнат после отмены может поменяться, сравниваем по именам
+    fn layout(house: &SmartHouse) -> Vec<(String, Vec<String>)> {
+        let mut layout: Vec<_> = house
+            .rooms
+            .iter()
+            .map(|room| (room.name.clone(), room.devices()))
+            .collect();
+        layout.sort();
+        layout
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_history() {
+        let mut house = house(10);
+        let before = layout(&house);
+        house.plug("lust", SmartThermometer::new("t1")).unwrap();
+        house.move_device("s1", "limb", "lust").unwrap();
+        house.remove_room("limb").unwrap();
+        let after = layout(&house);
+
+        assert_eq!(
+            house.undo(),
+            Ok(Some(HouseEvent::RoomAdded {
+                room: "limb".to_string()
+            }))
+        );
+        assert_eq!(
+            house.undo(),
+            Ok(Some(HouseEvent::DeviceMoved {
+                device: "s1".to_string(),
+                from: "lust".to_string(),
+                to: "limb".to_string(),
+            }))
+        );
+        house.undo().unwrap();
+        assert_eq!(layout(&house), before);
+
+        while house.can_redo() {
+            house.redo().unwrap();
+        }
+        assert_eq!(layout(&house), after);
+    }
+
+    #[test]
+    fn failed_operations_leave_no_history() {
+        let mut house = SmartHouse::new("hell").with_undo(10);
+        assert!(house.add(SmartRoom::new("limb")).is_ok());
+        assert!(house.undo().unwrap().is_some());
+        assert!(house.plug("limb", SmartSocket::new("s1")).is_err());
+        assert!(house.unplug("limb", "s1").is_err());
+
+        assert_eq!(house.undo(), Ok(None));
+        assert!(house.rooms.is_empty());
+        assert!(house.can_redo());
+    }
+
+    #[test]
+    fn new_changes_clear_redo() {
+        let mut house = house(10);
+        house.plug("lust", SmartSocket::new("s2")).unwrap();
+        house.undo().unwrap();
+        assert!(house.can_redo());
+
+        house.plug("lust", SmartSocket::new("s3")).unwrap();
+        assert!(!house.can_redo());
+        assert_eq!(house.redo(), Ok(None));
+        assert_eq!(house.room("lust").unwrap().devices(), ["s3"]);
+    }
+
+    #[test]
+    fn depth_evicts_oldest_changes() {
+        let mut house = house(2);
+        house.plug("lust", SmartSocket::new("s2")).unwrap();
+        house.plug("lust", SmartSocket::new("s3")).unwrap();
+
+        assert!(house.undo().unwrap().is_some());
+        assert!(house.undo().unwrap().is_some());
+        assert_eq!(house.undo(), Ok(None));
+        // добавления комнат вытеснены из истории
+        assert_eq!(house.rooms.len(), 2);
+    }
+
+    #[test]
+    fn history_is_off_by_default() {
+        let mut house = SmartHouse::new("hell");
+        house.add(SmartRoom::new("limb")).unwrap();
+        assert!(!house.can_undo());
+        assert_eq!(house.undo(), Ok(None));
+    }
+}
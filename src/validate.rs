@@ -0,0 +1,447 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{DeviceLocation, Named, Position, Rect, SmartHouse, SmartRoom};
+
+/// Something inconsistent in a house, found by [`SmartHouse::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    DuplicateRoom(String),
+    DuplicateDevice {
+        room: String,
+        device: String,
+    },
+    /// Only reported by [`SmartHouse::validate_strict`].
+    DeviceInSeveralRooms {
+        device: String,
+        rooms: Vec<String>,
+    },
+    /// The room at `index` in the house has an empty or blank name.
+    EmptyRoomName {
+        index: usize,
+    },
+    EmptyDeviceName {
+        room: String,
+        index: usize,
+    },
+    /// A name the house finds in the wrong place, e.g. after a device changed its name.
+    /// `room` is `None` for the house's own index of rooms.
+    Misindexed {
+        room: Option<String>,
+        name: String,
+    },
+    /// Two rooms accept each other's device handles.
+    SharedHandles {
+        rooms: (String, String),
+    },
+    OutsideBounds {
+        room: String,
+        device: String,
+        position: Position,
+        bounds: Rect,
+    },
+    /// A position kept for a device that is not in the room.
+    StrayPosition {
+        room: String,
+        device: String,
+    },
+    StrayLabel {
+        room: String,
+        device: String,
+    },
+    UnresolvedMember {
+        group: String,
+        member: DeviceLocation,
+    },
+    DanglingDoor {
+        rooms: (String, String),
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::DuplicateRoom(room) => write!(f, "room {room} appears twice"),
+            ValidationIssue::DuplicateDevice { room, device } => {
+                write!(f, "device {device} appears twice in room {room}")
+            }
+            ValidationIssue::DeviceInSeveralRooms { device, rooms } => {
+                write!(f, "device {device} is in rooms {}", rooms.join(", "))
+            }
+            ValidationIssue::EmptyRoomName { index } => write!(f, "room #{index} has no name"),
+            ValidationIssue::EmptyDeviceName { room, index } => {
+                write!(f, "device #{index} in room {room} has no name")
+            }
+            ValidationIssue::Misindexed { room: None, name } => {
+                write!(f, "room {name} is misindexed")
+            }
+            ValidationIssue::Misindexed {
+                room: Some(room),
+                name,
+            } => write!(f, "device {name} is misindexed in room {room}"),
+            ValidationIssue::SharedHandles { rooms: (a, b) } => {
+                write!(f, "rooms {a} and {b} share device handles")
+            }
+            ValidationIssue::OutsideBounds {
+                room,
+                device,
+                position,
+                bounds,
+            } => write!(
+                f,
+                "device {device} at {position} is outside {bounds} of room {room}"
+            ),
+            ValidationIssue::StrayPosition { room, device } => {
+                write!(f, "room {room} places missing device {device}")
+            }
+            ValidationIssue::StrayLabel { room, device } => {
+                write!(f, "room {room} labels missing device {device}")
+            }
+            ValidationIssue::UnresolvedMember { group, member } => {
+                write!(f, "group {group} has missing member {member}")
+            }
+            ValidationIssue::DanglingDoor { rooms: (a, b) } => {
+                write!(f, "door between {a} and {b} leads to a missing room")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ValidationIssue {}
+
+fn blank(name: &str) -> bool {
+    name.trim().is_empty()
+}
+
+impl SmartRoom {
+    fn issues(&self, issues: &mut Vec<ValidationIssue>) {
+        let room = || self.name.clone();
+        let mut seen = BTreeSet::new();
+        let mut duplicates = BTreeSet::new();
+        for (index, name) in self.plugged_names().enumerate() {
+            if blank(name) {
+                issues.push(ValidationIssue::EmptyDeviceName {
+                    room: room(),
+                    index,
+                });
+            }
+            if !seen.insert(name) && duplicates.insert(name) {
+                issues.push(ValidationIssue::DuplicateDevice {
+                    room: room(),
+                    device: name.to_string(),
+                });
+            }
+        }
+        for name in self.misindexed() {
+            if !duplicates.contains(name.as_str()) {
+                issues.push(ValidationIssue::Misindexed {
+                    room: Some(room()),
+                    name,
+                });
+            }
+        }
+
+        // положение и подпись мёртвого, но не вычищенного устройства не ошибка
+        let devices: BTreeSet<_> = self.plugged_names().collect();
+        for (device, &position) in &self.geometry.positions {
+            if !devices.contains(device.as_str()) {
+                issues.push(ValidationIssue::StrayPosition {
+                    room: room(),
+                    device: device.clone(),
+                });
+            }
+            if let Some(bounds) = self.bounds().filter(|b| !b.contains(position)) {
+                issues.push(ValidationIssue::OutsideBounds {
+                    room: room(),
+                    device: device.clone(),
+                    position,
+                    bounds,
+                });
+            }
+        }
+        for device in self.device_labels.keys() {
+            if !devices.contains(device.as_str()) {
+                issues.push(ValidationIssue::StrayLabel {
+                    room: room(),
+                    device: device.clone(),
+                });
+            }
+        }
+    }
+}
+
+impl SmartHouse {
+    /// Checks that the house is consistent: names are unique and not blank, the name
+    /// indexes agree with the rooms and devices, rooms keep their handles apart, device
+    /// positions are inside their room, and groups and doors refer to rooms and devices
+    /// of the house. Every issue found is returned, in house order.
+    ///
+    /// The same device name in two rooms is fine here; see
+    /// [`validate_strict`](Self::validate_strict).
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        let issues = self.issues();
+        match issues.is_empty() {
+            true => Ok(()),
+            false => Err(issues),
+        }
+    }
+
+    /// [`validate`](Self::validate), and also no device name in more than one room.
+    pub fn validate_strict(&self) -> Result<(), Vec<ValidationIssue>> {
+        let mut issues = self.issues();
+        let mut rooms: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for room in &self.rooms {
+            for device in room.plugged_names().collect::<BTreeSet<_>>() {
+                rooms.entry(device).or_default().push(room.name.clone());
+            }
+        }
+        for (device, rooms) in rooms.into_iter().filter(|(_, rooms)| rooms.len() > 1) {
+            issues.push(ValidationIssue::DeviceInSeveralRooms {
+                device: device.to_string(),
+                rooms,
+            });
+        }
+        match issues.is_empty() {
+            true => Ok(()),
+            false => Err(issues),
+        }
+    }
+
+    fn issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut seen = BTreeSet::new();
+        let mut duplicates = BTreeSet::new();
+        let mut owners: BTreeMap<usize, &str> = BTreeMap::new();
+        for (index, room) in self.rooms.iter().enumerate() {
+            if blank(&room.name) {
+                issues.push(ValidationIssue::EmptyRoomName { index });
+            }
+            if !seen.insert(room.name()) && duplicates.insert(room.name()) {
+                issues.push(ValidationIssue::DuplicateRoom(room.name.clone()));
+            }
+            if let Some(other) = owners.insert(room.owner(), room.name()) {
+                issues.push(ValidationIssue::SharedHandles {
+                    rooms: (other.to_string(), room.name.clone()),
+                });
+            }
+        }
+        for name in self.misindexed() {
+            if !duplicates.contains(name.as_str()) {
+                issues.push(ValidationIssue::Misindexed { room: None, name });
+            }
+        }
+        for room in &self.rooms {
+            room.issues(&mut issues);
+        }
+
+        for group in &self.groups {
+            for member in &group.members {
+                if self.locate(member).is_err() {
+                    issues.push(ValidationIssue::UnresolvedMember {
+                        group: group.name.clone(),
+                        member: member.clone(),
+                    });
+                }
+            }
+        }
+        for (a, b) in &self.doors {
+            if self.room(a).is_none() || self.room(b).is_none() {
+                issues.push(ValidationIssue::DanglingDoor {
+                    rooms: (a.clone(), b.clone()),
+                });
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::{testing::fixtures::small_house, Pluggable, SmartSocket};
+
+    // Устройство r1, которое уже в комнате называет себя s1
+    #[derive(Default)]
+    struct Renamable(AtomicBool);
+
+    impl Named for Renamable {
+        fn name(&self) -> &str {
+            match self.0.load(Ordering::SeqCst) {
+                true => "s1",
+                false => "r1",
+            }
+        }
+    }
+
+    impl Pluggable for Renamable {
+        fn boxed_clone(&self) -> Arc<dyn Pluggable> {
+            Arc::new(Renamable(AtomicBool::new(self.0.load(Ordering::SeqCst))))
+        }
+    }
+
+    fn path(text: &str) -> DeviceLocation {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn consistent_house_passes() {
+        let mut house = small_house();
+        house.connect_rooms("limb", "lust").unwrap();
+        house.create_group("all").unwrap();
+        house.add_to_group("all", &path("limb/s1")).unwrap();
+        assert_eq!(house.validate(), Ok(()));
+        assert_eq!(house.clone().validate_strict(), Ok(()));
+    }
+
+    #[test]
+    fn duplicate_and_renamed_devices() {
+        let device = Arc::new(Renamable::default());
+        let mut house = small_house();
+        house.plug("limb", device.clone()).unwrap();
+        let limb = house.room_mut("limb").unwrap();
+        limb.set_device_label("r1", Some("Fan".to_string()))
+            .unwrap();
+        device.0.store(true, Ordering::SeqCst);
+
+        assert_eq!(
+            house.validate(),
+            Err(Vec::from([
+                ValidationIssue::DuplicateDevice {
+                    room: "limb".to_string(),
+                    device: "s1".to_string(),
+                },
+                ValidationIssue::Misindexed {
+                    room: Some("limb".to_string()),
+                    name: "r1".to_string(),
+                },
+                ValidationIssue::StrayLabel {
+                    room: "limb".to_string(),
+                    device: "r1".to_string(),
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn same_device_name_in_two_rooms_is_strict_only() {
+        let mut house = small_house();
+        house.plug("lust", SmartSocket::new("s1")).unwrap();
+        assert_eq!(house.validate(), Ok(()));
+        assert_eq!(
+            house.validate_strict(),
+            Err(Vec::from([ValidationIssue::DeviceInSeveralRooms {
+                device: "s1".to_string(),
+                rooms: Vec::from(["limb".to_string(), "lust".to_string()]),
+            }]))
+        );
+    }
+
+    #[test]
+    fn blank_names() {
+        let mut house = small_house();
+        house.add(SmartRoom::new(" ")).unwrap();
+        house.plug("limb", SmartSocket::new("")).unwrap();
+        assert_eq!(
+            house.validate(),
+            Err(Vec::from([
+                ValidationIssue::EmptyRoomName { index: 2 },
+                ValidationIssue::EmptyDeviceName {
+                    room: "limb".to_string(),
+                    index: 2,
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn rooms_pushed_past_the_index() {
+        let mut house = small_house();
+        // дом сам такого не допускает, поэтому комнаты кладутся мимо add
+        let limb = house.room("limb").unwrap();
+        let mut twin = limb.snapshot();
+        twin.name = "twin".to_string();
+        house.rooms.push(limb.clone());
+        house.rooms.push(twin);
+
+        let issues = house.validate().unwrap_err();
+        assert_eq!(
+            issues,
+            [
+                ValidationIssue::DuplicateRoom("limb".to_string()),
+                ValidationIssue::SharedHandles {
+                    rooms: ("limb".to_string(), "twin".to_string()),
+                },
+                ValidationIssue::Misindexed {
+                    room: None,
+                    name: "twin".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn positions_outside_bounds_or_for_missing_devices() {
+        let mut house = small_house();
+        let limb = &mut house.rooms[0];
+        limb.set_bounds(Rect::new(Position::new(0.0, 0.0), Position::new(5.0, 5.0)))
+            .unwrap();
+        // set_position проверяет границы, поэтому положения пишутся напрямую
+        let outside = Position::new(6.0, 1.0);
+        limb.geometry.positions.insert("s1".to_string(), outside);
+        limb.geometry
+            .positions
+            .insert("gone".to_string(), Position::default());
+
+        assert_eq!(
+            house.validate(),
+            Err(Vec::from([
+                ValidationIssue::StrayPosition {
+                    room: "limb".to_string(),
+                    device: "gone".to_string(),
+                },
+                ValidationIssue::OutsideBounds {
+                    room: "limb".to_string(),
+                    device: "s1".to_string(),
+                    position: outside,
+                    bounds: Rect::new(Position::new(0.0, 0.0), Position::new(5.0, 5.0)),
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn group_members_and_doors_must_resolve() {
+        let socket: Arc<dyn Pluggable> = Arc::new(SmartSocket::new("weak"));
+        let mut house = small_house();
+        let lust = house.room_mut("lust").unwrap();
+        lust.plug_weak(Arc::downgrade(&socket)).unwrap();
+        house.create_group("all").unwrap();
+        house.add_to_group("all", &path("lust/weak")).unwrap();
+        house.connect_rooms("limb", "lust").unwrap();
+        // слабое устройство умирает, не сообщая группе
+        drop(socket);
+        house
+            .doors
+            .insert(("attic".to_string(), "limb".to_string()));
+
+        assert_eq!(
+            house.validate(),
+            Err(Vec::from([
+                ValidationIssue::UnresolvedMember {
+                    group: "all".to_string(),
+                    member: path("lust/weak"),
+                },
+                ValidationIssue::DanglingDoor {
+                    rooms: ("attic".to_string(), "limb".to_string()),
+                },
+            ]))
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{Pluggable, SmartHouse, SmartRoom};
+
+/// Callbacks for a depth-first walk over a house, see [`SmartHouse::accept`].
+///
+/// Every callback does nothing by default, so a visitor only writes the ones it needs.
+///
+/// ```
+/// use lesson_3::{HouseVisitor, Named, Pluggable, SmartHouse, SmartRoom, SmartSocket};
+///
+/// #[derive(Default)]
+/// struct Busiest {
+///     current: usize,
+///     most: Option<(String, usize)>,
+/// }
+///
+/// impl HouseVisitor for Busiest {
+///     fn enter_room(&mut self, _room: &SmartRoom) {
+///         self.current = 0;
+///     }
+///
+///     fn visit_device(&mut self, _device: &dyn Pluggable) {
+///         self.current += 1;
+///     }
+///
+///     fn leave_room(&mut self, room: &SmartRoom) {
+///         if self.most.as_ref().map_or(true, |(_, most)| self.current > *most) {
+///             self.most = Some((room.name().to_string(), self.current));
+///         }
+///     }
+/// }
+///
+/// let mut house = SmartHouse::new("home");
+/// house.add(SmartRoom::new("hall")).unwrap();
+/// house.add(SmartRoom::new("kitchen")).unwrap();
+/// house.plug("kitchen", SmartSocket::new("kettle")).unwrap();
+///
+/// let mut busiest = Busiest::default();
+/// house.accept(&mut busiest);
+/// assert_eq!(busiest.most, Some(("kitchen".to_string(), 1)));
+/// ```
+pub trait HouseVisitor {
+    /// Called once, before any room.
+    fn visit_house(&mut self, _house: &SmartHouse) {}
+
+    fn enter_room(&mut self, _room: &SmartRoom) {}
+
+    /// Called for every live device of the room entered last.
+    fn visit_device(&mut self, _device: &dyn Pluggable) {}
+
+    fn leave_room(&mut self, _room: &SmartRoom) {}
+}
+
+impl SmartHouse {
+    /// Walks the house with `visitor`: the house, then every room in the order the rooms
+    /// were added, each with its live devices in the order they were plugged. Every
+    /// `enter_room` is followed by its `leave_room`, also for a room without devices.
+    pub fn accept(&self, visitor: &mut dyn HouseVisitor) {
+        visitor.visit_house(self);
+        for room in &self.rooms {
+            visitor.enter_room(room);
+            for device in room.live_devices() {
+                visitor.visit_device(&*device);
+            }
+            visitor.leave_room(room);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        format,
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    use super::*;
+    use crate::{SmartSocket, SmartThermometer};
+
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl HouseVisitor for Trace {
+        fn visit_house(&mut self, house: &SmartHouse) {
+            self.0.push(format!("house {}", house.name));
+        }
+
+        fn enter_room(&mut self, room: &SmartRoom) {
+            self.0.push(format!("enter {}", room.name));
+        }
+
+        fn visit_device(&mut self, device: &dyn Pluggable) {
+            self.0.push(device.name().to_string());
+        }
+
+        fn leave_room(&mut self, room: &SmartRoom) {
+            self.0.push(format!("leave {}", room.name));
+        }
+    }
+
+    #[test]
+    fn walk_is_depth_first_and_pairs_rooms() {
+        let mut house = SmartHouse::new("home");
+        house.add(SmartRoom::new("hall")).unwrap();
+        house.add(SmartRoom::new("empty")).unwrap();
+        house.plug("hall", SmartSocket::new("s1")).unwrap();
+        house.plug("hall", SmartThermometer::new("t1")).unwrap();
+
+        let mut trace = Trace::default();
+        house.accept(&mut trace);
+        assert_eq!(
+            trace.0,
+            [
+                "house home",
+                "enter hall",
+                "s1",
+                "t1",
+                "leave hall",
+                "enter empty",
+                "leave empty"
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    error::Error,
+    fmt::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    telemetry::{civil, days_from_civil},
+    Clock, DeviceInfo, DeviceLocation, Named, Reportable, SmartHouse,
+};
+
+impl DeviceInfo {
+    /// When the warranty ends: the same day and time of day, `warranty_months` calendar
+    /// months after installation, or the last day of that month if it is shorter. `None`
+    /// unless both are known.
+    pub fn warranty_ends(&self) -> Option<SystemTime> {
+        let since = self.installed_at?.duration_since(UNIX_EPOCH).ok()?;
+        let months = u64::from(self.warranty_months?);
+        let (days, time) = (since.as_secs() / 86_400, since.as_secs() % 86_400);
+        let (year, month, day) = civil(days);
+
+        let end = year * 12 + (month - 1) + months;
+        let (year, month) = (end / 12, end % 12 + 1);
+        let first = days_from_civil(year, month, 1);
+        let next = days_from_civil(year + month / 12, month % 12 + 1, 1);
+        let days = first + day.min(next - first) - 1;
+        let secs = days * 86_400 + time;
+        Some(UNIX_EPOCH + Duration::new(secs, since.subsec_nanos()))
+    }
+}
+
+/// A device whose warranty ends soon or has ended, see
+/// [`SmartHouse::warranty_expiring`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarrantyLine {
+    pub path: DeviceLocation,
+    pub ends: SystemTime,
+    /// The warranty is over already.
+    pub expired: bool,
+}
+
+impl SmartHouse {
+    /// Devices whose warranty ends at the latest `within` from now, by `clock`, soonest
+    /// first; a warranty ending exactly now has expired. Devices missing the
+    /// installation date or the warranty length are left out.
+    pub fn warranty_expiring(&self, within: Duration, clock: &dyn Clock) -> Vec<WarrantyLine> {
+        self.warranty_expiring_at(clock.now(), within)
+    }
+
+    fn warranty_expiring_at(&self, now: SystemTime, within: Duration) -> Vec<WarrantyLine> {
+        let mut lines: Vec<_> = self
+            .all_devices()
+            .filter_map(|(room, device)| {
+                let ends = device.info()?.warranty_ends()?;
+                let path = DeviceLocation {
+                    house: None,
+                    room: room.name().into(),
+                    device: device.name().into(),
+                };
+                (ends <= now + within).then_some(WarrantyLine {
+                    path,
+                    ends,
+                    expired: ends <= now,
+                })
+            })
+            .collect();
+        lines.sort_by_key(|line| line.ends);
+        lines
+    }
+}
+
+/// Warranties ending within `within`, by the house's clock (see
+/// [`SmartHouse::set_clock`]).
+#[derive(Debug)]
+pub struct WarrantyReportProvider {
+    pub within: Duration,
+}
+
+impl Reportable for WarrantyReportProvider {
+    fn make(&self, house: &SmartHouse) -> Result<String, Box<dyn Error>> {
+        let lines = house.warranty_expiring_at(house.now(), self.within);
+        if lines.is_empty() {
+            return Ok("No warranties expiring\n".to_string());
+        }
+        let mut out = String::new();
+        for line in lines {
+            let secs = line.ends.duration_since(UNIX_EPOCH)?.as_secs();
+            let (year, month, day) = civil(secs / 86_400);
+            let state = if line.expired { "expired" } else { "expires" };
+            writeln!(out, "{}: {state} {year:04}-{month:02}-{day:02}", line.path)?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{testing::ManualClock, Pluggable, SmartRoom};
+
+    const DAY: Duration = Duration::from_secs(86_400);
+
+    struct Plug(&'static str, DeviceInfo);
+
+    impl Named for Plug {
+        fn name(&self) -> &str {
+            self.0
+        }
+    }
+
+    impl Pluggable for Plug {
+        fn boxed_clone(&self) -> Arc<dyn Pluggable> {
+            Arc::new(Plug(self.0, self.1.clone()))
+        }
+
+        fn info(&self) -> Option<DeviceInfo> {
+            Some(self.1.clone())
+        }
+    }
+
+    // Полночь по UTC
+    fn date(year: u64, month: u64, day: u64) -> SystemTime {
+        UNIX_EPOCH + DAY * days_from_civil(year, month, day) as u32
+    }
+
+    fn info(installed: Option<SystemTime>, months: Option<u16>) -> DeviceInfo {
+        let mut info = DeviceInfo::new("Shelly", "Plug S");
+        info.installed_at = installed;
+        info.warranty_months = months;
+        info
+    }
+
+    #[test]
+    fn warranty_counts_calendar_months() {
+        let ends = |installed, months| info(Some(installed), Some(months)).warranty_ends();
+        assert_eq!(ends(date(2023, 3, 15), 12), Some(date(2024, 3, 15)));
+        assert_eq!(ends(date(2023, 11, 30), 3), Some(date(2024, 2, 29)));
+        assert_eq!(ends(date(2024, 1, 31), 1), Some(date(2024, 2, 29)));
+        assert_eq!(ends(date(2023, 12, 31), 0), Some(date(2023, 12, 31)));
+        let noon = date(2023, 5, 1) + DAY / 2;
+        assert_eq!(ends(noon, 24), Some(date(2025, 5, 1) + DAY / 2));
+        assert_eq!(info(None, Some(12)).warranty_ends(), None);
+        assert_eq!(info(Some(noon), None).warranty_ends(), None);
+    }
+
+    #[test]
+    fn expiry_boundary() {
+        let mut hall = SmartRoom::new("hall");
+        let plugs = [
+            ("old", info(Some(date(2022, 1, 10)), Some(24))),
+            ("new", info(Some(date(2023, 2, 10)), Some(12))),
+            ("later", info(Some(date(2023, 2, 11)), Some(12))),
+            ("undated", info(None, Some(12))),
+            ("no-warranty", info(Some(date(2023, 1, 1)), None)),
+        ];
+        for (name, info) in plugs {
+            hall.plug(Plug(name, info)).unwrap();
+        }
+        let mut house = SmartHouse::new("home");
+        house.add(hall).unwrap();
+        let clock = ManualClock::new(date(2024, 1, 10));
+        let names = |lines: Vec<WarrantyLine>| -> Vec<(String, bool)> {
+            lines
+                .into_iter()
+                .map(|line| (line.path.device, line.expired))
+                .collect()
+        };
+
+        // ровно в момент окончания гарантия уже истекла
+        let window = DAY * 31;
+        assert_eq!(
+            names(house.warranty_expiring(window, &clock)),
+            [("old".to_string(), true), ("new".to_string(), false)]
+        );
+        // секундой раньше old ещё действует, а new выпадает из окна
+        clock.set(date(2024, 1, 10) - Duration::from_secs(1));
+        assert_eq!(
+            names(house.warranty_expiring(window, &clock)),
+            [("old".to_string(), false)]
+        );
+
+        clock.set(date(2024, 2, 10));
+        house.set_clock(clock.clone());
+        let report = WarrantyReportProvider { within: DAY };
+        assert_eq!(
+            house.create_report(report).unwrap(),
+            "hall/old: expired 2024-01-10\n\
+             hall/new: expired 2024-02-10\n\
+             hall/later: expires 2024-02-11\n"
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! Awaiting device state changes instead of polling devices.
+//!
+//! [`SmartSocket::watch`] and [`SmartThermometer::watch`] hand out [`Receiver`]s shaped
+//! like a `tokio::sync::watch` receiver; [`SmartHouse::watch_all`] merges the receivers of
+//! every stateful device in a house into one [`HouseWatch`].
+
+use core::fmt;
+use std::{
+    error::Error,
+    future::Future,
+    ops::Deref,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{devices::downcast, SmartHouse, SmartSocket, SmartThermometer};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceStateSnapshot {
+    Socket { on: bool, load: f64 },
+    Thermometer { celsius: Option<f64> },
+}
+
+/// The device was dropped, no more changes will come.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the watched device was dropped")
+    }
+}
+
+impl Error for RecvError {}
+
+struct State<T> {
+    value: T,
+    version: u64,
+    closed: bool,
+    wakers: Vec<Waker>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Отправитель держит состояние слабо: без получателей изменения ничего не стоят,
+// а брошенные получатели ничего не удерживают
+pub(crate) struct Sender<T> {
+    shared: Mutex<Weak<Mutex<State<T>>>>,
+}
+
+impl<T> Default for Sender<T> {
+    fn default() -> Self {
+        Self {
+            shared: Mutex::new(Weak::new()),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    pub(crate) fn subscribe(&self, current: impl FnOnce() -> T) -> Receiver<T> {
+        let mut shared = lock(&self.shared);
+        let state = shared.upgrade().unwrap_or_else(|| {
+            let state = Arc::new(Mutex::new(State {
+                value: current(),
+                version: 0,
+                closed: false,
+                wakers: Vec::new(),
+            }));
+            *shared = Arc::downgrade(&state);
+            state
+        });
+        let seen = lock(&state).version;
+        Receiver { state, seen }
+    }
+
+    pub(crate) fn send_with(&self, value: impl FnOnce() -> T) {
+        let Some(state) = lock(&self.shared).upgrade() else {
+            return;
+        };
+        let mut state = lock(&state);
+        state.value = value();
+        state.version += 1;
+        state.wakers.drain(..).for_each(Waker::wake);
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if let Some(state) = lock(&self.shared).upgrade() {
+            let mut state = lock(&state);
+            state.closed = true;
+            state.wakers.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+/// The latest state of one device. Clones share what has been seen so far.
+pub struct Receiver<T> {
+    state: Arc<Mutex<State<T>>>,
+    seen: u64,
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+            seen: self.seen,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = lock(&self.state);
+        f.debug_struct("Receiver")
+            .field("value", &state.value)
+            .field("changed", &(state.version != self.seen))
+            .finish()
+    }
+}
+
+/// The value borrowed from a [`Receiver`]; the device cannot publish while it is held.
+pub struct Ref<'a, T>(MutexGuard<'a, State<T>>);
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0.value
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref(lock(&self.state))
+    }
+
+    /// Like [`borrow`](Self::borrow), and marks the value as seen.
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        let state = lock(&self.state);
+        self.seen = state.version;
+        Ref(state)
+    }
+
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        let state = lock(&self.state);
+        match state.version != self.seen {
+            false if state.closed => Err(RecvError),
+            changed => Ok(changed),
+        }
+    }
+
+    /// Resolves once there is a value that has not been seen, and marks it as seen.
+    pub fn changed(&mut self) -> Changed<'_, T> {
+        Changed { receiver: self }
+    }
+
+    fn poll_changed(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), RecvError>> {
+        let mut state = lock(&self.state);
+        if state.version != self.seen {
+            self.seen = state.version;
+            return Poll::Ready(Ok(()));
+        }
+        if state.closed {
+            return Poll::Ready(Err(RecvError));
+        }
+        state.wakers.retain(|waker| !waker.will_wake(cx.waker()));
+        state.wakers.push(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Future returned by [`Receiver::changed`].
+#[derive(Debug)]
+pub struct Changed<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_changed(cx)
+    }
+}
+
+impl SmartSocket {
+    pub fn snapshot(&self) -> DeviceStateSnapshot {
+        DeviceStateSnapshot::Socket {
+            on: self.is_on(),
+            load: self.load(),
+        }
+    }
+
+    /// Gets a new value on every `turn_on`, `turn_off` and `set_load`.
+    pub fn watch(&self) -> Receiver<DeviceStateSnapshot> {
+        self.watch.subscribe(|| self.snapshot())
+    }
+}
+
+impl SmartThermometer {
+    pub fn snapshot(&self) -> DeviceStateSnapshot {
+        DeviceStateSnapshot::Thermometer {
+            celsius: self.temperature(),
+        }
+    }
+
+    /// Gets a new value on every `set_temperature`.
+    pub fn watch(&self) -> Receiver<DeviceStateSnapshot> {
+        self.watch.subscribe(|| self.snapshot())
+    }
+}
+
+/// State changes of every socket and thermometer that was in the house when
+/// [`watch_all`](SmartHouse::watch_all) was called.
+#[derive(Debug)]
+pub struct HouseWatch {
+    devices: Vec<(String, String, Receiver<DeviceStateSnapshot>)>,
+}
+
+impl HouseWatch {
+    /// The next change as `(room, device, snapshot)`, or `None` once every watched device
+    /// has been dropped.
+    pub fn changed(&mut self) -> NextChange<'_> {
+        NextChange { watch: self }
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+}
+
+/// Future returned by [`HouseWatch::changed`].
+#[derive(Debug)]
+pub struct NextChange<'a> {
+    watch: &'a mut HouseWatch,
+}
+
+impl Future for NextChange<'_> {
+    type Output = Option<(String, String, DeviceStateSnapshot)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let devices = &mut self.watch.devices;
+        let mut i = 0;
+        while i < devices.len() {
+            match devices[i].2.poll_changed(cx) {
+                Poll::Ready(Ok(())) => {
+                    let (room, device, receiver) = &devices[i];
+                    let snapshot = *receiver.borrow();
+                    return Poll::Ready(Some((room.clone(), device.clone(), snapshot)));
+                }
+                Poll::Ready(Err(RecvError)) => {
+                    devices.remove(i);
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+
+        match devices.is_empty() {
+            true => Poll::Ready(None),
+            false => Poll::Pending,
+        }
+    }
+}
+
+impl SmartHouse {
+    /// Each call gets its own stream, so several tasks can follow the same house.
+    pub fn watch_all(&self) -> HouseWatch {
+        let devices = self
+            .all_devices()
+            .filter_map(|(room, device)| {
+                let receiver = downcast::<SmartSocket>(device.as_ref())
+                    .map(SmartSocket::watch)
+                    .or_else(|| {
+                        downcast::<SmartThermometer>(device.as_ref()).map(SmartThermometer::watch)
+                    })?;
+                Some((room.name.clone(), device.name().to_string(), receiver))
+            })
+            .collect();
+        HouseWatch { devices }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+    use crate::{
+        async_report::{block_on, spawn_blocking},
+        testing::fixtures::{self, small_house},
+    };
+
+    // Ждёт `future`, но не дольше `limit`
+    fn with_timeout<F: Future + Unpin>(limit: Duration, future: F) -> Option<F::Output> {
+        let mut future = future;
+        let mut timer = spawn_blocking(move || thread::sleep(limit));
+        block_on(std::future::poll_fn(|cx| {
+            if let Poll::Ready(output) = Pin::new(&mut future).poll(cx) {
+                return Poll::Ready(Some(output));
+            }
+            Pin::new(&mut timer).poll(cx).map(|_| None)
+        }))
+    }
+
+    #[test]
+    fn await_a_change_from_another_thread() {
+        let socket = Arc::new(SmartSocket::new("s1"));
+        let mut receiver = socket.watch();
+        assert_eq!(receiver.has_changed(), Ok(false));
+
+        let remote = Arc::clone(&socket);
+        let switching = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            remote.turn_on();
+        });
+
+        let changed = with_timeout(Duration::from_secs(5), Box::pin(receiver.changed()));
+        assert_eq!(changed, Some(Ok(())));
+        assert_eq!(
+            *receiver.borrow(),
+            DeviceStateSnapshot::Socket {
+                on: true,
+                load: 0.0
+            }
+        );
+        switching.join().unwrap();
+
+        drop(receiver);
+        assert!(socket.watch.shared.lock().unwrap().upgrade().is_none());
+        socket.turn_off();
+    }
+
+    #[test]
+    fn dropping_the_device_closes_receivers() {
+        let thermo = SmartThermometer::new("t1");
+        let mut receiver = thermo.watch();
+        thermo.set_temperature(21.0);
+        assert_eq!(receiver.has_changed(), Ok(true));
+        assert_eq!(
+            *receiver.borrow_and_update(),
+            DeviceStateSnapshot::Thermometer {
+                celsius: Some(21.0)
+            }
+        );
+
+        drop(thermo);
+        assert_eq!(receiver.has_changed(), Err(RecvError));
+        assert_eq!(block_on(receiver.changed()), Err(RecvError));
+    }
+
+    #[test]
+    fn house_watch_merges_devices() {
+        let house = small_house();
+        let socket = fixtures::socket(&house, "limb", "s1");
+        let thermo = fixtures::thermometer(&house, "limb", "t1");
+
+        let mut first = house.watch_all();
+        let mut second = house.watch_all();
+        assert_eq!(first.len(), 3);
+
+        thermo.set_temperature(-3.5);
+        let expected = Some((
+            "limb".to_string(),
+            "t1".to_string(),
+            DeviceStateSnapshot::Thermometer {
+                celsius: Some(-3.5),
+            },
+        ));
+        assert_eq!(block_on(first.changed()), expected);
+        assert_eq!(block_on(second.changed()), expected);
+
+        socket.set_load(40.0);
+        assert_eq!(
+            block_on(first.changed()).map(|(room, device, _)| (room, device)),
+            Some(("limb".to_string(), "s1".to_string()))
+        );
+
+        drop((house, socket, thermo));
+        assert_eq!(block_on(first.changed()), None);
+        assert!(first.is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// Собирает библиотеку для C отдельно, чтобы не ждать блокировку основного target
+fn build_staticlib(target: &Path) -> PathBuf {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "ffi",
+            "--crate-type",
+            "staticlib",
+        ])
+        .arg("--target-dir")
+        .arg(target)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .expect("cargo should run");
+    assert!(status.success());
+    target.join("debug").join("liblesson_3.a")
+}
+
+#[test]
+fn c_program_drives_the_house() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target = root.join("target").join("ffi");
+    let lib = build_staticlib(&target);
+    let program = target.join("smoke");
+
+    let compiled = Command::new("cc")
+        .arg(root.join("tests/ffi/smoke.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(&lib)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&program)
+        .status()
+        .expect("a C compiler should be installed");
+    assert!(compiled.success());
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "devices: 2\n-> House: home\n--> Room: hall\n----> Device: s1\n----> Device: t1\n"
+    );
+}
@@ -0,0 +1,34 @@
+/* Builds a house through the C interface and prints its report. */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "smarthouse.h"
+
+static int fail(const char *what) {
+    const char *error = smarthouse_last_error();
+    fprintf(stderr, "%s: %s\n", what, error ? error : "no error");
+    return 1;
+}
+
+int main(void) {
+    SmartHouse *house = smarthouse_new("home");
+    if (!house) return fail("new");
+    if (smarthouse_add_room(house, "hall") != SMARTHOUSE_OK) return fail("add_room");
+    if (smarthouse_plug_socket(house, "hall", "s1") != SMARTHOUSE_OK) return fail("plug_socket");
+    if (smarthouse_plug_thermometer(house, "hall", "t1") != SMARTHOUSE_OK)
+        return fail("plug_thermometer");
+
+    if (smarthouse_plug_socket(house, "attic", "s2") != SMARTHOUSE_ERROR) return fail("attic");
+    if (strcmp(smarthouse_last_error(), "room attic not found") != 0) return fail("last_error");
+
+    size_t needed = smarthouse_report(house, NULL, 0);
+    char *report = malloc(needed);
+    if (!report || smarthouse_report(house, report, needed) != needed) return fail("report");
+    printf("devices: %lld\n%s", (long long)smarthouse_device_count(house), report);
+
+    free(report);
+    smarthouse_free(house);
+    return 0;
+}
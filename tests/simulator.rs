@@ -0,0 +1,129 @@
+use std::{
+    io::{BufRead, BufReader},
+    net::SocketAddr,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use lesson_3::{
+    net::{ReconnectPolicy, SocketClient},
+    udp::ThermometerReceiver,
+};
+
+struct Simulator {
+    child: Child,
+    ports: Vec<(String, String, SocketAddr)>,
+}
+
+impl Simulator {
+    fn launch(args: &[&str]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_device-simulator"))
+            .args(["--base-port", "0", "--seed", "7"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("simulator should start");
+
+        let mut ports = Vec::new();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        for line in stdout.lines() {
+            let line = line.unwrap();
+            if line == "ready" {
+                break;
+            }
+
+            let mut fields = line.split_whitespace();
+            let kind = fields.next().unwrap().to_string();
+            let name = fields.next().unwrap().to_string();
+            let addr = fields.next().unwrap().parse().unwrap();
+            ports.push((kind, name, addr));
+        }
+
+        Self { child, ports }
+    }
+
+    fn addr(&self, name: &str) -> SocketAddr {
+        self.ports.iter().find(|(_, n, _)| n == name).unwrap().2
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn simulator_prints_port_map() {
+    let simulator = Simulator::launch(&["--sockets", "2", "--thermometers", "3"]);
+
+    let kinds: Vec<_> = simulator
+        .ports
+        .iter()
+        .map(|(k, n, _)| (k.as_str(), n.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("socket", "socket-1"),
+            ("socket", "socket-2"),
+            ("thermometer", "thermometer-1"),
+            ("thermometer", "thermometer-2"),
+            ("thermometer", "thermometer-3"),
+        ]
+    );
+}
+
+#[test]
+fn simulated_devices_respond() {
+    let simulator = Simulator::launch(&[
+        "--temperature",
+        "30",
+        "--jitter",
+        "2",
+        "--interval-ms",
+        "20",
+    ]);
+
+    let socket = SocketClient::connect("socket-1", simulator.addr("socket-1")).unwrap();
+    socket.turn_on().unwrap();
+    assert!(socket.is_on().unwrap());
+
+    let thermo = ThermometerReceiver::subscribe(
+        "thermometer-1".to_string(),
+        simulator.addr("thermometer-1"),
+    )
+    .unwrap();
+    let reading = thermo.wait_for_reading(Duration::from_secs(5)).unwrap();
+    assert!(
+        (28.0..=32.0).contains(&reading),
+        "reading {reading} out of jitter range"
+    );
+}
+
+#[test]
+fn clients_survive_dropped_connections() {
+    let simulator = Simulator::launch(&[
+        "--thermometers",
+        "0",
+        "--drop-probability",
+        "1",
+        "--interval-ms",
+        "10",
+    ]);
+
+    let policy = ReconnectPolicy {
+        max_retries: 10,
+        initial_delay: Duration::from_millis(5),
+        ..ReconnectPolicy::default()
+    };
+    let socket = SocketClient::connect("socket-1", simulator.addr("socket-1"))
+        .unwrap()
+        .with_reconnect(policy);
+
+    for _ in 0..20 {
+        assert!(!socket.is_on().unwrap());
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}